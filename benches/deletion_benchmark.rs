@@ -6,6 +6,7 @@
 use criterion::{
     criterion_group, criterion_main, BenchmarkId, Criterion, SamplingMode, Throughput,
 };
+use fast_rm::listfile::{record_path, ListFile};
 use std::fs::{self, File};
 use std::hint::black_box;
 use std::io::Write;
@@ -190,6 +191,37 @@ fn create_mixed_structure(
     num_small + num_medium + num_large
 }
 
+/// Create a node_modules-like tree: many small packages, each a few small
+/// files and a lib/ directory, every fifth with its own nested node_modules
+fn create_node_modules_structure(base: &Path, num_packages: usize) -> usize {
+    fn package(dir: &Path, name: &str) -> usize {
+        let lib = dir.join("lib");
+        fs::create_dir_all(&lib).unwrap();
+        for file in ["package.json", "README.md", "LICENSE", "index.js"] {
+            fs::write(dir.join(file), format!("{} {}", name, file)).unwrap();
+        }
+        for i in 0..6 {
+            fs::write(lib.join(format!("module_{}.js", i)), "module.exports = {};").unwrap();
+        }
+        // The package directory, lib/ and 10 files
+        12
+    }
+
+    let mut count = 0;
+    for i in 0..num_packages {
+        let dir = base.join(format!("pkg-{}", i));
+        count += package(&dir, &format!("pkg-{}", i));
+        if i % 5 == 0 {
+            let nested = dir.join("node_modules");
+            count += 1;
+            for j in 0..2 {
+                count += package(&nested.join(format!("dep-{}", j)), "dep");
+            }
+        }
+    }
+    count
+}
+
 // ============================================================================
 // Benchmark Helpers
 // ============================================================================
@@ -281,6 +313,35 @@ fn bench_vs_system_rm(c: &mut Criterion) {
             },
         );
 
+        // Experimental: children renamed into a staging directory first.
+        // On ext4 with one CPU this took about twice as long at 100k files,
+        // the renames being serial, so it buys a quickly emptied directory
+        // rather than speed
+        group.bench_with_input(
+            BenchmarkId::new("fast-rm --rename-children", name),
+            &file_count,
+            |b, &count| {
+                b.iter_with_setup(
+                    || {
+                        let temp_dir = TempDir::new().unwrap();
+                        let target = create_target_dir(&temp_dir);
+                        create_flat_structure_parallel(&target, count);
+                        (temp_dir, target)
+                    },
+                    |(temp_dir, target)| {
+                        let output = Command::new(&fast_rm)
+                            .arg("--rename-children")
+                            .arg(&target)
+                            .output()
+                            .expect("Failed to run fast-rm");
+                        assert!(output.status.success(), "fast-rm failed: {:?}", output);
+                        drop(temp_dir);
+                        black_box(())
+                    },
+                );
+            },
+        );
+
         // Benchmark system rm -r
         group.bench_with_input(BenchmarkId::new("rm -r", name), &file_count, |b, &count| {
             b.iter_with_setup(
@@ -485,6 +546,31 @@ fn bench_mixed_sizes(c: &mut Criterion) {
             },
         );
 
+        group.bench_with_input(
+            BenchmarkId::new("fast-rm --size-aware", name),
+            &(small, medium, large),
+            |b, &(s, m, l)| {
+                b.iter_with_setup(
+                    || {
+                        let temp_dir = TempDir::new().unwrap();
+                        let target = create_target_dir(&temp_dir);
+                        create_mixed_structure(&target, s, m, l);
+                        (temp_dir, target)
+                    },
+                    |(temp_dir, target)| {
+                        let output = Command::new(&fast_rm)
+                            .arg("--size-aware")
+                            .arg(&target)
+                            .output()
+                            .expect("Failed to run fast-rm");
+                        assert!(output.status.success(), "fast-rm failed: {:?}", output);
+                        drop(temp_dir);
+                        black_box(())
+                    },
+                );
+            },
+        );
+
         group.bench_with_input(
             BenchmarkId::new("rm -r", name),
             &(small, medium, large),
@@ -562,6 +648,224 @@ fn bench_thread_scaling(c: &mut Criterion) {
     group.finish();
 }
 
+// ============================================================================
+// Benchmarks: Parallel Threshold (fast-rm only)
+// ============================================================================
+
+fn bench_parallel_threshold(c: &mut Criterion) {
+    let fast_rm = get_fast_rm_binary();
+    let mut group = c.benchmark_group("parallel_threshold");
+    group.sampling_mode(SamplingMode::Flat);
+    group.sample_size(15);
+
+    // (name, depth, breadth): many tiny directories, where fan-out is overhead
+    let configs = [("deep_narrow", 5, 3), ("very_deep", 8, 2)];
+    // 0 fans out every directory (previous behaviour), 32 is the default
+    let thresholds = [("always_parallel", 0), ("default", 32)];
+
+    for (name, depth, breadth) in configs {
+        let temp = TempDir::new().unwrap();
+        let target = create_target_dir(&temp);
+        let item_count = create_nested_structure(&target, depth, breadth);
+        drop(temp);
+
+        group.throughput(Throughput::Elements(item_count as u64));
+
+        for (mode, threshold) in thresholds {
+            group.bench_with_input(
+                BenchmarkId::new(mode, name),
+                &(depth, breadth),
+                |b, &(d, br)| {
+                    b.iter_with_setup(
+                        || {
+                            let temp_dir = TempDir::new().unwrap();
+                            let target = create_target_dir(&temp_dir);
+                            create_nested_structure(&target, d, br);
+                            (temp_dir, target)
+                        },
+                        |(temp_dir, target)| {
+                            let output = Command::new(&fast_rm)
+                                .args(["--parallel-threshold", &threshold.to_string()])
+                                .arg(&target)
+                                .output()
+                                .expect("Failed to run fast-rm");
+                            assert!(output.status.success(), "fast-rm failed: {:?}", output);
+                            drop(temp_dir);
+                            black_box(())
+                        },
+                    );
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+// ============================================================================
+// Benchmarks: Auto-Tuned vs Fixed Deleter Pool (fast-rm only)
+// ============================================================================
+
+fn bench_auto_threads(c: &mut Criterion) {
+    let fast_rm = get_fast_rm_binary();
+    let mut group = c.benchmark_group("auto_threads");
+    group.sampling_mode(SamplingMode::Flat);
+    group.sample_size(15);
+
+    // (name, depth, breadth); depth 0 means a flat directory of `breadth` files
+    let configs = [("flat_2000", 0, 2000), ("medium", 3, 5), ("deep_narrow", 5, 3)];
+    let modes: [(&str, &[&str]); 2] = [("fixed", &[]), ("auto", &["--auto-threads"])];
+
+    let create = |target: &Path, depth: usize, breadth: usize| {
+        if depth == 0 {
+            create_flat_structure(target, breadth)
+        } else {
+            create_nested_structure(target, depth, breadth)
+        }
+    };
+
+    for (name, depth, breadth) in configs {
+        let temp = TempDir::new().unwrap();
+        let target = create_target_dir(&temp);
+        let item_count = create(&target, depth, breadth);
+        drop(temp);
+
+        group.throughput(Throughput::Elements(item_count as u64));
+
+        for (mode, args) in modes {
+            group.bench_with_input(
+                BenchmarkId::new(mode, name),
+                &(depth, breadth),
+                |b, &(d, br)| {
+                    b.iter_with_setup(
+                        || {
+                            let temp_dir = TempDir::new().unwrap();
+                            let target = create_target_dir(&temp_dir);
+                            create(&target, d, br);
+                            (temp_dir, target)
+                        },
+                        |(temp_dir, target)| {
+                            let output = Command::new(&fast_rm)
+                                .args(args)
+                                .arg(&target)
+                                .output()
+                                .expect("Failed to run fast-rm");
+                            assert!(output.status.success(), "fast-rm failed: {:?}", output);
+                            drop(temp_dir);
+                            black_box(())
+                        },
+                    );
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+// ============================================================================
+// Benchmarks: Presets and Input Lists (fast-rm only)
+// ============================================================================
+
+fn bench_fast_known_dirs(c: &mut Criterion) {
+    let fast_rm = get_fast_rm_binary();
+    let mut group = c.benchmark_group("fast_known_dirs");
+    group.sampling_mode(SamplingMode::Flat);
+    group.sample_size(15);
+
+    let num_packages = 2000;
+    let item_count = {
+        let temp = TempDir::new().unwrap();
+        create_node_modules_structure(temp.path(), num_packages)
+    };
+    group.throughput(Throughput::Elements(item_count as u64));
+
+    let modes: [(&str, &[&str]); 2] = [("default", &[]), ("preset", &["--fast-known-dirs"])];
+    for (mode, args) in modes {
+        group.bench_with_input(
+            BenchmarkId::new(mode, "node_modules_2000_pkgs"),
+            &num_packages,
+            |b, &count| {
+                b.iter_with_setup(
+                    || {
+                        let temp_dir = TempDir::new().unwrap();
+                        // The preset keys off the directory name
+                        let target = temp_dir.path().join("node_modules");
+                        fs::create_dir(&target).unwrap();
+                        create_node_modules_structure(&target, count);
+                        (temp_dir, target)
+                    },
+                    |(temp_dir, target)| {
+                        let output = Command::new(&fast_rm)
+                            .args(args)
+                            .arg(&target)
+                            .output()
+                            .expect("Failed to run fast-rm");
+                        assert!(output.status.success(), "fast-rm failed: {:?}", output);
+                        drop(temp_dir);
+                        black_box(())
+                    },
+                );
+            },
+        );
+    }
+
+    group.finish();
+}
+
+/// Reading a 1M-line `--files-from` list: records split out of a buffered
+/// reader, one allocation each, versus path slices straight out of the
+/// memory map that `ListFile::records` walks
+fn bench_files_from_list(c: &mut Criterion) {
+    use std::io::{BufRead, BufReader};
+
+    let mut group = c.benchmark_group("files_from_list");
+    group.sample_size(10);
+
+    let num_lines = 1_000_000;
+    let temp_dir = TempDir::new().unwrap();
+    let list = temp_dir.path().join("list.txt");
+    {
+        let mut out = std::io::BufWriter::new(File::create(&list).unwrap());
+        for i in 0..num_lines {
+            writeln!(
+                out,
+                "/data/project/build/obj/{:03}/file_{:07}.o",
+                i % 997,
+                i
+            )
+            .unwrap();
+        }
+    }
+    group.throughput(Throughput::Elements(num_lines as u64));
+
+    group.bench_function(BenchmarkId::new("buffered_split", "1M_lines"), |b| {
+        b.iter(|| {
+            let reader = BufReader::new(File::open(&list).unwrap());
+            let mut count = 0;
+            for record in reader.split(b'\n') {
+                black_box(record.unwrap());
+                count += 1;
+            }
+            assert_eq!(count, num_lines);
+        });
+    });
+
+    group.bench_function(BenchmarkId::new("mmap_records", "1M_lines"), |b| {
+        b.iter(|| {
+            let contents = ListFile::open(&list).unwrap();
+            let mut count = 0;
+            for record in contents.records() {
+                black_box(record_path(record));
+                count += 1;
+            }
+            assert_eq!(count, num_lines);
+        });
+    });
+
+    group.finish();
+}
+
 // ============================================================================
 // Benchmarks: Large Scale (10s+ deletion times)
 // ============================================================================
@@ -636,6 +940,135 @@ fn bench_large_scale_flat(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_delete_order(c: &mut Criterion) {
+    let fast_rm = get_fast_rm_binary();
+    let mut group = c.benchmark_group("large_scale_delete_order");
+    group.sampling_mode(SamplingMode::Flat);
+    group.sample_size(10);
+    group.measurement_time(std::time::Duration::from_secs(60));
+
+    // One flat directory is where the unlink order within a directory matters
+    let file_count = 200_000;
+    group.throughput(Throughput::Elements(file_count as u64));
+
+    for order in ["natural", "name", "inode"] {
+        group.bench_with_input(
+            BenchmarkId::new(order, "200k_files"),
+            &file_count,
+            |b, &count| {
+                b.iter_with_setup(
+                    || {
+                        let temp_dir = TempDir::new().unwrap();
+                        let target = create_target_dir(&temp_dir);
+                        create_flat_structure_parallel(&target, count);
+                        (temp_dir, target)
+                    },
+                    |(temp_dir, target)| {
+                        let output = Command::new(&fast_rm)
+                            .args(["--delete-order", order])
+                            .arg(&target)
+                            .output()
+                            .expect("Failed to run fast-rm");
+                        assert!(output.status.success(), "fast-rm failed: {:?}", output);
+                        drop(temp_dir);
+                        black_box(())
+                    },
+                );
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn bench_engine_crossover(c: &mut Criterion) {
+    let fast_rm = get_fast_rm_binary();
+    let mut group = c.benchmark_group("large_scale_engine_crossover");
+    group.sampling_mode(SamplingMode::Flat);
+    group.sample_size(10);
+    group.measurement_time(std::time::Duration::from_secs(60));
+
+    // Recursion should win on the small input and the pipeline on the large
+    // one; --engine auto switches at engine::SMALL_INPUT_ENTRIES
+    for (label, file_count) in [("100_files", 100), ("200k_files", 200_000)] {
+        for engine in ["recursive", "pipeline"] {
+            group.bench_with_input(
+                BenchmarkId::new(engine, label),
+                &file_count,
+                |b, &count| {
+                    b.iter_with_setup(
+                        || {
+                            let temp_dir = TempDir::new().unwrap();
+                            let target = create_target_dir(&temp_dir);
+                            create_flat_structure_parallel(&target, count);
+                            (temp_dir, target)
+                        },
+                        |(temp_dir, target)| {
+                            let output = Command::new(&fast_rm)
+                                .args(["--engine", engine])
+                                .arg(&target)
+                                .output()
+                                .expect("Failed to run fast-rm");
+                            assert!(output.status.success(), "fast-rm failed: {:?}", output);
+                            drop(temp_dir);
+                            black_box(())
+                        },
+                    );
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_parallel_top_level(c: &mut Criterion) {
+    let fast_rm = get_fast_rm_binary();
+    let mut group = c.benchmark_group("large_scale_parallel_top_level");
+    group.sampling_mode(SamplingMode::Flat);
+    group.sample_size(10);
+    group.measurement_time(std::time::Duration::from_secs(60));
+
+    // Eight large sibling trees given as separate arguments
+    let (trees, files_per_tree) = (8, 25_000);
+    group.throughput(Throughput::Elements((trees * files_per_tree) as u64));
+
+    for lanes in [1, 2, 8] {
+        group.bench_with_input(
+            BenchmarkId::new("lanes", lanes),
+            &lanes,
+            |b, &lanes| {
+                b.iter_with_setup(
+                    || {
+                        let temp_dir = TempDir::new().unwrap();
+                        let targets: Vec<_> = (0..trees)
+                            .map(|i| {
+                                let target = temp_dir.path().join(format!("tree_{}", i));
+                                fs::create_dir(&target).unwrap();
+                                create_flat_structure_parallel(&target, files_per_tree);
+                                target
+                            })
+                            .collect();
+                        (temp_dir, targets)
+                    },
+                    |(temp_dir, targets)| {
+                        let output = Command::new(&fast_rm)
+                            .args(["--parallel-top-level", &lanes.to_string()])
+                            .args(&targets)
+                            .output()
+                            .expect("Failed to run fast-rm");
+                        assert!(output.status.success(), "fast-rm failed: {:?}", output);
+                        drop(temp_dir);
+                        black_box(())
+                    },
+                );
+            },
+        );
+    }
+
+    group.finish();
+}
+
 fn bench_large_scale_nested(c: &mut Criterion) {
     let fast_rm = get_fast_rm_binary();
     let mut group = c.benchmark_group("large_scale_nested");
@@ -737,6 +1170,52 @@ fn bench_extreme_scale(c: &mut Criterion) {
         );
     });
 
+    // Same run with every worker pinned to its own CPU
+    group.bench_function("fast-rm --pin-threads/1M_files", |b| {
+        b.iter_with_setup(
+            || {
+                let temp_dir = TempDir::new().unwrap();
+                let target = create_target_dir(&temp_dir);
+                create_flat_structure_parallel(&target, file_count);
+                (temp_dir, target)
+            },
+            |(temp_dir, target)| {
+                let output = Command::new(&fast_rm)
+                    .arg("--pin-threads")
+                    .arg(&target)
+                    .output()
+                    .expect("Failed to run fast-rm");
+                assert!(output.status.success(), "fast-rm failed: {:?}", output);
+                drop(temp_dir);
+                black_box(())
+            },
+        );
+    });
+
+    // Progress counter batching: 1 touches the shared atomics per deletion
+    for chunk in [1, 16, 64, 256] {
+        group.bench_function(format!("fast-rm --chunk-size {}/1M_files", chunk), |b| {
+            b.iter_with_setup(
+                || {
+                    let temp_dir = TempDir::new().unwrap();
+                    let target = create_target_dir(&temp_dir);
+                    create_flat_structure_parallel(&target, file_count);
+                    (temp_dir, target)
+                },
+                |(temp_dir, target)| {
+                    let output = Command::new(&fast_rm)
+                        .args(["--chunk-size", &chunk.to_string()])
+                        .arg(&target)
+                        .output()
+                        .expect("Failed to run fast-rm");
+                    assert!(output.status.success(), "fast-rm failed: {:?}", output);
+                    drop(temp_dir);
+                    black_box(())
+                },
+            );
+        });
+    }
+
     // Benchmark system rm -r
     group.bench_function("rm -r/1M_files", |b| {
         b.iter_with_setup(
@@ -768,6 +1247,10 @@ criterion_group!(
     bench_deep_chain,       // Deep directory chains
     bench_mixed_sizes,      // Various file sizes
     bench_thread_scaling,   // Thread pool tuning
+    bench_parallel_threshold, // Small-directory fan-out
+    bench_auto_threads,     // Auto-tuned deleter pool
+    bench_fast_known_dirs,  // node_modules preset
+    bench_files_from_list,  // --files-from list reading
 );
 
 // Separate group for large-scale tests (run with: cargo bench -- "large_scale")
@@ -776,7 +1259,8 @@ criterion_group!(
     config = Criterion::default()
         .sample_size(10)
         .measurement_time(std::time::Duration::from_secs(60));
-    targets = bench_large_scale_flat, bench_large_scale_nested
+    targets = bench_large_scale_flat, bench_large_scale_nested, bench_delete_order,
+        bench_parallel_top_level, bench_engine_crossover
 );
 
 // Extreme scale tests (run with: cargo bench -- "extreme_scale")
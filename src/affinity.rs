@@ -0,0 +1,102 @@
+//! Pinning worker threads to CPUs (`--pin-threads`)
+
+use std::io;
+
+/// Whether thread pinning is implemented on this platform
+pub const SUPPORTED: bool = cfg!(target_os = "linux");
+
+/// Hands out the CPUs this process may run on, one per worker
+///
+/// Worker `i` gets the `i`-th allowed CPU, wrapping around when there are
+/// more workers than CPUs. Pinning is best effort: a thread that cannot be
+/// pinned keeps running wherever the scheduler puts it.
+#[derive(Debug)]
+pub struct CpuPinner {
+    cpus: Vec<usize>,
+}
+
+impl CpuPinner {
+    /// A pinner over the current affinity mask, or `None` where unsupported
+    pub fn new() -> Option<Self> {
+        let cpus = allowed_cpus();
+        (!cpus.is_empty()).then_some(Self { cpus })
+    }
+
+    /// Pin the calling thread, worker number `worker`, to its CPU
+    pub fn pin(&self, worker: usize) {
+        let _ = pin_current_thread(self.cpus[worker % self.cpus.len()]);
+    }
+}
+
+/// CPUs the calling thread may run on, ascending; empty if unknown
+#[cfg(target_os = "linux")]
+pub fn allowed_cpus() -> Vec<usize> {
+    // SAFETY: cpu_set_t is a plain bit mask, valid when zeroed, and the
+    // kernel writes at most size_of::<cpu_set_t>() bytes into it
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return Vec::new();
+        }
+        (0..libc::CPU_SETSIZE as usize)
+            .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+            .collect()
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn allowed_cpus() -> Vec<usize> {
+    Vec::new()
+}
+
+/// Restrict the calling thread to `cpu`
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(cpu: usize) -> io::Result<()> {
+    // SAFETY: as above; pid 0 is the calling thread
+    let rc = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if rc == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_cpu: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "thread pinning is only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_pinned_thread_runs_on_its_cpu() {
+        let pinner = CpuPinner::new().expect("affinity mask is readable");
+        let cpus = pinner.cpus.clone();
+        let worker = cpus.len() + 1;
+        // Pin a throwaway thread so the test harness thread is left alone
+        let after = std::thread::spawn(move || {
+            pinner.pin(worker);
+            allowed_cpus()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(after, [cpus[1 % cpus.len()]]);
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    #[test]
+    fn test_pinning_unsupported() {
+        assert!(CpuPinner::new().is_none());
+        assert!(pin_current_thread(0).is_err());
+    }
+}
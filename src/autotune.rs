@@ -0,0 +1,164 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// How often the controller samples the queue (`--auto-threads`)
+pub const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Consecutive samples a condition must hold before the pool is resized
+const STREAK: usize = 3;
+
+/// Fraction of the queue capacity treated as "full"
+const FULL_RATIO: f64 = 0.9;
+
+/// Minimum rate gain (5%) a growth step must bring to keep growing
+const MIN_GAIN: f64 = 1.05;
+
+/// Number of deleter workers allowed to run; the rest stay parked
+///
+/// All workers are spawned up front and worker `id` only takes jobs while
+/// `id < active`, so resizing is a single atomic store.
+#[derive(Debug)]
+pub struct ThreadGate {
+    active: AtomicUsize,
+}
+
+impl ThreadGate {
+    pub fn new(active: usize) -> Self {
+        Self {
+            active: AtomicUsize::new(active),
+        }
+    }
+
+    pub fn is_active(&self, id: usize) -> bool {
+        id < self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn set_active(&self, active: usize) {
+        self.active.store(active, Ordering::Relaxed);
+    }
+
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+}
+
+/// Queue-depth driven controller for the number of active deleters
+///
+/// A queue that stays nearly full means deleters are the bottleneck, so one
+/// more is woken; a queue that stays empty means scanners are, so one is
+/// parked. If waking a deleter did not raise the deletion rate, the extra
+/// thread is only adding contention: it is parked again and the pool stops
+/// growing past that size.
+#[derive(Debug)]
+pub struct AutoTuner {
+    active: usize,
+    ceiling: usize,
+    full_streak: usize,
+    empty_streak: usize,
+    /// Rate observed just before the last growth step, awaiting evaluation
+    rate_before_growth: Option<f64>,
+}
+
+impl AutoTuner {
+    pub fn new(initial: usize, ceiling: usize) -> Self {
+        let ceiling = ceiling.max(1);
+        Self {
+            active: initial.clamp(1, ceiling),
+            ceiling,
+            full_streak: 0,
+            empty_streak: 0,
+            rate_before_growth: None,
+        }
+    }
+
+    /// A modest starting pool: a quarter of the ceiling, rounded up
+    pub fn initial_for(ceiling: usize) -> usize {
+        ceiling.saturating_add(3) / 4
+    }
+
+    /// Feed one sample and return the number of deleters that should run
+    pub fn step(&mut self, depth: usize, capacity: usize, rate: f64) -> usize {
+        if depth as f64 >= capacity as f64 * FULL_RATIO {
+            self.full_streak += 1;
+            self.empty_streak = 0;
+        } else if depth == 0 {
+            self.empty_streak += 1;
+            self.full_streak = 0;
+        } else {
+            self.full_streak = 0;
+            self.empty_streak = 0;
+        }
+
+        if self.full_streak >= STREAK {
+            self.full_streak = 0;
+            match self.rate_before_growth.take() {
+                Some(before) if rate < before * MIN_GAIN => {
+                    // The last extra deleter did not help: undo it and cap here
+                    self.active -= 1;
+                    self.ceiling = self.active;
+                }
+                _ if self.active < self.ceiling => {
+                    self.rate_before_growth = Some(rate);
+                    self.active += 1;
+                }
+                _ => {}
+            }
+        } else if self.empty_streak >= STREAK {
+            self.empty_streak = 0;
+            self.rate_before_growth = None;
+            self.active = (self.active - 1).max(1);
+        }
+
+        self.active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_initial_pool_is_modest() {
+        assert_eq!(AutoTuner::initial_for(1), 1);
+        assert_eq!(AutoTuner::initial_for(8), 2);
+        assert_eq!(AutoTuner::initial_for(10), 3);
+    }
+
+    #[test]
+    fn test_grows_when_full_and_shrinks_when_empty() {
+        let mut tuner = AutoTuner::new(2, 8);
+
+        // Two full samples are not yet a trend
+        assert_eq!(tuner.step(100, 100, 10.0), 2);
+        assert_eq!(tuner.step(95, 100, 10.0), 2);
+        assert_eq!(tuner.step(100, 100, 10.0), 3);
+        // The growth paid off, so keep growing
+        let steps = |tuner: &mut AutoTuner, n: usize, depth: usize| {
+            (0..n).map(|_| tuner.step(depth, 100, 20.0)).last().unwrap()
+        };
+        assert_eq!(steps(&mut tuner, 3, 100), 4);
+        assert_eq!(steps(&mut tuner, 3, 0), 3);
+        // Never below one deleter
+        assert_eq!(steps(&mut tuner, 30, 0), 1);
+    }
+
+    #[test]
+    fn test_reverts_growth_that_did_not_help() {
+        let mut tuner = AutoTuner::new(2, 8);
+        let mut full = |n: usize| (0..n).map(|_| tuner.step(100, 100, 50.0)).last().unwrap();
+        assert_eq!(full(3), 3);
+        // Still full, but the rate is flat: back to 2 and stay there
+        assert_eq!(full(3), 2);
+        assert_eq!(full(9), 2);
+    }
+
+    #[test]
+    fn test_gate() {
+        let gate = ThreadGate::new(2);
+        assert!(gate.is_active(1));
+        assert!(!gate.is_active(2));
+        gate.set_active(3);
+        assert!(gate.is_active(2));
+        assert_eq!(gate.active(), 3);
+    }
+}
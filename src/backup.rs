@@ -0,0 +1,145 @@
+//! Copying files aside before they are deleted (`--backup-to`)
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::errors::RemoveError;
+
+/// A mirror of the deleted files under a backup directory
+///
+/// Each file is copied to the backup directory under its root's name
+/// followed by its path below the root, so `build/out/app` deleted from
+/// the argument `build` ends up as `<dir>/build/out/app`. Only regular
+/// files are copied; symlinks, special files and directories are not.
+#[derive(Debug)]
+pub struct Backup {
+    dir: PathBuf,
+    roots: Vec<PathBuf>,
+    /// Compare checksums of each copy and its original (`--backup-verify`)
+    verify: bool,
+}
+
+impl Backup {
+    /// A mirror of files under the canonical `roots`; `dir` is refused if it
+    /// lies inside one of the roots, and created otherwise
+    pub fn new(dir: &Path, roots: &[PathBuf], verify: bool) -> Result<Self, RemoveError> {
+        let failed = |e| RemoveError::BackupFailed(dir.to_path_buf(), e);
+        let dir = resolve(dir).map_err(failed)?;
+        if let Some(root) = roots.iter().find(|root| dir.starts_with(root)) {
+            return Err(RemoveError::BackupFailed(
+                dir,
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "the backup directory is inside {:?}, which is deleted",
+                        root
+                    ),
+                ),
+            ));
+        }
+        fs::create_dir_all(&dir).map_err(failed)?;
+        Ok(Self {
+            dir,
+            roots: roots.to_vec(),
+            verify,
+        })
+    }
+
+    /// Where `path` is copied to
+    pub fn target(&self, path: &Path) -> PathBuf {
+        let root = self.roots.iter().find(|root| path.starts_with(root));
+        let relative = match root {
+            Some(root) => {
+                let name = root.file_name().map(Path::new).unwrap_or(Path::new(""));
+                name.join(path.strip_prefix(root).unwrap_or(path))
+            }
+            // Not under a root, e.g. from a plan: mirror the whole path
+            None => path.components().skip(1).collect(),
+        };
+        self.dir.join(relative)
+    }
+
+    /// Copy the file at `path` into the mirror, creating parent directories
+    pub fn copy(&self, path: &Path) -> io::Result<()> {
+        let target = self.target(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(path, &target)?;
+        if self.verify && checksum(path)? != checksum(&target)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the copy at {:?} does not match the original", target),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Canonical form of `dir`, which need not exist yet: its deepest existing
+/// ancestor is canonicalized and the rest appended
+fn resolve(dir: &Path) -> io::Result<PathBuf> {
+    let dir = std::env::current_dir()?.join(dir);
+    let mut missing = Vec::new();
+    let mut existing = dir.as_path();
+    while !existing.exists() {
+        missing.extend(existing.file_name());
+        existing = existing.parent().unwrap_or(Path::new("/"));
+    }
+    let mut resolved = existing.canonicalize()?;
+    resolved.extend(missing.iter().rev());
+    Ok(resolved)
+}
+
+/// 64-bit FNV-1a checksum of a file's contents
+fn checksum(path: &Path) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut buf = vec![0; 64 * 1024];
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            return Ok(hash);
+        }
+        for &byte in &buf[..read] {
+            hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_target_mirrors_the_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap().join("build");
+        fs::create_dir(&root).unwrap();
+        let roots = std::slice::from_ref(&root);
+        let backup = Backup::new(&temp_dir.path().join("backup"), roots, false).unwrap();
+        let dir = temp_dir.path().canonicalize().unwrap().join("backup");
+        assert_eq!(
+            backup.target(&root.join("out/app")),
+            dir.join("build/out/app")
+        );
+        assert_eq!(backup.target(&root), dir.join("build"));
+
+        // Inside a deleted root, the backup would be deleted too
+        assert!(Backup::new(&root.join("new/backup"), roots, false).is_err());
+        assert!(!root.join("new").exists());
+    }
+
+    #[test]
+    fn test_checksum_tells_contents_apart() {
+        let temp_dir = TempDir::new().unwrap();
+        let (a, b) = (temp_dir.path().join("a"), temp_dir.path().join("b"));
+        fs::write(&a, "same").unwrap();
+        fs::write(&b, "same").unwrap();
+        assert_eq!(checksum(&a).unwrap(), checksum(&b).unwrap());
+        fs::write(&b, "diff").unwrap();
+        assert_ne!(checksum(&a).unwrap(), checksum(&b).unwrap());
+    }
+}
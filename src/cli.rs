@@ -1,5 +1,78 @@
-use clap::Parser;
-use std::path::PathBuf;
+use clap::builder::{OsStringValueParser, TypedValueParser};
+use clap::{Parser, ValueEnum};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::engine::Engine;
+use crate::fsdetect::FsProfile;
+use crate::pool::DeleteThreads;
+use crate::progress::{DEFAULT_REFRESH_MS, MAX_REFRESH_MS, MIN_REFRESH_MS};
+use crate::schema::OutputVersion;
+
+/// Default for `--parallel-threshold`
+pub const DEFAULT_PARALLEL_THRESHOLD: usize = 32;
+
+/// Default for `--chunk-size`
+pub const DEFAULT_CHUNK_SIZE: usize = 64;
+
+/// Default for `--max-symlink-depth`, the kernel's own limit before ELOOP
+pub const DEFAULT_MAX_SYMLINK_DEPTH: usize = 40;
+
+/// Directory names `--fast-known-dirs` recognises among the given paths
+pub const KNOWN_DIRS: [&str; 2] = ["node_modules", ".git"];
+
+/// When to colorize output (`--color`)
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Color only when stdout and stderr are terminals and `NO_COLOR` is unset
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Resolve the choice against the environment
+    pub fn enabled(self, no_color_env: bool, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => !no_color_env && is_terminal,
+        }
+    }
+
+    /// Apply the choice to every `colored` call in the process
+    pub fn apply(self) {
+        // https://no-color.org: any non-empty value disables color
+        let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let is_terminal = std::io::stdout().is_terminal() && std::io::stderr().is_terminal();
+        colored::control::set_override(self.enabled(no_color_env, is_terminal));
+    }
+}
+
+/// Order in which a directory's entries are enqueued (`--delete-order`)
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeleteOrder {
+    /// Whatever order the directory is read in
+    #[default]
+    Natural,
+    /// Sorted by file name
+    Name,
+    /// Sorted by inode number (Unix; name order elsewhere)
+    Inode,
+}
+
+/// Kind of entry selected by `--only-type`
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryType {
+    /// Regular files, and FIFOs, sockets and device nodes
+    Files,
+    /// Directories
+    Dirs,
+    /// Symbolic links
+    Symlinks,
+}
 
 #[derive(Parser, Debug)]
 #[clap(
@@ -10,51 +83,884 @@ use std::path::PathBuf;
 )]
 pub struct Cli {
     /// Files or directories to remove
-    #[clap(required = true, num_args = 1..)]
+    // Parsed as plain OS strings so an empty argument reaches
+    // `validate_args`, which names it, instead of clap's "no value" error
+    #[clap(
+        required_unless_present = "plan_in",
+        num_args = 1..,
+        value_parser = OsStringValueParser::new().map(PathBuf::from)
+    )]
     pub paths: Vec<PathBuf>,
 
     /// Verbosity level: -v for standard, -vv for detailed
     #[clap(short = 'v', long = "verbose", action = clap::ArgAction::Count)]
     pub verbosity: u8,
 
-    /// Do not actually remove anything, just show what would be done
+    /// Do not actually remove anything, just show what would be done; exits
+    /// with code 3 if nothing would be deleted
     #[clap(short = 'n', long = "dry-run")]
     pub dry_run: bool,
 
+    /// With --dry-run, check that each entry could actually be removed and
+    /// report the ones that would fail as errors
+    #[clap(long = "verify", requires = "dry_run")]
+    pub verify: bool,
+
+    /// With --dry-run, list every entry as it is decided: `-` for what would
+    /// be deleted, dimmed with the reason for what is kept
+    #[clap(long = "diff", requires = "dry_run")]
+    pub diff: bool,
+
+    /// With --dry-run, write the operations it would perform to this file,
+    /// in order, for review and a later --plan-in
+    #[clap(long = "plan-out", value_name = "FILE", requires = "dry_run")]
+    pub plan_out: Option<PathBuf>,
+
+    /// Perform exactly the operations in a plan written by --plan-out, in
+    /// order, without scanning; entries that are gone or changed type since
+    /// are reported as errors
+    #[clap(
+        long = "plan-in",
+        value_name = "FILE",
+        conflicts_with_all = [
+            "paths", "plan_out", "rename_first", "stage_dir", "contents_only", "atomic",
+            "prune_empty", "pre_scan", "abort_over_bytes", "stay_within_root",
+            "resume", "require_typed_confirm", "force"
+        ]
+    )]
+    pub plan_in: Option<PathBuf>,
+
     /// Number of threads to use (defaults to number of CPU cores)
     /// Deprecated: use --scan-threads and --delete-threads for fine-grained control
-    #[clap(short = 'j', long = "threads")]
+    #[clap(short = 'j', long = "threads", env = "FASTRM_THREADS")]
     pub threads: Option<usize>,
 
     /// Number of threads for scanning (defaults to number of CPU cores)
     /// Takes precedence over --threads if both are specified
-    #[clap(long = "scan-threads")]
+    #[clap(long = "scan-threads", env = "FASTRM_SCAN_THREADS")]
     pub scan_threads: Option<usize>,
 
-    /// Number of threads for deletion (defaults to number of CPU cores)
+    /// Number of threads for deletion (defaults to number of CPU cores), or
+    /// `auto` to start a few and add more while the queue stays deep, up to
+    /// --threads or twice the cores; replaces --auto-threads
     /// Takes precedence over --threads if both are specified
-    #[clap(long = "delete-threads")]
-    pub delete_threads: Option<usize>,
+    #[clap(long = "delete-threads", env = "FASTRM_DELETE_THREADS", value_name = "N|auto", value_parser = crate::pool::parse_delete_threads)]
+    pub delete_threads: Option<DeleteThreads>,
+
+    /// How to delete: auto recurses on small inputs without filters or other
+    /// extras, pipeline always starts the scanner and deleter pools,
+    /// recursive never does
+    #[clap(long = "engine", value_enum, value_name = "ENGINE", default_value_t = Engine::Auto)]
+    pub engine: Engine,
+
+    /// Rescan and retry directories that gained entries while being deleted
+    #[clap(short = 'f', long = "force")]
+    pub force: bool,
+
+    /// Clear the immutable and append-only attributes (chattr +i, +a) that
+    /// keep an entry from being removed; needs CAP_LINUX_IMMUTABLE (Linux only)
+    #[clap(long = "clear-attrs")]
+    pub clear_attrs: bool,
+
+    /// Leave files that another process has open in place, with their
+    /// directories, instead of failing on them
+    #[cfg(windows)]
+    #[clap(long = "skip-open")]
+    pub skip_open: bool,
 
     /// Continue processing even if errors occur
-    #[clap(short = 'c', long = "continue-on-error")]
+    ///
+    /// An argument that cannot be accessed at all (missing, or under a
+    /// directory without search permission) is then skipped with a warning;
+    /// otherwise nothing is deleted.
+    #[clap(
+        short = 'c',
+        long = "continue-on-error",
+        env = "FASTRM_CONTINUE_ON_ERROR",
+        value_parser = clap::builder::FalseyValueParser::new()
+    )]
     pub continue_on_error: bool,
+
+    /// Maximum number of pending jobs between scanners and deleters
+    /// (defaults to scan threads * 1000, min 10000). Bounds peak memory on huge trees
+    #[clap(
+        long = "max-queue",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub max_queue: Option<usize>,
+
+    /// Print a table of deleted items, bytes and errors for each path at the end
+    #[clap(long = "per-path-summary")]
+    pub per_path_summary: bool,
+
+    /// Like --per-path-summary, as an aligned table with readable sizes and
+    /// how long after the start each path was done
+    #[clap(long = "summary-table")]
+    pub summary_table: bool,
+
+    /// Read .fastrmignore files (gitignore syntax) in each directory and keep matching entries
+    #[clap(long = "use-ignore-files")]
+    pub use_ignore_files: bool,
+
+    /// Use one scanner and one deleter and visit entries in name order,
+    /// making output and error reporting deterministic
+    #[clap(long = "sequential")]
+    pub sequential: bool,
+
+    /// Record the latency of every removal and print min/mean/p50/p99/max at the end
+    #[clap(long = "stats")]
+    pub stats: bool,
+
+    /// Follow symlinks and delete what they point to (as well as the links)
+    #[clap(long = "follow-symlinks")]
+    pub follow_symlinks: bool,
+
+    /// For the given paths only, replace a symlink to a directory with its
+    /// target (interior symlinks are still removed as links)
+    #[clap(long = "dereference-args")]
+    pub dereference_args: bool,
+
+    /// With --follow-symlinks or --dereference-args, stop following a chain
+    /// of more than N symlinks, e.g. one that loops: a given path is refused
+    /// with an error, a link met while scanning is removed as a link
+    #[clap(
+        long = "max-symlink-depth",
+        value_name = "N",
+        default_value_t = DEFAULT_MAX_SYMLINK_DEPTH,
+        value_parser = clap::value_parser!(u64).range(1..).map(|n| n as usize)
+    )]
+    pub max_symlink_depth: usize,
+
+    /// Refuse to delete anything that resolves outside the given paths
+    #[clap(long = "stay-within-root")]
+    pub stay_within_root: bool,
+
+    /// Rename each top-level directory to a hidden sibling before deleting it
+    #[clap(long = "rename-first")]
+    pub rename_first: bool,
+
+    /// Experimental: first rename the children of each given directory into
+    /// a hidden subdirectory of it, so it looks empty at once; a child that
+    /// cannot be renamed is deleted in place
+    #[clap(
+        long = "rename-children",
+        conflicts_with_all = [
+            "stage_dir", "tombstone", "skip_hidden", "exclude_vcs", "keep_globs",
+            "keep_list", "use_ignore_files", "match_prefix", "files_from", "resume",
+            "atomic", "backup_to", "plan_in", "two_phase", "top_largest", "until_free",
+            "no_recursive", "keep_dirs"
+        ]
+    )]
+    pub rename_children: bool,
+
+    /// Move each given path into DIR (same file system, created if needed)
+    /// before deleting it, so it disappears from its place at once
+    #[clap(
+        long = "stage-dir",
+        value_name = "DIR",
+        conflicts_with_all = ["rename_first", "no_recursive"]
+    )]
+    pub stage_dir: Option<PathBuf>,
+
+    /// Truncate files to zero length instead of deleting them, leaving an
+    /// empty placeholder of the same name; symlinks and empty directories
+    /// are removed as usual
+    #[clap(
+        long = "tombstone",
+        conflicts_with_all = ["rename_first", "stage_dir", "plan_in"]
+    )]
+    pub tombstone: bool,
+
+    /// With --tombstone, write this line into each truncated file
+    #[clap(long = "tombstone-marker", value_name = "TEXT", requires = "tombstone")]
+    pub tombstone_marker: Option<String>,
+
+    /// Empty each given directory but keep the directory itself
+    #[clap(long = "contents-only", conflicts_with_all = ["rename_first", "stage_dir"])]
+    pub contents_only: bool,
+
+    /// Like rm without -r: remove the given files and symlinks, but report
+    /// directories as errors instead of deleting them
+    #[clap(
+        long = "no-recursive",
+        conflicts_with_all = ["contents_only", "rename_first", "prune_empty"]
+    )]
+    pub no_recursive: bool,
+
+    /// Scan directories with at most this many entries on the current thread
+    /// instead of fanning out to the scanner pool (defaults to 32)
+    #[clap(long = "parallel-threshold", value_name = "N")]
+    pub parallel_threshold: Option<usize>,
+
+    /// Deleters add to the shared progress counters once per this many
+    /// deletions rather than for each one; 1 updates them every time
+    #[clap(long = "chunk-size", value_name = "N", default_value_t = DEFAULT_CHUNK_SIZE)]
+    pub chunk_size: usize,
+
+    /// Milliseconds between refreshes of the progress display; raise it over
+    /// slow or remote terminals
+    #[clap(
+        long = "progress-interval",
+        value_name = "MS",
+        default_value_t = DEFAULT_REFRESH_MS,
+        value_parser = clap::value_parser!(u64).range(MIN_REFRESH_MS..=MAX_REFRESH_MS)
+    )]
+    pub progress_interval: u64,
+
+    /// How many of the given paths are scanned at once; each one already uses
+    /// the whole scanner pool
+    #[clap(long = "parallel-top-level", value_name = "N", default_value_t = 1)]
+    pub parallel_top_level: usize,
+
+    /// Order in which each directory's files are unlinked; sorting can help
+    /// file systems with hashed or tree-indexed directories
+    #[clap(long = "delete-order", value_enum, default_value_t = DeleteOrder::Natural)]
+    pub delete_order: DeleteOrder,
+
+    /// Only delete entries of this type (repeatable). Directories are still
+    /// descended; one holding a kept entry is kept too, so `dirs` alone
+    /// removes only directories that contain nothing else
+    #[clap(
+        long = "only-type",
+        value_enum,
+        value_name = "TYPE",
+        conflicts_with = "prune_empty"
+    )]
+    pub only_type: Vec<EntryType>,
+
+    /// Only delete symlinks whose target does not exist; valid links, files
+    /// and directories are left alone
+    #[clap(
+        long = "broken-symlinks-only",
+        conflicts_with_all = ["only_type", "follow_symlinks", "prune_empty", "tombstone"]
+    )]
+    pub broken_symlinks_only: bool,
+
+    /// Delete files and symlinks but keep every directory, leaving the tree's
+    /// skeleton (with its permissions) in place
+    #[clap(
+        long = "keep-dirs",
+        conflicts_with_all = ["prune_empty", "rename_first", "stage_dir", "plan_in"]
+    )]
+    pub keep_dirs: bool,
+
+    /// Queue files of 64 KiB and more separately, largest first, and spread
+    /// them over the deleters in between the other jobs
+    #[clap(long = "size-aware")]
+    pub size_aware: bool,
+
+    /// Hardlink everything into a shadow directory first and restore it if a
+    /// deletion fails (single file system only)
+    #[clap(long = "atomic")]
+    pub atomic: bool,
+
+    /// Publish progress as JSON lines to clients of this Unix domain socket
+    #[cfg(unix)]
+    #[clap(long = "progress-socket", value_name = "PATH")]
+    pub progress_socket: Option<PathBuf>,
+
+    /// When to use colors: auto, always or never
+    #[clap(long = "color", value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// Draw the live progress display even when stdout is not a terminal
+    #[clap(long = "force-tui")]
+    pub force_tui: bool,
+
+    /// Draw no progress at all and print just the final summary line
+    #[clap(
+        long = "summary-only",
+        conflicts_with_all = ["verbosity", "diff", "force_tui"]
+    )]
+    pub summary_only: bool,
+
+    /// Start with a few deleters and grow or shrink the pool from queue
+    /// depth and deletion rate, up to the --delete-threads count
+    #[clap(long = "auto-threads")]
+    pub auto_threads: bool,
+
+    /// Keep at most N directory handles open while scanning, to stay under
+    /// the file descriptor limit on very wide or deep trees; listings are
+    /// then read in full before descending
+    #[clap(long = "max-open-dirs", value_name = "N")]
+    pub max_open_dirs: Option<usize>,
+
+    /// When a given path is a node_modules or .git directory, preset the
+    /// tuning options left unset for its many small files and directories
+    #[clap(long = "fast-known-dirs")]
+    pub fast_known_dirs: bool,
+
+    /// Pin each scanner and deleter thread to its own CPU (Linux only;
+    /// ignored with a warning elsewhere)
+    #[clap(long = "pin-threads")]
+    pub pin_threads: bool,
+
+    /// Time each directory from scan to removal and print the slowest ones
+    #[clap(long = "profile-dirs")]
+    pub profile_dirs: bool,
+
+    /// Tally deleted files by extension and print the top ones by count and by bytes
+    #[clap(long = "stats-by-ext")]
+    pub stats_by_ext: bool,
+
+    /// Delete the oldest files first, stopping as soon as the file system of
+    /// the first path has SIZE free (e.g. 20G); directories are kept
+    #[clap(
+        long = "until-free",
+        value_name = "SIZE",
+        value_parser = crate::filter::parse_size,
+        conflicts_with_all = [
+            "two_phase", "plan_in", "stage_dir", "rename_first", "atomic", "prune_empty"
+        ]
+    )]
+    pub until_free: Option<u64>,
+
+    /// Copy each file into this directory, under its path relative to the
+    /// given path's parent, before deleting it; a file that cannot be
+    /// copied is not deleted. Symlinks and special files are not copied
+    #[clap(
+        long = "backup-to",
+        value_name = "DIR",
+        conflicts_with_all = ["plan_in", "stage_dir", "rename_first"]
+    )]
+    pub backup_to: Option<PathBuf>,
+
+    /// With --backup-to, compare checksums of each copy and its original
+    /// before deleting the original
+    #[clap(long = "backup-verify", requires = "backup_to")]
+    pub backup_verify: bool,
+
+    /// Delete only the N largest files under the given paths, found by a
+    /// full scan first; directories and smaller files are kept
+    #[clap(
+        long = "top-largest",
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = [
+            "two_phase", "until_free", "plan_in", "stage_dir", "rename_first", "atomic",
+            "prune_empty"
+        ]
+    )]
+    pub top_largest: Option<u64>,
+
+    /// Scan everything first and show the totals, then delete only after
+    /// Enter is pressed (exit code 2 otherwise); nothing is removed before
+    #[clap(
+        long = "two-phase",
+        conflicts_with_all = ["dry_run", "plan_in", "stage_dir", "rename_first", "atomic"]
+    )]
+    pub two_phase: bool,
+
+    /// Before anything else, delete N regular files sampled from as many
+    /// directories as possible to confirm deletion works, then stop;
+    /// add --probe-then-delete to go on with the full run
+    #[clap(
+        long = "probe",
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = [
+            "dry_run", "plan_in", "stage_dir", "rename_first", "atomic", "until_free",
+            "top_largest", "two_phase"
+        ]
+    )]
+    pub probe: Option<u64>,
+
+    /// With --probe, go on to delete everything once the sample files were
+    /// deleted without error
+    #[clap(long = "probe-then-delete", requires = "probe")]
+    pub probe_then_delete: bool,
+
+    /// Allow deleting the current directory or one of its parents; the
+    /// process first moves to / (by default such a target is refused)
+    #[clap(long = "allow-delete-cwd")]
+    pub allow_delete_cwd: bool,
+
+    /// Accept arguments ending in "." or "..", such as `.` or `build/..`;
+    /// each stands for the directory it names. With --contents-only,
+    /// `--allow-dot .` empties the current directory (by default such
+    /// arguments are refused, as rm does)
+    #[clap(long = "allow-dot")]
+    pub allow_dot: bool,
+
+    /// Count each target, show its entries and size, and require re-typing
+    /// its path before deleting (exit code 2 on mismatch)
+    #[clap(long = "require-typed-confirm")]
+    pub require_typed_confirm: bool,
+
+    /// Answer yes to every prompt (such as --require-typed-confirm) and,
+    /// instead of refusing overlapping paths, drop those inside another
+    /// given path; safety checks like --abort-over-bytes still apply
+    #[clap(short = 'y', long = "assume-yes")]
+    pub assume_yes: bool,
+
+    /// Process the paths exactly as given: no canonicalizing, so links and
+    /// automounts are not resolved up front, and no merging of repeated or
+    /// nested paths, so each is attempted on its own (the current directory
+    /// and / are still refused)
+    #[clap(long = "no-dedup")]
+    pub no_dedup: bool,
+
+    /// Leave dotfiles and dot-directories (and hidden files on Windows) in place
+    #[clap(long = "skip-hidden")]
+    pub skip_hidden: bool,
+
+    /// Leave version control metadata (.git, .hg, .svn) below the given
+    /// paths in place, keeping their parents
+    #[clap(long = "exclude-vcs")]
+    pub exclude_vcs: bool,
+
+    /// Leave FIFOs, sockets, device nodes and mount points in place (by
+    /// default they are removed with a warning)
+    #[clap(long = "no-special")]
+    pub no_special: bool,
+
+    /// Run this shell command for each error (best effort, rate limited), with
+    /// FASTRM_ERROR_PATH and FASTRM_ERROR_MSG set
+    #[clap(long = "on-error", value_name = "CMD")]
+    pub on_error: Option<String>,
+
+    /// Append every deleted path to this file, fsynced periodically
+    #[clap(long = "journal", value_name = "FILE")]
+    pub journal: Option<PathBuf>,
+
+    /// Fsync the directories deletions changed, batched, so the deletions
+    /// survive a crash
+    #[clap(long = "fsync-dirs")]
+    pub fsync_dirs: bool,
+
+    /// Skip paths recorded as deleted in this journal by an interrupted run,
+    /// and keep appending to it
+    #[clap(long = "resume", value_name = "FILE")]
+    pub resume: Option<PathBuf>,
+
+    /// Only delete paths at or below this prefix
+    #[clap(long = "match-prefix", value_name = "PREFIX")]
+    pub match_prefix: Option<PathBuf>,
+
+    /// Only delete entries last modified more than this long ago (e.g. 30m, 12h, 7d)
+    #[clap(long = "older-than", value_name = "AGE", value_parser = crate::filter::parse_duration)]
+    pub older_than: Option<Duration>,
+
+    /// Only delete entries last modified before this file was, e.g. a marker
+    /// touched by each build; the file itself is kept
+    #[clap(long = "older-than-file", value_name = "FILE")]
+    pub older_than_file: Option<PathBuf>,
+
+    /// Only delete entries matching this expression, e.g.
+    /// "size > 10M and name ~ '*.log' and age > 7d"; tests are size, age,
+    /// name and type, combined with and, or, not and parentheses
+    #[clap(long = "expr", value_name = "EXPR", value_parser = crate::expr::Expr::parse)]
+    pub expr: Option<crate::expr::Expr>,
+
+    /// Stop deleting after this long (e.g. 90s, 30m) and exit with code 124,
+    /// leaving the rest in place; 0 deletes nothing
+    #[clap(long = "timeout", value_name = "DUR", value_parser = crate::filter::parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// When a deletion has been running for this long (e.g. 30s), print the
+    /// path each stuck deleter is on, such as a file on a hung network mount
+    #[clap(long = "stuck-timeout", value_name = "DUR", value_parser = crate::filter::parse_duration)]
+    pub stuck_timeout: Option<Duration>,
+
+    /// Give up on a single removal after this long (e.g. 10s), count it as
+    /// an error and move on; each removal then runs on a helper thread of
+    /// its own, and an abandoned one is left blocked in the background
+    #[clap(long = "op-timeout", value_name = "DUR", value_parser = crate::filter::parse_duration)]
+    pub op_timeout: Option<Duration>,
+
+    /// Abort once more than N errors have occurred, leaving the rest in place
+    #[clap(long = "max-errors", value_name = "N")]
+    pub max_errors: Option<usize>,
+
+    /// After deleting, also remove directories left empty (bottom-up)
+    #[clap(long = "prune-empty")]
+    pub prune_empty: bool,
+
+    /// Only delete the paths listed in this file (one per line, or
+    /// NUL-terminated as from find -print0)
+    #[clap(long = "files-from", value_name = "FILE")]
+    pub files_from: Option<PathBuf>,
+
+    /// Only delete entries whose inode number is listed in this file (one
+    /// per line); with --prune-empty, directories left empty go too
+    #[cfg(unix)]
+    #[clap(long = "inodes-from", value_name = "FILE")]
+    pub inodes_from: Option<PathBuf>,
+
+    /// Never delete the paths listed in this file (one per line, relative to
+    /// each given path); their parent directories are kept too
+    #[clap(long = "keep-list", value_name = "FILE")]
+    pub keep_list: Option<PathBuf>,
+
+    /// Never delete entries below the given paths whose name matches this
+    /// glob (`*` and `?`, e.g. '*.lock'); repeatable. Their parent
+    /// directories are kept too
+    #[clap(long = "keep", value_name = "GLOB")]
+    pub keep_globs: Vec<String>,
+
+    /// Count everything first so progress shows a real percentage and ETA;
+    /// the tree is traversed twice
+    #[clap(long = "pre-scan")]
+    pub pre_scan: bool,
+
+    /// Count everything first and abort with exit code 2, deleting nothing,
+    /// if the files add up to more than SIZE (e.g. 500G); a guard against
+    /// pointing at the wrong directory
+    #[clap(
+        long = "abort-over-bytes",
+        value_name = "SIZE",
+        value_parser = crate::filter::parse_size,
+        conflicts_with_all = ["rename_first", "stage_dir"]
+    )]
+    pub abort_over_bytes: Option<u64>,
+
+    /// Also write the final summary as a JSON object to this file, whatever
+    /// the exit code
+    #[clap(long = "summary-json-file", value_name = "PATH")]
+    pub summary_json_file: Option<PathBuf>,
+
+    /// Schema version of the JSON outputs (--summary-json-file,
+    /// --progress-socket); each object also carries it as "schema_version"
+    #[clap(
+        long = "output-version",
+        value_enum,
+        value_name = "N",
+        default_value_t = OutputVersion::V1
+    )]
+    pub output_version: OutputVersion,
+
+    /// Append one CSV line per run to this file (timestamp, outcome, dry
+    /// run, items, bytes freed, errors, seconds), with a header when new
+    #[clap(long = "stats-file", value_name = "PATH")]
+    pub stats_file: Option<PathBuf>,
+
+    /// Sample the queue, busy scanners and deletion rate during the run and
+    /// say at the end whether scanning or deleting held it back
+    #[clap(long = "concurrency-report")]
+    pub concurrency_report: bool,
+
+    /// Collect failed deletions and try each once more, bottom-up, after
+    /// the run
+    #[clap(long = "retry-failed")]
+    pub retry_failed: bool,
+
+    /// Write each deleted path to stdout, one per line; other messages go to
+    /// stderr (on Unix) and the live progress display is off
+    #[clap(long = "print-deleted")]
+    pub print_deleted: bool,
+
+    /// Like --print-deleted, but end each path with a NUL byte instead of a
+    /// newline, for xargs -0 and the like
+    #[clap(long = "print0")]
+    pub print0: bool,
 }
 
 impl Cli {
     /// Get the number of scanner threads to use
-    /// Priority: --scan-threads > --threads > CPU cores
+    /// Priority: --sequential > --scan-threads > --threads > CPU cores, where each
+    /// option falls back to its `FASTRM_*` environment variable when not given
     pub fn get_scan_threads(&self) -> usize {
+        if self.sequential {
+            return 1;
+        }
         self.scan_threads
             .or(self.threads)
             .unwrap_or_else(num_cpus::get)
     }
 
     /// Get the number of deleter threads to use
-    /// Priority: --delete-threads > --threads > CPU cores
+    /// Priority: --sequential > --delete-threads > --threads > CPU cores, where each
+    /// option falls back to its `FASTRM_*` environment variable when not given
     pub fn get_delete_threads(&self) -> usize {
-        self.delete_threads
-            .or(self.threads)
-            .unwrap_or_else(num_cpus::get)
+        if self.sequential {
+            return 1;
+        }
+        match self.delete_threads {
+            Some(DeleteThreads::Fixed(threads)) => threads,
+            // Unlinking is syscall bound, so an elastic pool may go past the cores
+            Some(DeleteThreads::Auto) => self.threads.unwrap_or_else(|| num_cpus::get() * 2),
+            None => self.threads.unwrap_or_else(num_cpus::get),
+        }
+    }
+
+    /// Whether deleters are started on demand (`--delete-threads auto`)
+    pub fn elastic_deleters(&self) -> bool {
+        !self.sequential && self.delete_threads == Some(DeleteThreads::Auto)
+    }
+
+    /// Whether an option only the pipeline engine implements is in use:
+    /// selecting, keeping, recording, checking or reordering entries
+    ///
+    /// The recursive engine deletes everything below each path, and only
+    /// honours --dry-run, --continue-on-error and the reporting options.
+    pub fn needs_pipeline(&self) -> bool {
+        let selects = self.match_prefix.is_some()
+            || self.files_from.is_some()
+            || self.inodes_from.is_some()
+            || self.older_than.is_some()
+            || self.older_than_file.is_some()
+            || self.expr.is_some()
+            || self.top_largest.is_some()
+            || self.until_free.is_some()
+            || self.plan_in.is_some()
+            || self.resume.is_some()
+            || !self.only_type.is_empty()
+            || self.broken_symlinks_only;
+        let keeps = self.rename_children
+            || self.use_ignore_files
+            || self.keep_list.is_some()
+            || !self.keep_globs.is_empty()
+            || self.skip_hidden
+            || self.exclude_vcs
+            || self.no_special
+            || self.keep_dirs
+            || self.contents_only
+            || self.no_recursive
+            || self.tombstone
+            || self.stay_within_root
+            || self.follow_symlinks;
+        #[cfg(windows)]
+        let keeps = keeps || self.skip_open;
+        let records = self.plan_out.is_some()
+            || self.journal.is_some()
+            || self.backup_to.is_some()
+            || self.print_deleted
+            || self.stats
+            || self.stats_by_ext
+            || self.profile_dirs
+            || self.concurrency_report
+            || self.fsync_dirs;
+        let checks = self.verify
+            || self.diff
+            || self.two_phase
+            || self.force
+            || self.clear_attrs
+            || self.retry_failed
+            || self.timeout.is_some()
+            || self.stuck_timeout.is_some()
+            || self.op_timeout.is_some()
+            || self.max_errors.is_some()
+            || self.max_open_dirs.is_some()
+            || self.atomic;
+        let orders = self.sequential || self.delete_order != DeleteOrder::Natural;
+        selects || keeps || records || checks || orders
+    }
+
+    /// Get the work queue capacity
+    /// Priority: --max-queue > max(scan threads * 1000, 10000)
+    pub fn get_queue_capacity(&self) -> usize {
+        self.max_queue
+            .unwrap_or_else(|| (self.get_scan_threads() * 1000).max(10000))
+    }
+
+    /// Get the most entries a directory may have to be scanned in place
+    /// Priority: --parallel-threshold > --fast-known-dirs > 32
+    pub fn get_parallel_threshold(&self) -> usize {
+        self.parallel_threshold
+            .unwrap_or(DEFAULT_PARALLEL_THRESHOLD)
+    }
+
+    /// With --fast-known-dirs, tune for a given `node_modules` or `.git`
+    /// directory: thousands of small directories holding small files
+    ///
+    /// Only options the user left unset are changed. Returns the name that
+    /// was recognised.
+    pub fn apply_known_dir_preset(&mut self) -> Option<&'static str> {
+        if !self.fast_known_dirs {
+            return None;
+        }
+        let name = self.paths.iter().find_map(|path| {
+            let file_name = path.file_name()?;
+            KNOWN_DIRS.into_iter().find(|known| file_name == *known)
+        })?;
+        // Unlinking small files is syscall bound, so twice the cores keeps the
+        // file system busy while some deleters wait
+        if self.delete_threads.is_none() && self.threads.is_none() {
+            self.delete_threads = Some(DeleteThreads::Fixed(num_cpus::get() * 2));
+        }
+        // Most package directories hold a handful of entries; fanning each
+        // out to the pool costs more than scanning it in place
+        if self.parallel_threshold.is_none() {
+            self.parallel_threshold = Some(256);
+        }
+        // Let the scanners run further ahead of the larger deleter pool
+        if self.max_queue.is_none() {
+            self.max_queue = Some(self.get_queue_capacity() * 4);
+        }
+        Some(name)
+    }
+
+    /// Size the deleter pool and queue for the file system being deleted
+    /// from (`fsdetect`), leaving options the user set alone
+    pub fn apply_fs_profile(&mut self, profile: &FsProfile) {
+        if self.delete_threads.is_none() && self.threads.is_none() {
+            self.delete_threads = Some(DeleteThreads::Fixed(profile.delete_threads));
+        }
+        if self.max_queue.is_none() {
+            self.max_queue = profile.queue_capacity;
+        }
+    }
+
+    /// Make the relative file and prefix options absolute against `cwd`, so
+    /// they keep naming the same paths after the process changes directory
+    /// (`--allow-delete-cwd`)
+    ///
+    /// The parent directory is resolved now, while it still exists: a path
+    /// like `../out.json` may lead through the directory about to go.
+    pub fn anchor_file_options(&mut self, cwd: &Path) {
+        for path in [
+            &mut self.plan_out,
+            &mut self.plan_in,
+            &mut self.stage_dir,
+            &mut self.progress_socket,
+            &mut self.journal,
+            &mut self.resume,
+            &mut self.files_from,
+            &mut self.inodes_from,
+            &mut self.match_prefix,
+            &mut self.older_than_file,
+            &mut self.keep_list,
+            &mut self.backup_to,
+            &mut self.summary_json_file,
+            &mut self.stats_file,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if path.is_relative() {
+                let joined = cwd.join(&*path);
+                *path = match (joined.parent(), joined.file_name()) {
+                    (Some(parent), Some(name)) => std::fs::canonicalize(parent)
+                        .map(|parent| parent.join(name))
+                        .unwrap_or(joined),
+                    _ => joined,
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_choice() {
+        let cli = Cli::parse_from(["fast-rm", "x"]);
+        assert_eq!(cli.color, ColorChoice::Auto);
+        let cli = Cli::parse_from(["fast-rm", "--color", "never", "x"]);
+        assert_eq!(cli.color, ColorChoice::Never);
+
+        assert!(ColorChoice::Auto.enabled(false, true));
+        assert!(!ColorChoice::Auto.enabled(true, true));
+        assert!(!ColorChoice::Auto.enabled(false, false));
+        // An explicit choice wins over NO_COLOR and redirection
+        assert!(ColorChoice::Always.enabled(true, false));
+        assert!(!ColorChoice::Never.enabled(false, true));
+    }
+
+    #[test]
+    fn test_queue_capacity_default_and_override() {
+        let cli = Cli::parse_from(["fast-rm", "--scan-threads", "4", "x"]);
+        assert_eq!(cli.get_queue_capacity(), 10000);
+
+        let cli = Cli::parse_from(["fast-rm", "--scan-threads", "20", "x"]);
+        assert_eq!(cli.get_queue_capacity(), 20000);
+
+        let cli = Cli::parse_from(["fast-rm", "--max-queue", "64", "x"]);
+        assert_eq!(cli.get_queue_capacity(), 64);
+
+        // A queue that holds nothing would make every send a rendezvous
+        assert!(Cli::try_parse_from(["fast-rm", "--max-queue", "0", "x"]).is_err());
+    }
+
+    #[test]
+    fn test_progress_interval_minimum() {
+        let cli = Cli::parse_from(["fast-rm", "x"]);
+        assert_eq!(cli.progress_interval, DEFAULT_REFRESH_MS);
+        let cli = Cli::parse_from(["fast-rm", "--progress-interval", "500", "x"]);
+        assert_eq!(cli.progress_interval, 500);
+        assert!(Cli::try_parse_from(["fast-rm", "--progress-interval", "5", "x"]).is_err());
+        assert!(Cli::try_parse_from(["fast-rm", "--progress-interval", "10001", "x"]).is_err());
+    }
+
+    #[test]
+    fn test_known_dir_preset_fills_unset_options() {
+        let mut cli = Cli::parse_from(["fast-rm", "--fast-known-dirs", "app/node_modules/"]);
+        assert_eq!(cli.apply_known_dir_preset(), Some("node_modules"));
+        assert_eq!(cli.get_parallel_threshold(), 256);
+        assert_eq!(cli.max_queue, Some(cli.get_scan_threads().max(10) * 4000));
+        assert_eq!(
+            cli.delete_threads,
+            Some(DeleteThreads::Fixed(num_cpus::get() * 2))
+        );
+
+        // Explicit options are kept
+        let mut cli = Cli::parse_from([
+            "fast-rm",
+            "--fast-known-dirs",
+            "--delete-threads",
+            "3",
+            "--parallel-threshold",
+            "8",
+            "--max-queue",
+            "64",
+            "repo/.git",
+        ]);
+        assert_eq!(cli.apply_known_dir_preset(), Some(".git"));
+        assert_eq!(cli.delete_threads, Some(DeleteThreads::Fixed(3)));
+        assert_eq!(cli.get_parallel_threshold(), 8);
+        assert_eq!(cli.max_queue, Some(64));
+
+        // Even when given as the default value
+        let mut cli = Cli::parse_from([
+            "fast-rm",
+            "--fast-known-dirs",
+            "--parallel-threshold",
+            "32",
+            "node_modules",
+        ]);
+        cli.apply_known_dir_preset();
+        assert_eq!(cli.get_parallel_threshold(), DEFAULT_PARALLEL_THRESHOLD);
+
+        // Other names, or no flag, leave everything alone
+        let mut cli = Cli::parse_from(["fast-rm", "--fast-known-dirs", "build"]);
+        assert_eq!(cli.apply_known_dir_preset(), None);
+        assert_eq!(cli.get_parallel_threshold(), DEFAULT_PARALLEL_THRESHOLD);
+        let mut cli = Cli::parse_from(["fast-rm", "node_modules"]);
+        assert_eq!(cli.apply_known_dir_preset(), None);
+        assert_eq!(cli.max_queue, None);
+    }
+
+    #[test]
+    fn test_fs_profile_fills_unset_options() {
+        let profile = FsProfile {
+            name: "tmpfs",
+            delete_threads: 16,
+            queue_capacity: Some(40_000),
+        };
+        let mut cli = Cli::parse_from(["fast-rm", "x"]);
+        cli.apply_fs_profile(&profile);
+        assert_eq!(cli.delete_threads, Some(DeleteThreads::Fixed(16)));
+        assert_eq!(cli.max_queue, Some(40_000));
+
+        let mut cli = Cli::parse_from([
+            "fast-rm",
+            "--delete-threads",
+            "auto",
+            "--max-queue",
+            "64",
+            "x",
+        ]);
+        cli.apply_fs_profile(&profile);
+        assert_eq!(cli.delete_threads, Some(DeleteThreads::Auto));
+        assert_eq!(cli.max_queue, Some(64));
+    }
+
+    #[test]
+    fn test_sequential_forces_single_threads() {
+        let cli = Cli::parse_from(["fast-rm", "--sequential", "-j", "8", "x"]);
+        assert_eq!(cli.get_scan_threads(), 1);
+        assert_eq!(cli.get_delete_threads(), 1);
     }
 }
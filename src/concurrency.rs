@@ -0,0 +1,218 @@
+//! Whether a run was limited by its scanners or its deleters
+//! (`--concurrency-report`)
+
+use std::time::Duration;
+
+use crate::human::{format_count, format_duration, format_rate};
+
+/// How often the sampler looks at the queue and the counters
+pub const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Fewer samples taken while scanning than this are no basis for advice
+const MIN_SAMPLES: usize = 5;
+
+/// Fraction of the queue's limit (`AdaptiveQueue::high_water`) treated as "full"
+const FULL_RATIO: f64 = 0.9;
+
+/// Fraction of samples a queue state must reach to call the run bound by it
+const MAJORITY: f64 = 0.5;
+
+/// Average share of the scanner pool that must be busy before more scanners
+/// are worth suggesting
+const SCANNERS_SATURATED: f64 = 0.75;
+
+/// One look at the run
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    /// Jobs waiting in the queue
+    pub depth: usize,
+    /// Scanner threads inside a directory at the time
+    pub busy_scanners: usize,
+    /// Deletions per second since the previous sample
+    pub rate: f64,
+    /// Whether the scan was still running; the queue only tells who waits
+    /// for whom while both sides are at work
+    pub scanning: bool,
+}
+
+/// What held the run back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The queue was mostly full: deleters could not keep up
+    Delete,
+    /// The queue was mostly empty and the scanners busy: more would help
+    Scan,
+    /// The queue was mostly empty but the scanners idle: the tree itself
+    /// offers little to scan in parallel
+    TreeShape,
+    /// Neither side waited on the other for long
+    Balanced,
+    /// The scan ended too quickly to tell
+    TooShort,
+}
+
+/// Samples collected over a run
+#[derive(Debug)]
+pub struct ConcurrencyReport {
+    limit: usize,
+    scan_threads: usize,
+    delete_threads: usize,
+    /// Samples taken while scanning
+    scanning: usize,
+    empty: usize,
+    full: usize,
+    depth_sum: usize,
+    busy_sum: usize,
+    /// Deletion rate over every sample
+    rate_sum: f64,
+    peak_rate: f64,
+    samples: usize,
+}
+
+impl ConcurrencyReport {
+    pub fn new(limit: usize, scan_threads: usize, delete_threads: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+            scan_threads: scan_threads.max(1),
+            delete_threads,
+            scanning: 0,
+            empty: 0,
+            full: 0,
+            depth_sum: 0,
+            busy_sum: 0,
+            rate_sum: 0.0,
+            peak_rate: 0.0,
+            samples: 0,
+        }
+    }
+
+    pub fn record(&mut self, sample: Sample) {
+        self.samples += 1;
+        self.rate_sum += sample.rate;
+        self.peak_rate = self.peak_rate.max(sample.rate);
+        if !sample.scanning {
+            return;
+        }
+        self.scanning += 1;
+        self.depth_sum += sample.depth;
+        self.busy_sum += sample.busy_scanners;
+        if sample.depth == 0 {
+            self.empty += 1;
+        } else if sample.depth as f64 >= self.limit as f64 * FULL_RATIO {
+            self.full += 1;
+        }
+    }
+
+    pub fn bound(&self) -> Bound {
+        if self.scanning < MIN_SAMPLES {
+            return Bound::TooShort;
+        }
+        let share = |count: usize| count as f64 / self.scanning as f64;
+        if share(self.full) >= MAJORITY {
+            Bound::Delete
+        } else if share(self.empty) >= MAJORITY {
+            if self.average_busy() >= self.scan_threads as f64 * SCANNERS_SATURATED {
+                Bound::Scan
+            } else {
+                Bound::TreeShape
+            }
+        } else {
+            Bound::Balanced
+        }
+    }
+
+    fn average_busy(&self) -> f64 {
+        self.busy_sum as f64 / self.scanning.max(1) as f64
+    }
+
+    /// The report printed at the end of the run
+    pub fn render(&self) -> String {
+        let percent = |count: usize| count * 100 / self.scanning.max(1);
+        let advice = match self.bound() {
+            Bound::Delete => format!(
+                "queue mostly full: delete-bound; increase --delete-threads (now {})",
+                self.delete_threads
+            ),
+            Bound::Scan => format!(
+                "queue mostly empty: scan-bound; increase --scan-threads (now {})",
+                self.scan_threads
+            ),
+            Bound::TreeShape => "queue mostly empty with scanners idle: the tree has little to \
+                                 scan in parallel; more threads will not help"
+                .to_string(),
+            Bound::Balanced => "scanners and deleters kept pace with each other".to_string(),
+            Bound::TooShort => "the scan finished too quickly to tell".to_string(),
+        };
+        let mut lines = vec![format!(
+            "{} samples, {} of them while scanning ({})",
+            format_count(self.samples as u64),
+            format_count(self.scanning as u64),
+            format_duration(SAMPLE_INTERVAL * self.scanning as u32)
+        )];
+        if self.scanning > 0 {
+            lines.push(format!(
+                "queue: {}% empty, {}% full, {} of {} on average",
+                percent(self.empty),
+                percent(self.full),
+                format_count((self.depth_sum / self.scanning.max(1)) as u64),
+                format_count(self.limit as u64)
+            ));
+            lines.push(format!(
+                "scanners busy: {:.1} of {} on average",
+                self.average_busy(),
+                self.scan_threads
+            ));
+        }
+        if self.samples > 0 {
+            lines.push(format!(
+                "deletion rate: {} average, {} peak",
+                format_rate(self.rate_sum / self.samples as f64, "items"),
+                format_rate(self.peak_rate, "items")
+            ));
+        }
+        lines.push(format!("=> {}", advice));
+        lines.join("\n  ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(depth: usize, busy_scanners: usize, scanning: usize, after: usize) -> ConcurrencyReport {
+        let mut report = ConcurrencyReport::new(100, 4, 8);
+        for i in 0..scanning + after {
+            report.record(Sample {
+                depth: if i < scanning { depth } else { 0 },
+                busy_scanners,
+                rate: 1000.0,
+                scanning: i < scanning,
+            });
+        }
+        report
+    }
+
+    #[test]
+    fn test_bound_classification() {
+        assert_eq!(run(95, 4, 10, 0).bound(), Bound::Delete);
+        assert_eq!(run(0, 4, 10, 0).bound(), Bound::Scan);
+        assert_eq!(run(0, 1, 10, 0).bound(), Bound::TreeShape);
+        assert_eq!(run(40, 4, 10, 0).bound(), Bound::Balanced);
+        assert_eq!(run(95, 4, 3, 0).bound(), Bound::TooShort);
+        // The queue draining after the scan says nothing about the scanners
+        assert_eq!(run(95, 4, 10, 50).bound(), Bound::Delete);
+    }
+
+    #[test]
+    fn test_render_gives_advice() {
+        let text = run(0, 4, 10, 0).render();
+        assert!(text.contains("increase --scan-threads (now 4)"), "{}", text);
+        assert!(text.contains("100% empty"), "{}", text);
+        let text = run(95, 4, 10, 0).render();
+        assert!(
+            text.contains("increase --delete-threads (now 8)"),
+            "{}",
+            text
+        );
+    }
+}
@@ -1,11 +1,28 @@
 use colored::*;
-use std::path::Path;
-use std::sync::Arc;
+#[cfg(windows)]
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use crate::progress::RemoveProgress;
+use crate::backup::Backup;
+use crate::cli::{DeleteOrder, EntryType};
+use crate::deleter::FailedJob;
+use crate::dirsync::DirSync;
+use crate::filter::PathFilter;
+use crate::journal::Journal;
+use crate::plan::PlanWriter;
+use crate::progress::{RemoveProgress, SkipReason};
+use crate::scanner::OpenDirLimit;
+use crate::schema::OutputVersion;
+use crate::watchdog::InFlight;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Verbosity {
+    #[default]
     Simple,
     Standard,
     Detailed,
@@ -25,22 +42,219 @@ impl Verbosity {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct RemoveConfig {
     pub verbosity: Verbosity,
     pub dry_run: bool,
+    /// Probe whether dry-run deletions would succeed (`--verify`)
+    pub verify: bool,
+    /// Print each scan decision, deleted or kept (`--diff`)
+    pub diff: bool,
     pub continue_on_error: bool,
     pub progress: Option<Arc<RemoveProgress>>,
+    /// Read `.fastrmignore` files while scanning and keep matching entries
+    pub use_ignore_files: bool,
+    /// Scan directory entries one at a time in name order (`--sequential`)
+    pub sequential: bool,
+    /// Delete symlink targets too, tracking visited objects to break cycles
+    pub follow_symlinks: bool,
+    /// Symlinks followed in a row before a chain is given up on
+    /// (`--max-symlink-depth`); `None` for `DEFAULT_MAX_SYMLINK_DEPTH`
+    pub max_symlink_depth: Option<usize>,
+    /// Canonical top-level roots every deleted path must stay under
+    /// (`--stay-within-root`), set once the paths have been resolved
+    pub stay_within_roots: Option<Arc<[PathBuf]>>,
+    /// Directories with at most this many entries are scanned on the current
+    /// thread; 0 always fans out to the pool
+    pub parallel_threshold: usize,
+    /// Only paths allowed by this predicate are deleted (`--match-prefix`,
+    /// `--files-from`); set once the paths have been resolved
+    pub filter: Option<PathFilter>,
+    /// Rescan directories that are not empty at removal time and retry once
+    pub force: bool,
+    /// Clear the immutable and append-only attributes that keep an entry
+    /// from being removed (`--clear-attrs`, Linux)
+    pub clear_attrs: bool,
+    /// Skip hidden entries below the given paths and keep their parents
+    pub skip_hidden: bool,
+    /// Skip VCS metadata below the given paths and keep their parents
+    pub exclude_vcs: bool,
+    /// Sort each directory's entries before enqueueing them
+    pub delete_order: DeleteOrder,
+    /// Set to stop scanning and deleting early (`--timeout`); queued jobs are
+    /// then dropped instead of run
+    pub cancelled: Arc<AtomicBool>,
+    /// Cancel the run once more errors than this have been counted
+    /// (`--max-errors`)
+    pub max_errors: Option<usize>,
+    /// Skip FIFOs, sockets, device nodes and mount points instead of
+    /// removing them with a warning
+    pub no_special: bool,
+    /// Append every deleted path here (`--journal`)
+    pub journal: Option<Arc<Journal>>,
+    /// Fsync the directories removals changed (`--fsync-dirs`); none in a
+    /// dry run
+    pub dir_sync: Option<Arc<DirSync>>,
+    /// Copy each file here before deleting it (`--backup-to`); set once the
+    /// paths have been resolved, never in a dry run
+    pub backup: Option<Arc<Backup>>,
+    /// Paths a previous run recorded as deleted (`--resume`); they and their
+    /// subtrees are not scanned again
+    pub already_deleted: Option<Arc<HashSet<PathBuf>>>,
+    /// Empty the top-level directories but keep them (`--contents-only`)
+    pub contents_only: bool,
+    /// Top-level directories given through a link as `link/`, which are
+    /// emptied but kept like with `--contents-only`
+    pub emptied_roots: Arc<HashSet<PathBuf>>,
+    /// Refuse directories instead of descending into them (`--no-recursive`)
+    pub no_recursive: bool,
+    /// Jobs that failed, kept for a second attempt (`--retry-failed`)
+    pub failed: Option<Arc<Mutex<Vec<FailedJob>>>>,
+    /// Paths whose removal failed, so `--force` does not take a directory
+    /// still holding one for a directory that gained entries after its scan
+    pub failed_paths: Option<Arc<Mutex<HashSet<PathBuf>>>>,
+    /// Write the final summary here as JSON (`--summary-json-file`)
+    pub summary_json_file: Option<PathBuf>,
+    /// Schema of the JSON outputs (`--output-version`)
+    pub output_version: OutputVersion,
+    /// Paths that must survive (`--keep-list`); their ancestors are kept too
+    pub keep: Option<Arc<HashSet<PathBuf>>>,
+    /// Name globs that must survive (`--keep`); their ancestors are kept too
+    pub keep_globs: Vec<String>,
+    /// Bound on directories listed at once (`--max-open-dirs`)
+    pub open_dirs: Option<Arc<OpenDirLimit>>,
+    /// Record every queued job here instead of only logging it (`--plan-out`)
+    pub plan: Option<Arc<PlanWriter>>,
+    /// Print only the final summary line (`--summary-only`)
+    pub summary_only: bool,
+    /// Deletions each deleter counts locally before updating the shared
+    /// progress counters (`--chunk-size`); 0 or 1 disables batching
+    pub chunk_size: usize,
+    /// Truncate regular files instead of unlinking them; directories holding
+    /// them are kept (`--tombstone`)
+    pub tombstone: bool,
+    /// Line written into each truncated file (`--tombstone-marker`)
+    pub tombstone_marker: Option<String>,
+    /// Only entries of these types are deleted; empty means all
+    /// (`--only-type`)
+    pub only_types: Vec<EntryType>,
+    /// Delete nothing but dangling symlinks (`--broken-symlinks-only`)
+    pub broken_symlinks_only: bool,
+    /// Never enqueue a directory, so the whole structure survives
+    /// (`--keep-dirs`)
+    pub keep_dirs: bool,
+    /// Append a CSV record of each run here (`--stats-file`)
+    pub stats_file: Option<PathBuf>,
+    /// Arguments were taken as given, repeats included (`--no-dedup`)
+    pub no_dedup: bool,
+    /// The path each deleter is on, for the watchdog (`--stuck-timeout`)
+    pub in_flight: Option<Arc<InFlight>>,
+    /// Give up on a removal syscall after this long (`--op-timeout`)
+    pub op_timeout: Option<Duration>,
+    /// Directories above files left in place because another process has
+    /// them open, with how many each holds (`--skip-open`)
+    #[cfg(windows)]
+    pub skipped_open: Option<Arc<Mutex<HashMap<PathBuf, usize>>>>,
 }
 
 impl RemoveConfig {
+    /// Whether entries of `kind` may be deleted (`--only-type`); only
+    /// symlinks with `--broken-symlinks-only`
+    pub fn deletes_type(&self, kind: EntryType) -> bool {
+        if self.broken_symlinks_only {
+            return kind == EntryType::Symlinks;
+        }
+        self.only_types.is_empty() || self.only_types.contains(&kind)
+    }
+
     pub fn from_cli(cli: &crate::cli::Cli, progress: Option<Arc<RemoveProgress>>) -> Self {
         Self {
             verbosity: Verbosity::from_count(cli.verbosity),
             dry_run: cli.dry_run,
+            verify: cli.verify,
+            diff: cli.diff,
             continue_on_error: cli.continue_on_error,
             progress,
+            use_ignore_files: cli.use_ignore_files,
+            sequential: cli.sequential,
+            follow_symlinks: cli.follow_symlinks,
+            max_symlink_depth: Some(cli.max_symlink_depth),
+            stay_within_roots: None,
+            parallel_threshold: cli.get_parallel_threshold(),
+            filter: None,
+            force: cli.force,
+            clear_attrs: cli.clear_attrs,
+            skip_hidden: cli.skip_hidden,
+            exclude_vcs: cli.exclude_vcs,
+            delete_order: cli.delete_order,
+            cancelled: Arc::default(),
+            max_errors: cli.max_errors,
+            no_special: cli.no_special,
+            journal: None,
+            dir_sync: (cli.fsync_dirs && !cli.dry_run).then(|| Arc::new(DirSync::new())),
+            backup: None,
+            already_deleted: None,
+            keep: None,
+            keep_globs: cli.keep_globs.clone(),
+            contents_only: cli.contents_only,
+            emptied_roots: Arc::default(),
+            no_recursive: cli.no_recursive,
+            summary_json_file: cli.summary_json_file.clone(),
+            output_version: cli.output_version,
+            failed: cli.retry_failed.then(Arc::default),
+            failed_paths: cli.force.then(Arc::default),
+            open_dirs: cli
+                .max_open_dirs
+                .map(|max| Arc::new(OpenDirLimit::new(max))),
+            plan: None,
+            summary_only: cli.summary_only,
+            chunk_size: cli.chunk_size,
+            tombstone: cli.tombstone,
+            tombstone_marker: cli.tombstone_marker.clone(),
+            only_types: cli.only_type.clone(),
+            broken_symlinks_only: cli.broken_symlinks_only,
+            keep_dirs: cli.keep_dirs,
+            stats_file: cli.stats_file.clone(),
+            no_dedup: cli.no_dedup,
+            in_flight: cli.stuck_timeout.map(|_| Arc::default()),
+            op_timeout: cli.op_timeout,
+            #[cfg(windows)]
+            skipped_open: cli.skip_open.then(Arc::default),
+        }
+    }
+
+    /// Whether the run should wind down: timed out, or past `--max-errors`
+    pub fn is_cancelled(&self) -> bool {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return true;
+        }
+        if self.error_limit_exceeded() {
+            self.cancelled.store(true, Ordering::Relaxed);
+            return true;
+        }
+        false
+    }
+
+    /// Whether more errors than `--max-errors` allows have been counted
+    pub fn error_limit_exceeded(&self) -> bool {
+        match (self.max_errors, &self.progress) {
+            (Some(max), Some(p)) => p.errors.load(Ordering::Relaxed) > max,
+            _ => false,
+        }
+    }
+
+    /// Whether `err` only says that `path` is gone, which under `--no-dedup`
+    /// means a repeated argument got to it first; it is then counted as
+    /// skipped rather than as an error
+    pub fn already_gone(&self, path: &Path, err: &io::Error) -> bool {
+        if !self.no_dedup || err.kind() != io::ErrorKind::NotFound {
+            return false;
         }
+        if let Some(p) = &self.progress {
+            p.inc_skipped(SkipReason::Gone);
+        }
+        self.log_skip(path, SkipReason::Gone.label());
+        true
     }
 
     pub fn log_action(&self, action: &str, action_dry: &str, path: &Path, color: colored::Color) {
@@ -50,6 +264,47 @@ impl RemoveConfig {
         }
     }
 
+    /// Log an entry the scanner hands to the deleters (`--diff`)
+    pub fn log_would_delete(&self, path: &Path) {
+        if self.diff {
+            println!("{} {:?}", "-".green(), path);
+        }
+    }
+
+    /// Log a directory that is kept because some of its entries were kept
+    pub fn log_kept_dir(&self, path: &Path, kept: usize) {
+        if self.diff {
+            let reason = format!(
+                "kept: {} {} kept",
+                kept,
+                if kept == 1 { "entry" } else { "entries" }
+            );
+            println!("  {}", format!("{:?} ({})", path, reason).dimmed());
+        } else if self.progress.is_none() && (self.verbosity.is_verbose() || self.dry_run) {
+            let msg = if self.dry_run {
+                "Would keep directory "
+            } else {
+                "Keeping directory "
+            };
+            println!(
+                "  {}{:?} ({} {} kept)",
+                msg.blue(),
+                path,
+                kept,
+                if kept == 1 { "entry" } else { "entries" }
+            );
+        }
+    }
+
+    /// Log an entry skipped by the scanner
+    pub fn log_skip(&self, path: &Path, reason: &str) {
+        if self.diff {
+            println!("  {}", format!("{:?} (kept: {})", path, reason).dimmed());
+        } else if self.progress.is_none() && (self.verbosity.is_verbose() || self.dry_run) {
+            println!("  {}{:?} ({})", "Skipping ".dimmed(), path, reason);
+        }
+    }
+
     pub fn log_check(&self, path: &Path) {
         if self.verbosity.is_verbose() {
             let msg = if self.dry_run {
@@ -0,0 +1,86 @@
+use colored::*;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::human::{format_bytes, format_count};
+use crate::scanner::JobCount;
+
+/// Exit code when a confirmation is refused; nothing has been deleted
+pub const EXIT_NOT_CONFIRMED: i32 = 2;
+
+/// A target as the user is asked to confirm it: the path as given on the
+/// command line, the resolved path, and what a scan of it found
+pub struct TypedTarget<'a> {
+    pub given: &'a Path,
+    pub resolved: &'a Path,
+    pub count: JobCount,
+}
+
+/// Ask the user to re-type each target path before deletion (`--require-typed-confirm`)
+///
+/// Each prompt names the resolved path with its entry count and size;
+/// typing either the given or the resolved path exactly confirms it.
+/// Returns `false` on the first mismatch or on end of input.
+pub fn confirm_typed(
+    targets: &[TypedTarget],
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<bool> {
+    for target in targets {
+        writeln!(
+            output,
+            "{} this will permanently delete {:?} ({} entries, {})",
+            "Warning:".red().bold(),
+            target.resolved,
+            format_count(target.count.entries),
+            format_bytes(target.count.bytes)
+        )?;
+        write!(output, "Type the path to confirm: ")?;
+        output.flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(false);
+        }
+        let answer = PathBuf::from(answer.trim_end_matches(['\r', '\n']));
+        if answer != target.given && answer != target.resolved {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(answers: &str) -> bool {
+        let target = TypedTarget {
+            given: Path::new("data"),
+            resolved: Path::new("/srv/data"),
+            count: JobCount {
+                entries: 120_000,
+                bytes: 9_000,
+            },
+        };
+        let mut input = answers.as_bytes();
+        let mut output = Vec::new();
+        let confirmed = confirm_typed(&[target], &mut input, &mut output).unwrap();
+        let shown = String::from_utf8(output).unwrap();
+        assert!(shown.contains("(120,000 entries, 8.8 KiB)"), "{}", shown);
+        confirmed
+    }
+
+    #[test]
+    fn test_wrong_answer_is_refused() {
+        assert!(!run("/srv/dat\n"));
+        assert!(!run("y\n"));
+        assert!(!run(""));
+    }
+
+    #[test]
+    fn test_exact_path_is_accepted() {
+        assert!(run("/srv/data\n"));
+        assert!(run("data\r\n"));
+    }
+}
@@ -0,0 +1,147 @@
+//! Giving up on removals that do not return (`--op-timeout`)
+//!
+//! A blocked syscall cannot be cancelled, so each removal runs on a helper
+//! thread and the deleter stops waiting for it once the timeout passes.
+//! This has costs:
+//!
+//! - every removal pays for spawning a thread, which is slower than the
+//!   unlink itself on a healthy file system;
+//! - an abandoned thread stays blocked in the kernel, holding its stack,
+//!   until the call returns or the process exits. At most `MAX_ABANDONED`
+//!   are left behind; past that, removals fail straight away instead of
+//!   leaking more;
+//! - an abandoned removal may still succeed later, so an entry reported
+//!   as timed out can be gone after the run, and its directory may then
+//!   be removable too.
+
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::human::format_duration;
+
+/// Abandoned removals that may still be blocked before no more are started
+pub const MAX_ABANDONED: usize = 64;
+
+/// Helper threads whose removal was abandoned and has not returned yet
+static ABANDONED: AtomicUsize = AtomicUsize::new(0);
+
+/// Where a removal handed to a helper thread stands
+enum Outcome {
+    Pending,
+    Done(io::Result<()>),
+    /// The deleter stopped waiting; the helper counts itself out when done
+    Abandoned,
+}
+
+/// Run `op` on `path` on a helper thread, waiting at most `timeout`
+///
+/// On timeout the thread is left to finish on its own and an error of kind
+/// `TimedOut` is returned.
+pub fn with_deadline<F>(path: &Path, timeout: Duration, op: F) -> io::Result<()>
+where
+    F: FnOnce(&Path) -> io::Result<()> + Send + 'static,
+{
+    if ABANDONED.load(Ordering::Relaxed) >= MAX_ABANDONED {
+        return Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!(
+                "not attempted: {} earlier removals are still blocked",
+                MAX_ABANDONED
+            ),
+        ));
+    }
+    let shared = Arc::new((Mutex::new(Outcome::Pending), Condvar::new()));
+    let helper = shared.clone();
+    let owned = path.to_path_buf();
+    thread::Builder::new()
+        .name("op-timeout".to_string())
+        .spawn(move || {
+            let result = op(&owned);
+            let (outcome, done) = &*helper;
+            let mut outcome = outcome.lock().unwrap();
+            match *outcome {
+                Outcome::Abandoned => {
+                    ABANDONED.fetch_sub(1, Ordering::Relaxed);
+                }
+                _ => {
+                    *outcome = Outcome::Done(result);
+                    done.notify_one();
+                }
+            }
+        })?;
+
+    // Deciding under the lock leaves the helper either to report back or to
+    // find itself abandoned, never both
+    let (outcome, done) = &*shared;
+    let (mut outcome, _) = done
+        .wait_timeout_while(outcome.lock().unwrap(), timeout, |outcome| {
+            matches!(outcome, Outcome::Pending)
+        })
+        .unwrap();
+    match std::mem::replace(&mut *outcome, Outcome::Abandoned) {
+        Outcome::Done(result) => result,
+        _ => {
+            ABANDONED.fetch_add(1, Ordering::Relaxed);
+            Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "did not return within {} and was abandoned (--op-timeout)",
+                    format_duration(timeout)
+                ),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Instant;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_slow_removal_is_abandoned() {
+        let temp_dir = TempDir::new().unwrap();
+        let slow = temp_dir.path().join("slow.txt");
+        let quick = temp_dir.path().join("quick.txt");
+        fs::write(&slow, "").unwrap();
+        fs::write(&quick, "").unwrap();
+
+        // A stub standing in for an unlink on a hung mount
+        let start = Instant::now();
+        let err = with_deadline(&slow, Duration::from_millis(50), |path| {
+            thread::sleep(Duration::from_millis(500));
+            fs::remove_file(path)
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_millis(400));
+
+        // The caller moves on to the next removal straight away
+        with_deadline(&quick, Duration::from_secs(5), |path| fs::remove_file(path)).unwrap();
+        assert!(!quick.exists());
+
+        // The abandoned one still completes in the background
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while slow.exists() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!slow.exists());
+    }
+
+    #[test]
+    fn test_errors_are_passed_through() {
+        let temp_dir = TempDir::new().unwrap();
+        let missing = temp_dir.path().join("missing");
+        let err = with_deadline(&missing, Duration::from_secs(5), |path| {
+            fs::remove_file(path)
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -1,40 +1,123 @@
 use colored::*;
 use std::fs;
+use std::io;
 use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::autotune::ThreadGate;
+use crate::backup::Backup;
 use crate::config::RemoveConfig;
-use crate::errors::RemoveError;
+use crate::deadline::with_deadline;
+use crate::errors::{ErrorCategory, RemoveError};
+#[cfg(windows)]
+use crate::openfiles::held_open_error;
+use crate::path::is_within_roots;
+use crate::pool::{ElasticPool, KEEP_ALIVE};
+#[cfg(windows)]
+use crate::progress::SkipReason;
+use crate::progress::{capture_errors, CountedError};
 use crate::queue::{AdaptiveQueue, FileJob};
+use crate::results::print_hint;
+use crate::scanner::scan_contents;
+use crate::watchdog::Slot;
 
 /// Worker function that consumes FileJob items from the queue and deletes them
 pub fn delete_worker(queue: &AdaptiveQueue, config: &RemoveConfig, scanners_done: &AtomicBool) {
+    worker_loop(queue, config, scanners_done, Role::Fixed);
+}
+
+/// Like `delete_worker`, but stays parked while `gate` does not admit worker
+/// `id` (`--auto-threads`)
+pub fn gated_delete_worker(
+    queue: &AdaptiveQueue,
+    config: &RemoveConfig,
+    scanners_done: &AtomicBool,
+    gate: &ThreadGate,
+    id: usize,
+) {
+    worker_loop(queue, config, scanners_done, Role::Gated(gate, id));
+}
+
+/// Like `delete_worker`, but exits after `pool::KEEP_ALIVE` without work if
+/// `pool` can spare it (`--delete-threads auto`)
+pub fn elastic_delete_worker(
+    queue: &AdaptiveQueue,
+    config: &RemoveConfig,
+    scanners_done: &AtomicBool,
+    pool: &ElasticPool,
+) {
+    worker_loop(queue, config, scanners_done, Role::Elastic(pool));
+}
+
+/// How a worker's pool is sized
+#[derive(Clone, Copy)]
+enum Role<'a> {
+    /// Runs until the queue is drained
+    Fixed,
+    /// Parked while the gate does not admit this id
+    Gated(&'a ThreadGate, usize),
+    /// Leaves the pool when idle for long enough
+    Elastic(&'a ElasticPool),
+}
+
+fn worker_loop(
+    queue: &AdaptiveQueue,
+    config: &RemoveConfig,
+    scanners_done: &AtomicBool,
+    role: Role<'_>,
+) {
+    // Flushed when the worker exits, so totals are exact after a join
+    let _batch = config
+        .progress
+        .as_ref()
+        .map(|p| p.batch_deletions(config.chunk_size));
+    let slot = config
+        .in_flight
+        .as_ref()
+        .map(|in_flight| in_flight.register());
+    let slot = slot.as_deref();
+    let mut turn = 0usize;
+    let mut last_job = Instant::now();
     loop {
+        if let Role::Gated(gate, id) = role {
+            if !gate.is_active(id) {
+                if scanners_done.load(Ordering::Relaxed) && queue.is_empty() {
+                    break;
+                }
+                thread::park_timeout(Duration::from_millis(50));
+                continue;
+            }
+        }
+
+        if let Some(job) = queue.next_large(&mut turn) {
+            queue.notify_drained();
+            run_job(&job, config, slot);
+            queue.large_done(job.path());
+            last_job = Instant::now();
+            continue;
+        }
+
         match queue.recv_timeout(Duration::from_millis(100)) {
             Ok(job) => {
-                let result = match job {
-                    FileJob::File(path) => delete_file(&path, config),
-                    FileJob::Symlink(path) => delete_symlink(&path, config),
-                    FileJob::EmptyDir(path) => delete_empty_dir(&path, config),
-                };
-
-                // Handle errors
-                if let Err(e) = result {
-                    if !config.continue_on_error {
-                        // In the two-pool architecture, we can't easily stop scanners
-                        // For now, just log the error and continue
-                        if config.progress.is_none() {
-                            eprintln!("{}", e.to_string().red());
-                        }
-                    }
+                queue.notify_drained();
+                if let FileJob::EmptyDir(dir) = &job {
+                    wait_for_large_files(dir, queue, config, slot);
                 }
+                run_job(&job, config, slot);
+                last_job = Instant::now();
             }
             Err(crossbeam_channel::RecvTimeoutError::Timeout) => {
                 // Check if scanners are done AND queue is empty
                 if scanners_done.load(Ordering::Relaxed) && queue.is_empty() {
                     break;
                 }
+                if let Role::Elastic(pool) = role {
+                    if last_job.elapsed() >= KEEP_ALIVE && pool.try_retire() {
+                        return;
+                    }
+                }
             }
             Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
                 // Channel closed, exit
@@ -42,10 +125,413 @@ pub fn delete_worker(queue: &AdaptiveQueue, config: &RemoveConfig, scanners_done
             }
         }
     }
+    if let Role::Elastic(pool) = role {
+        pool.finished();
+    }
+}
+
+/// Run a job for a worker, marking its path in the worker's `slot` while it
+/// runs (`--stuck-timeout`)
+fn run_job(job: &FileJob, config: &RemoveConfig, slot: Option<&Slot>) {
+    // Keep draining so blocked scanners can finish, but delete nothing more
+    if config.is_cancelled() {
+        return;
+    }
+    if let Some(slot) = slot {
+        slot.start(job.path());
+    }
+    let (result, errors) = capture_errors(|| execute(job, config));
+    if let Some(slot) = slot {
+        slot.finish();
+    }
+
+    // Handle errors
+    if let Err(e) = result {
+        if let Some(failed_paths) = &config.failed_paths {
+            failed_paths
+                .lock()
+                .unwrap()
+                .insert(job.path().to_path_buf());
+        }
+        if let Some(failed) = &config.failed {
+            failed.lock().unwrap().push(FailedJob {
+                job: job.clone(),
+                errors,
+            });
+        }
+        if !config.continue_on_error {
+            // In the two-pool architecture, we can't easily stop scanners
+            // For now, just log the error and continue
+            if config.progress.is_none() {
+                eprintln!("{}", e.to_string().red());
+                print_hint(&e);
+            }
+        }
+    }
+}
+
+/// Run one job: the safety checks, the removal and its journal record
+pub fn execute(job: &FileJob, config: &RemoveConfig) -> Result<(), RemoveError> {
+    check_within_roots(job.path(), config)
+        .and_then(|()| check_type_unchanged(job, config))
+        .and_then(|()| match job {
+            FileJob::File { path, size } => delete_file(path, *size, config),
+            FileJob::Symlink(path) => delete_symlink(path, config),
+            FileJob::EmptyDir(path) => delete_empty_dir(path, config),
+        })
+        .and_then(|()| record_deleted(job.path(), config))
+        .map(|()| sync_parent(job, config))
+}
+
+/// A job that failed, with the errors it counted (`--retry-failed`)
+#[derive(Debug, Clone)]
+pub struct FailedJob {
+    pub job: FileJob,
+    pub errors: Vec<CountedError>,
+}
+
+/// Attempt the jobs that failed during the run once more, deepest paths
+/// first so a directory comes after everything that was in it
+/// (`--retry-failed`)
+///
+/// The errors each retried job counted are withdrawn; a job that fails
+/// again counts its new ones. Returns how many succeeded.
+pub fn retry_failed(jobs: &mut [FailedJob], config: &RemoveConfig) -> usize {
+    jobs.sort_by_key(|failed| std::cmp::Reverse(failed.job.path().components().count()));
+    let mut resolved = 0;
+    for FailedJob { job, errors } in jobs.iter() {
+        if config.is_cancelled() {
+            break;
+        }
+        if let Some(p) = &config.progress {
+            for (path, category) in errors {
+                p.dec_error(path, *category);
+            }
+        }
+        if execute(job, config).is_ok() {
+            if let Some(failed_paths) = &config.failed_paths {
+                failed_paths.lock().unwrap().remove(job.path());
+            }
+            resolved += 1;
+        }
+    }
+    resolved
+}
+
+/// Before removing a directory, finish the large files queued ahead of it
+/// (`--size-aware`)
+///
+/// Large files bypass the channel, so one of the directory's own may still be
+/// waiting in the size-sorted queue or being unlinked by another worker.
+/// Keep helping with queued ones, whichever directory they are in, until
+/// none of `dir`'s own is left; every worker waiting here still drains the
+/// queue, so the count cannot get stuck.
+fn wait_for_large_files(
+    dir: &Path,
+    queue: &AdaptiveQueue,
+    config: &RemoveConfig,
+    slot: Option<&Slot>,
+) {
+    while queue.large_outstanding_in(dir) > 0 {
+        match queue.pop_large() {
+            Some(job) => {
+                run_job(&job, config, slot);
+                queue.large_done(job.path());
+            }
+            None => thread::sleep(Duration::from_millis(1)),
+        }
+    }
+}
+
+/// Append a deleted path to the journal, if one is kept (`--journal`)
+fn record_deleted(path: &Path, config: &RemoveConfig) -> Result<(), RemoveError> {
+    let Some(journal) = &config.journal else {
+        return Ok(());
+    };
+    let result = journal.record(path);
+    if let (Err(err), Some(p)) = (&result, &config.progress) {
+        p.inc_error(path, err.category(), err.to_string());
+    }
+    result
+}
+
+/// Mark the job's parent directory for fsyncing (`--fsync-dirs`)
+///
+/// A directory that could not be fsynced is counted as an error of its own;
+/// the deletion itself did happen.
+fn sync_parent(job: &FileJob, config: &RemoveConfig) {
+    let Some(dir_sync) = &config.dir_sync else {
+        return;
+    };
+    for err in dir_sync.removed(job.path(), matches!(job, FileJob::EmptyDir(_))) {
+        match &config.progress {
+            Some(p) => p.inc_error(job.path(), err.category(), err.to_string()),
+            None => eprintln!("{}", err.to_string().red()),
+        }
+    }
+}
+
+/// Refuse paths that resolve outside the deletion roots (`--stay-within-root`)
+fn check_within_roots(path: &Path, config: &RemoveConfig) -> Result<(), RemoveError> {
+    match &config.stay_within_roots {
+        Some(roots) if !is_within_roots(path, roots) => {
+            let err = RemoveError::EscapesRoot(path.to_path_buf());
+            if let Some(p) = &config.progress {
+                p.inc_error(path, err.category(), err.to_string());
+            }
+            Err(err)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Refuse a job whose path is no longer the kind of entry that was scanned,
+/// e.g. a directory swapped for a symlink since the scan
+///
+/// A path that cannot be examined is left for the removal to report.
+fn check_type_unchanged(job: &FileJob, config: &RemoveConfig) -> Result<(), RemoveError> {
+    let path = job.path();
+    let Ok(file_type) = fs::symlink_metadata(path).map(|m| m.file_type()) else {
+        return Ok(());
+    };
+    let unchanged = match job {
+        // Regular files, and FIFOs, sockets and devices
+        FileJob::File { .. } => !file_type.is_dir() && !file_type.is_symlink(),
+        FileJob::Symlink(_) => file_type.is_symlink(),
+        FileJob::EmptyDir(_) => file_type.is_dir(),
+    };
+    if unchanged {
+        return Ok(());
+    }
+    let err = RemoveError::TypeChanged(path.to_path_buf());
+    if let Some(p) = &config.progress {
+        p.inc_error(path, err.category(), err.to_string());
+    }
+    Err(err)
+}
+
+/// Run a removal syscall on `path`, recording its latency when `--stats` is
+/// enabled and giving up after `--op-timeout`
+fn timed(
+    config: &RemoveConfig,
+    path: &Path,
+    op: impl FnOnce(&Path) -> io::Result<()> + Send + 'static,
+) -> io::Result<()> {
+    let run = || match config.op_timeout {
+        Some(timeout) => with_deadline(path, timeout, op),
+        None => op(path),
+    };
+    match config.progress.as_ref().filter(|p| p.latency_enabled()) {
+        Some(p) => {
+            let start = Instant::now();
+            let result = run();
+            p.record_latency(start.elapsed());
+            result
+        }
+        None => run(),
+    }
+}
+
+/// Run a removal, or in a dry run only probe it (`--verify`)
+fn remove(
+    config: &RemoveConfig,
+    path: &Path,
+    op: impl Fn(&Path) -> io::Result<()> + Clone + Send + 'static,
+) -> io::Result<()> {
+    match (config.dry_run, config.verify) {
+        (false, _) => through_attrs(config, path, || timed(config, path, op.clone())),
+        (true, true) => check_removable(path),
+        (true, false) => Ok(()),
+    }
+}
+
+/// Check that `path` could be unlinked: its parent directory must be
+/// writable and searchable, and a sticky parent must belong to us or hold a
+/// file of ours
+#[cfg(unix)]
+fn check_removable(path: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+
+    let parent = match path.parent() {
+        Some(p) if p.as_os_str().is_empty() => Path::new("."),
+        Some(p) => p,
+        None => return Ok(()),
+    };
+    let c_parent = CString::new(parent.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: `c_parent` is a valid NUL-terminated string for the whole call
+    let rc = unsafe {
+        libc::faccessat(
+            libc::AT_FDCWD,
+            c_parent.as_ptr(),
+            libc::W_OK | libc::X_OK,
+            libc::AT_EACCESS,
+        )
+    };
+    if rc != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let parent_meta = fs::metadata(parent)?;
+    // S_ISVTX, the sticky bit
+    if parent_meta.mode() & 0o1000 != 0 {
+        // SAFETY: geteuid has no preconditions
+        let uid = unsafe { libc::geteuid() };
+        let owner = fs::symlink_metadata(path)?.uid();
+        if uid != 0 && uid != owner && uid != parent_meta.uid() {
+            return Err(io::Error::from_raw_os_error(libc::EPERM));
+        }
+    }
+    Ok(())
+}
+
+/// Check that `path` could be removed: read-only entries cannot be
+#[cfg(not(unix))]
+fn check_removable(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.permissions().readonly() {
+        return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+    }
+    Ok(())
+}
+
+/// Inode flags that keep an entry from being unlinked, and every entry in a
+/// directory carrying them (`chattr +i` and `+a`, linux/fs.h)
+#[cfg(target_os = "linux")]
+const FS_IMMUTABLE_FL: libc::c_int = 0x10;
+#[cfg(target_os = "linux")]
+const FS_APPEND_FL: libc::c_int = 0x20;
+
+/// Run `op`, which removes `path`; if it fails with EPERM because `path` or
+/// its parent is immutable or append-only, clear the flags and retry with
+/// `--clear-attrs`, or else name the attribute in the error
+///
+/// The entry's own flags are dropped with it. The parent's are restored
+/// afterwards, as it may be outside the deletion; if it is inside, its own
+/// removal clears them again.
+#[cfg(target_os = "linux")]
+fn through_attrs(
+    config: &RemoveConfig,
+    path: &Path,
+    op: impl Fn() -> io::Result<()>,
+) -> io::Result<()> {
+    let err = match op() {
+        Err(e) if e.raw_os_error() == Some(libc::EPERM) => e,
+        result => return result,
+    };
+    let parent = match path.parent() {
+        Some(p) if p.as_os_str().is_empty() => Path::new("."),
+        Some(p) => p,
+        None => return Err(err),
+    };
+    let protected = |entry: &Path| {
+        inode_flags(entry)
+            .ok()
+            .map(|flags| (flags, flags & (FS_IMMUTABLE_FL | FS_APPEND_FL)))
+            .filter(|&(_, protecting)| protecting != 0)
+    };
+    let (own, parents) = (protected(path), protected(parent));
+    let (holder, protecting) = match (own, parents) {
+        (Some((_, protecting)), _) => (path, protecting),
+        (None, Some((_, protecting))) => (parent, protecting),
+        (None, None) => return Err(err),
+    };
+    if !config.clear_attrs {
+        let (name, flag) = if protecting & FS_IMMUTABLE_FL != 0 {
+            ("immutable", 'i')
+        } else {
+            ("append-only", 'a')
+        };
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "{:?} is {} (chattr +{}); use --clear-attrs to clear it first",
+                holder, name, flag
+            ),
+        ));
+    }
+
+    let unprotect = |entry: &Path, (flags, protecting): (libc::c_int, libc::c_int)| {
+        set_inode_flags(entry, flags & !protecting)
+    };
+    if let Some(flags) = own {
+        unprotect(path, flags)?;
+    }
+    if let Some(flags) = parents {
+        unprotect(parent, flags)?;
+    }
+    let result = op();
+    if let Some((flags, _)) = parents {
+        set_inode_flags(parent, flags)?;
+    }
+    result
+}
+
+#[cfg(not(target_os = "linux"))]
+fn through_attrs(
+    _config: &RemoveConfig,
+    _path: &Path,
+    op: impl Fn() -> io::Result<()>,
+) -> io::Result<()> {
+    op()
+}
+
+/// Open a regular file or directory for its inode flags, never following a
+/// symlink; other types have none worth reading, and opening a device or
+/// FIFO could have side effects
+#[cfg(target_os = "linux")]
+fn open_for_flags(path: &Path) -> io::Result<fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let file_type = fs::symlink_metadata(path)?.file_type();
+    if !file_type.is_file() && !file_type.is_dir() {
+        return Err(io::Error::from(io::ErrorKind::Unsupported));
+    }
+    fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+        .open(path)
+}
+
+/// `FS_IOC_GETFLAGS` of `path`
+#[cfg(target_os = "linux")]
+fn inode_flags(path: &Path) -> io::Result<libc::c_int> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = open_for_flags(path)?;
+    let mut flags: libc::c_int = 0;
+    // SAFETY: an open descriptor and a c_int for the kernel to fill, which
+    // is what it writes despite the ioctl's declared long
+    if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(flags)
+}
+
+/// `FS_IOC_SETFLAGS` of `path`; clearing `FS_IMMUTABLE_FL` and
+/// `FS_APPEND_FL` needs `CAP_LINUX_IMMUTABLE`
+#[cfg(target_os = "linux")]
+fn set_inode_flags(path: &Path, flags: libc::c_int) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = open_for_flags(path)?;
+    // SAFETY: an open descriptor and a c_int for the kernel to read
+    if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_SETFLAGS, &flags) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
 }
 
 /// Delete a single file
-fn delete_file(path: &Path, config: &RemoveConfig) -> Result<(), RemoveError> {
+fn delete_file(path: &Path, size: u64, config: &RemoveConfig) -> Result<(), RemoveError> {
+    if let Some(backup) = &config.backup {
+        back_up(backup, path, config)?;
+    }
+    // FIFOs, sockets and devices are unlinked as usual
+    if config.tombstone && fs::symlink_metadata(path).is_ok_and(|m| m.is_file()) {
+        return tombstone_file(path, size, config);
+    }
     if config.progress.is_none() {
         config.log_action(
             "Removing file ",
@@ -55,23 +541,153 @@ fn delete_file(path: &Path, config: &RemoveConfig) -> Result<(), RemoveError> {
         );
     }
 
-    if !config.dry_run {
-        fs::remove_file(path).map_err(|e| {
+    let result = remove(config, path, |path: &Path| fs::remove_file(path));
+    #[cfg(windows)]
+    if let Some(outcome) = result
+        .as_ref()
+        .err()
+        .and_then(|e| held_open(path, e, config))
+    {
+        return outcome;
+    }
+    match result {
+        Err(e) if config.already_gone(path, &e) => return Ok(()),
+        result => result.map_err(|e| {
             let err_msg = e.to_string();
             if let Some(p) = &config.progress {
-                p.inc_error(path, err_msg);
+                p.inc_error(path, ErrorCategory::of_io(&e), err_msg);
             }
             RemoveError::RemoveFailed(path.to_path_buf(), e)
-        })?;
+        })?,
+    }
+
+    if let Some(p) = &config.progress {
+        p.inc_deleted(path);
+        p.add_deleted_bytes(path, size);
+        p.record_extension(path, size);
+    }
+
+    Ok(())
+}
+
+/// Deal with a file that could not be removed because another process has
+/// it open (Windows): under `--skip-open` it is left in place, otherwise the
+/// error names the processes
+///
+/// `None` when no process has it open, so the failure had another cause.
+#[cfg(windows)]
+fn held_open(
+    path: &Path,
+    err: &io::Error,
+    config: &RemoveConfig,
+) -> Option<Result<(), RemoveError>> {
+    let err = held_open_error(path, err)?;
+    if let Some(skipped) = &config.skipped_open {
+        let mut skipped = skipped.lock().unwrap();
+        for dir in path.ancestors().skip(1) {
+            *skipped.entry(dir.to_path_buf()).or_default() += 1;
+        }
+        if let Some(p) = &config.progress {
+            p.inc_skipped(SkipReason::Open);
+        }
+        config.log_skip(path, SkipReason::Open.label());
+        return Some(Ok(()));
+    }
+    if let Some(p) = &config.progress {
+        p.inc_error(path, err.category(), err.to_string());
+    }
+    Some(Err(err))
+}
+
+/// Keep a directory that still holds files left under `--skip-open`
+#[cfg(windows)]
+fn holds_open_files(path: &Path, config: &RemoveConfig) -> bool {
+    let Some(kept) = config
+        .skipped_open
+        .as_ref()
+        .and_then(|skipped| skipped.lock().unwrap().get(path).copied())
+    else {
+        return false;
+    };
+    if let Some(p) = &config.progress {
+        p.inc_kept_dir();
+    }
+    config.log_kept_dir(path, kept);
+    true
+}
+
+/// Copy a regular file into the `--backup-to` mirror; on failure it is
+/// counted as an error and must not be deleted
+fn back_up(backup: &Backup, path: &Path, config: &RemoveConfig) -> Result<(), RemoveError> {
+    // FIFOs, sockets and devices have nothing to copy
+    if !fs::symlink_metadata(path).is_ok_and(|m| m.is_file()) {
+        return Ok(());
+    }
+    backup.copy(path).map_err(|e| {
+        let err = RemoveError::BackupFailed(path.to_path_buf(), e);
+        if let Some(p) = &config.progress {
+            p.inc_error(path, err.category(), err.to_string());
+        }
+        err
+    })
+}
+
+/// Empty a regular file in place instead of unlinking it (`--tombstone`)
+///
+/// The name, permissions and owner stay, so whatever watches for the file
+/// still finds it. Its bytes count as freed.
+fn tombstone_file(path: &Path, size: u64, config: &RemoveConfig) -> Result<(), RemoveError> {
+    if config.progress.is_none() {
+        config.log_action(
+            "Truncating file ",
+            "Would truncate file ",
+            path,
+            colored::Color::Yellow,
+        );
+    }
+
+    let marker = config.tombstone_marker.clone();
+    match remove(config, path, move |path: &Path| {
+        write_tombstone(path, marker.as_deref())
+    }) {
+        Err(e) if config.already_gone(path, &e) => return Ok(()),
+        result => result.map_err(|e| {
+            let err_msg = e.to_string();
+            if let Some(p) = &config.progress {
+                p.inc_error(path, ErrorCategory::of_io(&e), err_msg);
+            }
+            RemoveError::RemoveFailed(path.to_path_buf(), e)
+        })?,
     }
 
     if let Some(p) = &config.progress {
         p.inc_deleted(path);
+        p.add_deleted_bytes(path, size);
+        p.record_extension(path, size);
     }
 
     Ok(())
 }
 
+/// Truncate an existing file, never following a symlink put in its place,
+/// and write `marker` as its only line
+fn write_tombstone(path: &Path, marker: Option<&str>) -> io::Result<()> {
+    use std::io::Write;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(libc::O_NOFOLLOW);
+    }
+    let mut file = options.open(path)?;
+    if let Some(marker) = marker {
+        writeln!(file, "{}", marker)?;
+    }
+    Ok(())
+}
+
 /// Delete a symlink
 fn delete_symlink(path: &Path, config: &RemoveConfig) -> Result<(), RemoveError> {
     if config.progress.is_none() {
@@ -83,14 +699,15 @@ fn delete_symlink(path: &Path, config: &RemoveConfig) -> Result<(), RemoveError>
         );
     }
 
-    if !config.dry_run {
-        fs::remove_file(path).map_err(|e| {
+    match remove(config, path, |path: &Path| fs::remove_file(path)) {
+        Err(e) if config.already_gone(path, &e) => return Ok(()),
+        result => result.map_err(|e| {
             let err_msg = e.to_string();
             if let Some(p) = &config.progress {
-                p.inc_error(path, err_msg);
+                p.inc_error(path, ErrorCategory::of_io(&e), err_msg);
             }
             RemoveError::RemoveFailed(path.to_path_buf(), e)
-        })?;
+        })?,
     }
 
     if let Some(p) = &config.progress {
@@ -111,11 +728,43 @@ fn delete_empty_dir(path: &Path, config: &RemoveConfig) -> Result<(), RemoveErro
         );
     }
 
-    if !config.dry_run {
-        fs::remove_dir(path).map_err(|e| {
+    if config.dry_run && config.verify {
+        check_removable(path).map_err(|e| {
             let err_msg = e.to_string();
             if let Some(p) = &config.progress {
-                p.inc_error(path, err_msg);
+                p.inc_error(path, ErrorCategory::of_io(&e), err_msg);
+            }
+            RemoveError::RemoveDirFailed(path.to_path_buf(), e)
+        })?;
+    } else if !config.dry_run {
+        let mut result = through_attrs(config, path, || {
+            timed(config, path, |path: &Path| fs::remove_dir(path))
+        });
+        #[cfg(windows)]
+        if result.is_err() && holds_open_files(path, config) {
+            return Ok(());
+        }
+        if result.is_err() && has_only_new_entries(path, config) {
+            // Something was created in the directory after it was scanned
+            if !config.force {
+                let err = RemoveError::DirNotEmpty(path.to_path_buf());
+                if let Some(p) = &config.progress {
+                    p.inc_error(path, err.category(), err.to_string());
+                }
+                return Err(err);
+            }
+            clear_new_entries(path, config)?;
+            result = timed(config, path, |path: &Path| fs::remove_dir(path));
+        }
+        if let Err(e) = &result {
+            if config.already_gone(path, e) {
+                return Ok(());
+            }
+        }
+        result.map_err(|e| {
+            let err_msg = e.to_string();
+            if let Some(p) = &config.progress {
+                p.inc_error(path, ErrorCategory::of_io(&e), err_msg);
             }
             RemoveError::RemoveDirFailed(path.to_path_buf(), e)
         })?;
@@ -123,11 +772,52 @@ fn delete_empty_dir(path: &Path, config: &RemoveConfig) -> Result<(), RemoveErro
 
     if let Some(p) = &config.progress {
         p.inc_deleted(path);
+        p.dir_removed(path);
     }
 
     Ok(())
 }
 
+/// Whether `path` is a readable directory with at least one entry, none of
+/// which failed to be removed
+///
+/// Such entries were created after the scan. A directory still holding an
+/// entry whose removal failed is not: it could not be removed either way,
+/// its own error says so, and the entry is not tried again. Failures are
+/// only recorded under `--force`, the one case that acts on the answer.
+fn has_only_new_entries(path: &Path, config: &RemoveConfig) -> bool {
+    let Ok(entries) = fs::read_dir(path) else {
+        return false;
+    };
+    let entries: Vec<_> = entries.collect();
+    let Some(failed_paths) = &config.failed_paths else {
+        return !entries.is_empty();
+    };
+    let failed = failed_paths.lock().unwrap();
+    !entries.is_empty()
+        && !entries
+            .iter()
+            .flatten()
+            .any(|entry| failed.contains(&entry.path()))
+}
+
+/// Capacity of the private queue used to clear late entries (`--force`)
+const RESCAN_QUEUE_CAPACITY: usize = 1000;
+
+/// Rescan a directory and delete the entries that appeared since the first
+/// scan, on this thread plus one helper, before its removal is retried
+fn clear_new_entries(path: &Path, config: &RemoveConfig) -> Result<(), RemoveError> {
+    let queue = AdaptiveQueue::new(RESCAN_QUEUE_CAPACITY);
+    let scan_done = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|| delete_worker(&queue, config, &scan_done));
+        let result = scan_contents(path, &queue, config);
+        scan_done.store(true, Ordering::Relaxed);
+        result
+    })
+    .map(|_| ())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -135,6 +825,254 @@ mod tests {
     use std::sync::Arc;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_tombstone_truncates_files_and_removes_empty_dirs() {
+        use crate::progress::RemoveProgress;
+        use crate::scanner::scan_path;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir_all(root.join("logs")).unwrap();
+        fs::create_dir_all(root.join("empty/nested")).unwrap();
+        fs::write(root.join("logs/app.log"), "lots of data").unwrap();
+        fs::write(root.join("top.txt"), "more").unwrap();
+
+        let progress = RemoveProgress::new();
+        let config = RemoveConfig {
+            tombstone: true,
+            tombstone_marker: Some("removed by policy".to_string()),
+            progress: Some(progress.clone()),
+            ..Default::default()
+        };
+        let queue = AdaptiveQueue::new(100);
+        // Directories holding a tombstone are kept, so they are not complete
+        assert!(!scan_path(&root, &queue, &config).unwrap());
+        delete_worker(&queue, &config, &AtomicBool::new(true));
+
+        for file in ["logs/app.log", "top.txt"] {
+            let text = fs::read_to_string(root.join(file)).unwrap();
+            assert_eq!(text, "removed by policy\n");
+        }
+        assert!(!root.join("empty").exists());
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 0);
+        assert_eq!(progress.bytes.load(Ordering::Relaxed), 16);
+
+        // Without a marker the files are left empty
+        let config = RemoveConfig {
+            tombstone: true,
+            ..Default::default()
+        };
+        scan_path(&root, &queue, &config).unwrap();
+        delete_worker(&queue, &config, &AtomicBool::new(true));
+        assert_eq!(fs::metadata(root.join("top.txt")).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_cancelled_worker_drains_without_deleting() {
+        let temp_dir = TempDir::new().unwrap();
+        let queue = AdaptiveQueue::new(10);
+        let mut files = Vec::new();
+        for i in 0..5 {
+            let path = temp_dir.path().join(format!("{}.txt", i));
+            File::create(&path).unwrap();
+            queue
+                .send(FileJob::File {
+                    path: Arc::from(path.as_path()),
+                    size: 0,
+                })
+                .unwrap();
+            files.push(path);
+        }
+
+        let config = RemoveConfig::default();
+        config.cancelled.store(true, Ordering::Relaxed);
+        delete_worker(&queue, &config, &AtomicBool::new(true));
+
+        assert!(queue.is_empty());
+        assert!(files.iter().all(|f| f.exists()));
+    }
+
+    #[test]
+    fn test_type_change_between_scan_and_delete_is_refused() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        std::fs::create_dir(&root).unwrap();
+        let swapped = root.join("swapped");
+        File::create(&swapped).unwrap();
+
+        let progress = crate::progress::RemoveProgress::new();
+        let config = RemoveConfig {
+            continue_on_error: true,
+            progress: Some(progress.clone()),
+            ..Default::default()
+        };
+        let queue = AdaptiveQueue::new(10);
+        crate::scanner::scan_path(&root, &queue, &config).unwrap();
+
+        // Between scan and delete the file becomes a directory
+        std::fs::remove_file(&swapped).unwrap();
+        std::fs::create_dir(&swapped).unwrap();
+        File::create(swapped.join("inner.txt")).unwrap();
+        delete_worker(&queue, &config, &AtomicBool::new(true));
+
+        assert!(swapped.join("inner.txt").exists());
+        // The file job is refused, and the root is then not empty
+        let errors = progress.get_error_files();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(&*errors[0].0, swapped.as_path());
+        assert!(errors[0].1.contains("type changed under us"));
+
+        // And a directory job whose path turned into something else
+        let dir = temp_dir.path().join("dir");
+        File::create(&dir).unwrap();
+        let job = FileJob::EmptyDir(Arc::from(dir.as_path()));
+        assert!(matches!(
+            check_type_unchanged(&job, &config),
+            Err(RemoveError::TypeChanged(_))
+        ));
+        assert!(dir.exists());
+    }
+
+    #[test]
+    fn test_retry_failed_removes_dir_after_delayed_child() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("dir");
+        std::fs::create_dir(&dir).unwrap();
+        let child = dir.join("child.txt");
+        File::create(&child).unwrap();
+
+        let progress = crate::progress::RemoveProgress::new();
+        let config = RemoveConfig {
+            continue_on_error: true,
+            progress: Some(progress.clone()),
+            failed: Some(Arc::default()),
+            ..Default::default()
+        };
+        // The child's deletion is delayed until after its directory's
+        let queue = AdaptiveQueue::new(10);
+        queue
+            .send(FileJob::EmptyDir(Arc::from(dir.as_path())))
+            .unwrap();
+        queue
+            .send(FileJob::File {
+                path: Arc::from(child.as_path()),
+                size: 0,
+            })
+            .unwrap();
+        delete_worker(&queue, &config, &AtomicBool::new(true));
+        assert!(dir.exists());
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 1);
+        assert_eq!(progress.error_groups().len(), 1);
+
+        let mut jobs = std::mem::take(&mut *config.failed.as_ref().unwrap().lock().unwrap());
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].errors.len(), 1);
+        assert_eq!(retry_failed(&mut jobs, &config), 1);
+        assert!(!dir.exists());
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 0);
+        assert!(progress.get_error_files().is_empty());
+        // Its group goes with it, so the summary lists no category
+        assert!(progress.error_groups().is_empty());
+        assert_eq!(progress.deleted.load(Ordering::Relaxed), 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_clear_attrs_removes_immutable_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("append-only");
+        let (file, kept) = (temp_dir.path().join("locked"), dir.join("kept"));
+        fs::create_dir(&dir).unwrap();
+        File::create(&file).unwrap();
+        File::create(&kept).unwrap();
+        let flags = |path: &Path| inode_flags(path).unwrap_or(0);
+        // Needs CAP_LINUX_IMMUTABLE and a file system with inode flags
+        if set_inode_flags(&file, flags(&file) | FS_IMMUTABLE_FL).is_err() {
+            return;
+        }
+        set_inode_flags(&dir, flags(&dir) | FS_APPEND_FL).unwrap();
+
+        let strict = RemoveConfig::default();
+        let err = delete_file(&file, 0, &strict).unwrap_err().to_string();
+        assert!(err.contains("is immutable (chattr +i)"), "{}", err);
+        let err = delete_file(&kept, 0, &strict).unwrap_err().to_string();
+        assert!(err.contains("is append-only (chattr +a)"), "{}", err);
+        assert!(file.exists() && kept.exists());
+
+        let clearing = RemoveConfig {
+            clear_attrs: true,
+            ..Default::default()
+        };
+        delete_file(&file, 0, &clearing).unwrap();
+        delete_file(&kept, 0, &clearing).unwrap();
+        assert!(!file.exists() && !kept.exists());
+        // The directory keeps its attribute until it is removed itself
+        assert_ne!(flags(&dir) & FS_APPEND_FL, 0);
+        delete_empty_dir(&dir, &clearing).unwrap();
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_max_errors_cancels_remaining_jobs() {
+        let temp_dir = TempDir::new().unwrap();
+        let queue = AdaptiveQueue::new(100);
+        for i in 0..50 {
+            // Missing files cannot be removed
+            let path = temp_dir.path().join(format!("missing_{}", i));
+            queue
+                .send(FileJob::File {
+                    path: Arc::from(path.as_path()),
+                    size: 0,
+                })
+                .unwrap();
+        }
+
+        let progress = crate::progress::RemoveProgress::new();
+        let config = RemoveConfig {
+            continue_on_error: true,
+            max_errors: Some(5),
+            progress: Some(progress.clone()),
+            ..Default::default()
+        };
+        delete_worker(&queue, &config, &AtomicBool::new(true));
+
+        // The sixth error trips the limit; the rest is drained untouched
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 6);
+        assert!(config.error_limit_exceeded());
+        assert!(config.cancelled.load(Ordering::Relaxed));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_size_aware_worker_clears_large_files_before_their_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("dir");
+        fs::create_dir(&dir).unwrap();
+        let queue = AdaptiveQueue::new(10).with_large_files(100);
+        for i in 0..3 {
+            let path = dir.join(format!("{}.bin", i));
+            fs::write(&path, vec![0u8; 200]).unwrap();
+            queue
+                .send(FileJob::File {
+                    path: Arc::from(path.as_path()),
+                    size: 200,
+                })
+                .unwrap();
+        }
+        // The directory is the only job in the channel, so it is received
+        // while its files are all still in the size-sorted queue
+        queue
+            .send(FileJob::EmptyDir(Arc::from(dir.as_path())))
+            .unwrap();
+
+        let config = RemoveConfig::default();
+        delete_worker(&queue, &config, &AtomicBool::new(true));
+
+        assert!(!dir.exists());
+        assert!(queue.is_empty());
+        assert_eq!(queue.large_outstanding(), 0);
+    }
+
     #[test]
     fn test_delete_file() {
         let temp_dir = TempDir::new().unwrap();
@@ -142,14 +1080,9 @@ mod tests {
         File::create(&test_file).unwrap();
         assert!(test_file.exists());
 
-        let config = RemoveConfig {
-            verbosity: crate::config::Verbosity::Simple,
-            dry_run: false,
-            continue_on_error: false,
-            progress: None,
-        };
+        let config = RemoveConfig::default();
 
-        delete_file(&test_file, &config).unwrap();
+        delete_file(&test_file, 0, &config).unwrap();
         assert!(!test_file.exists());
     }
 
@@ -160,16 +1093,56 @@ mod tests {
         File::create(&test_file).unwrap();
 
         let config = RemoveConfig {
-            verbosity: crate::config::Verbosity::Simple,
             dry_run: true,
-            continue_on_error: false,
-            progress: None,
+            ..Default::default()
         };
 
-        delete_file(&test_file, &config).unwrap();
+        delete_file(&test_file, 0, &config).unwrap();
         assert!(test_file.exists(), "Dry run should not delete files");
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_dry_run_verify_predicts_unwritable_parent() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let locked = temp_dir.path().join("locked");
+        let inner = locked.join("inner");
+        std::fs::create_dir_all(&inner).unwrap();
+        let test_file = locked.join("test.txt");
+        File::create(&test_file).unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o555)).unwrap();
+        // Root can write anyway, so nothing would fail
+        let writable = File::create(locked.join("probe")).is_ok();
+        let progress = crate::progress::RemoveProgress::new();
+        let config = RemoveConfig {
+            dry_run: true,
+            verify: true,
+            progress: Some(progress.clone()),
+            ..Default::default()
+        };
+
+        let file_result = delete_file(&test_file, 0, &config);
+        let dir_result = delete_empty_dir(&inner, &config);
+        // A plain dry run does not notice
+        let plain = RemoveConfig {
+            dry_run: true,
+            ..Default::default()
+        };
+        assert!(delete_file(&test_file, 0, &plain).is_ok());
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        if writable {
+            return;
+        }
+
+        assert!(matches!(file_result, Err(RemoveError::RemoveFailed(..))));
+        assert!(matches!(dir_result, Err(RemoveError::RemoveDirFailed(..))));
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 2);
+        assert!(test_file.exists());
+        assert!(inner.exists());
+    }
+
     #[test]
     fn test_delete_empty_dir() {
         let temp_dir = TempDir::new().unwrap();
@@ -177,35 +1150,237 @@ mod tests {
         std::fs::create_dir(&test_dir).unwrap();
         assert!(test_dir.exists());
 
-        let config = RemoveConfig {
-            verbosity: crate::config::Verbosity::Simple,
-            dry_run: false,
-            continue_on_error: false,
-            progress: None,
-        };
+        let config = RemoveConfig::default();
 
         delete_empty_dir(&test_dir, &config).unwrap();
         assert!(!test_dir.exists());
     }
 
+    /// Scan `dir`, then create a file in it before the deleter runs
+    fn delete_with_late_file(dir: &Path, config: &RemoveConfig) {
+        let queue = AdaptiveQueue::new(20);
+        crate::scanner::scan_path(dir, &queue, config).unwrap();
+        std::fs::create_dir(dir.join("late")).unwrap();
+        File::create(dir.join("late/file.txt")).unwrap();
+        delete_worker(&queue, config, &AtomicBool::new(true));
+    }
+
     #[test]
-    fn test_delete_worker_basic() {
+    fn test_dir_not_empty_race_is_reported() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("dir");
+        std::fs::create_dir(&dir).unwrap();
+        File::create(dir.join("early.txt")).unwrap();
+
+        let progress = crate::progress::RemoveProgress::new();
+        let config = RemoveConfig {
+            progress: Some(progress.clone()),
+            ..Default::default()
+        };
+        delete_with_late_file(&dir, &config);
+
+        assert!(!dir.join("early.txt").exists());
+        assert!(dir.join("late/file.txt").exists());
+        let errors = progress.get_error_files();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].1.contains("--force"), "{}", errors[0].1);
+    }
+
+    #[test]
+    fn test_force_rescans_and_retries_non_empty_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("dir");
+        std::fs::create_dir(&dir).unwrap();
+        File::create(dir.join("early.txt")).unwrap();
+
+        let progress = crate::progress::RemoveProgress::new();
+        let config = RemoveConfig {
+            progress: Some(progress.clone()),
+            force: true,
+            failed_paths: Some(Arc::default()),
+            ..Default::default()
+        };
+        delete_with_late_file(&dir, &config);
+
+        assert!(!dir.exists());
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 0);
+        // early.txt, dir, plus late/file.txt and late from the rescan
+        assert_eq!(progress.deleted.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn test_failed_child_is_not_a_race_nor_retried() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("dir");
+        std::fs::create_dir(&dir).unwrap();
+        let swapped = dir.join("swapped");
+        File::create(&swapped).unwrap();
+
+        let progress = crate::progress::RemoveProgress::new();
+        let config = RemoveConfig {
+            continue_on_error: true,
+            progress: Some(progress.clone()),
+            force: true,
+            failed_paths: Some(Arc::default()),
+            ..Default::default()
+        };
+        let queue = AdaptiveQueue::new(10);
+        crate::scanner::scan_path(&dir, &queue, &config).unwrap();
+        // The file job is refused, as the file became a directory
+        std::fs::remove_file(&swapped).unwrap();
+        std::fs::create_dir(&swapped).unwrap();
+        File::create(swapped.join("inner.txt")).unwrap();
+        delete_worker(&queue, &config, &AtomicBool::new(true));
+
+        // --force does not clear what already failed, and counts it once
+        assert!(swapped.join("inner.txt").exists());
+        let errors = progress.get_error_files();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[0].1.contains("type changed under us"));
+        assert!(!errors[1].1.contains("--force"), "{}", errors[1].1);
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_delete_records_latency_with_stats() {
         let temp_dir = TempDir::new().unwrap();
         let test_file = temp_dir.path().join("test.txt");
+        let test_dir = temp_dir.path().join("testdir");
         File::create(&test_file).unwrap();
+        std::fs::create_dir(&test_dir).unwrap();
 
+        let progress =
+            crate::progress::RemoveProgress::with_options(crate::progress::ProgressOptions {
+                latency_stats: true,
+                ..Default::default()
+            });
+        let config = RemoveConfig {
+            progress: Some(progress.clone()),
+            ..Default::default()
+        };
+
+        delete_file(&test_file, 0, &config).unwrap();
+        delete_empty_dir(&test_dir, &config).unwrap();
+        assert_eq!(progress.latency_stats().unwrap().histogram.len(), 2);
+    }
+
+    #[test]
+    fn test_stats_by_extension() {
+        use crate::scanner::scan_path;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("build");
+        std::fs::create_dir(&root).unwrap();
+        for (name, len) in [
+            ("a.o", 100),
+            ("b.o", 100),
+            ("c.O", 100),
+            ("main.rs", 1000),
+            ("Makefile", 10),
+        ] {
+            std::fs::write(root.join(name), vec![0u8; len]).unwrap();
+        }
+
+        let progress =
+            crate::progress::RemoveProgress::with_options(crate::progress::ProgressOptions {
+                ext_stats: true,
+                ..Default::default()
+            });
+        let config = RemoveConfig {
+            progress: Some(progress.clone()),
+            ..Default::default()
+        };
         let queue = AdaptiveQueue::new(10);
+        assert!(scan_path(&root, &queue, &config).unwrap());
+        delete_worker(&queue, &config, &AtomicBool::new(true));
+
+        let mut stats = progress.extension_stats().unwrap();
+        stats.sort();
+        assert_eq!(
+            stats,
+            [
+                ("(none)".to_string(), 1, 10),
+                ("o".to_string(), 3, 300),
+                ("rs".to_string(), 1, 1000),
+            ]
+        );
+        // Off by default: nothing is tallied
+        assert!(crate::progress::RemoveProgress::new()
+            .extension_stats()
+            .is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stay_within_root_refuses_symlink_escape() {
+        use crate::scanner::scan_path;
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let outside = temp_dir.path().join("outside");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::create_dir(&outside).unwrap();
+        File::create(root.join("inside.txt")).unwrap();
+        File::create(outside.join("victim.txt")).unwrap();
+        symlink(&outside, root.join("escape")).unwrap();
+
+        let progress = crate::progress::RemoveProgress::new();
         let config = RemoveConfig {
-            verbosity: crate::config::Verbosity::Simple,
-            dry_run: false,
-            continue_on_error: false,
-            progress: None,
+            progress: Some(progress.clone()),
+            follow_symlinks: true,
+            continue_on_error: true,
+            stay_within_roots: Some(vec![root.canonicalize().unwrap()].into()),
+            ..Default::default()
         };
+        let queue = AdaptiveQueue::new(20);
+        scan_path(&root, &queue, &config).unwrap();
+        delete_worker(&queue, &config, &AtomicBool::new(true));
+
+        // The link and everything under the root go; the target survives
+        assert!(outside.join("victim.txt").exists());
+        assert!(!root.exists());
+        // victim.txt and the outside directory itself
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_gated_worker_only_runs_when_admitted() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        File::create(&test_file).unwrap();
+
+        let queue = AdaptiveQueue::new(10);
+        let config = RemoveConfig::default();
+        let scanners_done = AtomicBool::new(true);
+        let path: Arc<Path> = Arc::from(test_file.as_path());
+        queue.send(FileJob::File { path, size: 0 }).unwrap();
+
+        // Worker 1 is parked with one active slot, so it must leave the job
+        let gate = ThreadGate::new(1);
+        std::thread::scope(|s| {
+            let parked = s.spawn(|| gated_delete_worker(&queue, &config, &scanners_done, &gate, 1));
+            std::thread::sleep(Duration::from_millis(150));
+            assert!(test_file.exists());
+            gate.set_active(2);
+            parked.join().unwrap();
+        });
+        assert!(!test_file.exists());
+    }
+
+    #[test]
+    fn test_delete_worker_basic() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        File::create(&test_file).unwrap();
+
+        let queue = AdaptiveQueue::new(10);
+        let config = RemoveConfig::default();
         let scanners_done = AtomicBool::new(false);
 
         // Enqueue file
         let path: Arc<Path> = Arc::from(test_file.as_path());
-        queue.send(FileJob::File(path)).unwrap();
+        queue.send(FileJob::File { path, size: 0 }).unwrap();
 
         // Mark scanners as done
         scanners_done.store(true, Ordering::Relaxed);
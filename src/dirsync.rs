@@ -0,0 +1,128 @@
+//! Making removals durable by fsyncing the directories they changed
+//! (`--fsync-dirs`)
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::errors::RemoveError;
+
+/// Changed directories collected before they are fsynced together
+const SYNC_BATCH: usize = 256;
+
+/// Directories whose entries changed and have not been fsynced yet
+///
+/// An unlink only survives a crash once the directory that held the entry
+/// is fsynced. Each removal marks its parent; a removed directory drops out
+/// of the set, since fsyncing it no longer means anything, and marks its own
+/// parent instead. The set is fsynced once it holds `SYNC_BATCH`
+/// directories and again by `flush` at the end, so a directory that loses
+/// thousands of entries is fsynced a handful of times rather than once per
+/// entry.
+#[derive(Debug, Default)]
+pub struct DirSync {
+    dirty: Mutex<HashSet<PathBuf>>,
+}
+
+impl DirSync {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note that `path` was removed; fsyncs a full batch
+    ///
+    /// Returns the directories that could not be fsynced.
+    pub fn removed(&self, path: &Path, was_dir: bool) -> Vec<RemoveError> {
+        let batch = {
+            let mut dirty = self.dirty.lock().unwrap();
+            if was_dir {
+                dirty.remove(path);
+            }
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                dirty.insert(parent.to_path_buf());
+            }
+            if dirty.len() < SYNC_BATCH {
+                return Vec::new();
+            }
+            std::mem::take(&mut *dirty)
+        };
+        sync_all(batch)
+    }
+
+    /// Fsync every directory still marked
+    pub fn flush(&self) -> Vec<RemoveError> {
+        let batch = std::mem::take(&mut *self.dirty.lock().unwrap());
+        sync_all(batch)
+    }
+}
+
+fn sync_all(dirs: HashSet<PathBuf>) -> Vec<RemoveError> {
+    dirs.into_iter()
+        .filter_map(|dir| match sync_dir(&dir) {
+            // Removed since it was marked, e.g. by another deleter
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => Some(RemoveError::DirSyncFailed(dir, e)),
+            Ok(()) => None,
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+/// Directories cannot be opened for fsync here; NTFS journals the change
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_removed_dirs_are_dropped_and_parents_synced() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("sub/a"), "a").unwrap();
+        fs::write(root.join("b"), "b").unwrap();
+
+        let sync = DirSync::new();
+        fs::remove_file(root.join("sub/a")).unwrap();
+        assert!(sync.removed(&root.join("sub/a"), false).is_empty());
+        fs::remove_dir(root.join("sub")).unwrap();
+        assert!(sync.removed(&root.join("sub"), true).is_empty());
+        fs::remove_file(root.join("b")).unwrap();
+        assert!(sync.removed(&root.join("b"), false).is_empty());
+        assert_eq!(*sync.dirty.lock().unwrap(), HashSet::from([root.clone()]));
+
+        assert!(sync.flush().is_empty());
+        assert!(sync.dirty.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_full_batch_is_synced_and_vanished_dirs_ignored() {
+        let temp_dir = TempDir::new().unwrap();
+        let sync = DirSync::new();
+        for i in 0..SYNC_BATCH - 1 {
+            let dir = temp_dir.path().join(format!("d{}", i));
+            fs::create_dir(&dir).unwrap();
+            assert!(sync.removed(&dir.join("f"), false).is_empty());
+        }
+        // Marked, then gone before the batch is synced
+        fs::remove_dir(temp_dir.path().join("d0")).unwrap();
+        assert_eq!(sync.dirty.lock().unwrap().len(), SYNC_BATCH - 1);
+
+        assert!(sync
+            .removed(&temp_dir.path().join("last/f"), false)
+            .is_empty());
+        assert!(sync.dirty.lock().unwrap().is_empty());
+    }
+}
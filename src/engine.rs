@@ -0,0 +1,140 @@
+//! Choosing between the scanner/deleter pipeline and plain recursion
+//! (`--engine`)
+
+use clap::ValueEnum;
+use std::fs;
+use std::path::Path;
+
+use crate::scanner::is_mount_point;
+
+/// Entries an input may have in all for `auto` to pick the recursive engine
+///
+/// Around this size starting the scanner pool, the deleters and the queue
+/// costs about as much as the deletion itself (`engine_crossover` bench).
+pub const SMALL_INPUT_ENTRIES: usize = 1_000;
+
+/// Directory levels below a top-level path the probe looks into; anything
+/// deeper counts as large
+const PROBE_DEPTH: usize = 2;
+
+/// How the entries are deleted
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    /// Recursive for small inputs and plain runs, the pipeline otherwise
+    #[default]
+    Auto,
+    /// Scanner and deleter pools joined by a queue
+    Pipeline,
+    /// Each path removed by recursion on the calling thread, fanning out to
+    /// rayon's pool per directory; no filters, journals or other extras
+    Recursive,
+}
+
+impl Engine {
+    /// The engine to run for `paths`: `auto` picks recursion only for a
+    /// small input when `plain`, i.e. no option needs the pipeline
+    pub fn resolve(self, paths: &[impl AsRef<Path>], plain: bool) -> Engine {
+        match self {
+            Engine::Auto if plain && is_small_input(paths, SMALL_INPUT_ENTRIES) => {
+                Engine::Recursive
+            }
+            Engine::Auto => Engine::Pipeline,
+            engine => engine,
+        }
+    }
+}
+
+/// Whether `paths` hold at most `budget` entries in all, none of them more
+/// than `PROBE_DEPTH` levels down
+///
+/// Files and symlinks count as one entry. Listing stops as soon as the
+/// budget is spent, so a large tree costs no more than a small one.
+/// Unreadable paths, special files and mount points are left to the
+/// pipeline, which warns about them.
+pub fn is_small_input(paths: &[impl AsRef<Path>], budget: usize) -> bool {
+    let mut left = budget;
+    paths
+        .iter()
+        .all(|path| fits(path.as_ref(), PROBE_DEPTH, &mut left))
+}
+
+/// Spend one entry of `left` on `path` and, for a directory, one per entry
+/// below it; false once `left` or `depth` runs out
+fn fits(path: &Path, depth: usize, left: &mut usize) -> bool {
+    let Some(rest) = left.checked_sub(1) else {
+        return false;
+    };
+    *left = rest;
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() && !is_mount_point(path, &metadata) => {}
+        Ok(metadata) => return metadata.is_file() || metadata.file_type().is_symlink(),
+        Err(_) => return false,
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return false;
+    };
+    for entry in entries {
+        let Ok(entry) = entry else {
+            return false;
+        };
+        let Ok(file_type) = entry.file_type() else {
+            return false;
+        };
+        let fits = if file_type.is_dir() {
+            depth > 0 && fits(&entry.path(), depth - 1, left)
+        } else {
+            (file_type.is_file() || file_type.is_symlink())
+                && left.checked_sub(1).map(|rest| *left = rest).is_some()
+        };
+        if !fits {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_small_input_is_probed_within_budget() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/b/c/d")).unwrap();
+        fs::create_dir(root.join("flat")).unwrap();
+        for i in 0..10 {
+            fs::write(root.join(format!("flat/{}", i)), "").unwrap();
+        }
+        let file = root.join("loose.txt");
+        fs::write(&file, "").unwrap();
+
+        assert!(is_small_input(&[&file], 1));
+        // flat and its 10 files
+        assert!(is_small_input(&[root.join("flat")], 11));
+        assert!(!is_small_input(&[root.join("flat")], 10));
+        // b, c and d, then the file
+        assert!(is_small_input(&[root.join("a/b"), file.clone()], 4));
+        assert!(!is_small_input(&[root.join("a/b"), file], 3));
+        // d is more than PROBE_DEPTH levels below a
+        assert!(!is_small_input(&[root.join("a")], 100));
+        assert!(!is_small_input(&[root.join("missing")], 100));
+        #[cfg(unix)]
+        {
+            let socket = root.join("flat/app.sock");
+            let _listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+            assert!(!is_small_input(&[root.join("flat")], 100));
+        }
+    }
+
+    #[test]
+    fn test_auto_needs_a_plain_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let paths = [temp_dir.path()];
+        assert_eq!(Engine::Auto.resolve(&paths, true), Engine::Recursive);
+        assert_eq!(Engine::Auto.resolve(&paths, false), Engine::Pipeline);
+        assert_eq!(Engine::Pipeline.resolve(&paths, true), Engine::Pipeline);
+        assert_eq!(Engine::Recursive.resolve(&paths, false), Engine::Recursive);
+    }
+}
@@ -2,6 +2,9 @@ use std::fmt;
 use std::io;
 use std::path::PathBuf;
 
+#[cfg(windows)]
+use crate::openfiles::Holder;
+
 #[derive(Debug)]
 pub enum RemoveError {
     MetadataFailed(PathBuf, io::Error),
@@ -12,6 +15,162 @@ pub enum RemoveError {
     UnsupportedType(PathBuf),
     PathOverlap(String),
     QueueFull,
+    IgnoreFileFailed(PathBuf, String),
+    EscapesRoot(PathBuf),
+    ShadowFailed(PathBuf, io::Error),
+    FilesFromFailed(PathBuf, io::Error),
+    DirNotEmpty(PathBuf),
+    JournalFailed(PathBuf, io::Error),
+    KeepListFailed(PathBuf, io::Error),
+    InodesFromFailed(PathBuf, io::Error),
+    ReferenceFileFailed(PathBuf, io::Error),
+    TypeChanged(PathBuf),
+    IsADirectory(PathBuf),
+    NotADirectory(PathBuf),
+    PlanFailed(PathBuf, io::Error),
+    PlanInvalid(PathBuf, usize, String),
+    SpillFailed(PathBuf, io::Error),
+    ContainsCwd(PathBuf, PathBuf),
+    DirSyncFailed(PathBuf, io::Error),
+    BackupFailed(PathBuf, io::Error),
+    SymlinkLoop(PathBuf, PathBuf),
+    SymlinkChainTooLong(PathBuf, usize),
+    ArgumentInaccessible(PathBuf, io::Error),
+    EmptyArgument(usize),
+    DotArgument(PathBuf),
+    RootArgument(PathBuf),
+    #[cfg(windows)]
+    HeldOpen(PathBuf, Vec<Holder>),
+}
+
+/// Broad class of a failure, for grouping errors in the summary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// Permission denied or not permitted, or a read-only file system
+    Permission,
+    NotFound,
+    /// A directory that still had entries
+    NotEmpty,
+    /// In use, or a mount point
+    Busy,
+    /// Abandoned under `--op-timeout`
+    TimedOut,
+    Other,
+}
+
+impl ErrorCategory {
+    pub const ALL: [ErrorCategory; 6] = [
+        ErrorCategory::Permission,
+        ErrorCategory::NotFound,
+        ErrorCategory::NotEmpty,
+        ErrorCategory::Busy,
+        ErrorCategory::TimedOut,
+        ErrorCategory::Other,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ErrorCategory::Permission => "permission denied",
+            ErrorCategory::NotFound => "not found",
+            ErrorCategory::NotEmpty => "not empty",
+            ErrorCategory::Busy => "busy",
+            ErrorCategory::TimedOut => "timed out",
+            ErrorCategory::Other => "other",
+        }
+    }
+
+    /// The category of an OS error; errno values are grouped as for `hint`
+    pub fn of_io(err: &io::Error) -> Self {
+        #[cfg(unix)]
+        match err.raw_os_error() {
+            Some(libc::ENOTEMPTY | libc::EEXIST) => return ErrorCategory::NotEmpty,
+            Some(libc::EBUSY | libc::ETXTBSY) => return ErrorCategory::Busy,
+            Some(libc::EROFS) => return ErrorCategory::Permission,
+            _ => {}
+        }
+        // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+        #[cfg(windows)]
+        if let Some(32 | 33) = err.raw_os_error() {
+            return ErrorCategory::Busy;
+        }
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => ErrorCategory::Permission,
+            io::ErrorKind::NotFound => ErrorCategory::NotFound,
+            io::ErrorKind::TimedOut => ErrorCategory::TimedOut,
+            _ => ErrorCategory::Other,
+        }
+    }
+}
+
+impl RemoveError {
+    /// The category the summary groups this error under
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            RemoveError::DirNotEmpty(_) => ErrorCategory::NotEmpty,
+            #[cfg(windows)]
+            RemoveError::HeldOpen(..) => ErrorCategory::Busy,
+            _ => self
+                .io_error()
+                .map_or(ErrorCategory::Other, ErrorCategory::of_io),
+        }
+    }
+
+    /// The OS error behind a failed file system call
+    fn io_error(&self) -> Option<&io::Error> {
+        match self {
+            RemoveError::MetadataFailed(_, err)
+            | RemoveError::RemoveFailed(_, err)
+            | RemoveError::ReadDirFailed(_, err)
+            | RemoveError::RemoveDirFailed(_, err)
+            | RemoveError::DirEntryFailed(_, err)
+            | RemoveError::ArgumentInaccessible(_, err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// What to try next, for the errno values users most often hit; the
+    /// message itself already ends with the errno, e.g. `(os error 13)`
+    #[cfg(unix)]
+    pub fn hint(&self) -> Option<&'static str> {
+        let errno = self.io_error()?.raw_os_error()?;
+        if let RemoveError::ArgumentInaccessible(..) = self {
+            return match errno {
+                libc::EACCES => Some(
+                    "a directory above it lacks search (x) permission; it cannot be examined, let alone deleted",
+                ),
+                libc::ENOENT => Some("check the spelling; it may also have been deleted already"),
+                _ => None,
+            };
+        }
+        match errno {
+            libc::EACCES | libc::EPERM => Some(
+                "check the parent directory's permissions: unlinking needs write and search access to it",
+            ),
+            libc::ENOTEMPTY | libc::EEXIST => {
+                Some("a child deletion likely failed; see the errors above")
+            }
+            libc::EBUSY | libc::ETXTBSY => {
+                Some("file in use or a mount point; try again later or with --retry-failed")
+            }
+            libc::EROFS => Some("the file system is mounted read-only"),
+            _ => None,
+        }
+    }
+
+    #[cfg(windows)]
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            RemoveError::HeldOpen(..) => {
+                Some("close it in those programs, or use --skip-open to leave it in place")
+            }
+            _ => None,
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    pub fn hint(&self) -> Option<&'static str> {
+        None
+    }
 }
 
 impl fmt::Display for RemoveError {
@@ -41,6 +200,110 @@ impl fmt::Display for RemoveError {
             }
             RemoveError::PathOverlap(msg) => write!(f, "{}", msg),
             RemoveError::QueueFull => write!(f, "Work queue is full, scanner blocked"),
+            RemoveError::IgnoreFileFailed(path, err) => {
+                write!(f, "Failed to parse ignore file {:?}: {}", path, err)
+            }
+            RemoveError::EscapesRoot(path) => write!(
+                f,
+                "Refusing to delete {:?}: it resolves outside the deletion roots",
+                path
+            ),
+            RemoveError::ShadowFailed(path, err) => {
+                write!(f, "Failed to create shadow links for {:?}: {}", path, err)
+            }
+            RemoveError::FilesFromFailed(path, err) => {
+                write!(f, "Failed to read path list {:?}: {}", path, err)
+            }
+            RemoveError::KeepListFailed(path, err) => {
+                write!(f, "Failed to read keep list {:?}: {}", path, err)
+            }
+            RemoveError::InodesFromFailed(path, err) => {
+                write!(f, "Failed to read inode list {:?}: {}", path, err)
+            }
+            RemoveError::ReferenceFileFailed(path, err) => {
+                write!(f, "Failed to read reference file {:?}: {}", path, err)
+            }
+            RemoveError::DirNotEmpty(path) => write!(
+                f,
+                "Directory {:?} is not empty: entries were created in it after it was scanned (use --force to rescan and retry)",
+                path
+            ),
+            RemoveError::JournalFailed(path, err) => {
+                write!(f, "Failed to access journal {:?}: {}", path, err)
+            }
+            RemoveError::IsADirectory(path) => write!(
+                f,
+                "Cannot remove {:?}: is a directory (--no-recursive)",
+                path
+            ),
+            RemoveError::PlanFailed(path, err) => {
+                write!(f, "Failed to access plan {:?}: {}", path, err)
+            }
+            RemoveError::PlanInvalid(path, line, reason) => {
+                write!(f, "Invalid plan {:?}, line {}: {}", path, line, reason)
+            }
+            RemoveError::SpillFailed(path, err) => {
+                write!(f, "Failed to spill the job list to {:?}: {}", path, err)
+            }
+            RemoveError::DirSyncFailed(path, err) => {
+                write!(f, "Failed to fsync directory {:?}: {}", path, err)
+            }
+            RemoveError::BackupFailed(path, err) => {
+                write!(f, "Failed to back up {:?}: {}", path, err)
+            }
+            RemoveError::SymlinkLoop(link, target) => write!(
+                f,
+                "Symlink loop: {:?} points back to {:?}, which contains it; not followed",
+                link, target
+            ),
+            RemoveError::SymlinkChainTooLong(link, max) => write!(
+                f,
+                "Symlink chain from {:?} is longer than {} links (--max-symlink-depth); not followed",
+                link, max
+            ),
+            RemoveError::ArgumentInaccessible(path, err) => {
+                write!(f, "Cannot access argument {:?}: {}", path, err)
+            }
+            RemoveError::EmptyArgument(position) => write!(
+                f,
+                "Path argument {} is empty; an empty string names no file",
+                position
+            ),
+            RemoveError::DotArgument(path) => write!(
+                f,
+                "Refusing to delete {:?}: \".\" and \"..\" name a directory from inside it (use --allow-dot, with --contents-only to keep the directory)",
+                path
+            ),
+            RemoveError::RootArgument(path) => write!(
+                f,
+                "Refusing to delete {:?}: it is the root of a file system",
+                path
+            ),
+            RemoveError::ContainsCwd(path, cwd) => write!(
+                f,
+                "Refusing to delete {:?}: it contains the current directory {:?} (use --allow-delete-cwd)",
+                path, cwd
+            ),
+            RemoveError::NotADirectory(path) => write!(
+                f,
+                "Cannot remove {:?}: Not a directory (a trailing slash names a directory)",
+                path
+            ),
+            RemoveError::TypeChanged(path) => write!(
+                f,
+                "Refusing to delete {:?}: type changed under us since it was scanned",
+                path
+            ),
+            #[cfg(windows)]
+            RemoveError::HeldOpen(path, holders) => {
+                let holders: Vec<String> = holders.iter().map(Holder::to_string).collect();
+                write!(
+                    f,
+                    "Failed to remove {:?}: it is open in {}",
+                    path,
+                    holders.join(", ")
+                )
+            }
         }
     }
 }
@@ -57,4 +320,59 @@ mod tests {
         assert!(display.contains("/tmp/test"));
         assert!(display.contains("not a file, directory, or symlink"));
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_permission_error_hint() {
+        let path = PathBuf::from("/tmp/test");
+        let error =
+            RemoveError::RemoveFailed(path.clone(), io::Error::from_raw_os_error(libc::EACCES));
+        assert!(error
+            .to_string()
+            .contains(&format!("(os error {})", libc::EACCES)));
+        assert!(error
+            .hint()
+            .unwrap()
+            .contains("parent directory's permissions"));
+
+        let error = RemoveError::RemoveDirFailed(
+            path.clone(),
+            io::Error::from_raw_os_error(libc::ENOTEMPTY),
+        );
+        assert!(error.hint().unwrap().contains("child deletion"));
+        let error =
+            RemoveError::RemoveFailed(path.clone(), io::Error::from_raw_os_error(libc::ENOENT));
+        assert_eq!(error.hint(), None);
+        assert_eq!(RemoveError::TypeChanged(path).hint(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_error_categories() {
+        let path = PathBuf::from("/tmp/test");
+        let io_error =
+            |errno| RemoveError::RemoveFailed(path.clone(), io::Error::from_raw_os_error(errno));
+        for (errno, category) in [
+            (libc::EACCES, ErrorCategory::Permission),
+            (libc::EPERM, ErrorCategory::Permission),
+            (libc::EROFS, ErrorCategory::Permission),
+            (libc::ENOENT, ErrorCategory::NotFound),
+            (libc::ENOTEMPTY, ErrorCategory::NotEmpty),
+            (libc::EBUSY, ErrorCategory::Busy),
+            (libc::ETXTBSY, ErrorCategory::Busy),
+            (libc::EIO, ErrorCategory::Other),
+        ] {
+            assert_eq!(io_error(errno).category(), category, "errno {}", errno);
+        }
+        let timed_out = io::Error::new(io::ErrorKind::TimedOut, "abandoned");
+        assert_eq!(ErrorCategory::of_io(&timed_out), ErrorCategory::TimedOut);
+        assert_eq!(
+            RemoveError::DirNotEmpty(path.clone()).category(),
+            ErrorCategory::NotEmpty
+        );
+        assert_eq!(
+            RemoveError::TypeChanged(path).category(),
+            ErrorCategory::Other
+        );
+    }
 }
@@ -0,0 +1,189 @@
+//! Deleting the oldest files until enough space is free (`--until-free`)
+
+use colored::*;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::config::RemoveConfig;
+use crate::deleter::execute;
+use crate::queue::FileJob;
+use crate::scanner::collect_jobs;
+
+/// A regular file found by the scan, with what eviction sorts and counts by
+#[derive(Debug)]
+pub struct Candidate {
+    pub path: Arc<Path>,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// How an eviction ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Eviction {
+    /// Files deleted, or that would be in a dry run
+    pub files: u64,
+    /// Their total size
+    pub bytes: u64,
+    /// Free space when it stopped (predicted in a dry run)
+    pub free: u64,
+    /// Whether the target was met; `false` if the files ran out first
+    pub reached: bool,
+}
+
+/// Scan `paths` and list their regular files, oldest first
+///
+/// Scan errors are reported and the path counted as failed, as in a normal
+/// run. Only files are candidates: symlinks free nothing worth counting and
+/// directories are left in place.
+pub fn oldest_first(paths: &[PathBuf], config: &RemoveConfig) -> Vec<Candidate> {
+    let mut files = Vec::new();
+    for path in paths {
+        let scanned = collect_jobs(path, config, |job| {
+            if let FileJob::File { path, size } = job {
+                // Gone already, or unreadable: the deletion would fail anyway
+                if let Ok(modified) = std::fs::symlink_metadata(&path).and_then(|m| m.modified()) {
+                    files.push(Candidate {
+                        path,
+                        size,
+                        modified,
+                    });
+                }
+            }
+        });
+        if let Err(e) = scanned {
+            eprintln!("{} {}", "Scan error:".red().bold(), e);
+            if let Some(p) = &config.progress {
+                p.inc_path_error(path);
+            }
+        }
+    }
+    files.sort_by_key(|file| file.modified);
+    files
+}
+
+/// Delete `files` in order until `free` reports at least `target` bytes
+///
+/// `free` is polled at the start and after every deletion, given the bytes
+/// freed so far; it returns `statvfs` free space in a real run and a
+/// prediction in a dry run. A file that cannot be deleted is reported and
+/// passed over.
+pub fn delete_until(
+    files: Vec<Candidate>,
+    target: u64,
+    config: &RemoveConfig,
+    mut free: impl FnMut(u64) -> io::Result<u64>,
+) -> io::Result<Eviction> {
+    let mut eviction = Eviction {
+        files: 0,
+        bytes: 0,
+        free: free(0)?,
+        reached: false,
+    };
+    for file in files {
+        if eviction.free >= target || config.is_cancelled() {
+            break;
+        }
+        let job = FileJob::File {
+            path: file.path,
+            size: file.size,
+        };
+        if execute(&job, config).is_ok() {
+            eviction.files += 1;
+            eviction.bytes += file.size;
+        }
+        eviction.free = free(eviction.bytes)?;
+    }
+    eviction.reached = eviction.free >= target;
+    Ok(eviction)
+}
+
+/// Space available to unprivileged users on the file system holding `path`
+#[cfg(unix)]
+pub fn free_space(path: &Path) -> io::Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: a valid C string and a zeroed statvfs for the call to fill
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn free_space(_path: &Path) -> io::Result<u64> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "free space can only be measured on Unix",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::RemoveProgress;
+    use std::fs::{self, File};
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_oldest_files_go_until_the_target_is_met() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = temp_dir.path().join("cache");
+        fs::create_dir_all(cache.join("sub")).unwrap();
+        let now = SystemTime::now();
+        // Written in a different order from their ages
+        for (name, age_days) in [("b", 30), ("sub/a", 40), ("d", 10), ("c", 20), ("e", 1)] {
+            let file = File::create(cache.join(name)).unwrap();
+            file.set_len(1000).unwrap();
+            file.set_modified(now - Duration::from_secs(age_days * 86_400))
+                .unwrap();
+        }
+
+        let progress = RemoveProgress::new();
+        let config = RemoveConfig {
+            progress: Some(progress.clone()),
+            ..Default::default()
+        };
+        let files = oldest_first(std::slice::from_ref(&cache), &config);
+        let names: Vec<_> = files
+            .iter()
+            .map(|f| f.path.strip_prefix(&cache).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(names, ["sub/a", "b", "c", "d", "e"].map(PathBuf::from));
+
+        // A simulated file system with 500 bytes free that gains what is freed
+        let eviction = delete_until(files, 2_200, &config, |freed| Ok(500 + freed)).unwrap();
+        assert_eq!(
+            eviction,
+            Eviction {
+                files: 2,
+                bytes: 2_000,
+                free: 2_500,
+                reached: true,
+            }
+        );
+        assert!(!cache.join("sub/a").exists());
+        assert!(!cache.join("b").exists());
+        assert!(cache.join("c").exists());
+        assert!(cache.join("sub").exists());
+
+        // Not enough files left to reach the target
+        let files = oldest_first(std::slice::from_ref(&cache), &config);
+        let eviction = delete_until(files, 1_000_000, &config, Ok).unwrap();
+        assert_eq!((eviction.files, eviction.reached), (3, false));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_free_space_is_measured() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(free_space(temp_dir.path()).unwrap() > 0);
+        assert!(free_space(&temp_dir.path().join("missing")).is_err());
+    }
+}
@@ -0,0 +1,588 @@
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::filter::{parse_duration, parse_size};
+
+/// A parsed `--expr` predicate
+///
+/// ```text
+/// expr  := and ("or" and)*
+/// and   := unary ("and" unary)*
+/// unary := "not" unary | "(" expr ")" | test
+/// test  := size OP SIZE | age OP AGE | name (= | != | ~) NAME | type (= | !=) TYPE
+/// OP    := < | <= | > | >= | = | !=
+/// ```
+///
+/// Keywords are case-insensitive and values may be quoted with `'` or `"`.
+/// `name ~ GLOB` matches the file name against a glob (`*` and `?`), `age`
+/// is the time since the last modification, and `type` is one of `file`,
+/// `dir` or `symlink` (or `f`, `d`, `l`). Tests that need metadata are false
+/// for entries whose metadata cannot be read.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Size(Cmp, u64),
+    Age(Cmp, Duration),
+    Name {
+        glob: bool,
+        negate: bool,
+        pattern: String,
+    },
+    Type {
+        negate: bool,
+        kind: EntryType,
+    },
+}
+
+/// Comparison in a `size` or `age` test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Cmp {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Cmp::Lt => ordering.is_lt(),
+            Cmp::Le => ordering.is_le(),
+            Cmp::Gt => ordering.is_gt(),
+            Cmp::Ge => ordering.is_ge(),
+            Cmp::Eq => ordering.is_eq(),
+            Cmp::Ne => ordering.is_ne(),
+        }
+    }
+}
+
+/// Entry kind in a `type` test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryType {
+    File,
+    Dir,
+    Symlink,
+}
+
+impl Expr {
+    /// Parse an expression, with an error message pointing at the problem
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+            end: input.chars().count(),
+        };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(format!(
+                "unexpected {} at position {}; expected 'and', 'or' or the end",
+                token.kind, token.at
+            )),
+        }
+    }
+
+    /// Whether `path` satisfies the expression
+    pub fn matches(&self, path: &Path) -> bool {
+        self.eval(&mut Entry {
+            path,
+            metadata: None,
+            now: SystemTime::now(),
+        })
+    }
+
+    fn eval(&self, entry: &mut Entry) -> bool {
+        match self {
+            Expr::And(a, b) => a.eval(entry) && b.eval(entry),
+            Expr::Or(a, b) => a.eval(entry) || b.eval(entry),
+            Expr::Not(a) => !a.eval(entry),
+            Expr::Size(cmp, size) => entry
+                .metadata()
+                .is_some_and(|m| cmp.holds(m.len().cmp(size))),
+            Expr::Age(cmp, age) => {
+                let now = entry.now;
+                entry
+                    .metadata()
+                    .and_then(|m| m.modified().ok())
+                    .is_some_and(|modified| {
+                        let elapsed = now.duration_since(modified).unwrap_or_default();
+                        cmp.holds(elapsed.cmp(age))
+                    })
+            }
+            Expr::Name {
+                glob,
+                negate,
+                pattern,
+            } => {
+                let name = entry
+                    .path
+                    .file_name()
+                    .map(|n| n.to_string_lossy())
+                    .unwrap_or_default();
+                let matched = if *glob {
+                    glob_match(pattern, &name)
+                } else {
+                    name == pattern.as_str()
+                };
+                matched != *negate
+            }
+            Expr::Type { negate, kind } => entry.metadata().is_some_and(|m| {
+                let t = m.file_type();
+                let is = match kind {
+                    EntryType::File => t.is_file(),
+                    EntryType::Dir => t.is_dir(),
+                    EntryType::Symlink => t.is_symlink(),
+                };
+                is != *negate
+            }),
+        }
+    }
+}
+
+/// The entry under test; metadata is read once, on first use
+struct Entry<'a> {
+    path: &'a Path,
+    metadata: Option<Option<fs::Metadata>>,
+    now: SystemTime,
+}
+
+impl Entry<'_> {
+    fn metadata(&mut self) -> Option<&fs::Metadata> {
+        let path = self.path;
+        self.metadata
+            .get_or_insert_with(|| fs::symlink_metadata(path).ok())
+            .as_ref()
+    }
+}
+
+/// Match `name` against a glob where `*` is any run of characters and `?`
+/// any one character
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was, and how much of the name it has taken so far
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // Let the last `*` take one more character and retry
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word(String),
+    Quoted(String),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenKind::Word(w) => write!(f, "'{}'", w),
+            TokenKind::Quoted(q) => write!(f, "{:?}", q),
+            TokenKind::Op(op) => write!(f, "'{}'", op),
+            TokenKind::Open => f.write_str("'('"),
+            TokenKind::Close => f.write_str("')'"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Token {
+    kind: TokenKind,
+    /// Character offset in the input, for error messages
+    at: usize,
+}
+
+const OPS: [&str; 7] = ["<=", ">=", "!=", "<", ">", "=", "~"];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let at = i;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let kind = match c {
+            '(' => {
+                i += 1;
+                TokenKind::Open
+            }
+            ')' => {
+                i += 1;
+                TokenKind::Close
+            }
+            '\'' | '"' => {
+                let close = chars[i + 1..]
+                    .iter()
+                    .position(|&q| q == c)
+                    .ok_or_else(|| format!("unterminated quote at position {}", at))?;
+                let value = chars[i + 1..i + 1 + close].iter().collect();
+                i += close + 2;
+                TokenKind::Quoted(value)
+            }
+            _ => {
+                let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+                if let Some(op) = OPS.into_iter().find(|op| rest.starts_with(op)) {
+                    i += op.len();
+                    TokenKind::Op(op)
+                } else if c == '!' {
+                    return Err(format!("unexpected '!' at position {}; use 'not'", at));
+                } else {
+                    let len = chars[i..]
+                        .iter()
+                        .position(|&c| c.is_whitespace() || "()'\"<>=!~".contains(c))
+                        .unwrap_or(chars.len() - i);
+                    i += len;
+                    TokenKind::Word(chars[at..i].iter().collect())
+                }
+            }
+        };
+        tokens.push(Token { kind, at });
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    /// Input length, the position reported for a missing token
+    end: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token { kind: TokenKind::Word(w), .. }) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.at_keyword("or") {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.at_keyword("and") {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.at_keyword("not") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        let end = self.end;
+        match self.next() {
+            Some(Token {
+                kind: TokenKind::Open,
+                at,
+            }) => {
+                let open = *at;
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token {
+                        kind: TokenKind::Close,
+                        ..
+                    }) => Ok(expr),
+                    Some(token) => Err(format!(
+                        "expected ')' at position {} to close '(' at position {}, found {}",
+                        token.at, open, token.kind
+                    )),
+                    None => Err(format!("missing ')' for '(' at position {}", open)),
+                }
+            }
+            Some(Token {
+                kind: TokenKind::Word(field),
+                at,
+            }) => {
+                let (field, at) = (field.to_ascii_lowercase(), *at);
+                self.test(&field, at)
+            }
+            Some(token) => Err(format!(
+                "expected a test such as 'size > 10M' at position {}, found {}",
+                token.at, token.kind
+            )),
+            None => Err(format!(
+                "expected a test at position {}, found the end",
+                end
+            )),
+        }
+    }
+
+    fn test(&mut self, field: &str, field_at: usize) -> Result<Expr, String> {
+        let end = self.end;
+        let op = match self.next() {
+            Some(Token {
+                kind: TokenKind::Op(op),
+                ..
+            }) => *op,
+            Some(token) => {
+                return Err(format!(
+                    "expected a comparison after '{}' at position {}, found {}",
+                    field, token.at, token.kind
+                ))
+            }
+            None => {
+                return Err(format!(
+                    "expected a comparison after '{}' at position {}",
+                    field, end
+                ))
+            }
+        };
+        let (value, value_at) = match self.next() {
+            Some(Token {
+                kind: TokenKind::Word(v) | TokenKind::Quoted(v),
+                at,
+            }) => (v.clone(), *at),
+            Some(token) => {
+                return Err(format!(
+                    "expected a value after '{} {}' at position {}, found {}",
+                    field, op, token.at, token.kind
+                ))
+            }
+            None => {
+                return Err(format!(
+                    "expected a value after '{} {}' at position {}",
+                    field, op, end
+                ))
+            }
+        };
+        let bad_op = || {
+            format!(
+                "'{}' does not support '{}' (position {})",
+                field, op, field_at
+            )
+        };
+        let bad_value = |e: String| format!("{} (position {})", e, value_at);
+        match field {
+            "size" => Ok(Expr::Size(
+                cmp(op).ok_or_else(bad_op)?,
+                parse_size(&value).map_err(bad_value)?,
+            )),
+            "age" => Ok(Expr::Age(
+                cmp(op).ok_or_else(bad_op)?,
+                parse_duration(&value).map_err(bad_value)?,
+            )),
+            "name" => match op {
+                "=" | "!=" | "~" => Ok(Expr::Name {
+                    glob: op == "~",
+                    negate: op == "!=",
+                    pattern: value,
+                }),
+                _ => Err(bad_op()),
+            },
+            "type" => {
+                let negate = match op {
+                    "=" => false,
+                    "!=" => true,
+                    _ => return Err(bad_op()),
+                };
+                let kind = match value.to_ascii_lowercase().as_str() {
+                    "f" | "file" => EntryType::File,
+                    "d" | "dir" => EntryType::Dir,
+                    "l" | "symlink" => EntryType::Symlink,
+                    _ => {
+                        return Err(bad_value(format!(
+                            "unknown type {:?}: use file, dir or symlink",
+                            value
+                        )))
+                    }
+                };
+                Ok(Expr::Type { negate, kind })
+            }
+            _ => Err(format!(
+                "unknown test {:?} at position {}: use size, age, name or type",
+                field, field_at
+            )),
+        }
+    }
+}
+
+fn cmp(op: &str) -> Option<Cmp> {
+    Some(match op {
+        "<" => Cmp::Lt,
+        "<=" => Cmp::Le,
+        ">" => Cmp::Gt,
+        ">=" => Cmp::Ge,
+        "=" => Cmp::Eq,
+        "!=" => Cmp::Ne,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::TempDir;
+
+    fn name(pattern: &str) -> Box<Expr> {
+        Box::new(Expr::Name {
+            glob: true,
+            negate: false,
+            pattern: pattern.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_precedence_not_and_or() {
+        // not binds tightest, then and, then or
+        assert_eq!(
+            Expr::parse("name~a or not name~b and name~c").unwrap(),
+            Expr::Or(
+                name("a"),
+                Box::new(Expr::And(Box::new(Expr::Not(name("b"))), name("c")))
+            )
+        );
+        assert_eq!(
+            Expr::parse("(name~a OR name~b) AND name~c").unwrap(),
+            Expr::And(Box::new(Expr::Or(name("a"), name("b"))), name("c"))
+        );
+        // Chains associate to the left
+        assert_eq!(
+            Expr::parse("name~a and name~b and name~c").unwrap(),
+            Expr::And(Box::new(Expr::And(name("a"), name("b"))), name("c"))
+        );
+    }
+
+    #[test]
+    fn test_parse_tests() {
+        assert_eq!(
+            Expr::parse("size>10M").unwrap(),
+            Expr::Size(Cmp::Gt, 10 << 20)
+        );
+        assert_eq!(
+            Expr::parse("age >= 7d").unwrap(),
+            Expr::Age(Cmp::Ge, Duration::from_secs(7 * 86400))
+        );
+        assert_eq!(
+            Expr::parse("name != 'my file.txt'").unwrap(),
+            Expr::Name {
+                glob: false,
+                negate: true,
+                pattern: "my file.txt".to_string()
+            }
+        );
+        assert_eq!(
+            Expr::parse("type=d").unwrap(),
+            Expr::Type {
+                negate: false,
+                kind: EntryType::Dir
+            }
+        );
+    }
+
+    #[test]
+    fn test_error_messages() {
+        let err = |input| Expr::parse(input).unwrap_err();
+        assert_eq!(
+            err("size > 10M and"),
+            "expected a test at position 14, found the end"
+        );
+        assert_eq!(err("(size>1"), "missing ')' for '(' at position 0");
+        assert_eq!(
+            err("size 10M"),
+            "expected a comparison after 'size' at position 5, found '10M'"
+        );
+        assert_eq!(
+            err("colour = red"),
+            "unknown test \"colour\" at position 0: use size, age, name or type"
+        );
+        assert_eq!(err("name > x"), "'name' does not support '>' (position 0)");
+        assert_eq!(
+            err("type = pipe"),
+            "unknown type \"pipe\": use file, dir or symlink (position 7)"
+        );
+        assert!(err("size > lots").contains("(position 7)"));
+        assert_eq!(err("name ~ 'x"), "unterminated quote at position 7");
+        assert_eq!(
+            err("name~a name~b"),
+            "unexpected 'name' at position 7; expected 'and', 'or' or the end"
+        );
+        assert_eq!(err("! name~a"), "unexpected '!' at position 0; use 'not'");
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.log", "app.log"));
+        assert!(glob_match("*.log", ".log"));
+        assert!(!glob_match("*.log", "app.log.1"));
+        assert!(glob_match("a?c*", "abcdef"));
+        assert!(glob_match("*a*b*", "xxaxxbxx"));
+        assert!(!glob_match("a*b", "acbd"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn test_matches_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let log = temp_dir.path().join("app.log");
+        fs::write(&log, vec![0u8; 2048]).unwrap();
+        let small = temp_dir.path().join("small.log");
+        File::create(&small).unwrap();
+        let dir = temp_dir.path().join("logs.log");
+        fs::create_dir(&dir).unwrap();
+
+        let expr = Expr::parse("name ~ '*.log' and type = file and size > 1K").unwrap();
+        assert!(expr.matches(&log));
+        assert!(!expr.matches(&small));
+        assert!(!expr.matches(&dir));
+
+        // Freshly written, so younger than a day
+        assert!(Expr::parse("age < 1d").unwrap().matches(&log));
+        assert!(!Expr::parse("age > 1d").unwrap().matches(&log));
+        // Unreadable metadata fails every test that needs it
+        let missing = temp_dir.path().join("missing.log");
+        assert!(!Expr::parse("size >= 0").unwrap().matches(&missing));
+        assert!(Expr::parse("not type = file").unwrap().matches(&missing));
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use fast_rm::listfile::{record_path, ListFile};
+
+use crate::cli::Cli;
+use crate::errors::RemoveError;
+use crate::expr::Expr;
+use crate::path::canonical_arg;
+use crate::progress::SkipReason;
+
+/// Predicate deciding which scanned paths may be deleted
+///
+/// Evaluated by the scanner before anything is enqueued. Rejected files and
+/// symlinks are skipped, and a rejected directory is kept even once its
+/// contents are gone; either way the parent directory is kept too. Each
+/// rejection carries the `SkipReason` reported in the summary.
+#[derive(Clone)]
+pub struct PathFilter(Arc<Rejection>);
+
+/// Returns why a path is rejected, or `None` if it may be deleted
+type Rejection = dyn Fn(&Path) -> Option<SkipReason> + Send + Sync;
+
+impl PathFilter {
+    /// A filter allowing what `predicate` accepts, rejecting the rest for `reason`
+    pub fn new(
+        reason: SkipReason,
+        predicate: impl Fn(&Path) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self(Arc::new(move |path| (!predicate(path)).then_some(reason)))
+    }
+
+    #[allow(dead_code)]
+    pub fn allows(&self, path: &Path) -> bool {
+        self.rejection(path).is_none()
+    }
+
+    /// Why `path` is rejected, or `None` if it may be deleted
+    pub fn rejection(&self, path: &Path) -> Option<SkipReason> {
+        (self.0)(path)
+    }
+
+    /// A filter allowing only what both `self` and `other` allow; the first
+    /// rejecting filter gives the reason
+    pub fn and(self, other: PathFilter) -> Self {
+        Self(Arc::new(move |path| {
+            self.rejection(path).or_else(|| other.rejection(path))
+        }))
+    }
+
+    /// Allow paths at or below `prefix` (matched by whole components)
+    pub fn prefix(prefix: &Path) -> Self {
+        let prefix = resolve(prefix);
+        Self::new(SkipReason::Excluded, move |path| path.starts_with(&prefix))
+    }
+
+    /// Allow exactly the listed paths
+    pub fn allowlist(paths: HashSet<PathBuf>) -> Self {
+        Self::new(SkipReason::Excluded, move |path| paths.contains(path))
+    }
+
+    /// Allow entries whose inode number is in `inodes`
+    ///
+    /// Entries whose metadata cannot be read are not allowed.
+    #[cfg(unix)]
+    pub fn inodes(inodes: HashSet<u64>) -> Self {
+        use std::os::unix::fs::MetadataExt;
+
+        Self::new(SkipReason::Excluded, move |path| {
+            fs::symlink_metadata(path).is_ok_and(|m| inodes.contains(&m.ino()))
+        })
+    }
+
+    /// Allow paths last modified more than `age` ago
+    ///
+    /// Entries whose modification time cannot be read are not allowed.
+    pub fn older_than(age: Duration) -> Self {
+        let cutoff = SystemTime::now()
+            .checked_sub(age)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        Self::modified_before(cutoff)
+    }
+
+    /// Allow paths last modified before `cutoff`
+    ///
+    /// Entries whose modification time cannot be read are not allowed.
+    pub fn modified_before(cutoff: SystemTime) -> Self {
+        Self::new(SkipReason::NewerThan, move |path| {
+            fs::symlink_metadata(path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified < cutoff)
+        })
+    }
+
+    /// Allow paths matching an `--expr` expression
+    pub fn expr(expr: Expr) -> Self {
+        Self::new(SkipReason::Excluded, move |path| expr.matches(path))
+    }
+
+    /// Build the filter requested by `--match-prefix`, `--files-from`,
+    /// `--inodes-from`, `--older-than`, `--older-than-file` and `--expr`, if
+    /// any; all given conditions must hold
+    pub fn from_cli(cli: &Cli) -> Result<Option<Self>, RemoveError> {
+        let mut filters = Vec::new();
+        filters.extend(cli.match_prefix.as_deref().map(Self::prefix));
+        if let Some(list) = &cli.files_from {
+            filters.push(Self::allowlist(load_allowlist(list)?));
+        }
+        #[cfg(unix)]
+        if let Some(list) = &cli.inodes_from {
+            filters.push(Self::inodes(load_inode_list(list)?));
+        }
+        filters.extend(cli.older_than.map(Self::older_than));
+        if let Some(reference) = &cli.older_than_file {
+            filters.push(Self::modified_before(reference_mtime(reference)?));
+        }
+        filters.extend(cli.expr.clone().map(Self::expr));
+        Ok(filters.into_iter().reduce(Self::and))
+    }
+}
+
+impl fmt::Debug for PathFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PathFilter(..)")
+    }
+}
+
+/// Modification time of the `--older-than-file` reference, which must exist
+/// and be readable
+fn reference_mtime(path: &Path) -> Result<SystemTime, RemoveError> {
+    fs::File::open(path)
+        .and_then(|file| file.metadata())
+        .and_then(|metadata| metadata.modified())
+        .map_err(|e| RemoveError::ReferenceFileFailed(path.to_path_buf(), e))
+}
+
+/// Parse a duration such as `500ms`, `90s`, `30m`, `12h`, `7d` or `2w`
+/// (`--older-than`, `--timeout`)
+///
+/// A bare number is taken as seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration {:?}: expected e.g. 30m, 12h or 7d", s))?;
+    let millis = match unit {
+        "ms" => 1,
+        "" | "s" => 1000,
+        "m" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        "d" => 24 * 60 * 60 * 1000,
+        "w" => 7 * 24 * 60 * 60 * 1000,
+        _ => {
+            return Err(format!(
+                "invalid duration unit {:?}: use ms, s, m, h, d or w",
+                unit
+            ))
+        }
+    };
+    number
+        .checked_mul(millis)
+        .map(Duration::from_millis)
+        .ok_or_else(|| format!("duration {:?} is too large", s))
+}
+
+/// Parse a size such as `4096`, `64K`, `10MiB` or `500G` (`--abort-over-bytes`)
+///
+/// Units are binary (K = 1024) and case-insensitive; a trailing `B` or `iB`
+/// is accepted, and a bare number is taken as bytes.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid size {:?}: expected e.g. 512M, 10G or 1T", s))?;
+    let unit = unit.to_ascii_lowercase();
+    let unit = unit
+        .strip_suffix("ib")
+        .or_else(|| unit.strip_suffix('b'))
+        .unwrap_or(&unit);
+    let shift = match unit {
+        "" => 0,
+        "k" => 10,
+        "m" => 20,
+        "g" => 30,
+        "t" => 40,
+        "p" => 50,
+        _ => return Err(format!("invalid size unit {:?}: use K, M, G, T or P", unit)),
+    };
+    number
+        .checked_mul(1 << shift)
+        .ok_or_else(|| format!("size {:?} is too large", s))
+}
+
+/// Read a `--files-from` list: one path per line, or NUL-terminated paths,
+/// blank records ignored
+///
+/// The list is memory-mapped and each path taken straight from it (see
+/// `ListFile`). Paths are resolved the same way as the command line
+/// arguments so they compare equal to what the scanner sees.
+pub fn load_allowlist(list: &Path) -> Result<HashSet<PathBuf>, RemoveError> {
+    let contents =
+        ListFile::open(list).map_err(|e| RemoveError::FilesFromFailed(list.to_path_buf(), e))?;
+    Ok(contents
+        .records()
+        .map(|record| resolve(&record_path(record)))
+        .collect())
+}
+
+/// Read an `--inodes-from` list: one inode number per line, blank lines and
+/// `#` comments ignored
+#[cfg(unix)]
+pub fn load_inode_list(list: &Path) -> Result<HashSet<u64>, RemoveError> {
+    let failed = |e| RemoveError::InodesFromFailed(list.to_path_buf(), e);
+    let contents = fs::read_to_string(list).map_err(failed)?;
+    contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            line.parse().map_err(|_| {
+                failed(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("line {}: {:?} is not an inode number", number, line),
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Read a `--keep-list`: one path per line, blank lines and `#` comments
+/// ignored
+///
+/// Relative entries are taken relative to each of the canonical `roots`;
+/// absolute ones are used as they are. Entries are canonicalized like the
+/// command line arguments, keeping a final symlink as the link.
+pub fn load_keep_list(list: &Path, roots: &[PathBuf]) -> Result<HashSet<PathBuf>, RemoveError> {
+    let contents =
+        fs::read_to_string(list).map_err(|e| RemoveError::KeepListFailed(list.to_path_buf(), e))?;
+    let entries: Vec<&str> = contents
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    Ok(roots
+        .iter()
+        .flat_map(|root| entries.iter().map(move |entry| root.join(entry)))
+        .map(|path| canonical_arg(&path).unwrap_or(path))
+        .collect())
+}
+
+/// Canonical form of `path`, or its absolute form if it does not exist
+fn resolve(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_prefix_filter() {
+        let filter = PathFilter::prefix(Path::new("/data/build"));
+        assert!(filter.allows(Path::new("/data/build")));
+        assert!(filter.allows(Path::new("/data/build/out/a.o")));
+        // Component-wise: a sibling sharing the string prefix is not included
+        assert!(!filter.allows(Path::new("/data/build2/a.o")));
+        assert!(!filter.allows(Path::new("/data/src/a.c")));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(1800)));
+        assert_eq!(parse_duration("7d"), Ok(Duration::from_secs(7 * 86400)));
+        assert_eq!(parse_duration("2w"), Ok(Duration::from_secs(14 * 86400)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("3y").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("64K"), Ok(64 * 1024));
+        assert_eq!(parse_size("10MiB"), Ok(10 << 20));
+        assert_eq!(parse_size("500g"), Ok(500 << 30));
+        assert_eq!(parse_size("2TB"), Ok(2 << 40));
+        assert_eq!(parse_size("1b"), Ok(1));
+        assert!(parse_size("G").is_err());
+        assert!(parse_size("3X").is_err());
+        assert!(parse_size("99999999P").is_err());
+    }
+
+    #[test]
+    fn test_older_than_filter() {
+        let temp_dir = TempDir::new().unwrap();
+        let old = temp_dir.path().join("old.txt");
+        let new = temp_dir.path().join("new.txt");
+        let file = fs::File::create(&old).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(3 * 86400))
+            .unwrap();
+        fs::write(&new, "").unwrap();
+
+        let filter = PathFilter::older_than(Duration::from_secs(86400));
+        assert!(filter.allows(&old));
+        assert!(!filter.allows(&new));
+        assert!(!filter.allows(&temp_dir.path().join("missing")));
+    }
+
+    #[test]
+    fn test_older_than_file_uses_the_reference_mtime() {
+        let temp_dir = TempDir::new().unwrap();
+        let now = SystemTime::now();
+        let touch = |name: &str, age: u64| {
+            let path = temp_dir.path().join(name);
+            let file = fs::File::create(&path).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+            path
+        };
+        let reference = touch(".last-build", 3600);
+        let (before, after) = (touch("stale.o", 7200), touch("fresh.o", 60));
+
+        let filter = PathFilter::modified_before(reference_mtime(&reference).unwrap());
+        assert!(filter.allows(&before));
+        assert_eq!(filter.rejection(&after), Some(SkipReason::NewerThan));
+        // The marker itself is not older than itself, so it survives
+        assert!(!filter.allows(&reference));
+
+        let err = reference_mtime(&temp_dir.path().join("missing")).unwrap_err();
+        assert!(matches!(err, RemoveError::ReferenceFileFailed(..)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_inode_list_parsing() {
+        let temp_dir = TempDir::new().unwrap();
+        let list = temp_dir.path().join("inodes.txt");
+        fs::write(&list, "# from debugfs\n12\n\n  34 \n").unwrap();
+        assert_eq!(load_inode_list(&list).unwrap(), HashSet::from([12, 34]));
+
+        fs::write(&list, "12\nabc\n").unwrap();
+        let err = load_inode_list(&list).unwrap_err().to_string();
+        assert!(err.contains("line 2: \"abc\""), "{}", err);
+    }
+
+    #[test]
+    fn test_allowlist_from_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::write(root.join("keep.txt"), "").unwrap();
+        fs::write(root.join("gone.txt"), "").unwrap();
+        let list = root.join("index.txt");
+        fs::write(&list, format!("{}\n\n", root.join("gone.txt").display())).unwrap();
+
+        let filter = PathFilter::allowlist(load_allowlist(&list).unwrap());
+        assert!(filter.allows(&root.join("gone.txt")));
+        assert!(!filter.allows(&root.join("keep.txt")));
+
+        let both = filter.and(PathFilter::older_than(Duration::from_secs(3600)));
+        assert!(!both.allows(&root.join("gone.txt")));
+        // Listed but too new, so the age condition is the reason
+        assert_eq!(
+            both.rejection(&root.join("gone.txt")),
+            Some(SkipReason::NewerThan)
+        );
+        assert_eq!(
+            both.rejection(&root.join("keep.txt")),
+            Some(SkipReason::Excluded)
+        );
+    }
+}
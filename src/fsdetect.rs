@@ -0,0 +1,117 @@
+//! Defaults tuned to the file system being deleted from
+
+use std::io;
+use std::path::Path;
+
+/// `statfs` `f_type` magic numbers (linux/magic.h)
+const TMPFS_MAGIC: u32 = 0x0102_1994;
+/// ext2, ext3 and ext4 share one
+const EXT4_MAGIC: u32 = 0xEF53;
+const XFS_MAGIC: u32 = 0x5846_5342;
+const BTRFS_MAGIC: u32 = 0x9123_683E;
+const NFS_MAGIC: u32 = 0x6969;
+const SMB_MAGIC: u32 = 0x517B;
+const CIFS_MAGIC: u32 = 0xFF53_4D42;
+const SMB2_MAGIC: u32 = 0xFE53_4D42;
+const CEPH_MAGIC: u32 = 0x00C3_6400;
+
+/// Deleter count and queue capacity suited to a file system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsProfile {
+    pub name: &'static str,
+    pub delete_threads: usize,
+    /// `None` keeps the usual capacity
+    pub queue_capacity: Option<usize>,
+}
+
+/// The profile for a file system of type `f_type` on a machine with `cpus`
+/// cores, or `None` to keep the general defaults
+///
+/// tmpfs unlinks are pure CPU work, and XFS spreads them over allocation
+/// groups, so both take more deleters than cores and a deeper queue.
+/// Btrfs serialises on its tree locks beyond a few deleters, and over a
+/// network each deleter mostly waits, so extra ones only load the server.
+pub fn profile_for(f_type: u32, cpus: usize) -> Option<FsProfile> {
+    let cpus = cpus.max(1);
+    let profile = |name, delete_threads: usize, queue_capacity| FsProfile {
+        name,
+        delete_threads: delete_threads.max(1),
+        queue_capacity,
+    };
+    match f_type {
+        TMPFS_MAGIC => Some(profile("tmpfs", cpus * 2, Some(40_000))),
+        EXT4_MAGIC => Some(profile("ext4", cpus, None)),
+        XFS_MAGIC => Some(profile("xfs", cpus * 2, Some(20_000))),
+        BTRFS_MAGIC => Some(profile("btrfs", cpus.min(4), None)),
+        NFS_MAGIC | SMB_MAGIC | CIFS_MAGIC | SMB2_MAGIC | CEPH_MAGIC => {
+            Some(profile("network", cpus.min(4), Some(5_000)))
+        }
+        _ => None,
+    }
+}
+
+/// The profile for the file systems holding `paths`: with several, the
+/// one with the fewest deleters, so no file system gets more than it takes
+///
+/// Paths whose file system is unknown or cannot be detected are ignored.
+pub fn detect_profile(paths: &[impl AsRef<Path>]) -> Option<FsProfile> {
+    paths
+        .iter()
+        .filter_map(|path| fs_type(path.as_ref()).ok())
+        .filter_map(|f_type| profile_for(f_type, num_cpus::get()))
+        .min_by_key(|profile| profile.delete_threads)
+}
+
+/// `statfs` `f_type` of the file system holding `path`
+#[cfg(target_os = "linux")]
+fn fs_type(path: &Path) -> io::Result<u32> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: a valid C string and a zeroed statfs for the call to fill
+    let mut stats: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stats) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // The magic numbers are 32 bits; f_type's width and sign vary by target
+    Ok(stats.f_type as u32)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn fs_type(_path: &Path) -> io::Result<u32> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "file system detection is only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_for_fs_type() {
+        let tmpfs = profile_for(TMPFS_MAGIC, 8).unwrap();
+        assert_eq!((tmpfs.name, tmpfs.delete_threads), ("tmpfs", 16));
+        assert_eq!(tmpfs.queue_capacity, Some(40_000));
+        assert_eq!(profile_for(EXT4_MAGIC, 8).unwrap().delete_threads, 8);
+        assert_eq!(profile_for(BTRFS_MAGIC, 8).unwrap().delete_threads, 4);
+        assert_eq!(profile_for(BTRFS_MAGIC, 1).unwrap().delete_threads, 1);
+        for network in [NFS_MAGIC, CIFS_MAGIC, SMB2_MAGIC] {
+            let profile = profile_for(network, 32).unwrap();
+            assert_eq!((profile.name, profile.delete_threads), ("network", 4));
+        }
+        assert_eq!(profile_for(0x794C_7630, 8), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_fs_type_is_detected() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        assert!(fs_type(temp_dir.path()).is_ok());
+        assert!(fs_type(&temp_dir.path().join("missing")).is_err());
+        assert_eq!(detect_profile(&[temp_dir.path().join("missing")]), None);
+    }
+}
@@ -0,0 +1,119 @@
+use crossbeam_channel::{bounded, Receiver, Sender};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Errors waiting for the hook beyond this are dropped
+const HOOK_QUEUE: usize = 100;
+
+/// At most this many hook invocations start per `HOOK_WINDOW`
+const HOOK_BURST: usize = 10;
+const HOOK_WINDOW: Duration = Duration::from_secs(1);
+
+/// One failed path and its error message
+pub type ErrorEvent = (Arc<Path>, String);
+
+/// Best-effort command run for deletion errors (`--on-error`)
+///
+/// Errors are handed over through a bounded channel and the command is run
+/// on a dedicated thread, one invocation at a time, with the path and message
+/// in `FASTRM_ERROR_PATH` and `FASTRM_ERROR_MSG`. Errors arriving while the
+/// queue is full or the rate limit is exhausted are not reported to it.
+pub struct ErrorHook {
+    tx: Sender<ErrorEvent>,
+    thread: JoinHandle<usize>,
+}
+
+impl ErrorHook {
+    pub fn spawn(command: String) -> Self {
+        let (tx, rx) = bounded(HOOK_QUEUE);
+        let thread = thread::Builder::new()
+            .name("error-hook".to_string())
+            .spawn(move || run_hooks(&command, &rx))
+            .expect("Failed to spawn error hook thread");
+        Self { tx, thread }
+    }
+
+    /// Sender for `RemoveProgress` to report errors on
+    pub fn sender(&self) -> Sender<ErrorEvent> {
+        self.tx.clone()
+    }
+
+    /// Wait for queued invocations once every sender is gone; returns the
+    /// number of errors the hook was not run for
+    pub fn finish(self) -> usize {
+        drop(self.tx);
+        self.thread.join().unwrap_or(0)
+    }
+}
+
+fn run_hooks(command: &str, rx: &Receiver<ErrorEvent>) -> usize {
+    let mut window_start = Instant::now();
+    let mut started = 0;
+    let mut dropped = 0;
+    for (path, message) in rx {
+        if window_start.elapsed() >= HOOK_WINDOW {
+            window_start = Instant::now();
+            started = 0;
+        }
+        if started >= HOOK_BURST {
+            dropped += 1;
+            continue;
+        }
+        started += 1;
+        let status = shell(command)
+            .env("FASTRM_ERROR_PATH", &*path)
+            .env("FASTRM_ERROR_MSG", &message)
+            .stdin(Stdio::null())
+            .status();
+        if let Err(e) = status {
+            eprintln!("Failed to run --on-error hook: {}", e);
+        }
+    }
+    dropped
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_hook_receives_error_and_is_rate_limited() {
+        let temp_dir = TempDir::new().unwrap();
+        let log = temp_dir.path().join("log");
+        let hook = ErrorHook::spawn(format!(
+            "printf '%s|%s\\n' \"$FASTRM_ERROR_PATH\" \"$FASTRM_ERROR_MSG\" >> {:?}",
+            log
+        ));
+
+        let tx = hook.sender();
+        for i in 0..HOOK_BURST + 5 {
+            let path: Arc<Path> = Arc::from(Path::new(&format!("/data/{}", i)));
+            tx.send((path, "Permission denied".to_string())).unwrap();
+        }
+        drop(tx);
+
+        // A burst this fast fits in one window, so only the first few run
+        assert_eq!(hook.finish(), 5);
+        let log = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(log.lines().count(), HOOK_BURST);
+        assert_eq!(log.lines().next(), Some("/data/0|Permission denied"));
+    }
+}
@@ -0,0 +1,109 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::errors::RemoveError;
+
+/// Name of the per-directory ignore file read with `--use-ignore-files`
+pub const IGNORE_FILE_NAME: &str = ".fastrmignore";
+
+/// One directory's `.fastrmignore` rules, linked to the rules of its ancestors
+#[derive(Debug)]
+struct IgnoreLayer {
+    matcher: Gitignore,
+    parent: Option<Arc<IgnoreLayer>>,
+}
+
+/// Stack of `.fastrmignore` matchers accumulated while descending a tree
+///
+/// Cloning is cheap (an `Arc` bump), so each directory can hand its stack to
+/// children scanned in parallel. Deeper files take precedence over their
+/// ancestors, so a nested `!pattern` can re-include what a parent excluded.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreStack {
+    top: Option<Arc<IgnoreLayer>>,
+}
+
+impl IgnoreStack {
+    /// Return the stack to use for the children of `dir`, adding its
+    /// `.fastrmignore` as the innermost layer if one exists
+    pub fn enter(&self, dir: &Path) -> Result<Self, RemoveError> {
+        let ignore_file = dir.join(IGNORE_FILE_NAME);
+        if !ignore_file.is_file() {
+            return Ok(self.clone());
+        }
+
+        let mut builder = GitignoreBuilder::new(dir);
+        if let Some(err) = builder.add(&ignore_file) {
+            return Err(RemoveError::IgnoreFileFailed(ignore_file, err.to_string()));
+        }
+        let matcher = builder
+            .build()
+            .map_err(|e| RemoveError::IgnoreFileFailed(ignore_file, e.to_string()))?;
+
+        Ok(Self {
+            top: Some(Arc::new(IgnoreLayer {
+                matcher,
+                parent: self.top.clone(),
+            })),
+        })
+    }
+
+    /// Whether `path` should be kept according to the innermost matching rule
+    ///
+    /// The ignore files themselves are always kept so repeated cleanups keep
+    /// working.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if self.top.is_none() {
+            return false;
+        }
+        if path.file_name().is_some_and(|n| n == IGNORE_FILE_NAME) {
+            return true;
+        }
+
+        let mut layer = self.top.as_deref();
+        while let Some(l) = layer {
+            match l.matcher.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => layer = l.parent.as_deref(),
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_no_ignore_file_matches_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let stack = IgnoreStack::default().enter(temp_dir.path()).unwrap();
+        assert!(!stack.is_ignored(&temp_dir.path().join("a.log"), false));
+    }
+
+    #[test]
+    fn test_nested_ignore_file_overrides_parent() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let sub = root.join("sub");
+        fs::create_dir(&sub).unwrap();
+        fs::write(root.join(IGNORE_FILE_NAME), "*.log\n").unwrap();
+        fs::write(sub.join(IGNORE_FILE_NAME), "!keep-not.log\n*.tmp\n").unwrap();
+
+        let root_stack = IgnoreStack::default().enter(root).unwrap();
+        let sub_stack = root_stack.enter(&sub).unwrap();
+
+        assert!(root_stack.is_ignored(&root.join("a.log"), false));
+        assert!(!root_stack.is_ignored(&root.join("a.tmp"), false));
+        assert!(sub_stack.is_ignored(&sub.join("b.log"), false));
+        assert!(sub_stack.is_ignored(&sub.join("b.tmp"), false));
+        assert!(!sub_stack.is_ignored(&sub.join("keep-not.log"), false));
+        assert!(sub_stack.is_ignored(&sub.join(IGNORE_FILE_NAME), false));
+    }
+}
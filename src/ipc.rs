@@ -0,0 +1,132 @@
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+use crate::progress::RemoveProgress;
+use crate::schema::{JsonObject, OutputVersion};
+
+/// Unix domain socket publishing progress as JSON lines (`--progress-socket`)
+///
+/// Any number of clients may connect at any time; each receives one JSON
+/// object per line on every `publish`. Clients that stop reading are dropped
+/// rather than allowed to stall the progress loop. The socket file is removed
+/// when this is dropped.
+pub struct ProgressSocket {
+    path: PathBuf,
+    listener: UnixListener,
+    clients: Vec<UnixStream>,
+    version: OutputVersion,
+}
+
+impl ProgressSocket {
+    /// Bind the socket, replacing a stale socket file left by a previous run
+    pub fn bind(path: &Path, version: OutputVersion) -> io::Result<Self> {
+        if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+            fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            listener,
+            clients: Vec::new(),
+            version,
+        })
+    }
+
+    /// Accept pending clients and send them the current stats
+    pub fn publish(&mut self, progress: &RemoveProgress, queue_depth: usize, done: bool) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.clients.push(stream);
+            }
+        }
+        if self.clients.is_empty() {
+            return;
+        }
+
+        let line = stats_json(progress, queue_depth, done, self.version) + "\n";
+        // A short write would corrupt the stream, so any failure drops the client
+        self.clients
+            .retain_mut(|client| client.write_all(line.as_bytes()).is_ok());
+    }
+}
+
+impl Drop for ProgressSocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// One progress snapshot as a single-line JSON object in schema `version`
+pub fn stats_json(
+    progress: &RemoveProgress,
+    queue_depth: usize,
+    done: bool,
+    version: OutputVersion,
+) -> String {
+    let (scanned, deleted, errors, rate, eta) = progress.get_stats();
+    match version {
+        OutputVersion::V1 => JsonObject::new(version)
+            .raw("scanned", scanned)
+            .raw("deleted", deleted)
+            .raw("errors", errors)
+            .raw("rate", format!("{:.1}", rate))
+            .raw("eta_secs", format!("{:.1}", eta))
+            .raw("queue_depth", queue_depth)
+            .raw("done", done)
+            .finish(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_stats_json() {
+        let progress = RemoveProgress::new();
+        progress.inc_scanned();
+        progress.inc_scanned();
+        progress.inc_deleted(Path::new("/tmp/a"));
+
+        let json = stats_json(&progress, 7, false, OutputVersion::V1);
+        assert!(json.starts_with("{\"schema_version\":1,\"scanned\":2,\"deleted\":1,\"errors\":0,"));
+        assert!(json.ends_with("\"queue_depth\":7,\"done\":false}"));
+        assert_eq!(
+            crate::schema::keys(&json),
+            [
+                "schema_version",
+                "scanned",
+                "deleted",
+                "errors",
+                "rate",
+                "eta_secs",
+                "queue_depth",
+                "done"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_publish_to_client_and_cleanup() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("progress.sock");
+        let progress = RemoveProgress::new();
+
+        let mut socket = ProgressSocket::bind(&path, OutputVersion::V1).unwrap();
+        let client = UnixStream::connect(&path).unwrap();
+        socket.publish(&progress, 0, true);
+
+        let mut line = String::new();
+        BufReader::new(client).read_line(&mut line).unwrap();
+        assert!(line.contains("\"done\":true"));
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+}
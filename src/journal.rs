@@ -0,0 +1,218 @@
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::errors::RemoveError;
+
+/// Records between two fsyncs of the journal
+const SYNC_EVERY: usize = 1000;
+
+/// Append-only record of deleted paths (`--journal`, `--resume`)
+///
+/// Each path is written as its raw bytes followed by a NUL, which no path can
+/// contain. The file is fsynced every `SYNC_EVERY` records and when the
+/// journal is dropped, so a crash loses at most the last unsynced batch and
+/// leaves at worst a torn final record, which `load` ignores.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    state: Mutex<JournalState>,
+}
+
+#[derive(Debug)]
+struct JournalState {
+    writer: BufWriter<File>,
+    unsynced: usize,
+}
+
+impl Journal {
+    /// Open `path` for appending, creating it if needed
+    ///
+    /// A torn final record is cut off first, or the next record would be
+    /// glued to it and lost along with it on the following resume.
+    pub fn open(path: &Path) -> Result<Self, RemoveError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| {
+                let complete = complete_len(&mut file)?;
+                if complete < file.metadata()?.len() {
+                    file.set_len(complete)?;
+                }
+                Ok(file)
+            })
+            .map_err(|e| RemoveError::JournalFailed(path.to_path_buf(), e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            state: Mutex::new(JournalState {
+                writer: BufWriter::new(file),
+                unsynced: 0,
+            }),
+        })
+    }
+
+    /// Append one deleted path
+    pub fn record(&self, deleted: &Path) -> Result<(), RemoveError> {
+        let mut state = self.state.lock().unwrap();
+        let result = (|| {
+            state.writer.write_all(&path_bytes(deleted))?;
+            state.writer.write_all(b"\0")?;
+            state.unsynced += 1;
+            if state.unsynced >= SYNC_EVERY {
+                state.unsynced = 0;
+                sync(&mut state.writer)?;
+            }
+            Ok(())
+        })();
+        result.map_err(|e| RemoveError::JournalFailed(self.path.clone(), e))
+    }
+
+    /// Write out and fsync everything recorded so far
+    pub fn sync(&self) -> Result<(), RemoveError> {
+        let mut state = self.state.lock().unwrap();
+        state.unsynced = 0;
+        sync(&mut state.writer).map_err(|e| RemoveError::JournalFailed(self.path.clone(), e))
+    }
+}
+
+impl Drop for Journal {
+    fn drop(&mut self) {
+        if let Ok(state) = self.state.get_mut() {
+            let _ = sync(&mut state.writer);
+        }
+    }
+}
+
+/// Length of the journal up to and including its last NUL, i.e. without a
+/// torn final record; read backwards, since the tail is at most one path
+fn complete_len(file: &mut File) -> io::Result<u64> {
+    const CHUNK: u64 = 64 * 1024;
+    let mut end = file.seek(SeekFrom::End(0))?;
+    let mut buf = vec![0u8; CHUNK as usize];
+    while end > 0 {
+        let start = end.saturating_sub(CHUNK);
+        let chunk = &mut buf[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+        if let Some(nul) = chunk.iter().rposition(|&b| b == 0) {
+            return Ok(start + nul as u64 + 1);
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+fn sync(writer: &mut BufWriter<File>) -> io::Result<()> {
+    writer.flush()?;
+    writer.get_ref().sync_data()
+}
+
+/// Read the paths recorded in a journal; a missing file is an empty journal
+pub fn load(path: &Path) -> Result<HashSet<PathBuf>, RemoveError> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(e) => return Err(RemoveError::JournalFailed(path.to_path_buf(), e)),
+    };
+    // Only NUL-terminated records are complete; a torn tail is dropped
+    let complete = bytes
+        .iter()
+        .rposition(|&b| b == 0)
+        .map_or(&[][..], |end| &bytes[..end]);
+    Ok(complete
+        .split(|&b| b == 0)
+        .filter(|record| !record.is_empty())
+        .map(path_from_bytes)
+        .collect())
+}
+
+#[cfg(unix)]
+pub fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+pub fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(unix)]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_and_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("journal");
+        assert!(load(&path).unwrap().is_empty());
+
+        let journal = Journal::open(&path).unwrap();
+        journal.record(Path::new("/data/a.txt")).unwrap();
+        journal.record(Path::new("/data/with\nnewline")).unwrap();
+        drop(journal);
+        // Reopening appends rather than truncates
+        Journal::open(&path)
+            .unwrap()
+            .record(Path::new("/data"))
+            .unwrap();
+
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert!(loaded.contains(Path::new("/data/with\nnewline")));
+    }
+
+    #[test]
+    fn test_torn_record_is_ignored() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("journal");
+        fs::write(&path, b"/data/a.txt\0/data/b.t").unwrap();
+
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded, HashSet::from([PathBuf::from("/data/a.txt")]));
+    }
+
+    #[test]
+    fn test_resume_after_torn_record_keeps_new_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("journal");
+        fs::write(&path, b"/data/a.txt\0/data/b.t").unwrap();
+
+        // First resume: the torn tail is cut before appending
+        Journal::open(&path)
+            .unwrap()
+            .record(Path::new("/data/b.txt"))
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"/data/a.txt\0/data/b.txt\0");
+
+        // Second resume sees both records
+        Journal::open(&path)
+            .unwrap()
+            .record(Path::new("/data/c.txt"))
+            .unwrap();
+        let loaded = load(&path).unwrap();
+        let expected = ["/data/a.txt", "/data/b.txt", "/data/c.txt"];
+        assert_eq!(loaded, expected.into_iter().map(PathBuf::from).collect());
+
+        // A journal with no complete record at all is emptied
+        fs::write(&path, b"/data/torn").unwrap();
+        drop(Journal::open(&path).unwrap());
+        assert!(fs::read(&path).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,3 @@
+//! The parts of fast-rm that benchmarks measure directly, outside the binary
+
+pub mod listfile;
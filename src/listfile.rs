@@ -0,0 +1,157 @@
+//! Reading huge path lists (`--files-from`) without a copy per line
+
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// How far into a list to look for a NUL byte when deciding how its
+/// records are delimited
+const SNIFF_LEN: usize = 64 * 1024;
+
+/// The contents of a path list, memory-mapped where possible
+///
+/// Regular files are mapped read-only, so a multi-gigabyte list is paged in
+/// as it is walked instead of being copied into one allocation up front.
+/// Pipes and other special files are read into memory instead.
+pub struct ListFile {
+    contents: Contents,
+}
+
+enum Contents {
+    Mapped(Mmap),
+    Read(Vec<u8>),
+}
+
+impl ListFile {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        if let Some(mapping) = map(&file) {
+            return Ok(Self {
+                contents: Contents::Mapped(mapping),
+            });
+        }
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        Ok(Self {
+            contents: Contents::Read(bytes),
+        })
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        match &self.contents {
+            Contents::Mapped(mapping) => mapping,
+            Contents::Read(bytes) => bytes,
+        }
+    }
+
+    /// Iterate over the non-empty records of the list
+    ///
+    /// A list with a NUL byte in its first 64 KiB is NUL-delimited (`find
+    /// -print0`), since no path can contain one and the first record ends
+    /// well before that; otherwise records are lines, with a `\r` before the
+    /// `\n` dropped. Only that prefix is looked at, so the rest of a mapped
+    /// list is paged in once, as it is walked.
+    pub fn records(&self) -> impl Iterator<Item = &[u8]> {
+        let bytes = self.bytes();
+        let prefix = &bytes[..bytes.len().min(SNIFF_LEN)];
+        let delimiter = if prefix.contains(&0) { 0 } else { b'\n' };
+        bytes
+            .split(move |&b| b == delimiter)
+            .map(move |record| match (delimiter, record) {
+                (b'\n', [rest @ .., b'\r']) => rest,
+                _ => record,
+            })
+            .filter(|record| !record.is_empty())
+    }
+}
+
+/// Map `file` read-only, or `None` if it is empty, not a regular file, or
+/// the mapping fails
+fn map(file: &File) -> Option<Mmap> {
+    let metadata = file.metadata().ok()?;
+    if !metadata.is_file() || metadata.len() == 0 {
+        return None;
+    }
+    // SAFETY: a private read-only mapping of an open file. Like any mapping,
+    // its bytes change if another process rewrites the file meanwhile, which
+    // at worst garbles the list being read.
+    let mapping = unsafe { Mmap::map(file) }.ok()?;
+    // The list is walked front to back exactly once; failure is harmless
+    #[cfg(unix)]
+    let _ = mapping.advise(memmap2::Advice::Sequential);
+    Some(mapping)
+}
+
+/// A record as a path, borrowed straight from the list where the platform
+/// allows
+#[cfg(unix)]
+pub fn record_path(record: &[u8]) -> Cow<'_, Path> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(Path::new(std::ffi::OsStr::from_bytes(record)))
+}
+
+#[cfg(not(unix))]
+pub fn record_path(record: &[u8]) -> Cow<'_, Path> {
+    Cow::Owned(String::from_utf8_lossy(record).into_owned().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn records(contents: &[u8]) -> Vec<Vec<u8>> {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("list");
+        std::fs::write(&path, contents).unwrap();
+        let list = ListFile::open(&path).unwrap();
+        list.records().map(<[u8]>::to_vec).collect()
+    }
+
+    #[test]
+    fn test_newline_and_nul_delimited_records() {
+        assert_eq!(
+            records(b"/data/a\n\n/data/b c\r\n/data/d"),
+            [&b"/data/a"[..], b"/data/b c", b"/data/d"]
+        );
+        // NUL-delimited: newlines and carriage returns belong to the name
+        assert_eq!(
+            records(b"/data/with\nnewline\0/data/cr\r\0\0"),
+            [&b"/data/with\nnewline"[..], b"/data/cr\r"]
+        );
+        assert!(records(b"").is_empty());
+    }
+
+    #[test]
+    fn test_nul_is_looked_for_in_the_prefix_only() {
+        // A NUL past the first 64 KiB does not turn a line list into a
+        // NUL-delimited one
+        let mut contents = b"/data/a\n/data/b\n".to_vec();
+        contents.resize(SNIFF_LEN + 10, b'x');
+        contents.extend_from_slice(b"\0\n/data/c\n");
+        let found = records(&contents);
+        assert_eq!(found.len(), 4);
+        assert_eq!(found[..2], [&b"/data/a"[..], b"/data/b"]);
+        assert_eq!(found[3], b"/data/c");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_special_file_is_read_instead_of_mapped() {
+        let list = ListFile::open(Path::new("/dev/null")).unwrap();
+        assert!(matches!(list.contents, Contents::Read(_)));
+        assert_eq!(list.records().count(), 0);
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("list");
+        std::fs::write(&path, b"/data/a\n").unwrap();
+        let list = ListFile::open(&path).unwrap();
+        assert!(matches!(list.contents, Contents::Mapped(_)));
+        assert_eq!(
+            record_path(list.records().next().unwrap()),
+            Path::new("/data/a")
+        );
+    }
+}
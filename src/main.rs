@@ -1,55 +1,634 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashSet;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
 use clap::Parser;
 use colored::*;
-use rayon::prelude::*;
 
+mod affinity;
+mod autotune;
+mod backup;
 mod cli;
+mod concurrency;
 mod config;
+mod confirm;
+mod deadline;
 mod deleter;
+mod dirsync;
+mod engine;
 mod errors;
+mod evict;
+mod expr;
+mod filter;
+mod fsdetect;
+mod hook;
+mod human;
+mod ignores;
+#[cfg(unix)]
+mod ipc;
+mod journal;
+mod moveutil;
+#[cfg(windows)]
+mod openfiles;
 mod path;
+mod plan;
+mod pool;
+mod printer;
 mod progress;
 mod queue;
 mod removal;
 mod results;
 mod scanner;
+mod schema;
+mod shadow;
+mod table;
+mod twophase;
+mod watchdog;
 
+use crate::affinity::CpuPinner;
+use crate::autotune::{AutoTuner, ThreadGate};
+use crate::backup::Backup;
 use crate::cli::Cli;
+use crate::concurrency::{ConcurrencyReport, Sample};
 use crate::config::{RemoveConfig, Verbosity};
-use crate::deleter::delete_worker;
-use crate::path::deduplicate_and_check_paths;
-use crate::progress::{ProgressDisplay, RemoveProgress};
-use crate::queue::AdaptiveQueue;
-use crate::results::print_summary_and_exit;
-use crate::scanner::scan_path;
+use crate::deleter::{
+    delete_worker, elastic_delete_worker, execute, gated_delete_worker, retry_failed,
+};
+use crate::engine::Engine;
+use crate::errors::RemoveError;
+use crate::filter::{load_keep_list, PathFilter};
+use crate::hook::ErrorHook;
+use crate::human::{format_bytes, format_count};
+use crate::journal::Journal;
+use crate::path::{
+    absolute_lexically, canonical_arg, check_cwd, deduplicate_and_check_paths,
+    deduplicate_and_prune_paths, follow_symlink_chain, is_fs_root, names_link_target,
+    rename_for_deletion, resolve_trailing_separator, split_inaccessible, stage_children,
+    stage_for_deletion, validate_args,
+};
+use crate::plan::{enqueue_plan, PlanWriter};
+use crate::pool::ElasticPool;
+use crate::printer::DeletedPrinter;
+use crate::progress::{refresh_until, ProgressOptions, ProgressOutput, RemoveProgress};
+use crate::queue::{AdaptiveQueue, LARGE_FILE_BYTES};
+use crate::results::{
+    print_extension_stats, print_hint, print_latency_stats, print_per_path_summary,
+    print_slowest_dirs, print_summary_and_exit, print_summary_table, process_results,
+    EXIT_OVER_BYTES,
+};
+use crate::scanner::{count_jobs, sample_files, scan_empty_dirs, scan_root};
+use crate::shadow::Shadow;
 
 fn main() {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    // Claim stdout for deleted paths before anything else is printed
+    let printer = if cli.print_deleted || cli.print0 {
+        match printer::claim_stdout() {
+            Ok(out) => Some(DeletedPrinter::spawn(out, cli.print0)),
+            Err(e) => {
+                eprintln!(
+                    "{} cannot set up --print-deleted: {}",
+                    "Error:".red().bold(),
+                    e
+                );
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    cli.color.apply();
+
+    // Empty, "." and ".." arguments, and `/`, are refused before any of
+    // them is used
+    if let Err(e) = validate_args(&cli.paths, cli.allow_dot) {
+        eprintln!("{} {}. Nothing was deleted.", "Error:".red().bold(), e);
+        std::process::exit(1);
+    }
+
+    // Recognise node_modules and .git before the pools are sized
+    if let Some(name) = cli.apply_known_dir_preset() {
+        if Verbosity::from_count(cli.verbosity).is_verbose() {
+            println!("Tuning for {} (--fast-known-dirs)", name);
+        }
+    }
+    if let Some(profile) = fsdetect::detect_profile(&cli.paths) {
+        cli.apply_fs_profile(&profile);
+        if Verbosity::from_count(cli.verbosity).is_verbose() {
+            println!(
+                "File system profile: {} ({} deleters, queue of {})",
+                profile.name,
+                cli.get_delete_threads(),
+                cli.get_queue_capacity()
+            );
+        }
+    }
 
     // Get thread pool sizes from CLI
     let scan_threads = cli.get_scan_threads();
     let delete_threads = cli.get_delete_threads();
 
-    // Deduplicate and validate paths
-    let paths_to_process = match deduplicate_and_check_paths(&cli.paths) {
-        Ok(paths) => paths,
+    // With --dereference-args, or when given as `link/`, a top-level link to
+    // a directory stands for the directory itself; the link is left in place.
+    // As with rm, the directory behind `link/` is emptied but kept.
+    let mut emptied_roots = HashSet::new();
+    let args: Vec<PathBuf> = cli
+        .paths
+        .iter()
+        .map(|arg| {
+            let path = resolve_trailing_separator(arg)?;
+            if names_link_target(arg) {
+                emptied_roots.insert(path.clone());
+            }
+            if !(cli.dereference_args && path.is_symlink()) {
+                return Ok(path);
+            }
+            let target = follow_symlink_chain(&path, cli.max_symlink_depth)?
+                .and_then(|end| fs::canonicalize(end).ok());
+            Ok(match target {
+                Some(target) if target.is_dir() => target,
+                _ => path,
+            })
+        })
+        .collect::<Result<_, RemoveError>>()
+        .unwrap_or_else(|e| {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+            std::process::exit(1);
+        });
+
+    // An argument that cannot even be examined is reported as such up front;
+    // with --continue-on-error the others are still deleted
+    let (args, inaccessible) = split_inaccessible(args);
+    for e in &inaccessible {
+        if cli.continue_on_error {
+            eprintln!("{} {}; skipped", "Warning:".yellow(), e);
+        } else {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+        }
+        print_hint(e);
+    }
+    if !inaccessible.is_empty() && (!cli.continue_on_error || args.is_empty()) {
+        eprintln!(
+            "Nothing was deleted{}.",
+            if args.is_empty() {
+                ""
+            } else {
+                "; use --continue-on-error to delete the other arguments anyway"
+            }
+        );
+        std::process::exit(1);
+    }
+
+    // A plan names its own entries; its top-level ones are checked like
+    // command-line paths
+    let plan_jobs = cli.plan_in.as_ref().map(|path| match plan::load(path) {
+        Ok(jobs) => jobs,
         Err(e) => {
             eprintln!("{} {}", "Error:".red().bold(), e);
             std::process::exit(1);
         }
+    });
+    let plan_roots = plan_jobs.as_deref().map(plan::roots).unwrap_or_default();
+    if let Err(e) = validate_args(&plan_roots, cli.allow_dot) {
+        eprintln!("{} {}. Nothing was deleted.", "Error:".red().bold(), e);
+        std::process::exit(1);
+    }
+
+    // Deduplicate and validate paths; --no-dedup takes them as given
+    let mut paths_to_process = if cli.no_dedup {
+        args
+    } else if cli.assume_yes {
+        let (paths, pruned) = deduplicate_and_prune_paths(&args);
+        for (path, ancestor) in pruned {
+            eprintln!(
+                "{} {:?} is inside {:?} and is deleted with it (--assume-yes)",
+                "Note:".bold(),
+                path,
+                ancestor
+            );
+        }
+        paths
+    } else {
+        match deduplicate_and_check_paths(&args) {
+            Ok(paths) => paths,
+            Err(e) => {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    // `/` is never a target, however an argument led to it
+    if let Some(root) = paths_to_process.iter().find(|p| is_fs_root(p)) {
+        let e = RemoveError::RootArgument(root.clone());
+        eprintln!("{} {}. Nothing was deleted.", "Error:".red().bold(), e);
+        std::process::exit(1);
+    }
+
+    // Deleting the directory we run in would strand the process in it.
+    // Uncanonicalized --no-dedup targets and the roots of a --plan-in plan
+    // are compared by their absolute form, worked out without touching them.
+    let cwd_guard = std::env::current_dir()
+        .and_then(fs::canonicalize)
+        .ok()
+        .and_then(|cwd| {
+            let absolute: Vec<PathBuf>;
+            let uncanonical = if cli.plan_in.is_some() {
+                Some(&plan_roots)
+            } else {
+                Some(&paths_to_process).filter(|_| cli.no_dedup)
+            };
+            let targets = match uncanonical {
+                Some(paths) => {
+                    absolute = paths
+                        .iter()
+                        .map(|path| absolute_lexically(path, &cwd))
+                        .collect();
+                    &absolute
+                }
+                None => &paths_to_process,
+            };
+            let doomed = check_cwd(targets, &cwd, cli.contents_only).err()?;
+            Some((cwd, doomed))
+        });
+    if let (Some((_, e)), false) = (&cwd_guard, cli.allow_delete_cwd) {
+        eprintln!("{} {}", "Error:".red().bold(), e);
+        std::process::exit(1);
+    }
+    let doomed_cwd = cwd_guard.map(|(cwd, _)| cwd);
+
+    // Make the user spell out every target, knowing how much goes with it,
+    // before anything irreversible happens
+    if cli.require_typed_confirm && !cli.dry_run && !cli.assume_yes {
+        let given: Vec<PathBuf> = paths_to_process
+            .iter()
+            .map(|resolved| {
+                cli.paths
+                    .iter()
+                    .find(|p| canonical_arg(p).ok().as_ref() == Some(resolved))
+                    .unwrap_or(resolved)
+                    .clone()
+            })
+            .collect();
+        // Counted as --pre-scan does, before staging moves anything; a bad
+        // filter or keep list is reported once the run is set up
+        let mut count_config = RemoveConfig::from_cli(&cli, None);
+        count_config.emptied_roots = Arc::new(emptied_roots.clone());
+        count_config.filter = PathFilter::from_cli(&cli).ok().flatten();
+        count_config.keep = cli
+            .keep_list
+            .as_ref()
+            .and_then(|list| load_keep_list(list, &paths_to_process).ok())
+            .map(Arc::new);
+        let targets: Vec<_> = given
+            .iter()
+            .zip(&paths_to_process)
+            .map(|(given, resolved)| confirm::TypedTarget {
+                given,
+                resolved,
+                // Scan errors are reported by the real scan
+                count: count_jobs(resolved, &count_config).unwrap_or_default(),
+            })
+            .collect();
+        let confirmed = confirm::confirm_typed(
+            &targets,
+            &mut std::io::stdin().lock(),
+            &mut std::io::stderr(),
+        );
+        if !matches!(confirmed, Ok(true)) {
+            eprintln!(
+                "{} Confirmation did not match. Nothing was deleted.",
+                "Aborted:".red().bold()
+            );
+            std::process::exit(confirm::EXIT_NOT_CONFIRMED);
+        }
+    }
+
+    // --allow-delete-cwd: step out of the directory before it goes
+    if let (Some(cwd), false) = (&doomed_cwd, cli.dry_run) {
+        cli.anchor_file_options(cwd);
+        if let Err(e) = std::env::set_current_dir("/") {
+            eprintln!(
+                "{} Could not leave the current directory {:?}: {}. Nothing was deleted.",
+                "Error:".red().bold(),
+                cwd,
+                e
+            );
+            std::process::exit(1);
+        }
+        eprintln!(
+            "{} running from / instead of {:?}, which is deleted (--allow-delete-cwd)",
+            "Note:".bold(),
+            cwd
+        );
+    }
+
+    // Move the targets into the staging directory so they are gone at once
+    if let (Some(stage_dir), false) = (&cli.stage_dir, cli.dry_run) {
+        for path in paths_to_process
+            .iter_mut()
+            .filter(|p| !emptied_roots.contains(*p))
+        {
+            match stage_for_deletion(path, stage_dir) {
+                Ok(staged) => *path = staged,
+                Err(e) => eprintln!(
+                    "{} Could not move {:?} into {:?}: {}. Deleting in place.",
+                    "Warning:".yellow(),
+                    path,
+                    stage_dir,
+                    e
+                ),
+            }
+        }
+    }
+
+    // Move top-level directories out of sight before emptying them
+    if cli.rename_first && !cli.dry_run {
+        for path in paths_to_process
+            .iter_mut()
+            .filter(|p| p.is_dir() && !emptied_roots.contains(*p))
+        {
+            match rename_for_deletion(path) {
+                Ok(staged) => *path = staged,
+                Err(e) => eprintln!(
+                    "{} Could not rename {:?} before deletion: {}. Deleting in place.",
+                    "Warning:".yellow(),
+                    path,
+                    e
+                ),
+            }
+        }
+    }
+
+    // Keep hardlinks to everything so a failed run can be rolled back
+    let shadow = if cli.atomic && !cli.dry_run {
+        match Shadow::create(&paths_to_process) {
+            Ok(shadow) => Some(shadow),
+            Err(e) => {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                eprintln!("Nothing was deleted.");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
     };
 
     // Initialize progress tracking and configuration
-    let progress = RemoveProgress::new();
+    let error_hook = cli.on_error.clone().map(ErrorHook::spawn);
+    let progress = RemoveProgress::with_options(ProgressOptions {
+        path_roots: (cli.per_path_summary || cli.summary_table).then(|| paths_to_process.clone()),
+        latency_stats: cli.stats,
+        profile_dirs: cli.profile_dirs,
+        ext_stats: cli.stats_by_ext,
+        error_hook: error_hook.as_ref().map(ErrorHook::sender),
+        deleted_feed: printer.as_ref().map(DeletedPrinter::sender),
+    });
     let verbosity = Verbosity::from_count(cli.verbosity);
-    let display = Arc::new(ProgressDisplay::new(verbosity, cli.dry_run));
-    let config = RemoveConfig::from_cli(&cli, Some(progress.clone()));
+    // Without a terminal the TUI is just escape-code noise; print plain lines
+    // --diff prints a line per entry, which would tear through the TUI
+    // --print-deleted keeps stdout for the paths alone
+    let use_tui =
+        (cli.force_tui || std::io::stdout().is_terminal()) && !cli.diff && printer.is_none();
+    let mut config = RemoveConfig::from_cli(&cli, Some(progress.clone()));
+    config.emptied_roots = Arc::new(emptied_roots);
+    config.filter = match PathFilter::from_cli(&cli) {
+        Ok(filter) => filter,
+        Err(e) => {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(list) = &cli.keep_list {
+        match load_keep_list(list, &paths_to_process) {
+            Ok(keep) => config.keep = Some(Arc::new(keep)),
+            Err(e) => {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if cli.stay_within_root {
+        config.stay_within_roots = Some(paths_to_process.clone().into());
+    }
+    if let (Some(dir), false) = (&cli.backup_to, cli.dry_run) {
+        match Backup::new(dir, &paths_to_process, cli.backup_verify) {
+            Ok(backup) => config.backup = Some(Arc::new(backup)),
+            Err(e) => {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                eprintln!("Nothing was deleted.");
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(resume) = &cli.resume {
+        match journal::load(resume) {
+            Ok(done) => config.already_deleted = Some(Arc::new(done)),
+            Err(e) => {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+    // A resumed run keeps appending to the journal it resumed from
+    if let (Some(path), false) = (cli.journal.as_ref().or(cli.resume.as_ref()), cli.dry_run) {
+        match Journal::open(path) {
+            Ok(journal) => config.journal = Some(Arc::new(journal)),
+            Err(e) => {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(path) = &cli.plan_out {
+        match PlanWriter::create(path) {
+            Ok(plan) => config.plan = Some(Arc::new(plan)),
+            Err(e) => {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let counted = (cli.pre_scan || cli.abort_over_bytes.is_some()).then(|| {
+        // Scan errors are reported by the real scan
+        let (entries, bytes) = paths_to_process
+            .iter()
+            .filter_map(|path| count_jobs(path, &config).ok())
+            .fold((0, 0), |(entries, bytes), count| {
+                (entries + count.entries, bytes + count.bytes)
+            });
+        if !cli.summary_only {
+            println!(
+                "Pre-scan: {} entries found ({})",
+                format_count(entries),
+                format_bytes(bytes)
+            );
+        }
+        (entries, bytes)
+    });
+    if let (Some(cap), Some((_, bytes))) = (cli.abort_over_bytes, counted) {
+        if bytes > cap {
+            eprintln!(
+                "{} {} to delete exceeds --abort-over-bytes {}. Nothing was deleted.",
+                "Aborted:".red().bold(),
+                format_bytes(bytes),
+                format_bytes(cap)
+            );
+            if let Some(shadow) = shadow {
+                let _ = shadow.discard();
+            }
+            std::process::exit(EXIT_OVER_BYTES);
+        }
+    }
+
+    // --probe: delete a few sample files first and stop at the first sign of trouble
+    if let Some(n) = cli.probe {
+        let n = n as usize;
+        let mut sample = Vec::new();
+        for (i, path) in paths_to_process.iter().enumerate() {
+            let share = n / paths_to_process.len() + usize::from(i < n % paths_to_process.len());
+            if share == 0 {
+                continue;
+            }
+            match sample_files(path, &config, share) {
+                Ok(jobs) => sample.extend(jobs),
+                Err(e) => {
+                    eprintln!("{} {}", "Error:".red().bold(), e);
+                    eprintln!("Nothing was deleted.");
+                    std::process::exit(1);
+                }
+            }
+        }
+        let mut deleted = 0;
+        let mut failures = Vec::new();
+        for job in &sample {
+            progress.inc_scanned();
+            match execute(job, &config) {
+                Ok(()) => deleted += 1,
+                Err(e) => failures.push(e),
+            }
+        }
+        let dirs = sample
+            .iter()
+            .filter_map(|job| job.path().parent())
+            .collect::<HashSet<_>>()
+            .len();
+        let report = format!(
+            "Probe: deleted {} of {} sample files in {} directories",
+            deleted,
+            sample.len(),
+            dirs
+        );
+        if !failures.is_empty() {
+            eprintln!("{} {}", "Error:".red().bold(), report);
+            for e in &failures {
+                eprintln!("  {}", e);
+                print_hint(e);
+            }
+            eprintln!("Nothing else was deleted.");
+            std::process::exit(1);
+        }
+        if !cli.probe_then_delete {
+            if !cli.summary_only {
+                println!(
+                    "{}; nothing else was deleted (use --probe-then-delete to continue)",
+                    report
+                );
+            }
+            std::process::exit(0);
+        }
+        if !cli.summary_only {
+            println!("{}", report);
+        }
+    }
 
-    if config.dry_run {
+    // --two-phase: scan everything now; nothing is deleted until Enter is pressed
+    let mut phased_total = None;
+    let phased_jobs = (cli.two_phase || cli.top_largest.is_some()).then(|| {
+        if !cli.summary_only {
+            println!(
+                "Scanning before deleting anything ({})...",
+                if cli.two_phase {
+                    "--two-phase"
+                } else {
+                    "--top-largest"
+                }
+            );
+        }
+        let list = match cli.top_largest {
+            Some(n) => twophase::scan_largest(&paths_to_process, &config, n as usize),
+            None => twophase::scan_all(&paths_to_process, &config, twophase::MEMORY_JOBS),
+        }
+        .unwrap_or_else(|e| {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+            eprintln!("Nothing was deleted.");
+            std::process::exit(1);
+        });
+        if cli.two_phase && !cli.assume_yes {
+            let confirmed = twophase::confirm_totals(
+                &list,
+                &mut std::io::stdin().lock(),
+                &mut std::io::stderr(),
+            );
+            if !matches!(confirmed, Ok(true)) {
+                eprintln!("{} Nothing was deleted.", "Aborted:".red().bold());
+                std::process::exit(confirm::EXIT_NOT_CONFIRMED);
+            }
+        }
+        phased_total = Some(list.entries());
+        list.into_jobs().unwrap_or_else(|e| {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+            eprintln!("Nothing was deleted.");
+            std::process::exit(1);
+        })
+    });
+    let total = counted
+        .filter(|_| cli.pre_scan)
+        .map(|(entries, _)| entries)
+        .or(phased_total);
+    if let Some(total) = total {
+        progress.total.store(total as usize, Ordering::Relaxed);
+    }
+    let display = Arc::new(if cli.summary_only {
+        ProgressOutput::Hidden
+    } else {
+        ProgressOutput::new(use_tui, verbosity, cli.dry_run, total)
+    });
+
+    // Small plain inputs are removed by recursion, without the pools; the
+    // directory behind a `link/` argument is kept, which only the pools do
+    let needs_pipeline = cli.needs_pipeline() || !config.emptied_roots.is_empty();
+    let recursive = match cli.engine.resolve(&paths_to_process, !needs_pipeline) {
+        Engine::Recursive if needs_pipeline => {
+            eprintln!(
+                "{} --engine recursive only honours --dry-run, --continue-on-error and the reporting options; use --engine pipeline for the others. Nothing was deleted.",
+                "Error:".red().bold()
+            );
+            std::process::exit(1);
+        }
+        engine => engine == Engine::Recursive,
+    };
+    if verbosity.is_verbose() && !cli.summary_only {
+        println!(
+            "Engine: {}",
+            if recursive { "recursive" } else { "pipeline" }
+        );
+        if !recursive {
+            println!(
+                "Threads: {} scanners, {} deleters",
+                scan_threads, delete_threads
+            );
+        }
+    }
+
+    if config.dry_run && !cli.summary_only {
         println!(
             "{}",
             "Dry run mode activated. No files will be deleted."
@@ -59,56 +638,308 @@ fn main() {
         println!();
     }
 
+    // Watchdog: past the deadline scanners and deleters wind down as if
+    // interrupted. It is never joined; an earlier finish simply outlives it.
+    // A zero timeout is up before anything is deleted.
+    if let Some(timeout) = cli.timeout {
+        let cancelled = config.cancelled.clone();
+        if timeout.is_zero() {
+            cancelled.store(true, Ordering::Relaxed);
+        } else {
+            thread::spawn(move || {
+                thread::sleep(timeout);
+                cancelled.store(true, Ordering::Relaxed);
+            });
+        }
+    }
+
     // Create adaptive queue for coordinating scan/delete
-    let queue_capacity = (scan_threads * 1000).max(10000);
-    let queue = Arc::new(AdaptiveQueue::new(queue_capacity));
+    let queue_capacity = cli.get_queue_capacity();
+    let mut queue = AdaptiveQueue::new(queue_capacity).with_backpressure();
+    if cli.size_aware {
+        queue = queue.with_large_files(LARGE_FILE_BYTES);
+    }
+    let queue = Arc::new(queue);
 
     // Signal for coordinating scanner/deleter shutdown
     let scanners_done = Arc::new(AtomicBool::new(false));
 
+    // Scanners take the first CPUs, deleters the ones after them
+    let pinner = if cli.pin_threads {
+        if !affinity::SUPPORTED {
+            eprintln!(
+                "{} --pin-threads is only supported on Linux; threads are not pinned.",
+                "Warning:".yellow()
+            );
+        }
+        CpuPinner::new().map(Arc::new)
+    } else {
+        None
+    };
+
     // Spawn scanner thread pool
     let queue_scan = queue.clone();
     let config_scan = config.clone();
     let paths_scan = paths_to_process.clone();
+    let parallel_top_level = cli.parallel_top_level;
     let scanners_done_clone = scanners_done.clone();
 
+    let pinner_scan = pinner.clone();
+    let until_free = cli.until_free;
+    let rename_children = cli.rename_children && !cli.dry_run;
     let scanner_thread = thread::spawn(move || {
+        // --two-phase: the scan is already done
+        if let Some(jobs) = phased_jobs {
+            if let Err(e) = twophase::enqueue_jobs(jobs, &queue_scan, &config_scan) {
+                eprintln!("{} {}", "Scan error:".red().bold(), e);
+            }
+            scanners_done_clone.store(true, Ordering::Release);
+            return None;
+        }
+
+        // --plan-in: the plan replaces the scan
+        if let Some(jobs) = plan_jobs {
+            if let Err(e) = enqueue_plan(jobs, &queue_scan, &config_scan) {
+                eprintln!("{} {}", "Plan error:".red().bold(), e);
+            }
+            scanners_done_clone.store(true, Ordering::Release);
+            return None;
+        }
+
+        // --until-free: oldest files first, one at a time, checking the free
+        // space after each; the deleters have nothing to do
+        if let Some(target) = until_free {
+            let files = evict::oldest_first(&paths_scan, &config_scan);
+            let root = &paths_scan[0];
+            let eviction = if config_scan.dry_run {
+                evict::free_space(root).and_then(|free| {
+                    evict::delete_until(files, target, &config_scan, |freed| Ok(free + freed))
+                })
+            } else {
+                evict::delete_until(files, target, &config_scan, |_| evict::free_space(root))
+            };
+            scanners_done_clone.store(true, Ordering::Release);
+            return Some(eviction.map_err(|e| (root.clone(), e)));
+        }
+
+        // --engine recursive: each path is removed in turn; the deleters
+        // have nothing to do
+        if recursive {
+            let results = paths_scan
+                .iter()
+                .map(|path| (path, removal::fast_remove(path, &config_scan)))
+                .collect();
+            process_results(results, &config_scan);
+            scanners_done_clone.store(true, Ordering::Release);
+            return None;
+        }
+
         // Create a custom rayon thread pool for scanning
-        let scan_pool = rayon::ThreadPoolBuilder::new()
+        let mut scan_pool = rayon::ThreadPoolBuilder::new()
             .num_threads(scan_threads)
-            .thread_name(|i| format!("scanner-{}", i))
+            .thread_name(|i| format!("scanner-{}", i));
+        if let Some(pinner) = pinner_scan {
+            scan_pool = scan_pool.start_handler(move |i| pinner.pin(i));
+        }
+        let scan_pool = scan_pool
             .build()
             .expect("Failed to create scanner thread pool");
 
-        // Scan all paths in parallel
-        scan_pool.install(|| {
-            paths_scan.par_iter().for_each(|path| {
-                if let Err(e) = scan_path(path, &queue_scan, &config_scan) {
-                    eprintln!("{} {}", "Scan error:".red().bold(), e);
+        let scan_one = |path: &PathBuf| {
+            if rename_children && path.is_dir() {
+                stage_children_of(path);
+            }
+            if let Err(e) = scan_root(path, &queue_scan, &config_scan) {
+                eprintln!("{} {}", "Scan error:".red().bold(), e);
+                print_hint(&e);
+                if let Some(p) = &config_scan.progress {
+                    p.inc_path_error(path);
                 }
-            });
+            }
+        };
+
+        // Scan up to --parallel-top-level paths at once (in argument order
+        // with --sequential); each scan still fans out across the whole pool
+        let lanes = if config_scan.sequential {
+            1
+        } else {
+            parallel_top_level.clamp(1, paths_scan.len().max(1))
+        };
+        scan_pool.install(|| {
+            if lanes == 1 {
+                paths_scan.iter().for_each(scan_one);
+            } else {
+                let next = AtomicUsize::new(0);
+                rayon::scope(|s| {
+                    for _ in 0..lanes {
+                        s.spawn(|_| {
+                            while let Some(path) =
+                                paths_scan.get(next.fetch_add(1, Ordering::Relaxed))
+                            {
+                                scan_one(path);
+                            }
+                        });
+                    }
+                });
+            }
         });
 
         // Signal that scanning is complete
         scanners_done_clone.store(true, Ordering::Release);
+        None
     });
 
-    // Spawn deleter worker threads
-    let mut deleter_threads = Vec::new();
-    for i in 0..delete_threads {
-        let queue_delete = queue.clone();
-        let config_delete = config.clone();
-        let scanners_done_delete = scanners_done.clone();
+    // With --delete-threads auto deleters are started as the queue grows
+    let elastic = (cli.elastic_deleters() && !recursive)
+        .then(|| Arc::new(ElasticPool::new(pool::MIN_DELETERS, delete_threads)));
+
+    // With --auto-threads every deleter is spawned but only the gated ones run
+    let gate = (cli.auto_threads && elastic.is_none() && !recursive)
+        .then(|| Arc::new(ThreadGate::new(AutoTuner::initial_for(delete_threads))));
+
+    let spawn_deleter = {
+        let queue = queue.clone();
+        let config = config.clone();
+        let scanners_done = scanners_done.clone();
+        let gate = gate.clone();
+        let elastic = elastic.clone();
+        let pinner = pinner.clone();
+        move |i: usize| {
+            let queue_delete = queue.clone();
+            let config_delete = config.clone();
+            let scanners_done_delete = scanners_done.clone();
+            let gate_delete = gate.clone();
+            let elastic_delete = elastic.clone();
+            let pinner_delete = pinner.clone();
 
-        let deleter = thread::spawn(move || {
-            if config_delete.verbosity.is_verbose() && config_delete.progress.is_none() {
-                println!("Deleter worker {} started", i);
+            thread::spawn(move || {
+                if let Some(pinner) = &pinner_delete {
+                    pinner.pin(scan_threads + i);
+                }
+                if config_delete.verbosity.is_verbose() && config_delete.progress.is_none() {
+                    println!("Deleter worker {} started", i);
+                }
+                match (&gate_delete, &elastic_delete) {
+                    (Some(gate), _) => gated_delete_worker(
+                        &queue_delete,
+                        &config_delete,
+                        &scanners_done_delete,
+                        gate,
+                        i,
+                    ),
+                    (None, Some(pool)) => elastic_delete_worker(
+                        &queue_delete,
+                        &config_delete,
+                        &scanners_done_delete,
+                        pool,
+                    ),
+                    (None, None) => {
+                        delete_worker(&queue_delete, &config_delete, &scanners_done_delete)
+                    }
+                }
+            })
+        }
+    };
+
+    // Spawn deleter worker threads; an elastic pool's are spawned and joined
+    // by its manager
+    let deleter_threads: Vec<_> = match &elastic {
+        Some(elastic) => {
+            let elastic = elastic.clone();
+            let queue_pool = queue.clone();
+            vec![thread::spawn(move || {
+                pool::run(&elastic, &queue_pool, spawn_deleter)
+            })]
+        }
+        None if recursive => Vec::new(),
+        None => (0..delete_threads).map(spawn_deleter).collect(),
+    };
+
+    // Controller resizing the active deleter pool from queue depth and rate
+    let deleters_done = Arc::new(AtomicBool::new(false));
+    let tuner_thread = gate.clone().map(|gate| {
+        let queue_tune = queue.clone();
+        let progress_tune = progress.clone();
+        let deleters_done = deleters_done.clone();
+        thread::spawn(move || {
+            let mut tuner = AutoTuner::new(gate.active(), delete_threads);
+            let mut last_deleted = progress_tune.deleted.load(Ordering::Relaxed);
+            while !deleters_done.load(Ordering::Relaxed) {
+                thread::sleep(autotune::SAMPLE_INTERVAL);
+                // Rate over the last interval, not the run-wide average
+                let deleted = progress_tune.deleted.load(Ordering::Relaxed);
+                let rate =
+                    (deleted - last_deleted) as f64 / autotune::SAMPLE_INTERVAL.as_secs_f64();
+                last_deleted = deleted;
+                gate.set_active(tuner.step(queue_tune.depth(), queue_tune.capacity(), rate));
             }
-            delete_worker(&queue_delete, &config_delete, &scanners_done_delete);
-        });
+        })
+    });
 
-        deleter_threads.push(deleter);
-    }
+    // Sampler for --concurrency-report, running until the deleters finish
+    let sampler_thread = cli.concurrency_report.then(|| {
+        let queue_sample = queue.clone();
+        let progress_sample = progress.clone();
+        let scanners_done = scanners_done.clone();
+        let deleters_done = deleters_done.clone();
+        thread::spawn(move || {
+            let mut report =
+                ConcurrencyReport::new(queue_sample.high_water(), scan_threads, delete_threads);
+            let mut last_deleted = progress_sample.get_stats().1;
+            while !deleters_done.load(Ordering::Relaxed) {
+                thread::sleep(concurrency::SAMPLE_INTERVAL);
+                let (_, deleted, _, _, _) = progress_sample.get_stats();
+                report.record(Sample {
+                    depth: queue_sample.depth(),
+                    busy_scanners: progress_sample.busy_scanners.load(Ordering::Relaxed),
+                    rate: deleted.saturating_sub(last_deleted) as f64
+                        / concurrency::SAMPLE_INTERVAL.as_secs_f64(),
+                    scanning: !scanners_done.load(Ordering::Acquire),
+                });
+                last_deleted = deleted;
+            }
+            report
+        })
+    });
+
+    // Watchdog for --stuck-timeout, naming the paths of stalled deleters
+    let watchdog_thread =
+        config
+            .in_flight
+            .clone()
+            .zip(cli.stuck_timeout)
+            .map(|(in_flight, timeout)| {
+                let deleters_done = deleters_done.clone();
+                thread::spawn(move || {
+                    watchdog::watch(&in_flight, timeout, &deleters_done, watchdog::print_stall)
+                })
+            });
+
+    // Bind the progress socket up front so clients can connect straight away
+    #[cfg(unix)]
+    let mut progress_socket = match &cli.progress_socket {
+        Some(path) => match ipc::ProgressSocket::bind(path, cli.output_version) {
+            Ok(socket) => {
+                // The socket file would otherwise outlive an interrupted run
+                let path = path.clone();
+                let _ = ctrlc::set_handler(move || {
+                    let _ = std::fs::remove_file(&path);
+                    std::process::exit(130);
+                });
+                Some(socket)
+            }
+            Err(e) => {
+                eprintln!(
+                    "{} Failed to bind progress socket {:?}: {}",
+                    "Error:".red().bold(),
+                    path,
+                    e
+                );
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
 
     // Spawn TUI thread with queue depth tracking
     let display_clone = display.clone();
@@ -118,32 +949,232 @@ fn main() {
     let is_done = Arc::new(AtomicBool::new(false));
     let is_done_clone = is_done.clone();
 
+    let refresh_interval = Duration::from_millis(cli.progress_interval);
+
     let tui_thread = thread::spawn(move || {
-        while !is_done_clone.load(Ordering::Relaxed) {
-            let depth = queue_clone.depth();
-            display_clone.update(&progress_clone, dry_run, Some(depth));
-            thread::sleep(Duration::from_millis(50));
-        }
-        let depth = queue_clone.depth();
-        display_clone.update(&progress_clone, dry_run, Some(depth));
+        // The final snapshot is published last; the socket file is removed
+        // when it drops at the end of this thread
+        refresh_until(
+            &is_done_clone,
+            refresh_interval,
+            thread::park_timeout,
+            |last| {
+                let depth = queue_clone.depth();
+                display_clone.update(&progress_clone, dry_run, Some(depth));
+                #[cfg(unix)]
+                if let Some(socket) = &mut progress_socket {
+                    socket.publish(&progress_clone, depth, last);
+                }
+            },
+        );
     });
 
     // Wait for scanner to finish
-    scanner_thread.join().expect("Scanner thread panicked");
+    let eviction = scanner_thread.join().expect("Scanner thread panicked");
 
     // Wait for all deleter threads to finish
     for deleter in deleter_threads {
         deleter.join().expect("Deleter thread panicked");
     }
+    deleters_done.store(true, Ordering::Relaxed);
+    if let Some(tuner) = tuner_thread {
+        tuner.join().expect("Auto-tuner thread panicked");
+    }
+    let concurrency_report =
+        sampler_thread.map(|sampler| sampler.join().expect("Concurrency sampler thread panicked"));
+    if let Some(watchdog) = watchdog_thread {
+        watchdog.thread().unpark();
+        watchdog.join().expect("Watchdog thread panicked");
+    }
+
+    // Give failed deletions one more try now that everything else is gone
+    let retried = config.failed.as_ref().and_then(|failed| {
+        let mut jobs = std::mem::take(&mut *failed.lock().unwrap());
+        if jobs.is_empty() || config.is_cancelled() {
+            return None;
+        }
+        Some((retry_failed(&mut jobs, &config), jobs.len()))
+    });
+
+    // Second pass: remove directories the filtered pass left empty. A dry run
+    // deleted nothing, so there is nothing it could have emptied. The
+    // selection filters picked the files, so of the path filters only the
+    // --match-prefix scope applies to the directories they left behind.
+    if cli.prune_empty && !cli.dry_run && !config.is_cancelled() {
+        let prune_config = RemoveConfig {
+            filter: cli.match_prefix.as_deref().map(PathFilter::prefix),
+            ..config.clone()
+        };
+        prune_empty_dirs(&paths_to_process, &queue, &prune_config);
+    }
 
     // Signal TUI to finish
     is_done.store(true, Ordering::Relaxed);
+    tui_thread.thread().unpark();
     tui_thread.join().expect("TUI thread panicked");
 
     // Display final summary
     let final_depth = queue.depth();
     display.finish(&progress, cli.dry_run, Some(final_depth));
 
+    if let (Some(plan), Some(path)) = (&config.plan, &cli.plan_out) {
+        match plan.finish() {
+            Ok(operations) => println!(
+                "{} {} operations written to {:?} (--plan-out)",
+                "Plan:".bold(),
+                format_count(operations),
+                path
+            ),
+            Err(e) => {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                progress.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    match (eviction, cli.until_free) {
+        (Some(Ok(eviction)), Some(target)) => {
+            let free = format!(
+                "{} {} free after deleting {} files ({})",
+                format_bytes(eviction.free),
+                if cli.dry_run { "would be" } else { "is" },
+                format_count(eviction.files),
+                format_bytes(eviction.bytes)
+            );
+            if eviction.reached {
+                println!(
+                    "{} {} (--until-free {})",
+                    "Space:".bold(),
+                    free,
+                    format_bytes(target)
+                );
+            } else {
+                eprintln!(
+                    "{} {}, but no files are left to reach --until-free {}",
+                    "Error:".red().bold(),
+                    free,
+                    format_bytes(target)
+                );
+                progress.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        (Some(Err((root, e))), _) => {
+            eprintln!(
+                "{} cannot measure free space at {:?}: {}",
+                "Error:".red().bold(),
+                root,
+                e
+            );
+            progress.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        _ => {}
+    }
+    if let Some(n) = cli.top_largest {
+        println!(
+            "{} {} {}, {} {} (--top-largest {})",
+            "Largest files:".bold(),
+            format_count(progress.deleted.load(Ordering::Relaxed) as u64),
+            if cli.dry_run {
+                "would be deleted"
+            } else {
+                "deleted"
+            },
+            format_bytes(progress.bytes.load(Ordering::Relaxed)),
+            if cli.dry_run { "to free" } else { "freed" },
+            n
+        );
+    }
+
+    if let Some((resolved, failed)) = retried {
+        println!(
+            "{} resolved {} of {} failed {} (--retry-failed)",
+            "Retry:".bold(),
+            resolved,
+            failed,
+            if failed == 1 { "deletion" } else { "deletions" }
+        );
+    }
+
+    if let Some(tally) = progress.path_tally() {
+        if cli.summary_table {
+            print_summary_table(tally, cli.dry_run);
+        } else {
+            print_per_path_summary(tally, cli.dry_run);
+        }
+    }
+    if let Some(stats) = progress.latency_stats() {
+        print_latency_stats(&stats);
+    }
+    if let Some(dirs) = progress.slowest_dirs() {
+        print_slowest_dirs(&dirs);
+    }
+    if let Some(mut stats) = progress.extension_stats() {
+        print_extension_stats(&mut stats, cli.dry_run);
+    }
+
+    if config.verbosity.is_verbose() {
+        println!(
+            "{} {} of {} (tune with --max-queue)",
+            "Peak queue depth:".bold(),
+            progress.peak_queue_depth.load(Ordering::Relaxed),
+            queue.capacity()
+        );
+        if let Some(gate) = &gate {
+            println!(
+                "{} {} of {} deleters active at the end (--auto-threads)",
+                "Auto-threads:".bold(),
+                gate.active(),
+                delete_threads
+            );
+        }
+        if let Some(elastic) = &elastic {
+            println!(
+                "{} at most {} of {} deleters ran at once (--delete-threads auto)",
+                "Deleters:".bold(),
+                elastic.peak(),
+                delete_threads
+            );
+        }
+    }
+
+    if let Some(report) = &concurrency_report {
+        println!("{} {}", "Concurrency:".bold(), report.render());
+    }
+
+    if let Some(Err(e)) = printer.map(DeletedPrinter::finish) {
+        eprintln!(
+            "{} --print-deleted output failed: {}",
+            "Warning:".yellow(),
+            e
+        );
+    }
+
+    // Let queued hook invocations run before exiting
+    if let Some(hook) = error_hook {
+        progress.close_error_hook();
+        let dropped = hook.finish();
+        if dropped > 0 {
+            eprintln!(
+                "{} --on-error hook not run for {} error(s) (rate limited)",
+                "Warning:".yellow(),
+                dropped
+            );
+        }
+    }
+
+    if let Some(dir_sync) = &config.dir_sync {
+        for e in dir_sync.flush() {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+            progress.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Exiting skips destructors, so make the journal durable now
+    if let Some(Err(e)) = config.journal.as_ref().map(|journal| journal.sync()) {
+        eprintln!("{} {}", "Error:".red().bold(), e);
+        progress.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
     let total_errors = progress.errors.load(Ordering::Relaxed) as u64;
     let total_items = if cli.dry_run {
         progress.scanned.load(Ordering::Relaxed) as u64
@@ -151,5 +1182,80 @@ fn main() {
         progress.deleted.load(Ordering::Relaxed) as u64
     };
 
+    if let Some(shadow) = shadow {
+        let failed = total_errors > 0 && !config.continue_on_error;
+        finish_atomic(shadow, failed || config.is_cancelled());
+    }
+
     print_summary_and_exit(total_items, total_errors, &config);
 }
+
+/// Move the children of top-level directory `dir` out of sight before it is
+/// scanned (`--rename-children`); whatever stays is deleted in place
+fn stage_children_of(dir: &Path) {
+    match stage_children(dir) {
+        Ok((_, failed)) => {
+            for (child, e) in failed {
+                eprintln!(
+                    "{} Could not rename {:?} before deletion: {}. Deleting in place.",
+                    "Warning:".yellow(),
+                    child,
+                    e
+                );
+            }
+        }
+        Err(e) => eprintln!(
+            "{} Could not stage the children of {:?}: {}. Deleting in place.",
+            "Warning:".yellow(),
+            dir,
+            e
+        ),
+    }
+}
+
+/// Roll back from the shadow links after a failure, or drop them on success
+fn finish_atomic(shadow: Shadow, failed: bool) {
+    let dirs: Vec<PathBuf> = shadow.dirs().map(PathBuf::from).collect();
+    let result = if failed {
+        shadow.restore().map(|restored| {
+            println!(
+                "{} restored {} {}; nothing was permanently removed.",
+                "Atomic:".bold(),
+                restored,
+                if restored == 1 { "entry" } else { "entries" }
+            );
+        })
+    } else {
+        shadow.discard()
+    };
+    if let Err(e) = result {
+        eprintln!(
+            "{} Failed to {} shadow links: {}. They are kept in {:?}.",
+            "Warning:".yellow(),
+            if failed { "restore from" } else { "remove" },
+            e,
+            dirs
+        );
+    }
+}
+
+/// Enqueue and delete the directories left empty under each top-level path
+/// (`--prune-empty`), reusing the now idle work queue
+fn prune_empty_dirs(paths: &[PathBuf], queue: &AdaptiveQueue, config: &RemoveConfig) {
+    let scan_done = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|| delete_worker(queue, config, &scan_done));
+        for path in paths {
+            if !std::fs::symlink_metadata(path).is_ok_and(|m| m.is_dir()) {
+                continue;
+            }
+            if let Err(e) = scan_empty_dirs(path, queue, config) {
+                eprintln!("{} {}", "Prune error:".red().bold(), e);
+                if let Some(p) = &config.progress {
+                    p.inc_path_error(path);
+                }
+            }
+        }
+        scan_done.store(true, Ordering::Release);
+    });
+}
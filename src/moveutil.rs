@@ -0,0 +1,21 @@
+//! Telling a rename that crossed file systems from other failures
+
+use std::io;
+
+/// Whether a rename failed because source and destination are on different
+/// file systems (or mounts)
+#[cfg(unix)]
+pub fn is_cross_device(e: &io::Error) -> bool {
+    e.raw_os_error() == Some(libc::EXDEV)
+}
+
+#[cfg(windows)]
+pub fn is_cross_device(e: &io::Error) -> bool {
+    // ERROR_NOT_SAME_DEVICE
+    e.raw_os_error() == Some(17)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn is_cross_device(_e: &io::Error) -> bool {
+    false
+}
@@ -0,0 +1,161 @@
+//! Naming the processes that hold a file open (Windows)
+//!
+//! Windows refuses to delete a file another process has open without
+//! delete sharing, and only says "access denied" or "being used by another
+//! process". The Restart Manager can tell which processes those are.
+
+use std::fmt;
+use std::io;
+use std::os::windows::ffi::OsStrExt;
+use std::path::Path;
+use std::ptr;
+
+use crate::errors::RemoveError;
+use windows_sys::Win32::Foundation::{
+    ERROR_ACCESS_DENIED, ERROR_LOCK_VIOLATION, ERROR_MORE_DATA, ERROR_SHARING_VIOLATION,
+    ERROR_SUCCESS, WIN32_ERROR,
+};
+use windows_sys::Win32::System::RestartManager::{
+    RmEndSession, RmGetList, RmRegisterResources, RmStartSession, CCH_RM_SESSION_KEY,
+    RM_PROCESS_INFO,
+};
+
+/// A process holding a file open
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holder {
+    pub pid: u32,
+    pub name: String,
+}
+
+impl fmt::Display for Holder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (pid {})", self.name, self.pid)
+    }
+}
+
+/// Whether a failed removal may be down to the file being open elsewhere
+pub fn may_be_open(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error().map(|code| code as WIN32_ERROR),
+        Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION | ERROR_ACCESS_DENIED)
+    )
+}
+
+/// The processes that have `path` open; empty when none does
+pub fn holders(path: &Path) -> io::Result<Vec<Holder>> {
+    let session = Session::start()?;
+    let name: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let names = [name.as_ptr()];
+    // SAFETY: `names` holds one NUL-terminated string that outlives the call
+    check(unsafe {
+        RmRegisterResources(session.0, 1, names.as_ptr(), 0, ptr::null(), 0, ptr::null())
+    })?;
+
+    let mut infos: Vec<RM_PROCESS_INFO> = Vec::new();
+    loop {
+        let mut needed = 0;
+        let mut count = infos.capacity() as u32;
+        let mut reasons = 0;
+        // SAFETY: `infos` has room for `count` entries, which RmGetList fills
+        let rc = unsafe {
+            RmGetList(
+                session.0,
+                &mut needed,
+                &mut count,
+                infos.as_mut_ptr(),
+                &mut reasons,
+            )
+        };
+        match rc {
+            ERROR_SUCCESS => {
+                // SAFETY: RmGetList initialised the first `count` entries
+                unsafe { infos.set_len(count as usize) };
+                break;
+            }
+            // Another process opened the file in between; ask again
+            ERROR_MORE_DATA => infos.reserve(needed as usize),
+            _ => return Err(io::Error::from_raw_os_error(rc as i32)),
+        }
+    }
+    Ok(infos
+        .iter()
+        .map(|info| Holder {
+            pid: info.Process.dwProcessId,
+            name: wide_str(&info.strAppName),
+        })
+        .collect())
+}
+
+/// A Restart Manager session, ended on drop
+struct Session(u32);
+
+impl Session {
+    fn start() -> io::Result<Self> {
+        let mut handle = 0;
+        let mut key = [0u16; CCH_RM_SESSION_KEY as usize + 1];
+        // SAFETY: `key` has room for the session key and its NUL
+        check(unsafe { RmStartSession(&mut handle, 0, key.as_mut_ptr()) })?;
+        Ok(Session(handle))
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // SAFETY: the handle came from RmStartSession and is ended only here
+        unsafe { RmEndSession(self.0) };
+    }
+}
+
+fn check(rc: WIN32_ERROR) -> io::Result<()> {
+    match rc {
+        ERROR_SUCCESS => Ok(()),
+        _ => Err(io::Error::from_raw_os_error(rc as i32)),
+    }
+}
+
+/// The error for a file that could not be removed because processes have
+/// it open, naming them; `None` if that is not why
+pub fn held_open_error(path: &Path, err: &io::Error) -> Option<RemoveError> {
+    if !may_be_open(err) {
+        return None;
+    }
+    let holders = holders(path).ok().filter(|holders| !holders.is_empty())?;
+    Some(RemoveError::HeldOpen(path.to_path_buf(), holders))
+}
+
+/// A NUL-terminated UTF-16 buffer as a string
+fn wide_str(buf: &[u16]) -> String {
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    String::from_utf16_lossy(&buf[..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, OpenOptions};
+    use std::os::windows::fs::OpenOptionsExt;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_holders_names_this_process() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("open.txt");
+        fs::write(&path, "x").unwrap();
+        assert!(holders(&path).unwrap().is_empty());
+
+        // Without delete sharing, the file cannot go while the handle is open
+        let _file = OpenOptions::new()
+            .read(true)
+            .share_mode(0)
+            .open(&path)
+            .unwrap();
+        let err = fs::remove_file(&path).unwrap_err();
+        assert!(may_be_open(&err), "{:?}", err);
+        let holders = holders(&path).unwrap();
+        assert!(
+            holders.iter().any(|h| h.pid == std::process::id()),
+            "{:?}",
+            holders
+        );
+    }
+}
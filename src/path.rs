@@ -1,21 +1,152 @@
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use colored::*;
 
 use crate::errors::RemoveError;
+use crate::moveutil::is_cross_device;
 
 pub fn deduplicate_and_check_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>, RemoveError> {
+    let canonical_paths = deduplicate(paths);
+    check_overlaps(&canonical_paths)?;
+    Ok(canonical_paths)
+}
+
+/// Like `deduplicate_and_check_paths`, but a path inside another given path
+/// is dropped instead of refused, as its ancestor covers it (`--assume-yes`)
+///
+/// Returns the remaining paths, and each dropped path with its ancestor.
+pub fn deduplicate_and_prune_paths(paths: &[PathBuf]) -> (Vec<PathBuf>, Vec<(PathBuf, PathBuf)>) {
+    let canonical_paths = deduplicate(paths);
+    let mut kept = Vec::new();
+    let mut pruned = Vec::new();
+    for path in &canonical_paths {
+        let ancestor = canonical_paths
+            .iter()
+            .find(|other| *other != path && path.starts_with(other));
+        match ancestor {
+            Some(ancestor) => pruned.push((path.clone(), ancestor.clone())),
+            None => kept.push(path.clone()),
+        }
+    }
+    (kept, pruned)
+}
+
+/// Refuse arguments that cannot mean what they seem to, before any of them
+/// is looked up
+///
+/// An empty string is always refused: it names no file, though some shells
+/// produce one from an unset variable. An argument whose last component is
+/// `.` or `..`, like `.`, `../..` or `build/.`, is refused unless
+/// `allow_dot`, as rm does; it then stands for the directory it names.
+/// `/`, or a drive root on Windows, is always refused, however it is
+/// spelled. `position` in the error counts from 1.
+pub fn validate_args(paths: &[PathBuf], allow_dot: bool) -> Result<(), RemoveError> {
+    for (i, path) in paths.iter().enumerate() {
+        if path.as_os_str().is_empty() {
+            return Err(RemoveError::EmptyArgument(i + 1));
+        }
+        if is_fs_root(&absolute_lexically(path, path)) {
+            return Err(RemoveError::RootArgument(path.clone()));
+        }
+        if !allow_dot && names_dot(path) {
+            return Err(RemoveError::DotArgument(path.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Whether the absolute `path` is `/`, or a drive root on Windows
+pub fn is_fs_root(path: &Path) -> bool {
+    path.has_root() && path.parent().is_none()
+}
+
+/// Whether the last component of `path`, as typed, is `.` or `..`
+///
+/// `Path::components` drops a `.` that is not leading, so `build/.` is
+/// checked on the text itself.
+fn names_dot(path: &Path) -> bool {
+    let text = path.to_string_lossy();
+    let last = text
+        .trim_end_matches(std::path::is_separator)
+        .rsplit(std::path::is_separator)
+        .next();
+    matches!(last, Some(".") | Some(".."))
+}
+
+/// Refuse a target that is the current directory `cwd` or one of its
+/// ancestors
+///
+/// Deleting it would leave the process in a removed directory, where every
+/// relative path fails. With `keep_roots` (`--contents-only`) the targets
+/// themselves stay, so only a target strictly above `cwd` is refused. `cwd`
+/// must be canonical, like the targets.
+pub fn check_cwd(
+    canonical_paths: &[PathBuf],
+    cwd: &Path,
+    keep_roots: bool,
+) -> Result<(), RemoveError> {
+    let doomed = |path: &&PathBuf| cwd.starts_with(path) && !(keep_roots && cwd == *path);
+    match canonical_paths.iter().find(doomed) {
+        Some(path) => Err(RemoveError::ContainsCwd(path.clone(), cwd.to_path_buf())),
+        None => Ok(()),
+    }
+}
+
+/// `path` made absolute against `cwd`, with `.` and `..` folded away
+/// without looking at the file system (`--no-dedup`)
+///
+/// Unlike canonicalizing, this neither resolves symlinks nor wakes an
+/// automounter, so `..` after a link is taken as the link's parent.
+pub fn absolute_lexically(path: &Path, cwd: &Path) -> PathBuf {
+    let mut absolute = PathBuf::new();
+    for component in cwd.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            component => absolute.push(component),
+        }
+    }
+    absolute
+}
+
+/// Canonicalize the paths, dropping repeats
+///
+/// A directory reached under a second name that canonicalizing does not
+/// undo, such as a bind mount, is recognised by its `(dev, inode)` and kept
+/// only under the first; scanning both would race to delete the same
+/// entries and fail on whichever went first.
+fn deduplicate(paths: &[PathBuf]) -> Vec<PathBuf> {
     let mut canonical_paths = Vec::new();
     let mut seen = HashSet::new();
+    let mut seen_dirs: HashMap<(u64, u64), PathBuf> = HashMap::new();
 
     for path in paths {
-        match path.canonicalize() {
+        match canonical_arg(path) {
             Ok(canonical) => {
-                if !seen.contains(&canonical) {
-                    seen.insert(canonical.clone());
-                    canonical_paths.push(canonical);
+                if seen.contains(&canonical) {
+                    continue;
                 }
+                if let Some(key) = dir_key(&canonical) {
+                    if let Some(first) = seen_dirs.get(&key) {
+                        eprintln!(
+                            "{} {:?} is the same directory as {:?}; deleting it once",
+                            "Note:".cyan(),
+                            canonical,
+                            first
+                        );
+                        continue;
+                    }
+                    seen_dirs.insert(key, canonical.clone());
+                }
+                seen.insert(canonical.clone());
+                canonical_paths.push(canonical);
             }
             Err(e) => {
                 eprintln!(
@@ -31,7 +162,25 @@ pub fn deduplicate_and_check_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>, Re
             }
         }
     }
+    canonical_paths
+}
 
+/// `(dev, inode)` of `path` if it is a directory, not following a symlink
+#[cfg(unix)]
+fn dir_key(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    fs::symlink_metadata(path)
+        .ok()
+        .filter(|m| m.is_dir())
+        .map(|m| (m.dev(), m.ino()))
+}
+
+#[cfg(not(unix))]
+fn dir_key(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+fn check_overlaps(canonical_paths: &[PathBuf]) -> Result<(), RemoveError> {
     for i in 0..canonical_paths.len() {
         for j in (i + 1)..canonical_paths.len() {
             let path_i = &canonical_paths[i];
@@ -50,8 +199,219 @@ pub fn deduplicate_and_check_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>, Re
             }
         }
     }
+    Ok(())
+}
 
-    Ok(canonical_paths)
+/// Canonical form of a command line path, keeping a symlink as the link
+///
+/// Only the parent is resolved when `path` is itself a symlink, so deleting
+/// it removes the link rather than whatever it points to.
+pub fn canonical_arg(path: &Path) -> io::Result<PathBuf> {
+    let is_link = fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if is_link => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            Ok(parent.canonicalize()?.join(name))
+        }
+        _ => path.canonicalize(),
+    }
+}
+
+/// Follow the symlink at `path` link by link to the first path that is not
+/// a symlink, or `None` if the chain is broken (`--max-symlink-depth`)
+///
+/// Only the last component of each hop is counted; links among the parent
+/// directories are left to the kernel. A chain of more than `max_depth`
+/// links, which includes any that loops, is a `SymlinkChainTooLong` error.
+pub fn follow_symlink_chain(path: &Path, max_depth: usize) -> Result<Option<PathBuf>, RemoveError> {
+    let mut current = path.to_path_buf();
+    let mut links = 0;
+    loop {
+        match fs::symlink_metadata(&current) {
+            Ok(metadata) if !metadata.file_type().is_symlink() => return Ok(Some(current)),
+            Ok(_) => {}
+            // Missing, or not reachable through its parents
+            Err(_) => return Ok(None),
+        }
+        if links == max_depth {
+            return Err(RemoveError::SymlinkChainTooLong(
+                path.to_path_buf(),
+                max_depth,
+            ));
+        }
+        links += 1;
+        let target =
+            fs::read_link(&current).map_err(|e| RemoveError::MetadataFailed(current.clone(), e))?;
+        // An absolute target replaces the parent when joined
+        current = match current.parent() {
+            Some(parent) => parent.join(target),
+            None => target,
+        };
+    }
+}
+
+/// Resolve a command line path that ends in a separator
+///
+/// As with rm, `link/` names the directory a symlink points to rather than
+/// the link, so the target is returned (canonicalized) and the link is left
+/// alone; see `names_link_target` for what becomes of the target. A trailing separator on anything that is not, or does not lead
+/// to, a directory is refused. Paths without one, and missing paths, are
+/// returned unchanged.
+pub fn resolve_trailing_separator(path: &Path) -> Result<PathBuf, RemoveError> {
+    if !path.to_string_lossy().ends_with(std::path::is_separator) {
+        return Ok(path.to_path_buf());
+    }
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => Ok(path.canonicalize().unwrap_or(path.to_path_buf())),
+        // The name exists, but as a file or a link to no directory
+        _ if fs::symlink_metadata(path.components().as_path()).is_ok() => {
+            Err(RemoveError::NotADirectory(path.to_path_buf()))
+        }
+        _ => Ok(path.to_path_buf()),
+    }
+}
+
+/// Whether a command line path is a symlink given as `link/`
+///
+/// rm empties the directory behind such an argument but cannot remove it
+/// through the link, so the target is kept as with `--contents-only`.
+pub fn names_link_target(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(std::path::is_separator)
+        && path.components().as_path().is_symlink()
+}
+
+/// Split the top-level arguments into those that can be examined and the
+/// failures for those that cannot, e.g. under a parent without search
+/// permission, or missing
+///
+/// Such an argument would otherwise only fail once scanned, reported like
+/// any error deep inside a tree.
+pub fn split_inaccessible(paths: Vec<PathBuf>) -> (Vec<PathBuf>, Vec<RemoveError>) {
+    let mut accessible = Vec::new();
+    let mut inaccessible = Vec::new();
+    for path in paths {
+        match fs::symlink_metadata(&path) {
+            Ok(_) => accessible.push(path),
+            Err(e) => inaccessible.push(RemoveError::ArgumentInaccessible(path, e)),
+        }
+    }
+    (accessible, inaccessible)
+}
+
+/// Whether `path` still lives under one of the canonical `roots`
+///
+/// Only the parent is canonicalized, so a symlink being deleted is judged by
+/// where it sits rather than where it points.
+pub fn is_within_roots(path: &Path, roots: &[PathBuf]) -> bool {
+    let resolved = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => match parent.canonicalize() {
+            Ok(parent) => parent.join(name),
+            Err(_) => return false,
+        },
+        _ => return false,
+    };
+    roots.iter().any(|root| resolved.starts_with(root))
+}
+
+/// Prefix of the sibling name a directory is moved to with `--rename-first`
+pub const STAGING_PREFIX: &str = ".fastrm-deleting-";
+
+/// Move `path` to a unique hidden sibling so observers never see it half-emptied
+///
+/// Renames never cross devices, so this fails (rather than copies) when the
+/// parent is on another file system or not writable; callers fall back to
+/// deleting in place.
+pub fn rename_for_deletion(path: &Path) -> io::Result<PathBuf> {
+    let parent = path
+        .parent()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no parent"))?;
+    let staged = parent.join(staging_name());
+    fs::rename(path, &staged)?;
+    Ok(staged)
+}
+
+/// Move every child of directory `dir` into a new hidden subdirectory of it
+/// (`--rename-children`), so `dir` looks empty long before the children
+/// are deleted
+///
+/// Returns the staging directory and the children that could not be
+/// renamed; those stay where they are and are deleted in place. Only
+/// failing to list `dir` or create the staging directory is an error.
+pub fn stage_children(dir: &Path) -> io::Result<(PathBuf, Vec<(PathBuf, io::Error)>)> {
+    let staging = dir.join(staging_name());
+    let entries = fs::read_dir(dir)?;
+    fs::create_dir(&staging)?;
+    let mut failed = Vec::new();
+    for entry in entries {
+        let child = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                failed.push((dir.to_path_buf(), e));
+                continue;
+            }
+        };
+        let Some(name) = child.file_name() else {
+            continue;
+        };
+        if child == staging {
+            continue;
+        }
+        if let Err(e) = fs::rename(&child, staging.join(name)) {
+            failed.push((child, e));
+        }
+    }
+    Ok((staging, failed))
+}
+
+/// Move `path` into `stage_dir` under a unique name (`--stage-dir`), so it
+/// vanishes from its original location before it is emptied
+///
+/// The staging directory is created if needed. Only a rename will do: a
+/// staging directory on another file system, or on another mount of the same
+/// one (bind mounts refuse renames too), is an error, and callers delete in
+/// place with the deleter pool rather than copy the tree only to delete it.
+pub fn stage_for_deletion(path: &Path, stage_dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(stage_dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if fs::symlink_metadata(path)?.dev() != fs::metadata(stage_dir)?.dev() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the staging directory is on another file system",
+            ));
+        }
+    }
+    let staged = stage_dir.join(staging_name());
+    match fs::rename(path, &staged) {
+        Ok(()) => Ok(staged),
+        Err(e) if is_cross_device(&e) => Err(io::Error::new(
+            e.kind(),
+            "the staging directory is on another mount, which cannot be renamed into",
+        )),
+        Err(e) => Err(e),
+    }
+}
+
+/// A name no other staged path uses: process id, time and a counter
+fn staging_name() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!(
+        "{}{}-{}-{}",
+        STAGING_PREFIX,
+        std::process::id(),
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
 }
 
 #[cfg(test)]
@@ -83,4 +443,287 @@ mod tests {
         let err = result.unwrap_err();
         assert!(matches!(err, RemoveError::PathOverlap(_)));
     }
+
+    #[test]
+    fn test_overlaps_pruned() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        let parent = root.join("parent");
+        let child = parent.join("child");
+        let other = root.join("other");
+        std::fs::create_dir_all(&child).unwrap();
+        std::fs::create_dir(&other).unwrap();
+
+        let (kept, pruned) =
+            deduplicate_and_prune_paths(&[child.join("."), other.clone(), parent.clone()]);
+        assert_eq!(kept, vec![other, parent.clone()]);
+        assert_eq!(pruned, vec![(child, parent)]);
+    }
+
+    #[test]
+    fn test_target_containing_cwd_is_refused() {
+        let root = Path::new("/srv/data");
+        let paths = [PathBuf::from("/srv/other"), root.to_path_buf()];
+        assert!(check_cwd(&paths, Path::new("/srv"), false).is_ok());
+        assert!(check_cwd(&paths, Path::new("/srv/database"), false).is_ok());
+        for cwd in ["/srv/data", "/srv/data/logs/2020"] {
+            let err = check_cwd(&paths, Path::new(cwd), false).unwrap_err();
+            assert!(matches!(err, RemoveError::ContainsCwd(ref p, _) if p == root));
+        }
+        // --contents-only keeps /srv/data itself, but not what is inside it
+        assert!(check_cwd(&paths, Path::new("/srv/data"), true).is_ok());
+        assert!(check_cwd(&paths, Path::new("/srv/data/logs"), true).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_absolute_lexically_folds_dots() {
+        let cwd = Path::new("/srv/data");
+        assert_eq!(absolute_lexically(Path::new("."), cwd), cwd);
+        assert_eq!(
+            absolute_lexically(Path::new("logs/./2020/"), cwd),
+            cwd.join("logs/2020")
+        );
+        assert_eq!(absolute_lexically(Path::new("../.."), cwd), Path::new("/"));
+        assert_eq!(
+            absolute_lexically(Path::new("/../../tmp"), cwd),
+            Path::new("/tmp")
+        );
+    }
+
+    #[test]
+    fn test_empty_and_dot_arguments_are_refused() {
+        let args = |list: &[&str]| list.iter().map(PathBuf::from).collect::<Vec<_>>();
+        let err = validate_args(&args(&["build", ""]), true).unwrap_err();
+        assert!(matches!(err, RemoveError::EmptyArgument(2)));
+
+        for dot in [".", "..", "./", "../..", "build/.", "build/../", "/a/b/.."] {
+            let err = validate_args(&args(&[dot]), false).unwrap_err();
+            assert!(matches!(err, RemoveError::DotArgument(ref p) if p == Path::new(dot)));
+            assert!(validate_args(&args(&[dot]), true).is_ok());
+        }
+        for name in ["./build", "../build", ".hidden", "...", "/build"] {
+            assert!(validate_args(&args(&[name]), false).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_root_is_refused() {
+        let args = |list: &[&str]| list.iter().map(PathBuf::from).collect::<Vec<_>>();
+        for root in ["/", "//", "/.", "/..", "/tmp/..", "/tmp/../.."] {
+            let err = validate_args(&args(&["build", root]), true).unwrap_err();
+            assert!(matches!(err, RemoveError::RootArgument(ref p) if p == Path::new(root)));
+        }
+        assert!(is_fs_root(Path::new("/")));
+        assert!(!is_fs_root(Path::new("/tmp")));
+        #[cfg(windows)]
+        {
+            assert!(validate_args(&args(&["C:\\"]), true).is_err());
+            assert!(validate_args(&args(&["C:\\build"]), true).is_ok());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_argument_is_kept_as_link() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        let target = root.join("target");
+        let link = root.join("link");
+        std::fs::create_dir(&target).unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        // The link and its target are distinct arguments, not duplicates
+        let result = deduplicate_and_check_paths(&[link.clone(), target.clone()]).unwrap();
+        assert_eq!(result, vec![link, target]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_directory_behind_two_links_is_kept_once() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        let target = root.join("target");
+        fs::create_dir(&target).unwrap();
+        symlink(&target, root.join("link1")).unwrap();
+        symlink("target", root.join("link2")).unwrap();
+
+        // As main resolves `link1/` and `link2/`
+        let args: Vec<PathBuf> = ["link1/", "link2/"]
+            .iter()
+            .map(|name| resolve_trailing_separator(&root.join(name)).unwrap())
+            .collect();
+        assert_eq!(
+            deduplicate_and_check_paths(&args).unwrap(),
+            vec![target.clone()]
+        );
+
+        // Any name of the directory has the same key; a link has none
+        assert_eq!(dir_key(&target), dir_key(&root.join("link1/.")));
+        assert_eq!(dir_key(&root.join("link1")), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_directory_behind_bind_mount_is_kept_once() {
+        use std::process::Command;
+
+        /// Unmounts on drop, so a failed assertion leaves no mount behind
+        struct Unmount<'a>(&'a Path);
+        impl Drop for Unmount<'_> {
+            fn drop(&mut self) {
+                let _ = Command::new("umount").arg(self.0).status();
+            }
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        let (target, mount) = (root.join("target"), root.join("mount"));
+        fs::create_dir(&target).unwrap();
+        fs::create_dir(&mount).unwrap();
+        // Two canonical paths to one directory, which only its (dev, inode)
+        // tells apart; mounting needs privileges the test may not have
+        let mounted = Command::new("mount")
+            .arg("--bind")
+            .arg(&target)
+            .arg(&mount)
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if !mounted {
+            eprintln!("skipping: cannot bind-mount here");
+            return;
+        }
+        let _unmount = Unmount(&mount);
+
+        let args = vec![target.clone(), mount.clone()];
+        assert_eq!(deduplicate_and_check_paths(&args).unwrap(), vec![target]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_trailing_separator_names_the_link_target() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        let target = root.join("target");
+        fs::create_dir(&target).unwrap();
+        File::create(root.join("file.txt")).unwrap();
+        symlink(&target, root.join("link")).unwrap();
+        symlink(root.join("file.txt"), root.join("file_link")).unwrap();
+        symlink(root.join("missing"), root.join("broken")).unwrap();
+
+        let arg = |name: &str| resolve_trailing_separator(&root.join(name));
+        assert_eq!(arg("link/").unwrap(), target);
+        assert!(names_link_target(&root.join("link/")));
+        assert!(!names_link_target(&root.join("link")));
+        assert!(!names_link_target(&root.join("target/")));
+        assert_eq!(arg("link").unwrap(), root.join("link"));
+        assert_eq!(arg("target/").unwrap(), target);
+        for name in ["file.txt/", "file_link/", "broken/"] {
+            assert!(
+                matches!(arg(name), Err(RemoveError::NotADirectory(_))),
+                "{}",
+                name
+            );
+        }
+        // Left for the usual missing-path handling
+        assert_eq!(arg("missing/").unwrap(), root.join("missing/"));
+    }
+
+    #[test]
+    fn test_rename_for_deletion_hides_original_name() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("target");
+        fs::create_dir(&target).unwrap();
+        File::create(target.join("file.txt")).unwrap();
+
+        let staged = rename_for_deletion(&target).unwrap();
+
+        // The original name is gone before any contents are removed
+        assert!(!target.exists());
+        assert!(staged.join("file.txt").exists());
+        assert_eq!(staged.parent(), Some(temp_dir.path()));
+        assert!(staged
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with(STAGING_PREFIX));
+    }
+
+    #[test]
+    fn test_stage_children_empties_the_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("target");
+        fs::create_dir_all(target.join("sub")).unwrap();
+        File::create(target.join("file.txt")).unwrap();
+        File::create(target.join("sub/inner.txt")).unwrap();
+
+        let (staging, failed) = stage_children(&target).unwrap();
+        assert!(failed.is_empty(), "{:?}", failed);
+        let names: Vec<_> = fs::read_dir(&target)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(names, std::slice::from_ref(&staging));
+        assert!(staging.join("file.txt").exists());
+        assert!(staging.join("sub/inner.txt").exists());
+    }
+
+    #[test]
+    fn test_stage_for_deletion_moves_into_stage_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("target");
+        let stage = temp_dir.path().join("stage/nested");
+        fs::create_dir(&target).unwrap();
+        File::create(target.join("file.txt")).unwrap();
+        File::create(temp_dir.path().join("single.txt")).unwrap();
+
+        let staged = stage_for_deletion(&target, &stage).unwrap();
+        assert!(!target.exists());
+        assert_eq!(staged.parent(), Some(stage.as_path()));
+        assert!(staged.join("file.txt").exists());
+
+        // Files are staged too, each under its own name
+        let single = stage_for_deletion(&temp_dir.path().join("single.txt"), &stage).unwrap();
+        assert_ne!(single, staged);
+        assert!(single.is_file());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_stage_dir_on_other_file_system_is_refused() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("target");
+        fs::create_dir(&target).unwrap();
+        // /proc is never the file system temporary files live on
+        let err = stage_for_deletion(&target, Path::new("/proc/self")).unwrap_err();
+        assert!(err.to_string().contains("another file system"), "{}", err);
+        assert!(target.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_within_roots() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let outside = temp_dir.path().join("outside");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::create_dir(&outside).unwrap();
+        symlink(&outside, root.join("escape")).unwrap();
+        let roots = [root.canonicalize().unwrap()];
+
+        assert!(is_within_roots(&root, &roots));
+        assert!(is_within_roots(&root.join("file.txt"), &roots));
+        // The link itself sits inside the root...
+        assert!(is_within_roots(&root.join("escape"), &roots));
+        // ...but entries reached through it do not
+        assert!(!is_within_roots(&root.join("escape/file.txt"), &roots));
+        assert!(!is_within_roots(&outside.join("file.txt"), &roots));
+    }
 }
@@ -0,0 +1,325 @@
+//! Deletion plans: decide in one run, delete in another (`--plan-out`,
+//! `--plan-in`)
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::config::RemoveConfig;
+use crate::errors::RemoveError;
+use crate::journal::{path_bytes, path_from_bytes};
+use crate::queue::{AdaptiveQueue, FileJob};
+
+/// First line of every plan
+const HEADER: &str = "# fast-rm deletion plan";
+
+/// Ordered list of the operations a dry run would perform (`--plan-out`)
+///
+/// One operation per line, in the order the scanner queued them, so every
+/// directory comes after everything that was in it:
+///
+/// ```text
+/// file 4096 /data/logs/a.log
+/// symlink /data/current
+/// dir /data/logs
+/// ```
+///
+/// Paths are the rest of the line. Backslashes, control characters and
+/// bytes that are not UTF-8 are written as `\\` and `\xNN`, so any path
+/// survives the round trip and the plan stays readable for review.
+#[derive(Debug)]
+pub struct PlanWriter {
+    path: PathBuf,
+    writer: Mutex<BufWriter<File>>,
+    operations: AtomicU64,
+}
+
+impl PlanWriter {
+    /// Create (or truncate) the plan file
+    pub fn create(path: &Path) -> Result<Self, RemoveError> {
+        let fail = |e| RemoveError::PlanFailed(path.to_path_buf(), e);
+        let mut writer = BufWriter::new(File::create(path).map_err(fail)?);
+        writeln!(writer, "{}", HEADER).map_err(fail)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: Mutex::new(writer),
+            operations: AtomicU64::new(0),
+        })
+    }
+
+    /// Append one operation
+    pub fn record(&self, job: &FileJob) -> Result<(), RemoveError> {
+        let line = format_job(job);
+        self.writer
+            .lock()
+            .unwrap()
+            .write_all(line.as_bytes())
+            .map_err(|e| RemoveError::PlanFailed(self.path.clone(), e))?;
+        self.operations.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Flush the plan to disk; returns how many operations it holds
+    pub fn finish(&self) -> Result<u64, RemoveError> {
+        let mut writer = self.writer.lock().unwrap();
+        writer
+            .flush()
+            .and_then(|()| writer.get_ref().sync_data())
+            .map_err(|e| RemoveError::PlanFailed(self.path.clone(), e))?;
+        Ok(self.operations.load(Ordering::Relaxed))
+    }
+}
+
+/// Read a plan written by `--plan-out`, in its original order
+pub fn load(path: &Path) -> Result<Vec<FileJob>, RemoveError> {
+    let file = File::open(path).map_err(|e| RemoveError::PlanFailed(path.to_path_buf(), e))?;
+    let mut jobs = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| RemoveError::PlanFailed(path.to_path_buf(), e))?;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let job = parse_job(&line)
+            .map_err(|reason| RemoveError::PlanInvalid(path.to_path_buf(), index + 1, reason))?;
+        jobs.push(job);
+    }
+    Ok(jobs)
+}
+
+/// The top-level entries of a plan: those not inside one of its directories
+///
+/// They stand for the paths a command line would have named, and get the
+/// same checks before anything is deleted.
+pub fn roots(jobs: &[FileJob]) -> Vec<PathBuf> {
+    let dirs: HashSet<&Path> = jobs
+        .iter()
+        .filter(|job| matches!(job, FileJob::EmptyDir(_)))
+        .map(FileJob::path)
+        .collect();
+    jobs.iter()
+        .map(FileJob::path)
+        .filter(|path| !path.parent().is_some_and(|parent| dirs.contains(parent)))
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+/// Queue the plan's jobs for the deleters (`--plan-in`)
+///
+/// Nothing is scanned: the deleters check that each path is still the kind
+/// of entry the plan names before removing it, and report the ones that are
+/// gone.
+pub fn enqueue_plan(
+    jobs: Vec<FileJob>,
+    queue: &AdaptiveQueue,
+    config: &RemoveConfig,
+) -> Result<(), RemoveError> {
+    for job in jobs {
+        if config.is_cancelled() {
+            break;
+        }
+        if let Some(p) = &config.progress {
+            p.inc_scanned();
+        }
+        config.log_would_delete(job.path());
+        queue.send(job).map_err(|_| RemoveError::QueueFull)?;
+        if let Some(p) = &config.progress {
+            p.record_queue_depth(queue.depth());
+        }
+    }
+    Ok(())
+}
+
+/// One plan line for `job`, newline included
+pub fn format_job(job: &FileJob) -> String {
+    let (kind, path) = match job {
+        FileJob::File { path, size } => (format!("file {}", size), path),
+        FileJob::Symlink(path) => ("symlink".to_string(), path),
+        FileJob::EmptyDir(path) => ("dir".to_string(), path),
+    };
+    format!("{} {}\n", kind, escape(&path_bytes(path)))
+}
+
+/// Parse one plan line, without its newline
+pub fn parse_job(line: &str) -> Result<FileJob, String> {
+    let (kind, rest) = line
+        .split_once(' ')
+        .ok_or("expected an operation and a path")?;
+    let (size, path) = match kind {
+        "file" => {
+            let (size, path) = rest.split_once(' ').ok_or("expected a size and a path")?;
+            let size = size
+                .parse()
+                .map_err(|_| format!("invalid size '{}'", size))?;
+            (Some(size), path)
+        }
+        "symlink" | "dir" => (None, rest),
+        _ => return Err(format!("unknown operation '{}'", kind)),
+    };
+    if path.is_empty() {
+        return Err("missing path".to_string());
+    }
+    let path: Arc<Path> = Arc::from(path_from_bytes(&unescape(path)?).as_path());
+    Ok(match (kind, size) {
+        ("file", Some(size)) => FileJob::File { path, size },
+        ("symlink", _) => FileJob::Symlink(path),
+        _ => FileJob::EmptyDir(path),
+    })
+}
+
+/// Escape a path's bytes for a plan line
+fn escape(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    let mut rest = bytes;
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(text) => {
+                escape_str(&mut out, text);
+                return out;
+            }
+            Err(e) => {
+                let (valid, invalid) = rest.split_at(e.valid_up_to());
+                escape_str(&mut out, std::str::from_utf8(valid).expect("checked above"));
+                let bad = e.error_len().unwrap_or(invalid.len());
+                for byte in &invalid[..bad] {
+                    let _ = write!(out, "\\x{:02x}", byte);
+                }
+                rest = &invalid[bad..];
+            }
+        }
+    }
+}
+
+fn escape_str(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            c if c.is_ascii_control() => {
+                let _ = write!(out, "\\x{:02x}", c as u8);
+            }
+            c => out.push(c),
+        }
+    }
+}
+
+fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        match bytes.get(i + 1) {
+            Some(b'\\') => {
+                out.push(b'\\');
+                i += 2;
+            }
+            Some(b'x') => {
+                let byte = text
+                    .get(i + 2..i + 4)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| format!("invalid escape at column {}", i + 1))?;
+                out.push(byte);
+                i += 4;
+            }
+            _ => return Err(format!("invalid escape at column {}", i + 1)),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_plan_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("plan.txt");
+        let jobs = [
+            FileJob::File {
+                path: Arc::from(Path::new("/data/a b.txt")),
+                size: 4096,
+            },
+            FileJob::File {
+                path: Arc::from(Path::new("/data/with\nnewline\\and backslash")),
+                size: 0,
+            },
+            FileJob::Symlink(Arc::from(Path::new("/data/link"))),
+            FileJob::EmptyDir(Arc::from(Path::new("/data"))),
+        ];
+        let plan = PlanWriter::create(&path).unwrap();
+        for job in &jobs {
+            plan.record(job).unwrap();
+        }
+        assert_eq!(plan.finish().unwrap(), 4);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with(HEADER));
+        assert!(text.contains("file 4096 /data/a b.txt\n"));
+        assert!(text.contains("/data/with\\x0anewline\\\\and backslash\n"));
+        assert!(text.ends_with("dir /data\n"));
+
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.len(), jobs.len());
+        for (loaded, job) in loaded.iter().zip(&jobs) {
+            assert_eq!(format_job(loaded), format_job(job));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path_round_trip() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"/data/caf\xe9/\xc3\xa9t\xc3"));
+        let line = format_job(&FileJob::Symlink(Arc::from(path)));
+        assert_eq!(line, "symlink /data/caf\\xe9/\u{e9}t\\xc3\n");
+        let parsed = parse_job(line.trim_end_matches('\n')).unwrap();
+        assert_eq!(parsed.path(), path);
+    }
+
+    #[test]
+    fn test_plan_roots() {
+        let dir = |path: &str| FileJob::EmptyDir(Arc::from(Path::new(path)));
+        let jobs = [
+            FileJob::File {
+                path: Arc::from(Path::new("/data/logs/a.log")),
+                size: 1,
+            },
+            dir("/data/logs"),
+            FileJob::Symlink(Arc::from(Path::new("/data/link"))),
+            dir("/data"),
+            FileJob::File {
+                path: Arc::from(Path::new("/srv/single.txt")),
+                size: 1,
+            },
+        ];
+        assert_eq!(
+            roots(&jobs),
+            [PathBuf::from("/data"), PathBuf::from("/srv/single.txt")]
+        );
+    }
+
+    #[test]
+    fn test_invalid_plan_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("plan.txt");
+        std::fs::write(&path, "# comment\n\ndir /data\nfile big /data/a\n").unwrap();
+        let err = load(&path).unwrap_err().to_string();
+        assert!(err.contains("line 4: invalid size 'big'"), "{}", err);
+
+        assert!(parse_job("remove /data").is_err());
+        assert!(parse_job("dir").is_err());
+        assert!(parse_job("dir /data/\\q").is_err());
+        assert!(parse_job("dir /data/\\x4").is_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! Deleters started on demand (`--delete-threads auto`)
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::queue::AdaptiveQueue;
+
+/// How often the manager looks at the queue
+pub const SAMPLE_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Deleters an elastic pool starts with and never shrinks below
+pub const MIN_DELETERS: usize = 2;
+
+/// How long a deleter above the minimum waits for work before exiting
+pub const KEEP_ALIVE: Duration = Duration::from_millis(500);
+
+/// Queued jobs per running deleter above which the pool is short-handed
+const JOBS_PER_DELETER: usize = 64;
+
+/// Consecutive short-handed samples before another deleter is started
+const STREAK: usize = 2;
+
+/// `--delete-threads`: a fixed count, or `auto` for an elastic pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteThreads {
+    Auto,
+    Fixed(usize),
+}
+
+/// Parse a `--delete-threads` value: a number or `auto`
+pub fn parse_delete_threads(s: &str) -> Result<DeleteThreads, String> {
+    if s.eq_ignore_ascii_case("auto") {
+        return Ok(DeleteThreads::Auto);
+    }
+    s.parse()
+        .map(DeleteThreads::Fixed)
+        .map_err(|_| format!("invalid thread count {:?}: expected a number or auto", s))
+}
+
+/// Bookkeeping for a deleter pool that grows with the queue
+///
+/// Small deletions finish on the first few deleters without paying for
+/// threads they would not use. While the queue stays deeper than the
+/// running deleters can soon drain, `run` starts another, up to `max`; one
+/// that then waits `KEEP_ALIVE` without work exits, down to `min`.
+#[derive(Debug)]
+pub struct ElasticPool {
+    min: usize,
+    max: usize,
+    live: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl ElasticPool {
+    pub fn new(min: usize, max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            min: min.clamp(1, max),
+            max,
+            live: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    /// Deleters running now
+    pub fn live(&self) -> usize {
+        self.live.load(Ordering::Acquire)
+    }
+
+    /// Most deleters that ran at once
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// Count one more deleter, unless the pool is at `max`
+    fn try_start(&self) -> bool {
+        let started = self
+            .live
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |live| {
+                (live < self.max).then_some(live + 1)
+            });
+        if let Ok(live) = started {
+            self.peak.fetch_max(live + 1, Ordering::Relaxed);
+        }
+        started.is_ok()
+    }
+
+    /// Let an idle deleter go, unless the pool is at `min`; the deleter must
+    /// exit if this returns `true`
+    pub fn try_retire(&self) -> bool {
+        self.live
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |live| {
+                (live > self.min).then(|| live - 1)
+            })
+            .is_ok()
+    }
+
+    /// A deleter exits because the work is done
+    pub fn finished(&self) {
+        self.live.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Start `min` deleters with `spawn`, then another whenever the queue stays
+/// deep, until every deleter has exited; returns once they are joined
+///
+/// `spawn` is given the deleter's index and must run a worker that reports
+/// to `pool` (`deleter::elastic_delete_worker`).
+pub fn run(
+    pool: &ElasticPool,
+    queue: &AdaptiveQueue,
+    mut spawn: impl FnMut(usize) -> JoinHandle<()>,
+) {
+    let mut deleters = Vec::new();
+    while deleters.len() < pool.min && pool.try_start() {
+        deleters.push(spawn(deleters.len()));
+    }
+    let mut streak = 0;
+    while pool.live() > 0 {
+        thread::sleep(SAMPLE_INTERVAL);
+        if queue.depth() > pool.live() * JOBS_PER_DELETER {
+            streak += 1;
+        } else {
+            streak = 0;
+        }
+        if streak >= STREAK && pool.try_start() {
+            deleters.push(spawn(deleters.len()));
+            streak = 0;
+        }
+    }
+    for deleter in deleters {
+        deleter.join().expect("Deleter thread panicked");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RemoveConfig;
+    use crate::deleter::elastic_delete_worker;
+    use crate::queue::FileJob;
+    use std::fs;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    /// Delete `files` files through an elastic pool of up to 8 deleters and
+    /// return how many ran at once
+    fn peak_for(files: usize) -> usize {
+        let temp_dir = TempDir::new().unwrap();
+        let queue = Arc::new(AdaptiveQueue::unbounded());
+        for i in 0..files {
+            let path = temp_dir.path().join(format!("f{}", i));
+            fs::write(&path, "").unwrap();
+            queue
+                .send(FileJob::File {
+                    path: Arc::from(path.as_path()),
+                    size: 0,
+                })
+                .unwrap();
+        }
+
+        let config = RemoveConfig::default();
+        let scanners_done = Arc::new(AtomicBool::new(true));
+        let pool = Arc::new(ElasticPool::new(MIN_DELETERS, 8));
+        run(&pool, &queue, |_| {
+            let (queue, config, done, pool) = (
+                queue.clone(),
+                config.clone(),
+                scanners_done.clone(),
+                pool.clone(),
+            );
+            thread::spawn(move || elastic_delete_worker(&queue, &config, &done, &pool))
+        });
+
+        assert_eq!(pool.live(), 0);
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+        pool.peak()
+    }
+
+    #[test]
+    fn test_pool_grows_only_for_large_deletions() {
+        assert_eq!(peak_for(10), MIN_DELETERS);
+        assert!(peak_for(30_000) > MIN_DELETERS);
+    }
+
+    #[test]
+    fn test_pool_stays_within_bounds() {
+        let pool = ElasticPool::new(2, 3);
+        assert!(!pool.try_retire());
+        assert!(pool.try_start() && pool.try_start() && pool.try_start());
+        assert!(!pool.try_start());
+        assert!(pool.try_retire());
+        assert!(!pool.try_retire());
+        pool.finished();
+        pool.finished();
+        assert_eq!((pool.live(), pool.peak()), (0, 3));
+    }
+
+    #[test]
+    fn test_parse_delete_threads() {
+        assert_eq!(parse_delete_threads("auto"), Ok(DeleteThreads::Auto));
+        assert_eq!(parse_delete_threads("6"), Ok(DeleteThreads::Fixed(6)));
+        assert!(parse_delete_threads("lots").is_err());
+    }
+}
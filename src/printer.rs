@@ -0,0 +1,159 @@
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How long the printer waits for more paths before flushing what it has
+const FLUSH_IDLE: Duration = Duration::from_millis(50);
+
+/// Writes every deleted path to stdout (`--print-deleted`, `--print0`)
+///
+/// Paths are handed over through an unbounded channel so none is dropped,
+/// and written on a dedicated thread, each followed by `\n` or, with
+/// `--print0`, a NUL byte. If the output fails (say the reader went away)
+/// printing stops but deletion carries on.
+pub struct DeletedPrinter {
+    tx: Sender<Arc<Path>>,
+    done: Arc<AtomicBool>,
+    thread: JoinHandle<io::Result<()>>,
+}
+
+impl DeletedPrinter {
+    pub fn spawn(out: Box<dyn Write + Send>, nul_terminated: bool) -> Self {
+        let (tx, rx) = unbounded();
+        let done = Arc::new(AtomicBool::new(false));
+        let terminator = if nul_terminated { b'\0' } else { b'\n' };
+        let thread = {
+            let done = done.clone();
+            thread::Builder::new()
+                .name("print-deleted".to_string())
+                .spawn(move || print_paths(BufWriter::new(out), &rx, &done, terminator))
+                .expect("Failed to spawn printer thread")
+        };
+        Self { tx, done, thread }
+    }
+
+    /// Sender for `RemoveProgress` to report deleted paths on
+    pub fn sender(&self) -> Sender<Arc<Path>> {
+        self.tx.clone()
+    }
+
+    /// Print what is still queued and flush; call once nothing is deleted anymore
+    pub fn finish(self) -> io::Result<()> {
+        self.done.store(true, Ordering::Release);
+        self.thread.join().unwrap_or(Ok(()))
+    }
+}
+
+fn print_paths(
+    mut out: impl Write,
+    rx: &Receiver<Arc<Path>>,
+    done: &AtomicBool,
+    terminator: u8,
+) -> io::Result<()> {
+    loop {
+        match rx.recv_timeout(FLUSH_IDLE) {
+            Ok(path) => {
+                write_path(&mut out, &path)?;
+                out.write_all(&[terminator])?;
+            }
+            // Let a reader downstream see progress during lulls
+            Err(RecvTimeoutError::Timeout) if !done.load(Ordering::Acquire) => out.flush()?,
+            Err(_) => break,
+        }
+    }
+    for path in rx.try_iter() {
+        write_path(&mut out, &path)?;
+        out.write_all(&[terminator])?;
+    }
+    out.flush()
+}
+
+/// Write the path as raw bytes where the platform allows, so names that are
+/// not UTF-8 come out unchanged
+#[cfg(unix)]
+fn write_path(out: &mut impl Write, path: &Path) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    out.write_all(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn write_path(out: &mut impl Write, path: &Path) -> io::Result<()> {
+    out.write_all(path.to_string_lossy().as_bytes())
+}
+
+/// Claim stdout for the path stream
+///
+/// On Unix the original stdout is duplicated for the printer and descriptor
+/// 1 is pointed at stderr, so every other message of the run lands on stderr
+/// and the stream stays clean. Elsewhere the messages share stdout.
+#[cfg(unix)]
+pub fn claim_stdout() -> io::Result<Box<dyn Write + Send>> {
+    use std::fs::File;
+    use std::os::unix::io::FromRawFd;
+
+    io::stdout().flush()?;
+    // SAFETY: plain descriptor calls; the duplicate is owned by the File
+    unsafe {
+        let fd = libc::dup(libc::STDOUT_FILENO);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            let error = io::Error::last_os_error();
+            libc::close(fd);
+            return Err(error);
+        }
+        Ok(Box::new(File::from_raw_fd(fd)))
+    }
+}
+
+#[cfg(not(unix))]
+pub fn claim_stdout() -> io::Result<Box<dyn Write + Send>> {
+    Ok(Box::new(io::stdout()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Collects what the printer writes
+    #[derive(Clone, Default)]
+    struct Sink(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_printer_writes_every_path_nul_terminated() {
+        let sink = Sink::default();
+        let printer = DeletedPrinter::spawn(Box::new(sink.clone()), true);
+        let tx = printer.sender();
+        for i in 0..2000 {
+            tx.send(Arc::from(Path::new(&format!("dir/file {}", i))))
+                .unwrap();
+        }
+        // Sent right before finishing, still printed
+        tx.send(Arc::from(Path::new("dir"))).unwrap();
+        printer.finish().unwrap();
+
+        let out = sink.0.lock().unwrap();
+        let paths: Vec<_> = out.split(|&b| b == 0).collect();
+        // Every path is terminated, so the last split is empty
+        assert_eq!(paths.len(), 2002);
+        assert_eq!(paths[0], b"dir/file 0");
+        assert_eq!(paths[2000], b"dir");
+        assert!(paths[2001].is_empty());
+    }
+}
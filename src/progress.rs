@@ -1,11 +1,20 @@
+use colored::*;
 use crossbeam_channel::{bounded, Receiver, Sender};
+use hdrhistogram::Histogram;
+use indicatif::style::TemplateError;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use std::path::Path;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crate::config::Verbosity;
+use crate::errors::ErrorCategory;
+use crate::hook::ErrorEvent;
+use crate::human::{format_bytes, format_count, format_duration, format_rate};
 
 pub struct RemoveProgress {
     pub scanned: AtomicUsize,
@@ -14,19 +23,310 @@ pub struct RemoveProgress {
     _pad2: [u8; 56],
     pub errors: AtomicUsize,
     _pad3: [u8; 56],
+    /// High-water mark of the work queue depth, sampled by scanners on enqueue
+    pub peak_queue_depth: AtomicUsize,
+    /// Entries the scanner decided not to delete
+    pub skipped: AtomicUsize,
+    /// `skipped` broken down by `SkipReason`
+    skipped_by: [AtomicUsize; SkipReason::ALL.len()],
+    /// Directories kept because some of their contents were skipped
+    pub kept_dirs: AtomicUsize,
+    /// Entries counted up front by `--pre-scan`; 0 when not known
+    pub total: AtomicUsize,
+    /// Scanner threads currently working through a directory
+    pub busy_scanners: AtomicUsize,
+    /// Bytes of regular files deleted (or that would be, in a dry run)
+    pub bytes: AtomicU64,
+    /// Warnings raised while the display is live, printed with the summary
+    warnings: Mutex<Vec<String>>,
     recent_tx: Sender<Arc<Path>>,
-    pub recent_rx: Receiver<Arc<Path>>,
+    recent_rx: Receiver<Arc<Path>>,
     error_tx: Sender<(Arc<Path>, String)>,
-    pub error_rx: Receiver<(Arc<Path>, String)>,
+    error_rx: Receiver<(Arc<Path>, String)>,
+    /// Taken out by `close_error_hook` so the hook thread can finish
+    error_hook: Mutex<Option<Sender<ErrorEvent>>>,
+    deleted_feed: Option<Sender<Arc<Path>>>,
+    /// Errors counted per `ErrorCategory`, with the first few paths of each
+    error_groups: Mutex<[(usize, Vec<Arc<Path>>); ErrorCategory::ALL.len()]>,
+    // Channels are drained only into these caches, so every reader sees the same items
+    recent_cache: Mutex<VecDeque<Arc<Path>>>,
+    error_cache: Mutex<VecDeque<(Arc<Path>, String)>>,
+    path_tally: Option<PathTally>,
+    latency: Option<Mutex<LatencyStats>>,
+    dir_profile: Option<DirProfiler>,
+    /// Deleted files and bytes per lowercased extension (`--stats-by-ext`)
+    ext_tally: Option<Mutex<HashMap<String, (u64, u64)>>>,
     start_time: Instant,
 }
 
+/// Why the scanner left an entry in place
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Matched a `.fastrmignore` file
+    Ignored,
+    /// Hidden entry under `--skip-hidden`
+    Hidden,
+    /// Version control metadata under `--exclude-vcs`
+    Vcs,
+    /// Outside `--match-prefix`, not listed in `--files-from` or not
+    /// matching `--expr`
+    Excluded,
+    /// Modified more recently than `--older-than`
+    NewerThan,
+    /// Directory that could not be listed (permission denied, `-c` only)
+    Unreadable,
+    /// FIFO, socket, device node or mount point under `--no-special`
+    Special,
+    /// Listed in `--keep-list` or named like a `--keep` glob
+    Kept,
+    /// Not of a type selected by `--only-type`
+    OtherType,
+    /// Symlink with an existing target under `--broken-symlinks-only`
+    ValidLink,
+    /// Already removed under a repeated argument (`--no-dedup`)
+    Gone,
+    /// Held open by another process under `--skip-open` (Windows)
+    Open,
+}
+
+impl SkipReason {
+    pub const ALL: [SkipReason; 12] = [
+        SkipReason::Ignored,
+        SkipReason::Hidden,
+        SkipReason::Vcs,
+        SkipReason::Excluded,
+        SkipReason::NewerThan,
+        SkipReason::Unreadable,
+        SkipReason::Special,
+        SkipReason::Kept,
+        SkipReason::OtherType,
+        SkipReason::ValidLink,
+        SkipReason::Gone,
+        SkipReason::Open,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SkipReason::Ignored => "ignored",
+            SkipReason::Hidden => "hidden",
+            SkipReason::Vcs => "vcs",
+            SkipReason::Excluded => "excluded",
+            SkipReason::NewerThan => "newer-than",
+            SkipReason::Unreadable => "unreadable",
+            SkipReason::Special => "special",
+            SkipReason::Kept => "kept",
+            SkipReason::OtherType => "other-type",
+            SkipReason::ValidLink => "valid-link",
+            SkipReason::Gone => "gone",
+            SkipReason::Open => "open",
+        }
+    }
+}
+
+/// Options for `RemoveProgress::with_options`
+#[derive(Debug, Default)]
+pub struct ProgressOptions {
+    /// Top-level paths to attribute work to (`--per-path-summary`)
+    pub path_roots: Option<Vec<PathBuf>>,
+    /// Record the latency of every removal syscall (`--stats`)
+    pub latency_stats: bool,
+    /// Time each directory from scan to removal (`--profile-dirs`)
+    pub profile_dirs: bool,
+    /// Tally deleted files by extension (`--stats-by-ext`)
+    pub ext_stats: bool,
+    /// Also report every error here (`--on-error`)
+    pub error_hook: Option<Sender<ErrorEvent>>,
+    /// Also report every deleted path here, without dropping any (`--print-deleted`)
+    pub deleted_feed: Option<Sender<Arc<Path>>>,
+}
+
+/// Number of slowest directories kept by `--profile-dirs`
+pub const PROFILE_TOP_K: usize = 10;
+
+/// Number of extensions listed by `--stats-by-ext`, per ranking
+pub const EXT_TOP_N: usize = 10;
+
+/// Paths kept as examples per error category for the summary
+pub const ERROR_EXAMPLES: usize = 3;
+
+/// Tally key for a file: its lowercased extension, or `(none)`
+pub fn extension_key(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "(none)".to_string())
+}
+
+/// Wall time of one directory, from the start of its scan until its removal
+#[derive(Debug, Clone)]
+pub struct DirTiming {
+    pub path: Arc<Path>,
+    pub elapsed: Duration,
+    pub children: usize,
+}
+
+#[derive(Debug, Default)]
+struct DirProfiler {
+    /// Directories scanned but not yet removed: scan start and child count
+    pending: Mutex<HashMap<Arc<Path>, (Instant, usize)>>,
+    /// Slowest directories so far, longest first, at most `PROFILE_TOP_K`
+    slowest: Mutex<Vec<DirTiming>>,
+}
+
+/// Latency distribution of removal syscalls, in nanoseconds
+#[derive(Debug, Clone)]
+pub struct LatencyStats {
+    pub histogram: Histogram<u64>,
+    pub total: Duration,
+}
+
+/// Counters for a single top-level path
+#[derive(Debug, Default)]
+pub struct PathCounters {
+    pub items: AtomicU64,
+    pub bytes: AtomicU64,
+    pub errors: AtomicU64,
+    /// Nanoseconds from the start of the run to the last deletion or error
+    /// under the path
+    last_active: AtomicU64,
+}
+
+impl PathCounters {
+    /// How long after the start of the run the path was last worked on
+    pub fn duration(&self) -> Duration {
+        Duration::from_nanos(self.last_active.load(Ordering::Relaxed))
+    }
+}
+
+/// Per top-level path accounting for `--per-path-summary` and
+/// `--summary-table`
+///
+/// Top-level paths never overlap (see `deduplicate_and_check_paths`), so every
+/// deleted path belongs to at most one root and a linear prefix match is enough.
+#[derive(Debug)]
+pub struct PathTally {
+    roots: Vec<(PathBuf, PathCounters)>,
+    start: Instant,
+}
+
+impl PathTally {
+    pub fn new(roots: &[PathBuf]) -> Self {
+        Self {
+            roots: roots
+                .iter()
+                .map(|root| (root.clone(), PathCounters::default()))
+                .collect(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Find the counters of the top-level path containing `path`
+    pub fn counters_for(&self, path: &Path) -> Option<&PathCounters> {
+        self.roots
+            .iter()
+            .find(|(root, _)| path.starts_with(root))
+            .map(|(_, counters)| counters)
+    }
+
+    /// Iterate over the top-level paths in argument order
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &PathCounters)> {
+        self.roots.iter().map(|(root, c)| (root.as_path(), c))
+    }
+}
+
+/// An error as counted by `RemoveProgress::inc_error`, kept so it can be
+/// withdrawn again
+pub type CountedError = (Arc<Path>, ErrorCategory);
+
+thread_local! {
+    /// Errors counted on this thread inside `capture_errors`
+    static CAPTURED: RefCell<Option<Vec<CountedError>>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, also returning the errors it counted on this thread
+pub fn capture_errors<R>(f: impl FnOnce() -> R) -> (R, Vec<CountedError>) {
+    let outer = CAPTURED.with(|c| c.replace(Some(Vec::new())));
+    let result = f();
+    let captured = CAPTURED.with(|c| c.replace(outer)).unwrap_or_default();
+    (result, captured)
+}
+
+thread_local! {
+    /// Deletions counted on this thread but not yet added to the shared
+    /// counters, see `RemoveProgress::batch_deletions`
+    static BATCH: Cell<Option<Batch>> = const { Cell::new(None) };
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Batch {
+    /// The tracker the counts belong to
+    owner: *const RemoveProgress,
+    chunk: usize,
+    deleted: usize,
+    bytes: u64,
+}
+
+/// Keeps this thread's deletion counts batched until dropped, then adds
+/// whatever is left (`--chunk-size`)
+pub struct DeletedBatch<'a> {
+    progress: &'a RemoveProgress,
+    /// Batch of an enclosing guard on the same thread, restored on drop
+    previous: Option<Batch>,
+}
+
+impl Drop for DeletedBatch<'_> {
+    fn drop(&mut self) {
+        if let Some(batch) = BATCH.with(|cell| cell.replace(self.previous)) {
+            self.progress.flush_batch(&batch);
+        }
+    }
+}
+
+/// Maximum number of recent files/errors retained in the caches
+const CACHE_LIMIT: usize = 50;
+
+/// Minimum time a path stays on a row of the Detailed view
+const DETAILED_HOLD: Duration = Duration::from_millis(500);
+
+/// How often a status line is printed when the TUI is not in use
+const PLAIN_STATUS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Pause between two refreshes of the live display (`--progress-interval`)
+pub const DEFAULT_REFRESH_MS: u64 = 50;
+
+/// Shortest refresh interval accepted; faster only burns CPU on redraws
+pub const MIN_REFRESH_MS: u64 = 10;
+
+/// Longest refresh interval accepted; a display that still lags behind by
+/// more is no longer live
+pub const MAX_REFRESH_MS: u64 = 10_000;
+
+/// Call `refresh(false)` every `interval` until `done` is set, then
+/// `refresh(true)` once for the final state
+///
+/// `sleep` is `thread::park_timeout` outside of tests, so that unparking
+/// the thread after setting `done` ends the wait at once.
+pub fn refresh_until(
+    done: &AtomicBool,
+    interval: Duration,
+    mut sleep: impl FnMut(Duration),
+    mut refresh: impl FnMut(bool),
+) {
+    while !done.load(Ordering::Relaxed) {
+        refresh(false);
+        sleep(interval);
+    }
+    refresh(true);
+}
+
 impl std::fmt::Debug for RemoveProgress {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("RemoveProgress")
             .field("scanned", &self.scanned)
             .field("deleted", &self.deleted)
             .field("errors", &self.errors)
+            .field("peak_queue_depth", &self.peak_queue_depth)
+            .field("skipped", &self.skipped)
+            .field("kept_dirs", &self.kept_dirs)
             .field("start_time", &self.start_time)
             .finish()
     }
@@ -34,6 +334,19 @@ impl std::fmt::Debug for RemoveProgress {
 
 impl RemoveProgress {
     pub fn new() -> Arc<Self> {
+        Self::with_options(ProgressOptions::default())
+    }
+
+    /// Create a progress tracker with optional per-path and latency accounting
+    pub fn with_options(options: ProgressOptions) -> Arc<Self> {
+        let path_tally = options.path_roots.as_deref().map(PathTally::new);
+        let latency = options.latency_stats.then(|| {
+            Mutex::new(LatencyStats {
+                // Auto-resizing, 3 significant digits
+                histogram: Histogram::new(3).expect("valid histogram precision"),
+                total: Duration::ZERO,
+            })
+        });
         let (recent_tx, recent_rx) = bounded(1000);
         let (error_tx, error_rx) = bounded(100);
 
@@ -44,10 +357,27 @@ impl RemoveProgress {
             _pad2: [0; 56],
             errors: AtomicUsize::new(0),
             _pad3: [0; 56],
+            peak_queue_depth: AtomicUsize::new(0),
+            skipped: AtomicUsize::new(0),
+            skipped_by: Default::default(),
+            kept_dirs: AtomicUsize::new(0),
+            total: AtomicUsize::new(0),
+            busy_scanners: AtomicUsize::new(0),
+            bytes: AtomicU64::new(0),
+            warnings: Mutex::default(),
             recent_tx,
             recent_rx,
             error_tx,
             error_rx,
+            error_hook: Mutex::new(options.error_hook),
+            error_groups: Mutex::default(),
+            deleted_feed: options.deleted_feed,
+            recent_cache: Mutex::new(VecDeque::new()),
+            error_cache: Mutex::new(VecDeque::new()),
+            path_tally,
+            latency,
+            dir_profile: options.profile_dirs.then(DirProfiler::default),
+            ext_tally: options.ext_stats.then(Mutex::default),
             start_time: Instant::now(),
         })
     }
@@ -56,16 +386,317 @@ impl RemoveProgress {
         self.scanned.fetch_add(1, Ordering::Relaxed);
     }
     pub fn inc_deleted(&self, path: &Path) {
-        self.deleted.fetch_add(1, Ordering::Relaxed);
+        let flushed = self.count_deleted(1, 0);
+        if let Some(c) = self.path_counters(path) {
+            c.items.fetch_add(1, Ordering::Relaxed);
+        }
+        // The feed must see every path; the display is sampled once per batch
+        if self.deleted_feed.is_none() && !flushed {
+            return;
+        }
         // Non-blocking send, drops if channel full (acceptable for display)
         // Create Arc once instead of cloning PathBuf multiple times
-        let _ = self.recent_tx.try_send(Arc::from(path));
+        let path: Arc<Path> = Arc::from(path);
+        if let Some(feed) = &self.deleted_feed {
+            let _ = feed.send(path.clone());
+        }
+        if flushed {
+            let _ = self.recent_tx.try_send(path);
+        }
+    }
+
+    /// Count deletions on this thread and add them to `deleted` and `bytes`
+    /// only every `chunk` deletions and when the returned guard drops
+    ///
+    /// At high deletion rates the shared counters are contended cache lines;
+    /// batching trades a little display lag for fewer atomic operations.
+    /// Totals are exact once every guard has been dropped. A chunk of 0 or
+    /// 1 counts every deletion straight away.
+    pub fn batch_deletions(&self, chunk: usize) -> DeletedBatch<'_> {
+        let batch = (chunk > 1).then_some(Batch {
+            owner: self,
+            chunk,
+            deleted: 0,
+            bytes: 0,
+        });
+        DeletedBatch {
+            progress: self,
+            previous: BATCH.with(|cell| cell.replace(batch)),
+        }
+    }
+
+    /// Add to the deletion counters, through this thread's batch if it has
+    /// one for this tracker; returns whether the shared counters were updated
+    fn count_deleted(&self, deleted: usize, bytes: u64) -> bool {
+        let batched = BATCH.with(|cell| {
+            let mut batch = cell.get().filter(|b| std::ptr::eq(b.owner, self))?;
+            batch.deleted += deleted;
+            batch.bytes += bytes;
+            let full = batch.deleted >= batch.chunk;
+            if full {
+                self.flush_batch(&batch);
+                batch.deleted = 0;
+                batch.bytes = 0;
+            }
+            cell.set(Some(batch));
+            Some(full)
+        });
+        batched.unwrap_or_else(|| {
+            self.flush_batch(&Batch {
+                owner: self,
+                chunk: 1,
+                deleted,
+                bytes,
+            });
+            true
+        })
+    }
+
+    fn flush_batch(&self, batch: &Batch) {
+        if batch.deleted > 0 {
+            self.deleted.fetch_add(batch.deleted, Ordering::Relaxed);
+        }
+        if batch.bytes > 0 {
+            self.bytes.fetch_add(batch.bytes, Ordering::Relaxed);
+        }
     }
-    pub fn inc_error(&self, path: &Path, error: String) {
+    pub fn inc_error(&self, path: &Path, category: ErrorCategory, error: String) {
         self.errors.fetch_add(1, Ordering::Relaxed);
+        if let Some(c) = self.path_counters(path) {
+            c.errors.fetch_add(1, Ordering::Relaxed);
+        }
         // Non-blocking send, drops if channel full (acceptable for display)
         // Create Arc once instead of cloning PathBuf
-        let _ = self.error_tx.try_send((Arc::from(path), error));
+        let path: Arc<Path> = Arc::from(path);
+        {
+            let mut groups = self.error_groups.lock().unwrap();
+            let (count, examples) = &mut groups[category as usize];
+            *count += 1;
+            if examples.len() < ERROR_EXAMPLES {
+                examples.push(path.clone());
+            }
+        }
+        CAPTURED.with(|c| {
+            if let Some(captured) = c.borrow_mut().as_mut() {
+                captured.push((path.clone(), category));
+            }
+        });
+        if let Some(hook) = &*self.error_hook.lock().unwrap() {
+            let _ = hook.try_send((path.clone(), error.clone()));
+        }
+        let _ = self.error_tx.try_send((path, error));
+    }
+
+    /// Withdraw an error counted by `inc_error`, e.g. once a retry of the
+    /// failed job succeeded
+    pub fn dec_error(&self, path: &Path, category: ErrorCategory) {
+        self.errors.fetch_sub(1, Ordering::Relaxed);
+        if let Some(c) = self.path_counters(path) {
+            c.errors.fetch_sub(1, Ordering::Relaxed);
+        }
+        {
+            let mut groups = self.error_groups.lock().unwrap();
+            let (count, examples) = &mut groups[category as usize];
+            *count = count.saturating_sub(1);
+            if let Some(i) = examples.iter().position(|p| &**p == path) {
+                examples.remove(i);
+            }
+        }
+        let mut cache = self.error_cache();
+        if let Some(i) = cache.iter().rposition(|(p, _)| &**p == path) {
+            cache.remove(i);
+        }
+    }
+
+    /// Stop reporting errors to the `--on-error` hook
+    pub fn close_error_hook(&self) {
+        self.error_hook.lock().unwrap().take();
+    }
+
+    pub fn inc_skipped(&self, reason: SkipReason) {
+        self.skipped.fetch_add(1, Ordering::Relaxed);
+        self.skipped_by[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Keep a warning for the summary, as stderr belongs to the live display
+    pub fn add_warning(&self, message: String) {
+        self.warnings.lock().unwrap().push(message);
+    }
+
+    pub fn warnings(&self) -> Vec<String> {
+        self.warnings.lock().unwrap().clone()
+    }
+
+    /// Non-zero skip counts per reason, most frequent first
+    pub fn skipped_breakdown(&self) -> Vec<(SkipReason, usize)> {
+        let mut counts: Vec<_> = SkipReason::ALL
+            .iter()
+            .map(|&reason| {
+                (
+                    reason,
+                    self.skipped_by[reason as usize].load(Ordering::Relaxed),
+                )
+            })
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        counts
+    }
+    /// Non-zero error counts per category with their first paths, most
+    /// frequent first
+    pub fn error_groups(&self) -> Vec<(ErrorCategory, usize, Vec<Arc<Path>>)> {
+        let groups = self.error_groups.lock().unwrap();
+        let mut groups: Vec<_> = ErrorCategory::ALL
+            .iter()
+            .zip(groups.iter())
+            .filter(|(_, (count, _))| *count > 0)
+            .map(|(&category, (count, examples))| (category, *count, examples.clone()))
+            .collect();
+        groups.sort_by_key(|&(_, count, _)| std::cmp::Reverse(count));
+        groups
+    }
+
+    pub fn inc_kept_dir(&self) {
+        self.kept_dirs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Attribute the bytes of a deleted file to its top-level path
+    pub fn add_deleted_bytes(&self, path: &Path, bytes: u64) {
+        self.count_deleted(0, bytes);
+        if let Some(c) = self.path_counters(path) {
+            c.bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+    }
+
+    /// Count one deleted file against its extension (`--stats-by-ext`)
+    ///
+    /// A no-op unless enabled, so the lock is only taken when asked for.
+    pub fn record_extension(&self, path: &Path, bytes: u64) {
+        let Some(tally) = &self.ext_tally else {
+            return;
+        };
+        let mut tally = tally.lock().unwrap();
+        let entry = tally.entry(extension_key(path)).or_default();
+        entry.0 += 1;
+        entry.1 += bytes;
+    }
+
+    /// Per-extension `(extension, files, bytes)`, if `--stats-by-ext` is on
+    pub fn extension_stats(&self) -> Option<Vec<(String, u64, u64)>> {
+        self.ext_tally.as_ref().map(|tally| {
+            tally
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(ext, &(files, bytes))| (ext.clone(), files, bytes))
+                .collect()
+        })
+    }
+
+    /// Whether removal latencies should be recorded (`--stats`)
+    pub fn latency_enabled(&self) -> bool {
+        self.latency.is_some()
+    }
+
+    /// Record the duration of one removal syscall
+    pub fn record_latency(&self, elapsed: Duration) {
+        if let Some(latency) = &self.latency {
+            let mut stats = latency.lock().unwrap();
+            let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+            // Auto-resizing histogram only rejects values it cannot represent
+            let _ = stats.histogram.record(nanos);
+            stats.total += elapsed;
+        }
+    }
+
+    /// Snapshot of the recorded removal latencies, if `--stats` is enabled
+    pub fn latency_stats(&self) -> Option<LatencyStats> {
+        self.latency.as_ref().map(|l| l.lock().unwrap().clone())
+    }
+
+    /// Whether directories should be timed (`--profile-dirs`)
+    pub fn profile_enabled(&self) -> bool {
+        self.dir_profile.is_some()
+    }
+
+    /// Note a fully scanned directory whose removal is still to come
+    pub fn dir_scanned(&self, path: &Path, started: Instant, children: usize) {
+        if let Some(profile) = &self.dir_profile {
+            profile
+                .pending
+                .lock()
+                .unwrap()
+                .insert(Arc::from(path), (started, children));
+        }
+    }
+
+    /// Close the timing of a directory that has just been removed
+    pub fn dir_removed(&self, path: &Path) {
+        let Some(profile) = &self.dir_profile else {
+            return;
+        };
+        let Some((path, (started, children))) = profile.pending.lock().unwrap().remove_entry(path)
+        else {
+            return;
+        };
+        let elapsed = started.elapsed();
+
+        let mut slowest = profile.slowest.lock().unwrap();
+        if slowest.len() == PROFILE_TOP_K && slowest.last().is_some_and(|t| t.elapsed >= elapsed) {
+            return;
+        }
+        let at = slowest.partition_point(|t| t.elapsed >= elapsed);
+        slowest.insert(
+            at,
+            DirTiming {
+                path,
+                elapsed,
+                children,
+            },
+        );
+        slowest.truncate(PROFILE_TOP_K);
+    }
+
+    /// The slowest directories, longest first, if `--profile-dirs` is enabled
+    pub fn slowest_dirs(&self) -> Option<Vec<DirTiming>> {
+        self.dir_profile
+            .as_ref()
+            .map(|p| p.slowest.lock().unwrap().clone())
+    }
+
+    /// Count a failure against a top-level path only (scan errors reported by `main`)
+    pub fn inc_path_error(&self, path: &Path) {
+        if let Some(c) = self.path_counters(path) {
+            c.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Per top-level path counters, if per-path tallying is enabled
+    pub fn path_tally(&self) -> Option<&PathTally> {
+        self.path_tally.as_ref()
+    }
+
+    /// The counters of the top-level path containing `path`, marked as
+    /// active now
+    fn path_counters(&self, path: &Path) -> Option<&PathCounters> {
+        let tally = self.path_tally.as_ref()?;
+        let counters = tally.counters_for(path)?;
+        let nanos = tally.start.elapsed().as_nanos() as u64;
+        counters.last_active.fetch_max(nanos, Ordering::Relaxed);
+        Some(counters)
+    }
+
+    /// Record an observed queue depth, keeping the maximum seen so far
+    pub fn record_queue_depth(&self, depth: usize) {
+        // Plain load first so the common (not a new peak) case stays read-only
+        if depth > self.peak_queue_depth.load(Ordering::Relaxed) {
+            self.peak_queue_depth.fetch_max(depth, Ordering::Relaxed);
+        }
+    }
+
+    /// Time since the tracker was created
+    pub fn elapsed(&self) -> Duration {
+        self.start_time.elapsed()
     }
 
     pub fn get_stats(&self) -> (usize, usize, usize, f64, f64) {
@@ -78,73 +709,271 @@ impl RemoveProgress {
         } else {
             0.0
         };
-        let eta = if speed > 0.0 && scanned > deleted {
-            (scanned - deleted) as f64 / speed
+        // Before the scan is over `scanned` undercounts; a pre-scanned total does not
+        let total = match self.total.load(Ordering::Relaxed) {
+            0 => scanned,
+            total => total,
+        };
+        let eta = if speed > 0.0 && total > deleted {
+            (total - deleted) as f64 / speed
         } else {
             0.0
         };
         (scanned, deleted, errors, speed, eta)
     }
 
-    pub fn get_recent_files(&self) -> Vec<Arc<Path>> {
-        let mut files = Vec::new();
+    /// Drain the recent files channel into the cache and run `f` over the cached items
+    ///
+    /// This is the only place `recent_rx` is consumed, so the display and
+    /// `get_recent_files` never steal items from each other.
+    pub fn with_recent_files<R>(&self, f: impl FnOnce(&VecDeque<Arc<Path>>) -> R) -> R {
+        let mut cache = self.recent_cache.lock().unwrap();
         while let Ok(path) = self.recent_rx.try_recv() {
-            files.push(path);
-            // Keep only last 50 items
-            if files.len() > 50 {
-                files.remove(0);
+            cache.push_back(path);
+            while cache.len() > CACHE_LIMIT {
+                cache.pop_front();
             }
         }
-        files
+        f(&cache)
     }
-    pub fn get_error_files(&self) -> Vec<(Arc<Path>, String)> {
-        let mut errors = Vec::new();
+
+    /// Drain the error channel into the cache and run `f` over the cached items
+    pub fn with_error_files<R>(&self, f: impl FnOnce(&VecDeque<(Arc<Path>, String)>) -> R) -> R {
+        f(&self.error_cache())
+    }
+
+    /// The error cache, with the error channel drained into it
+    fn error_cache(&self) -> std::sync::MutexGuard<'_, VecDeque<(Arc<Path>, String)>> {
+        let mut cache = self.error_cache.lock().unwrap();
         while let Ok(error) = self.error_rx.try_recv() {
-            errors.push(error);
-            // Keep only last 50 items
-            if errors.len() > 50 {
-                errors.remove(0);
+            cache.push_back(error);
+            while cache.len() > CACHE_LIMIT {
+                cache.pop_front();
             }
         }
-        errors
+        cache
+    }
+
+    /// Get the most recently deleted files (oldest first, at most 50)
+    #[allow(dead_code)]
+    pub fn get_recent_files(&self) -> Vec<Arc<Path>> {
+        self.with_recent_files(|cache| cache.iter().cloned().collect())
+    }
+
+    /// Get the most recent errors (oldest first, at most 50)
+    #[allow(dead_code)]
+    pub fn get_error_files(&self) -> Vec<(Arc<Path>, String)> {
+        self.with_error_files(|cache| cache.iter().cloned().collect())
     }
 }
 
+/// One-line summary of the current counters, shared by the TUI and plain output
+pub fn status_message(
+    progress: &RemoveProgress,
+    dry_run: bool,
+    queue_depth: Option<usize>,
+) -> String {
+    let (scanned, deleted, errors, speed, _eta) = progress.get_stats();
+    if let Some(depth) = queue_depth {
+        // Two-pool mode: show scanned, queue depth, deleted
+        if dry_run {
+            format!(
+                "{} scanned | {} in queue | {} errors | {:.1} items/s",
+                scanned, depth, errors, speed
+            )
+        } else {
+            format!(
+                "{} scanned | {} in queue | {} deleted | {} errors | {:.1} items/s",
+                scanned, depth, deleted, errors, speed
+            )
+        }
+    } else {
+        // Legacy mode: show only deleted/scanned
+        if dry_run {
+            format!(
+                "{} scanned | {} errors | {:.1} items/s",
+                scanned, errors, speed
+            )
+        } else {
+            format!(
+                "{} deleted | {} errors | {:.1} items/s",
+                deleted, errors, speed
+            )
+        }
+    }
+}
+
+/// Final one-line summary printed when the run completes
+pub fn final_message(
+    progress: &RemoveProgress,
+    dry_run: bool,
+    queue_depth: Option<usize>,
+) -> String {
+    let (scanned, deleted, errors, _, _) = progress.get_stats();
+    if let Some(depth) = queue_depth {
+        // Two-pool mode: show all stats
+        if dry_run {
+            format!(
+                "✓ Dry run complete: {} scanned, {} in queue, {} errors{}",
+                format_count(scanned as u64),
+                format_count(depth as u64),
+                format_count(errors as u64),
+                timing(progress, scanned)
+            )
+        } else {
+            format!(
+                "✓ Complete: {} scanned, {} deleted, {} errors{}",
+                format_count(scanned as u64),
+                format_count(deleted as u64),
+                format_count(errors as u64),
+                timing(progress, deleted)
+            )
+        }
+    } else {
+        // Legacy mode
+        if dry_run {
+            format!(
+                "✓ Dry run complete: {} items scanned, {} errors",
+                scanned, errors
+            )
+        } else {
+            format!("✓ Complete: {} items deleted, {} errors", deleted, errors)
+        }
+    }
+}
+
+/// ` in 1m 23s (12,345 items/s, 1.2 GiB/s)`; the byte rate only once any bytes were counted
+fn timing(progress: &RemoveProgress, items: usize) -> String {
+    let elapsed = progress.elapsed();
+    let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+    let mut out = format!(
+        " in {} ({}",
+        format_duration(elapsed),
+        format_rate(items as f64 / secs, "items")
+    );
+    let bytes = progress.bytes.load(Ordering::Relaxed);
+    if bytes > 0 {
+        out.push_str(&format!(
+            ", {}/s",
+            format_bytes((bytes as f64 / secs) as u64)
+        ));
+    }
+    out.push(')');
+    out
+}
+
 pub struct ProgressDisplay {
-    multi: MultiProgress,
     main_bar: ProgressBar,
     file_bars: Vec<ProgressBar>,
     error_bar: Option<ProgressBar>,
-    verbosity: Verbosity,
-    // TUI-local caches to avoid allocating Vec on every update
-    recent_cache: Mutex<std::collections::VecDeque<Arc<Path>>>,
-    error_cache: Mutex<std::collections::VecDeque<(Arc<Path>, String)>>,
+    /// Rows that hold their paths (Detailed); otherwise the rows show the tail
+    held: Option<Mutex<HeldFiles>>,
+}
+
+/// Rows of the Detailed view, each holding its path for a minimum time
+///
+/// On a fast run files go by far quicker than the display refreshes, so
+/// showing the tail of the recent window only flickers. Instead each row
+/// keeps its path for `hold`; rows that have served their time take paths
+/// deleted since the last refresh, picked evenly across them so the view
+/// samples the whole window rather than its end.
+struct HeldFiles {
+    hold: Duration,
+    rows: Vec<Option<(Arc<Path>, Instant)>>,
+    /// Newest cached path already considered, to tell which ones are new
+    newest: Option<Arc<Path>>,
+}
+
+impl HeldFiles {
+    fn new(rows: usize, hold: Duration) -> Self {
+        Self {
+            hold,
+            rows: vec![None; rows],
+            newest: None,
+        }
+    }
+
+    /// Bring the rows up to date with the recent files cache
+    fn refresh(&mut self, recent: &VecDeque<Arc<Path>>, now: Instant) {
+        let start = self
+            .newest
+            .as_ref()
+            .and_then(|newest| recent.iter().rposition(|p| Arc::ptr_eq(p, newest)))
+            .map_or(0, |i| i + 1);
+        let fresh = recent.len() - start;
+        if let Some(newest) = recent.back() {
+            self.newest = Some(newest.clone());
+        }
+
+        // Empty rows first, then the ones showing the same path longest
+        let mut free: Vec<usize> = (0..self.rows.len())
+            .filter(|&i| match &self.rows[i] {
+                Some((_, shown)) => now.saturating_duration_since(*shown) >= self.hold,
+                None => true,
+            })
+            .collect();
+        free.sort_by_key(|&i| self.rows[i].as_ref().map(|(_, shown)| *shown));
+
+        let take = free.len().min(fresh);
+        for (k, &row) in free.iter().take(take).enumerate() {
+            // Spread over the new paths, the last pick being the newest
+            let index = start + (k + 1) * fresh / take - 1;
+            self.rows[row] = Some((recent[index].clone(), now));
+        }
+    }
 }
 
 impl ProgressDisplay {
-    pub fn new(verbosity: Verbosity, dry_run: bool) -> Self {
-        let multi = MultiProgress::new();
-        let main_bar = multi.add(ProgressBar::new_spinner());
-        let template = if dry_run {
-            "[Dry Run] Scanned: {msg}"
+    /// A display for a run of `total` entries, if known (`--pre-scan`), shows
+    /// a bar with the real percentage and ETA instead of a spinner
+    pub fn new(
+        verbosity: Verbosity,
+        dry_run: bool,
+        total: Option<u64>,
+    ) -> Result<Self, TemplateError> {
+        let label = if dry_run {
+            "[Dry Run] Scanned:"
         } else {
-            "Deleted: {msg}"
+            "Deleted:"
+        };
+        Self::with_label(label, verbosity, total)
+    }
+
+    fn with_label(
+        label: &str,
+        verbosity: Verbosity,
+        total: Option<u64>,
+    ) -> Result<Self, TemplateError> {
+        let multi = MultiProgress::new();
+        // Styles are built before any bar is added, so a failure draws nothing
+        let file_style = ProgressStyle::default_spinner().template("  {msg}")?;
+        let error_style = ProgressStyle::default_spinner().template("{msg}")?;
+        let main_bar = match total {
+            Some(total) => {
+                let template = format!("{} [{{bar:30}}] {{percent}}% ETA {{eta}} | {{msg}}", label);
+                let style = ProgressStyle::default_bar().template(&template)?;
+                let bar = multi.add(ProgressBar::new(total));
+                bar.set_style(style);
+                bar
+            }
+            None => {
+                let template = format!("{} {{msg}}", label);
+                let style = ProgressStyle::default_spinner().template(&template)?;
+                let bar = multi.add(ProgressBar::new_spinner());
+                bar.set_style(style);
+                bar
+            }
         };
-        main_bar.set_style(ProgressStyle::default_spinner().template(template).unwrap());
 
         let mut file_bars = Vec::new();
-        let mut error_bar = None;
+        let mut held = None;
 
         match verbosity {
             Verbosity::Simple => {}
             Verbosity::Standard => {
                 for _ in 0..10 {
                     let bar = multi.add(ProgressBar::new_spinner());
-                    bar.set_style(
-                        ProgressStyle::default_spinner()
-                            .template("  {msg}")
-                            .unwrap(),
-                    );
+                    bar.set_style(file_style.clone());
                     file_bars.push(bar);
                 }
             }
@@ -152,104 +981,66 @@ impl ProgressDisplay {
                 let height = crossterm::terminal::size()
                     .map(|(_, h)| h as usize)
                     .unwrap_or(24);
-                let file_count = (height.saturating_sub(5)).min(50).max(5);
+                let file_count = (height.saturating_sub(5)).clamp(5, 50);
                 for _ in 0..file_count {
                     let bar = multi.add(ProgressBar::new_spinner());
-                    bar.set_style(
-                        ProgressStyle::default_spinner()
-                            .template("  {msg}")
-                            .unwrap(),
-                    );
+                    bar.set_style(file_style.clone());
                     file_bars.push(bar);
                 }
+                held = Some(Mutex::new(HeldFiles::new(file_count, DETAILED_HOLD)));
             }
         }
 
         let err_bar = multi.add(ProgressBar::new_spinner());
-        err_bar.set_style(ProgressStyle::default_spinner().template("{msg}").unwrap());
-        error_bar = Some(err_bar);
+        err_bar.set_style(error_style);
+        let error_bar = Some(err_bar);
 
-        Self {
-            multi,
+        Ok(Self {
             main_bar,
             file_bars,
             error_bar,
-            verbosity,
-            recent_cache: Mutex::new(std::collections::VecDeque::new()),
-            error_cache: Mutex::new(std::collections::VecDeque::new()),
-        }
+            held,
+        })
     }
 
     pub fn update(&self, progress: &RemoveProgress, dry_run: bool, queue_depth: Option<usize>) {
-        let (scanned, deleted, errors, speed, _eta) = progress.get_stats();
-        let main_msg = if let Some(depth) = queue_depth {
-            // Two-pool mode: show scanned, queue depth, deleted
-            if dry_run {
-                format!(
-                    "{} scanned | {} in queue | {} errors | {:.1} items/s",
-                    scanned, depth, errors, speed
-                )
-            } else {
-                format!(
-                    "{} scanned | {} in queue | {} deleted | {} errors | {:.1} items/s",
-                    scanned, depth, deleted, errors, speed
-                )
-            }
-        } else {
-            // Legacy mode: show only deleted/scanned
-            if dry_run {
-                format!(
-                    "{} scanned | {} errors | {:.1} items/s",
-                    scanned, errors, speed
-                )
-            } else {
-                format!(
-                    "{} deleted | {} errors | {:.1} items/s",
-                    deleted, errors, speed
-                )
-            }
-        };
-        self.main_bar.set_message(main_msg);
-
-        if !self.file_bars.is_empty() {
-            // Drain new files from channel into local cache
-            let mut cache = self.recent_cache.lock().unwrap();
-            while let Ok(path) = progress.recent_rx.try_recv() {
-                cache.push_back(path);
-                // Keep only last 50 items
-                while cache.len() > 50 {
-                    cache.pop_front();
-                }
-            }
+        let errors = progress.errors.load(Ordering::Relaxed);
+        self.set_position(progress);
+        self.main_bar
+            .set_message(status_message(progress, dry_run, queue_depth));
 
-            // Display recent files from cache (no allocation)
-            let display_count = self.file_bars.len().min(cache.len());
-            for (i, bar) in self.file_bars.iter().enumerate() {
-                if i < display_count {
-                    let file = &cache[cache.len() - display_count + i];
-                    bar.set_message(format!("{:?}", file));
-                } else {
-                    bar.set_message("");
+        if let Some(held) = &self.held {
+            let mut held = held.lock().unwrap();
+            progress.with_recent_files(|cache| held.refresh(cache, Instant::now()));
+            for (bar, row) in self.file_bars.iter().zip(&held.rows) {
+                match row {
+                    Some((file, _)) => bar.set_message(format!("{:?}", file)),
+                    None => bar.set_message(""),
                 }
             }
+        } else if !self.file_bars.is_empty() {
+            // Display recent files from the shared cache (no allocation)
+            progress.with_recent_files(|cache| {
+                let display_count = self.file_bars.len().min(cache.len());
+                for (i, bar) in self.file_bars.iter().enumerate() {
+                    if i < display_count {
+                        let file = &cache[cache.len() - display_count + i];
+                        bar.set_message(format!("{:?}", file));
+                    } else {
+                        bar.set_message("");
+                    }
+                }
+            });
         }
 
         if let Some(err_bar) = &self.error_bar {
             if errors > 0 {
-                // Drain new errors from channel into local cache
-                let mut cache = self.error_cache.lock().unwrap();
-                while let Ok(error) = progress.error_rx.try_recv() {
-                    cache.push_back(error);
-                    // Keep only last 50 items
-                    while cache.len() > 50 {
-                        cache.pop_front();
+                // Display last error from the shared cache (no allocation)
+                progress.with_error_files(|cache| {
+                    if let Some((path, msg)) = cache.back() {
+                        err_bar.set_message(format!("Last error: {:?} - {}", path, msg));
                     }
-                }
-
-                // Display last error from cache (no allocation)
-                if let Some((path, msg)) = cache.back() {
-                    err_bar.set_message(format!("Last error: {:?} - {}", path, msg));
-                }
+                });
             } else {
                 err_bar.set_message("");
             }
@@ -257,32 +1048,10 @@ impl ProgressDisplay {
     }
 
     pub fn finish(&self, progress: &RemoveProgress, dry_run: bool, queue_depth: Option<usize>) {
-        let (scanned, deleted, errors, _, _) = progress.get_stats();
-        let final_msg = if let Some(depth) = queue_depth {
-            // Two-pool mode: show all stats
-            if dry_run {
-                format!(
-                    "✓ Dry run complete: {} scanned, {} in queue, {} errors",
-                    scanned, depth, errors
-                )
-            } else {
-                format!(
-                    "✓ Complete: {} scanned, {} deleted, {} errors",
-                    scanned, deleted, errors
-                )
-            }
-        } else {
-            // Legacy mode
-            if dry_run {
-                format!(
-                    "✓ Dry run complete: {} items scanned, {} errors",
-                    scanned, errors
-                )
-            } else {
-                format!("✓ Complete: {} items deleted, {} errors", deleted, errors)
-            }
-        };
-        self.main_bar.finish_with_message(final_msg);
+        let errors = progress.errors.load(Ordering::Relaxed);
+        self.set_position(progress);
+        self.main_bar
+            .finish_with_message(final_message(progress, dry_run, queue_depth));
         for bar in &self.file_bars {
             bar.finish_and_clear();
         }
@@ -292,8 +1061,478 @@ impl ProgressDisplay {
             }
         }
     }
+
+    /// Move a determinate bar to the number of entries dealt with so far
+    fn set_position(&self, progress: &RemoveProgress) {
+        if self.main_bar.length().is_some() {
+            self.main_bar
+                .set_position(progress.deleted.load(Ordering::Relaxed) as u64);
+        }
+    }
+}
+
+/// Where progress goes: the live TUI on a terminal, plain status lines otherwise
+///
+/// Plain lines carry no cursor movement, so redirected output stays readable
+/// in CI logs and cron mail.
+pub enum ProgressOutput {
+    Tui(ProgressDisplay),
+    Lines {
+        last: Mutex<Instant>,
+    },
+    /// Counting only, nothing drawn (`--summary-only`)
+    Hidden,
+}
+
+impl ProgressOutput {
+    /// The TUI if asked for and it can be set up, plain lines otherwise
+    ///
+    /// A terminal that cannot report its size (some minimal or oddly
+    /// redirected ones) would be drawn over blindly, so it gets plain lines.
+    pub fn new(use_tui: bool, verbosity: Verbosity, dry_run: bool, total: Option<u64>) -> Self {
+        if !use_tui {
+            return Self::lines();
+        }
+        let display = match crossterm::terminal::size() {
+            Err(e) if std::io::stdout().is_terminal() => {
+                Err(format!("cannot determine the terminal size: {}", e))
+            }
+            _ => ProgressDisplay::new(verbosity, dry_run, total).map_err(|e| e.to_string()),
+        };
+        Self::tui_or_lines(display)
+    }
+
+    fn tui_or_lines(display: Result<ProgressDisplay, String>) -> Self {
+        match display {
+            Ok(display) => Self::Tui(display),
+            Err(e) => {
+                eprintln!(
+                    "{} live progress unavailable ({}); printing status lines instead",
+                    "Warning:".yellow(),
+                    e
+                );
+                Self::lines()
+            }
+        }
+    }
+
+    fn lines() -> Self {
+        Self::Lines {
+            last: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn update(&self, progress: &RemoveProgress, dry_run: bool, queue_depth: Option<usize>) {
+        match self {
+            Self::Tui(display) => display.update(progress, dry_run, queue_depth),
+            Self::Lines { last } => {
+                let mut last = last.lock().unwrap();
+                if last.elapsed() >= PLAIN_STATUS_INTERVAL {
+                    *last = Instant::now();
+                    println!("{}", status_message(progress, dry_run, queue_depth));
+                }
+            }
+            Self::Hidden => {}
+        }
+    }
+
+    pub fn finish(&self, progress: &RemoveProgress, dry_run: bool, queue_depth: Option<usize>) {
+        match self {
+            Self::Tui(display) => display.finish(progress, dry_run, queue_depth),
+            Self::Lines { .. } => println!("{}", final_message(progress, dry_run, queue_depth)),
+            Self::Hidden => {}
+        }
+    }
 }
 
-pub struct TuiHandle {
-    pub is_done: Arc<AtomicBool>,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::RemoveError;
+    use std::io;
+
+    #[test]
+    fn test_dir_profile_keeps_slowest() {
+        let progress = RemoveProgress::with_options(ProgressOptions {
+            profile_dirs: true,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        for i in 0..PROFILE_TOP_K + 5 {
+            let path = PathBuf::from(format!("/data/{}", i));
+            // Directory i started i seconds ago
+            progress.dir_scanned(&path, now - Duration::from_secs(i as u64), i);
+            progress.dir_removed(&path);
+        }
+        // Never scanned (e.g. kept), so not timed
+        progress.dir_removed(Path::new("/data/unknown"));
+
+        let slowest = progress.slowest_dirs().unwrap();
+        assert_eq!(slowest.len(), PROFILE_TOP_K);
+        assert_eq!(&*slowest[0].path, Path::new("/data/14"));
+        assert_eq!(slowest[0].children, 14);
+        assert!(slowest.windows(2).all(|w| w[0].elapsed >= w[1].elapsed));
+        assert!(RemoveProgress::new().slowest_dirs().is_none());
+    }
+
+    #[test]
+    fn test_pre_scan_total_matches_final_position() {
+        use crate::config::RemoveConfig;
+        use crate::deleter::delete_worker;
+        use crate::queue::AdaptiveQueue;
+        use crate::scanner::{count_jobs, scan_path};
+        use std::sync::atomic::AtomicBool;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        for dir in ["a", "a/b", "c"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            for i in 0..5 {
+                std::fs::write(root.join(dir).join(format!("{}.txt", i)), "x").unwrap();
+            }
+        }
+
+        let progress = RemoveProgress::new();
+        let config = RemoveConfig {
+            progress: Some(progress.clone()),
+            ..Default::default()
+        };
+        let total = count_jobs(&root, &config).unwrap().entries;
+        // root, 3 directories and 15 files; counting deletes and reports nothing
+        assert_eq!(total, 19);
+        assert!(root.exists());
+        assert_eq!(progress.scanned.load(Ordering::Relaxed), 0);
+
+        let display = ProgressDisplay::new(Verbosity::Simple, false, Some(total)).unwrap();
+        let queue = AdaptiveQueue::new(100);
+        scan_path(&root, &queue, &config).unwrap();
+        display.update(&progress, false, None);
+        delete_worker(&queue, &config, &AtomicBool::new(true));
+        display.finish(&progress, false, None);
+
+        assert!(!root.exists());
+        assert_eq!(display.main_bar.length(), Some(total));
+        assert_eq!(display.main_bar.position(), total);
+    }
+
+    #[test]
+    fn test_display_and_accessor_share_recent_files() {
+        let progress = RemoveProgress::new();
+        let display = ProgressDisplay::new(Verbosity::Standard, false, None).unwrap();
+
+        progress.inc_deleted(Path::new("/tmp/a"));
+        progress.inc_deleted(Path::new("/tmp/b"));
+
+        // The display drains the channel first; the accessor must still see the items
+        display.update(&progress, false, None);
+        let recent = progress.get_recent_files();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(&*recent[0], Path::new("/tmp/a"));
+        assert_eq!(&*recent[1], Path::new("/tmp/b"));
+
+        let shown: Vec<String> = display
+            .file_bars
+            .iter()
+            .map(|bar| bar.message())
+            .filter(|msg| !msg.is_empty())
+            .collect();
+        assert_eq!(shown, vec!["\"/tmp/a\"", "\"/tmp/b\""]);
+
+        // And the other way round: accessor first, display afterwards
+        progress.inc_deleted(Path::new("/tmp/c"));
+        assert_eq!(progress.get_recent_files().len(), 3);
+        display.update(&progress, false, None);
+        assert_eq!(display.file_bars[2].message(), "\"/tmp/c\"");
+    }
+
+    #[test]
+    fn test_refresh_interval_is_respected() {
+        // A mocked clock: each sleep advances it, and the run ends after 1s
+        fn refreshes(interval_ms: u64) -> (usize, usize) {
+            let done = AtomicBool::new(false);
+            let mut now = Duration::ZERO;
+            let (mut live, mut last) = (0, 0);
+            refresh_until(
+                &done,
+                Duration::from_millis(interval_ms),
+                |pause| {
+                    now += pause;
+                    if now >= Duration::from_secs(1) {
+                        done.store(true, Ordering::Relaxed);
+                    }
+                },
+                |is_last| if is_last { last += 1 } else { live += 1 },
+            );
+            (live, last)
+        }
+
+        assert_eq!(refreshes(DEFAULT_REFRESH_MS), (20, 1));
+        assert_eq!(refreshes(250), (4, 1));
+        assert_eq!(refreshes(MIN_REFRESH_MS), (100, 1));
+    }
+
+    #[test]
+    fn test_detailed_rows_hold_their_paths() {
+        let paths: Vec<Arc<Path>> = ["/a", "/b", "/c", "/d", "/e", "/f"]
+            .iter()
+            .map(|p| Arc::from(Path::new(p)))
+            .collect();
+        let shown = |held: &HeldFiles| -> Vec<String> {
+            held.rows
+                .iter()
+                .map(|row| {
+                    row.as_ref()
+                        .map_or(String::new(), |(p, _)| p.display().to_string())
+                })
+                .collect()
+        };
+        let hold = Duration::from_millis(500);
+        let mut held = HeldFiles::new(2, hold);
+        let mut recent: VecDeque<Arc<Path>> = paths[..3].iter().cloned().collect();
+        let start = Instant::now();
+
+        // Two rows for three new paths: a sample across them, ending with the newest
+        held.refresh(&recent, start);
+        assert_eq!(shown(&held), ["/a", "/c"]);
+
+        // Within the hold nothing changes, however many paths go by
+        recent.extend(paths[3..5].iter().cloned());
+        held.refresh(&recent, start + Duration::from_millis(100));
+        assert_eq!(shown(&held), ["/a", "/c"]);
+
+        // Once held long enough, rows take paths deleted since the last refresh
+        recent.push_back(paths[5].clone());
+        held.refresh(&recent, start + hold);
+        assert_eq!(shown(&held), ["/f", "/c"]);
+
+        // Nothing new: the rows keep what they show
+        held.refresh(&recent, start + hold * 3);
+        assert_eq!(shown(&held), ["/f", "/c"]);
+    }
+
+    #[test]
+    fn test_broken_template_degrades_to_plain_lines() {
+        // A placeholder without a key makes the template invalid
+        let display = ProgressDisplay::with_label("Deleted: {:}", Verbosity::Standard, Some(10));
+        assert!(display.is_err());
+
+        let output = ProgressOutput::tui_or_lines(display.map_err(|e| e.to_string()));
+        assert!(matches!(output, ProgressOutput::Lines { .. }));
+        let progress = RemoveProgress::new();
+        progress.inc_deleted(Path::new("/tmp/a"));
+        output.update(&progress, false, Some(0));
+        output.finish(&progress, false, Some(0));
+    }
+
+    #[test]
+    fn test_error_cache_shared() {
+        let progress = RemoveProgress::new();
+        progress.inc_error(
+            Path::new("/tmp/x"),
+            ErrorCategory::Other,
+            "boom".to_string(),
+        );
+
+        let display = ProgressDisplay::new(Verbosity::Simple, false, None).unwrap();
+        display.update(&progress, false, None);
+
+        let errors = progress.get_error_files();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].1, "boom");
+    }
+
+    #[test]
+    fn test_captured_errors_are_withdrawn_exactly() {
+        let progress = RemoveProgress::new();
+        let fail = |path: &str| {
+            progress.inc_error(Path::new(path), ErrorCategory::Other, "boom".to_string());
+        };
+        fail("/tmp/before");
+        let ((), captured) = capture_errors(|| {
+            fail("/tmp/a");
+            fail("/tmp/b");
+        });
+        fail("/tmp/after");
+        let paths: Vec<&Path> = captured.iter().map(|(p, _)| &**p).collect();
+        assert_eq!(paths, [Path::new("/tmp/a"), Path::new("/tmp/b")]);
+
+        for (path, category) in &captured {
+            progress.dec_error(path, *category);
+        }
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 2);
+        let groups = progress.error_groups();
+        assert_eq!(
+            (groups[0].1, &*groups[0].2[0]),
+            (2, Path::new("/tmp/before"))
+        );
+        let left: Vec<_> = progress
+            .get_error_files()
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect();
+        assert_eq!(
+            left,
+            [Path::new("/tmp/before"), Path::new("/tmp/after")].map(Arc::from)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_errors_are_grouped_by_category() {
+        let progress = RemoveProgress::new();
+        let fail = |path: &str, errno| {
+            let err = RemoveError::RemoveFailed(path.into(), io::Error::from_raw_os_error(errno));
+            progress.inc_error(Path::new(path), err.category(), err.to_string());
+        };
+        for i in 0..5 {
+            fail(&format!("/srv/locked/{}", i), libc::EACCES);
+        }
+        fail("/srv/ro", libc::EROFS);
+        fail("/srv/mnt", libc::EBUSY);
+        fail("/srv/gone", libc::ENOENT);
+        fail("/srv/dir", libc::ENOTEMPTY);
+        let err = RemoveError::DirNotEmpty("/srv/dir2".into());
+        progress.inc_error(Path::new("/srv/dir2"), err.category(), err.to_string());
+        fail("/srv/io", libc::EIO);
+
+        let groups = progress.error_groups();
+        let counts: Vec<_> = groups.iter().map(|(c, n, _)| (*c, *n)).collect();
+        assert_eq!(
+            counts,
+            [
+                (ErrorCategory::Permission, 6),
+                (ErrorCategory::NotEmpty, 2),
+                (ErrorCategory::NotFound, 1),
+                (ErrorCategory::Busy, 1),
+                (ErrorCategory::Other, 1),
+            ]
+        );
+        // The first ERROR_EXAMPLES paths of each are kept
+        let examples: Vec<&Path> = groups[0].2.iter().map(|p| &**p).collect();
+        assert_eq!(
+            examples,
+            ["/srv/locked/0", "/srv/locked/1", "/srv/locked/2"].map(Path::new)
+        );
+        assert_eq!(&*groups[1].2[1], Path::new("/srv/dir2"));
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 11);
+    }
+
+    #[test]
+    fn test_batched_deletions_are_exact_after_flush() {
+        let progress = RemoveProgress::new();
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let progress = &progress;
+                s.spawn(move || {
+                    let _batch = progress.batch_deletions(64);
+                    for i in 0..1000 {
+                        progress.inc_deleted(Path::new(&format!("/data/{}/{}", t, i)));
+                        progress.add_deleted_bytes(Path::new("/data"), 2);
+                    }
+                    // 15 full chunks are in, the last 40 deletions are not
+                    assert!(progress.deleted.load(Ordering::Relaxed) >= 960);
+                });
+            }
+        });
+        assert_eq!(progress.deleted.load(Ordering::Relaxed), 4000);
+        assert_eq!(progress.bytes.load(Ordering::Relaxed), 8000);
+
+        // Without a guard, or on another tracker, counts go straight in
+        let other = RemoveProgress::new();
+        let batch = progress.batch_deletions(64);
+        other.inc_deleted(Path::new("/data/x"));
+        assert_eq!(other.deleted.load(Ordering::Relaxed), 1);
+        progress.inc_deleted(Path::new("/data/x"));
+        assert_eq!(progress.deleted.load(Ordering::Relaxed), 4000);
+        drop(batch);
+        assert_eq!(progress.deleted.load(Ordering::Relaxed), 4001);
+    }
+
+    #[test]
+    fn test_peak_queue_depth_keeps_maximum() {
+        let progress = RemoveProgress::new();
+        progress.record_queue_depth(5);
+        progress.record_queue_depth(12);
+        progress.record_queue_depth(3);
+        assert_eq!(progress.peak_queue_depth.load(Ordering::Relaxed), 12);
+    }
+
+    #[test]
+    fn test_path_tally_attributes_to_root() {
+        let progress = RemoveProgress::with_options(ProgressOptions {
+            path_roots: Some(vec![PathBuf::from("/data/a"), PathBuf::from("/data/b")]),
+            ..Default::default()
+        });
+
+        progress.inc_deleted(Path::new("/data/a/x.txt"));
+        progress.add_deleted_bytes(Path::new("/data/a/x.txt"), 100);
+        progress.inc_deleted(Path::new("/data/a"));
+        progress.inc_deleted(Path::new("/data/b/y.txt"));
+        progress.add_deleted_bytes(Path::new("/data/b/y.txt"), 7);
+        progress.inc_error(
+            Path::new("/data/b/z.txt"),
+            ErrorCategory::Permission,
+            "denied".to_string(),
+        );
+        // Prefix match is component-wise, so a sibling with a shared prefix is not attributed
+        progress.inc_deleted(Path::new("/data/ab/w.txt"));
+
+        let rows: Vec<(u64, u64, u64)> = progress
+            .path_tally()
+            .unwrap()
+            .iter()
+            .map(|(_, c)| {
+                (
+                    c.items.load(Ordering::Relaxed),
+                    c.bytes.load(Ordering::Relaxed),
+                    c.errors.load(Ordering::Relaxed),
+                )
+            })
+            .collect();
+        assert_eq!(rows, vec![(2, 100, 0), (1, 7, 1)]);
+        let tally = progress.path_tally().unwrap();
+        let durations: Vec<Duration> = tally.iter().map(|(_, c)| c.duration()).collect();
+        assert!(
+            durations.iter().all(|d| *d > Duration::ZERO),
+            "{:?}",
+            durations
+        );
+        assert_eq!(progress.deleted.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn test_latency_stats_disabled_by_default() {
+        let progress = RemoveProgress::new();
+        assert!(!progress.latency_enabled());
+        progress.record_latency(Duration::from_micros(5));
+        assert!(progress.latency_stats().is_none());
+    }
+
+    #[test]
+    fn test_latency_stats_records_durations() {
+        let progress = RemoveProgress::with_options(ProgressOptions {
+            latency_stats: true,
+            ..Default::default()
+        });
+        for micros in [10, 20, 30, 40, 1000] {
+            progress.record_latency(Duration::from_micros(micros));
+        }
+        let stats = progress.latency_stats().unwrap();
+        assert_eq!(stats.histogram.len(), 5);
+        assert_eq!(stats.total, Duration::from_micros(1100));
+        // 3 significant digits of precision
+        assert!(stats.histogram.max().abs_diff(1_000_000) <= 1_000);
+        assert!(stats.histogram.min().abs_diff(10_000) <= 10);
+    }
+
+    #[test]
+    fn test_recent_cache_bounded() {
+        let progress = RemoveProgress::new();
+        for i in 0..(CACHE_LIMIT + 10) {
+            progress.inc_deleted(Path::new(&format!("/tmp/{}", i)));
+        }
+        let recent = progress.get_recent_files();
+        assert_eq!(recent.len(), CACHE_LIMIT);
+        assert_eq!(&*recent[0], Path::new("/tmp/10"));
+    }
 }
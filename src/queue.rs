@@ -1,31 +1,93 @@
-use crossbeam_channel::{bounded, Receiver, SendError, Sender};
-use std::path::Path;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use crossbeam_channel::{bounded, unbounded, Receiver, SendError, Sender};
+use std::collections::{BinaryHeap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// Files at least this large go to the size-sorted queue (`--size-aware`)
+pub const LARGE_FILE_BYTES: u64 = 64 * 1024;
+
+/// Every this many jobs a deleter takes a large file before the channel
+const LARGE_INTERLEAVE: usize = 4;
 
 /// Work item for the deletion queue
 #[derive(Debug, Clone)]
 pub enum FileJob {
-    /// A regular file to delete
-    File(Arc<Path>),
+    /// A regular file to delete, with its size in bytes from the scan
+    File { path: Arc<Path>, size: u64 },
     /// A symbolic link to delete
     Symlink(Arc<Path>),
     /// An empty directory to delete (enqueued after all children)
     EmptyDir(Arc<Path>),
 }
 
+impl FileJob {
+    /// Path of the entry to delete
+    pub fn path(&self) -> &Path {
+        match self {
+            FileJob::File { path, .. } | FileJob::Symlink(path) | FileJob::EmptyDir(path) => path,
+        }
+    }
+}
+
 /// Adaptive bounded queue for coordinating between scanner and deleter threads
+///
+/// The capacity (`--max-queue`) is the main memory knob: scanners block once it
+/// is reached, so pending jobs never exceed `capacity + scan_threads` (one
+/// in-flight `send` per blocked scanner). Together with the `ReadDir` handles and
+/// stack frames of directories currently being scanned, peak memory is
+/// O(capacity + active directory fanout) regardless of the total tree size.
+/// With `with_backpressure` scanners already park at 3/4 of the capacity.
 pub struct AdaptiveQueue {
     sender: Sender<FileJob>,
     receiver: Receiver<FileJob>,
     capacity: AtomicUsize,
     enqueued: Arc<AtomicUsize>,
     dequeued: Arc<AtomicUsize>,
+    large: Option<LargeFiles>,
+    backpressure: Option<Backpressure>,
+}
+
+/// Gate pausing scanners between two queue depths
+///
+/// Scanners park once the depth reaches `high` and stay parked until the
+/// deleters have drained it to `low`, instead of each blocking in `send` and
+/// waking for every single free slot. Resuming in one go at `low` keeps the
+/// deleters fed while the scanners refill the queue.
+struct Backpressure {
+    high: usize,
+    low: usize,
+    paused: Mutex<bool>,
+    /// Mirror of `paused` for the lock-free fast paths
+    is_paused: AtomicBool,
+    drained: Condvar,
+}
+
+/// How long a parked scanner waits before checking the depth itself, in case
+/// no deleter is left to notify it
+const BACKPRESSURE_RECHECK: Duration = Duration::from_millis(50);
+
+/// Second queue holding large files, largest first (`--size-aware`)
+///
+/// Deleters take from it in between ordinary jobs, so the slow unlinks are
+/// started early and spread over all workers instead of piling up on
+/// whichever worker happens to reach a run of them. It holds at most the
+/// channel capacity; further large files go through the channel as usual.
+struct LargeFiles {
+    threshold: u64,
+    heap: Mutex<BinaryHeap<(u64, Arc<Path>)>>,
+    /// Files taken into the heap whose deletion has not finished yet
+    outstanding: AtomicUsize,
+    /// The same, per parent directory
+    by_dir: Mutex<HashMap<PathBuf, usize>>,
 }
 
 impl AdaptiveQueue {
     /// Create a new adaptive queue with the given initial capacity
     pub fn new(initial_capacity: usize) -> Self {
+        // A zero-capacity channel would turn every send into a rendezvous
+        assert!(initial_capacity > 0, "queue capacity must be at least 1");
         let (sender, receiver) = bounded(initial_capacity);
 
         Self {
@@ -34,22 +96,201 @@ impl AdaptiveQueue {
             capacity: AtomicUsize::new(initial_capacity),
             enqueued: Arc::new(AtomicUsize::new(0)),
             dequeued: Arc::new(AtomicUsize::new(0)),
+            large: None,
+            backpressure: None,
+        }
+    }
+
+    /// Park scanners in `wait_if_full` at 3/4 of the capacity until the
+    /// deleters have drained the queue to 1/4 (a no-op below 4 slots)
+    pub fn with_backpressure(mut self) -> Self {
+        let capacity = self.capacity();
+        let (high, low) = (capacity / 4 * 3, capacity / 4);
+        if low > 0 {
+            self.backpressure = Some(Backpressure {
+                high,
+                low,
+                paused: Mutex::new(false),
+                is_paused: AtomicBool::new(false),
+                drained: Condvar::new(),
+            });
+        }
+        self
+    }
+
+    /// Route files of at least `threshold` bytes to a size-sorted second
+    /// queue (`--size-aware`); see `pop_large`
+    pub fn with_large_files(mut self, threshold: u64) -> Self {
+        self.large = Some(LargeFiles {
+            threshold,
+            heap: Mutex::new(BinaryHeap::new()),
+            outstanding: AtomicUsize::new(0),
+            by_dir: Mutex::new(HashMap::new()),
+        });
+        self
+    }
+
+    /// Create a queue that never blocks, for collecting jobs on one thread
+    /// (see `scanner::scan_to_vec`); its capacity is reported as `usize::MAX`
+    #[allow(dead_code)]
+    pub fn unbounded() -> Self {
+        let (sender, receiver) = unbounded();
+
+        Self {
+            sender,
+            receiver,
+            capacity: AtomicUsize::new(usize::MAX),
+            enqueued: Arc::new(AtomicUsize::new(0)),
+            dequeued: Arc::new(AtomicUsize::new(0)),
+            large: None,
+            backpressure: None,
         }
     }
 
     /// Send a job to the queue (blocking if full)
     pub fn send(&self, job: FileJob) -> Result<(), SendError<FileJob>> {
         self.enqueued.fetch_add(1, Ordering::Relaxed);
+        let job = match self.push_large(job) {
+            Some(job) => job,
+            None => return Ok(()),
+        };
         self.sender.send(job)
     }
 
-    /// Try to send a job without blocking
-    pub fn try_send(&self, job: FileJob) -> Result<(), crossbeam_channel::TrySendError<FileJob>> {
-        self.enqueued.fetch_add(1, Ordering::Relaxed);
-        self.sender.try_send(job)
+    /// Block a scanner while the queue is above its high-water mark, until
+    /// it has drained to the low-water mark (`with_backpressure`)
+    pub fn wait_if_full(&self) {
+        let Some(gate) = &self.backpressure else {
+            return;
+        };
+        if !gate.is_paused.load(Ordering::Acquire) && self.depth() < gate.high {
+            return;
+        }
+        let mut paused = gate.paused.lock().unwrap();
+        if self.depth() >= gate.high {
+            *paused = true;
+            gate.is_paused.store(true, Ordering::Release);
+        }
+        while *paused {
+            paused = gate
+                .drained
+                .wait_timeout(paused, BACKPRESSURE_RECHECK)
+                .unwrap()
+                .0;
+            if *paused && self.depth() <= gate.low {
+                *paused = false;
+                gate.is_paused.store(false, Ordering::Release);
+                gate.drained.notify_all();
+            }
+        }
+    }
+
+    /// Wake the parked scanners once a deleter has drained the queue to the
+    /// low-water mark; call after taking a job
+    pub fn notify_drained(&self) {
+        let Some(gate) = &self.backpressure else {
+            return;
+        };
+        if !gate.is_paused.load(Ordering::Acquire) || self.depth() > gate.low {
+            return;
+        }
+        let mut paused = gate.paused.lock().unwrap();
+        if *paused {
+            *paused = false;
+            gate.is_paused.store(false, Ordering::Release);
+            gate.drained.notify_all();
+        }
+    }
+
+    /// Depth at which scanners stop adding jobs: the high-water mark with
+    /// backpressure, otherwise the capacity
+    pub fn high_water(&self) -> usize {
+        self.backpressure
+            .as_ref()
+            .map_or_else(|| self.capacity(), |gate| gate.high)
+    }
+
+    /// Put a large file on the size-sorted queue if there is room; gives the
+    /// job back if it belongs in the channel
+    fn push_large(&self, job: FileJob) -> Option<FileJob> {
+        let (Some(large), FileJob::File { path, size }) = (&self.large, &job) else {
+            return Some(job);
+        };
+        if *size < large.threshold {
+            return Some(job);
+        }
+        let mut heap = large.heap.lock().unwrap();
+        if heap.len() >= self.capacity() {
+            return Some(job);
+        }
+        large.outstanding.fetch_add(1, Ordering::Relaxed);
+        if let Some(dir) = path.parent() {
+            let mut by_dir = large.by_dir.lock().unwrap();
+            *by_dir.entry(dir.to_path_buf()).or_default() += 1;
+        }
+        heap.push((*size, path.clone()));
+        None
+    }
+
+    /// Take the largest file from the size-sorted queue, if any
+    ///
+    /// The caller must call `large_done` once the file has been dealt with.
+    pub fn pop_large(&self) -> Option<FileJob> {
+        let (size, path) = self.large.as_ref()?.heap.lock().unwrap().pop()?;
+        self.dequeued.fetch_add(1, Ordering::Relaxed);
+        Some(FileJob::File { path, size })
+    }
+
+    /// The next large file for a deleter, if one is due: once every
+    /// `LARGE_INTERLEAVE` jobs, and whenever the channel has nothing ready
+    ///
+    /// `turn` is the deleter's own job counter, advanced here.
+    pub fn next_large(&self, turn: &mut usize) -> Option<FileJob> {
+        *turn = (*turn + 1) % LARGE_INTERLEAVE;
+        if *turn == 0 || self.receiver.is_empty() {
+            self.pop_large()
+        } else {
+            None
+        }
+    }
+
+    /// Mark a file taken with `pop_large` as deleted (or failed)
+    pub fn large_done(&self, path: &Path) {
+        let Some(large) = &self.large else {
+            return;
+        };
+        if let Some(dir) = path.parent() {
+            let mut by_dir = large.by_dir.lock().unwrap();
+            if let Some(count) = by_dir.get_mut(dir) {
+                *count -= 1;
+                if *count == 0 {
+                    by_dir.remove(dir);
+                }
+            }
+        }
+        large.outstanding.fetch_sub(1, Ordering::Release);
+    }
+
+    /// Number of large files queued or being deleted
+    #[allow(dead_code)]
+    pub fn large_outstanding(&self) -> usize {
+        self.large
+            .as_ref()
+            .map_or(0, |large| large.outstanding.load(Ordering::Acquire))
+    }
+
+    /// Number of large files directly in `dir` queued or being deleted
+    ///
+    /// A directory's large files are queued before the directory itself, so
+    /// once this drops to zero none of them can still be in its way.
+    pub fn large_outstanding_in(&self, dir: &Path) -> usize {
+        self.large.as_ref().map_or(0, |large| {
+            large.by_dir.lock().unwrap().get(dir).copied().unwrap_or(0)
+        })
     }
 
     /// Receive a job from the queue (blocking)
+    #[cfg(test)]
     pub fn recv(&self) -> Result<FileJob, crossbeam_channel::RecvError> {
         let job = self.receiver.recv()?;
         self.dequeued.fetch_add(1, Ordering::Relaxed);
@@ -85,11 +326,6 @@ impl AdaptiveQueue {
         self.capacity.load(Ordering::Relaxed)
     }
 
-    /// Get a reference to the enqueued counter (for progress tracking)
-    pub fn enqueued_counter(&self) -> Arc<AtomicUsize> {
-        self.enqueued.clone()
-    }
-
     /// Check if the queue is empty
     pub fn is_empty(&self) -> bool {
         self.depth() == 0
@@ -105,10 +341,15 @@ mod tests {
         let queue = AdaptiveQueue::new(10);
         let path: Arc<Path> = Arc::from(Path::new("/tmp/test.txt"));
 
-        queue.send(FileJob::File(path.clone())).unwrap();
+        queue
+            .send(FileJob::File {
+                path: path.clone(),
+                size: 0,
+            })
+            .unwrap();
 
         match queue.recv().unwrap() {
-            FileJob::File(p) => assert_eq!(p, path),
+            FileJob::File { path: p, .. } => assert_eq!(p, path),
             _ => panic!("Wrong job type"),
         }
     }
@@ -122,9 +363,24 @@ mod tests {
         let path2: Arc<Path> = Arc::from(Path::new("/tmp/2"));
         let path3: Arc<Path> = Arc::from(Path::new("/tmp/3"));
 
-        queue.send(FileJob::File(path1)).unwrap();
-        queue.send(FileJob::File(path2)).unwrap();
-        queue.send(FileJob::File(path3)).unwrap();
+        queue
+            .send(FileJob::File {
+                path: path1,
+                size: 0,
+            })
+            .unwrap();
+        queue
+            .send(FileJob::File {
+                path: path2,
+                size: 0,
+            })
+            .unwrap();
+        queue
+            .send(FileJob::File {
+                path: path3,
+                size: 0,
+            })
+            .unwrap();
 
         assert_eq!(queue.depth(), 3);
 
@@ -136,6 +392,88 @@ mod tests {
         assert_eq!(queue.depth(), 0);
     }
 
+    #[test]
+    #[should_panic(expected = "queue capacity must be at least 1")]
+    fn test_zero_capacity_rejected() {
+        AdaptiveQueue::new(0);
+    }
+
+    #[test]
+    fn test_large_files_come_out_largest_first() {
+        let queue = AdaptiveQueue::new(2).with_large_files(100);
+        let file = |name: &str, size| FileJob::File {
+            path: Arc::from(Path::new(name)),
+            size,
+        };
+        queue.send(file("/tmp/small", 10)).unwrap();
+        queue.send(file("/tmp/big", 200)).unwrap();
+        queue.send(file("/tmp/huge", 500)).unwrap();
+        // The size-sorted queue is full, so this one takes the channel
+        queue.send(file("/tmp/overflow", 300)).unwrap();
+        assert_eq!(queue.depth(), 4);
+        assert_eq!(queue.large_outstanding(), 2);
+
+        let popped: Vec<_> = std::iter::from_fn(|| queue.pop_large()).collect();
+        assert_eq!(popped[0].path(), Path::new("/tmp/huge"));
+        assert_eq!(popped[1].path(), Path::new("/tmp/big"));
+        assert_eq!(popped.len(), 2);
+        assert_eq!(queue.recv().unwrap().path(), Path::new("/tmp/small"));
+        assert_eq!(queue.recv().unwrap().path(), Path::new("/tmp/overflow"));
+        assert!(queue.is_empty());
+
+        // Outstanding until the deleter reports back
+        assert_eq!(queue.large_outstanding(), 2);
+        assert_eq!(queue.large_outstanding_in(Path::new("/tmp")), 2);
+        assert_eq!(queue.large_outstanding_in(Path::new("/var")), 0);
+        queue.large_done(popped[0].path());
+        queue.large_done(popped[1].path());
+        assert_eq!(queue.large_outstanding(), 0);
+        assert_eq!(queue.large_outstanding_in(Path::new("/tmp")), 0);
+    }
+
+    #[test]
+    fn test_backpressure_parks_scanner_until_drained() {
+        use std::sync::atomic::AtomicUsize;
+        use std::thread;
+
+        // High-water mark 6, low-water mark 2
+        let queue = Arc::new(AdaptiveQueue::new(8).with_backpressure());
+        assert_eq!(queue.high_water(), 6);
+        let sent = Arc::new(AtomicUsize::new(0));
+        let scanner = {
+            let (queue, sent) = (queue.clone(), sent.clone());
+            thread::spawn(move || {
+                for i in 0..10 {
+                    queue.wait_if_full();
+                    queue
+                        .send(FileJob::EmptyDir(Arc::from(Path::new(&format!("/d{}", i)))))
+                        .unwrap();
+                    sent.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+        };
+
+        // Parked at the high-water mark, although the channel has room
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(sent.load(Ordering::SeqCst), 6);
+
+        // Still parked above the low-water mark
+        for _ in 0..3 {
+            queue.recv().unwrap();
+            queue.notify_drained();
+        }
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(sent.load(Ordering::SeqCst), 6);
+
+        // Resumes at 2 and refills to the high-water mark
+        queue.recv().unwrap();
+        queue.notify_drained();
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(sent.load(Ordering::SeqCst), 10);
+        assert_eq!(queue.depth(), 6);
+        scanner.join().unwrap();
+    }
+
     #[test]
     fn test_empty_dir_variant() {
         let queue = AdaptiveQueue::new(5);
@@ -4,7 +4,7 @@ use std::fs;
 use std::path::Path;
 
 use crate::config::RemoveConfig;
-use crate::errors::RemoveError;
+use crate::errors::{ErrorCategory, RemoveError};
 
 pub fn remove_symlink(path: &Path, config: &RemoveConfig) -> Result<u64, RemoveError> {
     if config.progress.is_none() {
@@ -26,7 +26,7 @@ pub fn remove_symlink(path: &Path, config: &RemoveConfig) -> Result<u64, RemoveE
             Err(e) => {
                 let err_msg = e.to_string();
                 if let Some(p) = &config.progress {
-                    p.inc_error(path, err_msg);
+                    p.inc_error(path, ErrorCategory::of_io(&e), err_msg);
                 }
                 return Err(RemoveError::RemoveFailed(path.to_path_buf(), e));
             }
@@ -57,9 +57,13 @@ pub fn remove_file(path: &Path, config: &RemoveConfig) -> Result<u64, RemoveErro
                 }
             }
             Err(e) => {
+                #[cfg(windows)]
+                if let Some(err) = crate::openfiles::held_open_error(path, &e) {
+                    return Err(counted(config, path, err));
+                }
                 let err_msg = e.to_string();
                 if let Some(p) = &config.progress {
-                    p.inc_error(path, err_msg);
+                    p.inc_error(path, ErrorCategory::of_io(&e), err_msg);
                 }
                 return Err(RemoveError::RemoveFailed(path.to_path_buf(), e));
             }
@@ -82,8 +86,13 @@ pub fn remove_directory(path: &Path, config: &RemoveConfig) -> Result<u64, Remov
         );
     }
 
-    let children =
-        fs::read_dir(path).map_err(|e| RemoveError::ReadDirFailed(path.to_path_buf(), e))?;
+    let children = fs::read_dir(path).map_err(|e| {
+        counted(
+            config,
+            path,
+            RemoveError::ReadDirFailed(path.to_path_buf(), e),
+        )
+    })?;
 
     let results: Vec<Result<u64, RemoveError>> = children
         .par_bridge()
@@ -92,7 +101,7 @@ pub fn remove_directory(path: &Path, config: &RemoveConfig) -> Result<u64, Remov
             Err(e) => {
                 let error = RemoveError::DirEntryFailed(path.to_path_buf(), e);
                 if let Some(p) = &config.progress {
-                    p.inc_error(path, error.to_string());
+                    p.inc_error(path, error.category(), error.to_string());
                 } else {
                     eprintln!("  {}", error.to_string().red().dimmed());
                 }
@@ -138,7 +147,7 @@ pub fn remove_directory(path: &Path, config: &RemoveConfig) -> Result<u64, Remov
             Err(e) => {
                 let err_msg = e.to_string();
                 if let Some(p) = &config.progress {
-                    p.inc_error(path, err_msg);
+                    p.inc_error(path, ErrorCategory::of_io(&e), err_msg);
                 }
                 return Err(RemoveError::RemoveDirFailed(path.to_path_buf(), e));
             }
@@ -161,23 +170,47 @@ pub fn fast_remove(path_ref: impl AsRef<Path>, config: &RemoveConfig) -> Result<
         config.log_check(path);
     }
 
-    let metadata = fs::symlink_metadata(path)
-        .map_err(|e| RemoveError::MetadataFailed(path.to_path_buf(), e))?;
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if config.already_gone(path, &e) => return Ok(0),
+        Err(e) => {
+            return Err(counted(
+                config,
+                path,
+                RemoveError::MetadataFailed(path.to_path_buf(), e),
+            ))
+        }
+    };
     if metadata.file_type().is_symlink() {
         remove_symlink(path, config)
     } else if metadata.is_file() {
-        remove_file(path, config)
+        let removed = remove_file(path, config)?;
+        if let Some(p) = &config.progress {
+            p.add_deleted_bytes(path, metadata.len());
+        }
+        Ok(removed)
     } else if metadata.is_dir() {
         remove_directory(path, config)
     } else {
-        Err(RemoveError::UnsupportedType(path.to_path_buf()))
+        Err(counted(
+            config,
+            path,
+            RemoveError::UnsupportedType(path.to_path_buf()),
+        ))
+    }
+}
+
+/// Count `err` against its path, as the removal functions do for theirs
+fn counted(config: &RemoveConfig, path: &Path, err: RemoveError) -> RemoveError {
+    if let Some(p) = &config.progress {
+        p.inc_error(path, err.category(), err.to_string());
     }
+    err
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::File;
     use tempfile::TempDir;
 
     #[test]
@@ -188,10 +221,8 @@ mod tests {
         assert!(test_file.exists());
 
         let config = RemoveConfig {
-            verbosity: crate::config::Verbosity::Simple,
             dry_run: true,
-            continue_on_error: false,
-            progress: None,
+            ..Default::default()
         };
         let result = remove_file(&test_file, &config);
         assert!(result.is_ok());
@@ -205,10 +236,8 @@ mod tests {
         std::fs::create_dir(&test_dir).unwrap();
 
         let config = RemoveConfig {
-            verbosity: crate::config::Verbosity::Simple,
             dry_run: true,
-            continue_on_error: false,
-            progress: None,
+            ..Default::default()
         };
         let result = fast_remove(&test_dir, &config);
         assert!(result.is_ok());
@@ -1,20 +1,44 @@
 use colored::*;
-use std::path::PathBuf;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::config::RemoveConfig;
-use crate::errors::RemoveError;
+use crate::errors::{ErrorCategory, RemoveError};
+use crate::human::{format_bytes, format_count, format_duration, format_timestamp};
+use crate::progress::{DirTiming, LatencyStats, PathTally, RemoveProgress, SkipReason, EXT_TOP_N};
+use crate::schema::{JsonObject, OutputVersion};
+use crate::table::{Align, Table};
 
-pub fn process_results(
-    results: Vec<(&PathBuf, Result<u64, RemoveError>)>,
-    config: &RemoveConfig,
-) -> (u64, u64) {
-    let mut total_errors = 0;
-    let mut total_items = 0;
+/// Exit code when `--timeout` expired before the run finished (as timeout(1))
+pub const EXIT_TIMED_OUT: i32 = 124;
 
+/// Exit code when the pre-scan found more than `--abort-over-bytes`; nothing
+/// has been deleted
+pub const EXIT_OVER_BYTES: i32 = 2;
+
+/// Exit code when a dry run found nothing that would be deleted, so scripts
+/// can tell an empty match from a real one
+pub const EXIT_NOTHING_MATCHED: i32 = 3;
+
+/// Print `err`'s hint, if it has one, dimmed on the line below it
+pub fn print_hint(err: &RemoveError) {
+    if let Some(hint) = err.hint() {
+        eprintln!("  {}", format!("hint: {}", hint).dimmed());
+    }
+}
+
+/// Report each path's outcome from the recursive engine
+///
+/// Only the per-path lines: the items and errors themselves are counted in
+/// `RemoveProgress` as they happen, which the summary and exit status read.
+pub fn process_results(results: Vec<(&PathBuf, Result<u64, RemoveError>)>, config: &RemoveConfig) {
     for (path, result) in results {
         match result {
             Ok(count) => {
-                total_items += count;
                 if (count > 0 || config.verbosity.is_verbose()) && config.progress.is_none() {
                     println!(
                         "{} {:?} ({} {} {})",
@@ -35,47 +59,684 @@ pub fn process_results(
                 }
             }
             Err(e) => {
-                total_errors += 1;
                 eprintln!(
                     "{} {:?}: {}",
                     "Failed to remove".red(),
                     path,
                     e.to_string().red()
                 );
+                print_hint(&e);
             }
         }
     }
+}
+
+pub fn print_per_path_summary(tally: &PathTally, dry_run: bool) {
+    let rows: Vec<(String, u64, u64, u64)> = tally
+        .iter()
+        .map(|(path, c)| {
+            (
+                path.display().to_string(),
+                c.items.load(Ordering::Relaxed),
+                c.bytes.load(Ordering::Relaxed),
+                c.errors.load(Ordering::Relaxed),
+            )
+        })
+        .collect();
+    let width = rows
+        .iter()
+        .map(|(path, ..)| path.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    let items_header = if dry_run { "Would delete" } else { "Deleted" };
+
+    println!("\n{}", "Per-path summary:".bold());
+    println!(
+        "  {:<width$}  {:>12}  {:>14}  {:>8}",
+        "Path",
+        items_header,
+        "Bytes",
+        "Errors",
+        width = width
+    );
+    for (path, items, bytes, errors) in rows {
+        let line = format!(
+            "  {:<width$}  {:>12}  {:>14}  {:>8}",
+            path,
+            items,
+            bytes,
+            errors,
+            width = width
+        );
+        if errors > 0 {
+            println!("{}", line.red());
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+/// `--summary-table`: each top-level path's results in aligned columns,
+/// with sizes and times for people
+pub fn print_summary_table(tally: &PathTally, dry_run: bool) {
+    let mut table = Table::new(&[
+        ("Path", Align::Left),
+        (if dry_run { "Would delete" } else { "Items" }, Align::Right),
+        ("Bytes", Align::Right),
+        ("Errors", Align::Right),
+        ("Done after", Align::Right),
+    ]);
+    let mut failed = Vec::new();
+    for (path, c) in tally.iter() {
+        let errors = c.errors.load(Ordering::Relaxed);
+        failed.push(errors > 0);
+        table.row(vec![
+            path.display().to_string(),
+            format_count(c.items.load(Ordering::Relaxed)),
+            format_bytes(c.bytes.load(Ordering::Relaxed)),
+            format_count(errors),
+            format_duration(c.duration()),
+        ]);
+    }
+
+    println!("\n{}", "Summary by path:".bold());
+    let mut lines = table.render().into_iter();
+    if let Some(header) = lines.next() {
+        println!("  {}", header.bold());
+    }
+    for (line, failed) in lines.zip(failed) {
+        if failed {
+            println!("  {}", line.red());
+        } else {
+            println!("  {}", line);
+        }
+    }
+}
+
+/// Format a latency given in nanoseconds with a unit suited to its magnitude
+fn format_latency(nanos: u64) -> String {
+    if nanos < 1_000 {
+        format!("{}ns", nanos)
+    } else if nanos < 1_000_000 {
+        format!("{:.1}µs", nanos as f64 / 1_000.0)
+    } else if nanos < 1_000_000_000 {
+        format!("{:.1}ms", nanos as f64 / 1_000_000.0)
+    } else {
+        format!("{:.2}s", nanos as f64 / 1_000_000_000.0)
+    }
+}
+
+pub fn print_latency_stats(stats: &LatencyStats) {
+    let h = &stats.histogram;
+    println!("\n{}", "Removal latency:".bold());
+    if h.is_empty() {
+        println!("  no removals recorded");
+        return;
+    }
+    println!(
+        "  min {} | mean {} | p50 {} | p99 {} | max {}",
+        format_latency(h.min()),
+        format_latency(h.mean() as u64),
+        format_latency(h.value_at_quantile(0.50)),
+        format_latency(h.value_at_quantile(0.99)),
+        format_latency(h.max())
+    );
+    println!(
+        "  {} operations, {} total",
+        h.len(),
+        format_latency(u64::try_from(stats.total.as_nanos()).unwrap_or(u64::MAX))
+    );
+}
+
+/// Print the `--profile-dirs` table of the slowest directories
+pub fn print_slowest_dirs(dirs: &[DirTiming]) {
+    println!("\n{}", "Slowest directories (scan to removal):".bold());
+    if dirs.is_empty() {
+        println!("  no directories removed");
+        return;
+    }
+    for timing in dirs {
+        println!(
+            "  {:>10}  {:>8} {}  {:?}",
+            format_latency(u64::try_from(timing.elapsed.as_nanos()).unwrap_or(u64::MAX)),
+            timing.children,
+            if timing.children == 1 {
+                "child   "
+            } else {
+                "children"
+            },
+            timing.path
+        );
+    }
+}
+
+/// Print the extensions with the most files and with the most bytes
+/// (`--stats-by-ext`), at most `EXT_TOP_N` of each
+pub fn print_extension_stats(stats: &mut [(String, u64, u64)], dry_run: bool) {
+    let verb = if dry_run {
+        "would be deleted"
+    } else {
+        "deleted"
+    };
+    println!("\n{} ({})", "Top extensions:".bold(), verb);
+    if stats.is_empty() {
+        println!("  no files {}", verb);
+        return;
+    }
+    for (title, by_bytes) in [("by count", false), ("by bytes", true)] {
+        // Ties are broken by name so the output is stable
+        stats.sort_by(|a, b| {
+            let key = |s: &(String, u64, u64)| if by_bytes { s.2 } else { s.1 };
+            key(b).cmp(&key(a)).then_with(|| a.0.cmp(&b.0))
+        });
+        println!("  {}:", title);
+        for (ext, files, bytes) in stats.iter().take(EXT_TOP_N) {
+            println!(
+                "    {:<12} {:>10} files  {:>10}",
+                ext,
+                files,
+                format_bytes(*bytes)
+            );
+        }
+    }
+}
 
-    (total_items, total_errors)
+/// Format skip counts as e.g. `12 (excluded), 3 (newer-than)`
+pub fn format_skip_breakdown(breakdown: &[(SkipReason, usize)]) -> String {
+    if breakdown.is_empty() {
+        return "0".to_string();
+    }
+    breakdown
+        .iter()
+        .map(|(reason, count)| format!("{} ({})", count, reason.label()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Lines listing errors by category, e.g.
+/// `permission denied  1,204  "/srv/a", "/srv/b", "/srv/c", …`
+pub fn format_error_groups(groups: &[(ErrorCategory, usize, Vec<Arc<Path>>)]) -> Vec<String> {
+    let mut table = Table::new(&[("", Align::Left), ("", Align::Right), ("", Align::Left)]);
+    for (category, count, examples) in groups {
+        let mut paths: Vec<String> = examples.iter().map(|path| format!("{:?}", path)).collect();
+        if *count > examples.len() {
+            paths.push("…".to_string());
+        }
+        table.row(vec![
+            category.label().to_string(),
+            format_count(*count as u64),
+            paths.join(", "),
+        ]);
+    }
+    // The columns speak for themselves; skip the blank header
+    table.render().into_iter().skip(1).collect()
+}
+
+/// List the deletions a `--dry-run --verify` run found would fail; only the
+/// most recent ones are kept
+fn print_predicted_failures(progress: &RemoveProgress, total_errors: u64) {
+    progress.with_error_files(|errors| {
+        if errors.is_empty() {
+            return;
+        }
+        eprintln!("{}", "Would fail:".bold().red());
+        if (errors.len() as u64) < total_errors {
+            eprintln!("  (last {} of {} shown)", errors.len(), total_errors);
+        }
+        for (path, message) in errors {
+            eprintln!("  {:?}: {}", path, message);
+        }
+    });
 }
 
 pub fn print_summary_and_exit(total_items: u64, total_errors: u64, config: &RemoveConfig) -> ! {
-    if config.dry_run {
+    if config.summary_only {
+        println!("{}", summary_line(total_items, total_errors, config));
+    } else if config.dry_run {
         println!("{}", "Dry run finished.".yellow().bold());
     }
 
-    if total_items > 0 || config.verbosity.is_verbose() {
+    if !config.summary_only && (total_items > 0 || config.verbosity.is_verbose()) {
+        let elapsed = config
+            .progress
+            .as_ref()
+            .map(|p| format!(" in {}", format_duration(p.elapsed())))
+            .unwrap_or_default();
         println!(
-            "\n{} {} total {} {}.",
+            "\n{} {} total {} {}{}.",
             "Summary:".bold(),
-            total_items,
+            format_count(total_items),
             if total_items == 1 { "item" } else { "items" },
             if config.dry_run {
                 "would be removed"
             } else {
                 "removed"
-            }
+            },
+            elapsed
         );
     }
 
+    if let Some(p) = &config.progress {
+        for warning in p.warnings() {
+            eprintln!("{} {}", "Warning:".yellow(), warning);
+        }
+        let breakdown = p.skipped_breakdown();
+        let kept_dirs = p.kept_dirs.load(Ordering::Relaxed);
+        if !config.summary_only && (!breakdown.is_empty() || kept_dirs > 0) {
+            println!(
+                "{} {}; {} {} kept.",
+                "Skipped:".bold(),
+                format_skip_breakdown(&breakdown),
+                kept_dirs,
+                if kept_dirs == 1 {
+                    "directory"
+                } else {
+                    "directories"
+                }
+            );
+        }
+    }
+
+    if let (true, Some(p)) = (config.dry_run && config.verify, &config.progress) {
+        print_predicted_failures(p, total_errors);
+    }
+
     if total_errors > 0 {
         eprintln!(
             "{} {} error(s) encountered.",
             "Errors:".bold().red(),
             total_errors
         );
-        std::process::exit(1);
+        if let (false, Some(p)) = (config.summary_only, &config.progress) {
+            for line in format_error_groups(&p.error_groups()) {
+                eprintln!("  {}", line);
+            }
+        }
+    }
+
+    let summary = Summary::new(total_items, total_errors, config);
+    match summary.outcome {
+        Outcome::Aborted => eprintln!(
+            "{} stopped after {} errors (--max-errors {}); the remaining entries were left in place.",
+            "Aborted:".bold().red(),
+            total_errors,
+            config.max_errors.unwrap_or_default()
+        ),
+        Outcome::TimedOut => eprintln!(
+            "{} stopped before finishing; the remaining entries were left in place.",
+            "Timed out:".bold().red()
+        ),
+        Outcome::NothingMatched => println!(
+            "{} no entries would be deleted.",
+            "Nothing matched:".bold().yellow()
+        ),
+        Outcome::Complete | Outcome::Errors => {}
+    }
+
+    if let Some(path) = &config.summary_json_file {
+        if let Err(e) = std::fs::write(path, summary.to_json(config.output_version) + "\n") {
+            eprintln!(
+                "{} Failed to write summary to {:?}: {}",
+                "Error:".red().bold(),
+                path,
+                e
+            );
+        }
+    }
+
+    if let Some(path) = &config.stats_file {
+        if let Err(e) = append_stats(path, &summary) {
+            eprintln!(
+                "{} Failed to append run statistics to {:?}: {}",
+                "Error:".red().bold(),
+                path,
+                e
+            );
+        }
+    }
+
+    std::process::exit(summary.outcome.exit_code());
+}
+
+/// Append the run's record to a CSV file (`--stats-file`), starting it with
+/// `STATS_HEADER` if it is new or empty, so one file collects every run
+fn append_stats(path: &Path, summary: &Summary) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut record = String::new();
+    if file.metadata()?.len() == 0 {
+        record.push_str(STATS_HEADER);
+        record.push('\n');
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    record.push_str(&summary.to_csv(&format_timestamp(now)));
+    record.push('\n');
+    // One write, so records of runs finishing together do not interleave
+    file.write_all(record.as_bytes())
+}
+
+/// The one line printed by `--summary-only`:
+/// `Complete: 1,501 deleted, 0 errors, 27.9 KiB freed in 157ms`
+fn summary_line(total_items: u64, total_errors: u64, config: &RemoveConfig) -> String {
+    let progress = config.progress.as_deref();
+    let bytes = progress.map_or(0, |p| p.bytes.load(Ordering::Relaxed));
+    let freed = if bytes > 0 {
+        format!(
+            ", {} {}",
+            format_bytes(bytes),
+            if config.dry_run { "to free" } else { "freed" }
+        )
+    } else {
+        String::new()
+    };
+    let elapsed = progress
+        .map(|p| format!(" in {}", format_duration(p.elapsed())))
+        .unwrap_or_default();
+    format!(
+        "{} {} {}, {} {}{}{}",
+        if config.dry_run {
+            "Dry run complete:"
+        } else {
+            "Complete:"
+        },
+        format_count(total_items),
+        if config.dry_run {
+            "would be deleted"
+        } else {
+            "deleted"
+        },
+        format_count(total_errors),
+        if total_errors == 1 { "error" } else { "errors" },
+        freed,
+        elapsed
+    )
+}
+
+/// How a run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Complete,
+    Errors,
+    /// A dry run without errors that would delete nothing
+    NothingMatched,
+    /// Stopped by `--timeout`
+    TimedOut,
+    /// Stopped by `--max-errors`
+    Aborted,
+}
+
+impl Outcome {
+    pub fn label(self) -> &'static str {
+        match self {
+            Outcome::Complete => "complete",
+            Outcome::Errors => "errors",
+            Outcome::NothingMatched => "nothing_matched",
+            Outcome::TimedOut => "timed_out",
+            Outcome::Aborted => "aborted",
+        }
+    }
+
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Complete => 0,
+            Outcome::Errors | Outcome::Aborted => 1,
+            Outcome::NothingMatched => EXIT_NOTHING_MATCHED,
+            Outcome::TimedOut => EXIT_TIMED_OUT,
+        }
+    }
+}
+
+/// Final results of a run, as written by `--summary-json-file`
+#[derive(Debug)]
+pub struct Summary {
+    pub outcome: Outcome,
+    pub dry_run: bool,
+    /// Entries removed, or scanned in a dry run
+    pub items: u64,
+    pub errors: u64,
+    pub skipped: Vec<(SkipReason, usize)>,
+    pub kept_dirs: usize,
+    pub elapsed_secs: f64,
+    /// Bytes freed, or that would be freed in a dry run
+    pub bytes: u64,
+}
+
+/// First line of a `--stats-file`
+pub const STATS_HEADER: &str = "timestamp,outcome,dry_run,items,bytes,errors,elapsed_secs";
+
+impl Summary {
+    pub fn new(total_items: u64, total_errors: u64, config: &RemoveConfig) -> Self {
+        let progress = config.progress.as_deref();
+        // A dry run's items are everything scanned; what would go is counted
+        // as deleted
+        let would_delete =
+            progress.map_or(total_items, |p| p.deleted.load(Ordering::Relaxed) as u64);
+        let outcome = if config.error_limit_exceeded() {
+            Outcome::Aborted
+        } else if config.is_cancelled() {
+            Outcome::TimedOut
+        } else if total_errors > 0 {
+            Outcome::Errors
+        } else if config.dry_run && would_delete == 0 {
+            Outcome::NothingMatched
+        } else {
+            Outcome::Complete
+        };
+        Self {
+            outcome,
+            dry_run: config.dry_run,
+            items: total_items,
+            errors: total_errors,
+            skipped: progress.map_or_else(Vec::new, RemoveProgress::skipped_breakdown),
+            kept_dirs: progress.map_or(0, |p| p.kept_dirs.load(Ordering::Relaxed)),
+            elapsed_secs: progress.map_or(0.0, |p| p.elapsed().as_secs_f64()),
+            bytes: progress.map_or(0, |p| p.bytes.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// The summary as a `--stats-file` record, without a newline
+    pub fn to_csv(&self, timestamp: &str) -> String {
+        format!(
+            "{},{},{},{},{},{},{:.3}",
+            timestamp,
+            self.outcome.label(),
+            self.dry_run,
+            self.items,
+            self.bytes,
+            self.errors,
+            self.elapsed_secs
+        )
+    }
+
+    /// The summary as a single-line JSON object in schema `version`
+    pub fn to_json(&self, version: OutputVersion) -> String {
+        let skipped: Vec<String> = self
+            .skipped
+            .iter()
+            .map(|(reason, count)| format!("\"{}\":{}", reason.label(), count))
+            .collect();
+        match version {
+            OutputVersion::V1 => JsonObject::new(version)
+                .string("outcome", self.outcome.label())
+                .raw("exit_code", self.outcome.exit_code())
+                .raw("dry_run", self.dry_run)
+                .raw("items", self.items)
+                .raw("errors", self.errors)
+                .raw("skipped", format!("{{{}}}", skipped.join(",")))
+                .raw("kept_dirs", self.kept_dirs)
+                .raw("elapsed_secs", format!("{:.3}", self.elapsed_secs))
+                .finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_summary_line() {
+        let progress = RemoveProgress::new();
+        progress.add_deleted_bytes(Path::new("/data/a.bin"), 1536);
+        let config = RemoveConfig {
+            progress: Some(progress),
+            summary_only: true,
+            ..Default::default()
+        };
+        let line = summary_line(1501, 1, &config);
+        assert!(
+            line.starts_with("Complete: 1,501 deleted, 1 error, 1.5 KiB freed in "),
+            "{}",
+            line
+        );
+
+        let config = RemoveConfig {
+            dry_run: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            summary_line(3, 0, &config),
+            "Dry run complete: 3 would be deleted, 0 errors"
+        );
+    }
+
+    #[test]
+    fn test_summary_json() {
+        let progress = RemoveProgress::new();
+        progress.inc_skipped(SkipReason::Hidden);
+        progress.inc_kept_dir();
+        let config = RemoveConfig {
+            progress: Some(progress),
+            max_errors: Some(2),
+            ..Default::default()
+        };
+
+        let summary = Summary::new(5, 1, &config);
+        assert_eq!(summary.outcome, Outcome::Errors);
+        let json = summary.to_json(OutputVersion::V1);
+        assert!(
+            json.starts_with("{\"schema_version\":1,\"outcome\":\"errors\",\"exit_code\":1,\"dry_run\":false,\"items\":5,\"errors\":1,\"skipped\":{\"hidden\":1},\"kept_dirs\":1,\"elapsed_secs\":"),
+            "{}",
+            json
+        );
+        // The fields documented for version 1, in order
+        assert_eq!(
+            crate::schema::keys(&json),
+            [
+                "schema_version",
+                "outcome",
+                "exit_code",
+                "dry_run",
+                "items",
+                "errors",
+                "skipped",
+                "kept_dirs",
+                "elapsed_secs"
+            ]
+        );
+
+        // Errors are read from the progress counters for --max-errors
+        let progress = config.progress.as_ref().unwrap();
+        progress.errors.store(3, Ordering::Relaxed);
+        let summary = Summary::new(5, 3, &config);
+        assert_eq!(summary.outcome, Outcome::Aborted);
+        assert_eq!(summary.outcome.exit_code(), 1);
+    }
+
+    #[test]
+    fn test_dry_run_matching_nothing() {
+        let progress = RemoveProgress::new();
+        let config = RemoveConfig {
+            progress: Some(progress.clone()),
+            dry_run: true,
+            ..Default::default()
+        };
+        // Scanned entries alone do not count
+        let summary = Summary::new(4, 0, &config);
+        assert_eq!(summary.outcome, Outcome::NothingMatched);
+        assert_eq!(summary.outcome.exit_code(), EXIT_NOTHING_MATCHED);
+        assert!(summary
+            .to_json(OutputVersion::V1)
+            .contains("\"exit_code\":3"));
+
+        progress.inc_deleted(Path::new("/tmp/a"));
+        assert_eq!(Summary::new(4, 0, &config).outcome, Outcome::Complete);
+
+        // A real run that deletes nothing still succeeds
+        let config = RemoveConfig {
+            progress: Some(RemoveProgress::new()),
+            ..Default::default()
+        };
+        assert_eq!(Summary::new(0, 0, &config).outcome, Outcome::Complete);
+    }
+
+    #[test]
+    fn test_stats_records_accumulate() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("stats.csv");
+        let progress = RemoveProgress::new();
+        progress.bytes.store(4096, Ordering::Relaxed);
+        let config = RemoveConfig {
+            progress: Some(progress),
+            ..Default::default()
+        };
+
+        append_stats(&path, &Summary::new(12, 0, &config)).unwrap();
+        append_stats(&path, &Summary::new(3, 1, &config)).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], STATS_HEADER);
+        let first: Vec<_> = lines[1].split(',').collect();
+        assert!(first[0].ends_with('Z'), "{}", lines[1]);
+        assert_eq!(first[1..6], ["complete", "false", "12", "4096", "0"]);
+        let second: Vec<_> = lines[2].split(',').collect();
+        assert_eq!(second[1..6], ["errors", "false", "3", "4096", "1"]);
+    }
+
+    #[test]
+    fn test_format_skip_breakdown() {
+        assert_eq!(format_skip_breakdown(&[]), "0");
+        assert_eq!(
+            format_skip_breakdown(&[(SkipReason::Excluded, 12), (SkipReason::NewerThan, 3)]),
+            "12 (excluded), 3 (newer-than)"
+        );
     }
 
-    std::process::exit(0);
+    #[test]
+    fn test_format_error_groups() {
+        let paths = |names: &[&str]| {
+            names
+                .iter()
+                .map(|&name| Arc::from(Path::new(name)))
+                .collect()
+        };
+        let lines = format_error_groups(&[
+            (
+                ErrorCategory::Permission,
+                1204,
+                paths(&["/srv/a", "/srv/b", "/srv/c"]),
+            ),
+            (ErrorCategory::NotEmpty, 2, paths(&["/srv/d", "/srv/e"])),
+        ]);
+        assert_eq!(
+            lines,
+            [
+                r#"permission denied  1,204  "/srv/a", "/srv/b", "/srv/c", …"#,
+                r#"not empty              2  "/srv/d", "/srv/e""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_latency_units() {
+        assert_eq!(format_latency(850), "850ns");
+        assert_eq!(format_latency(12_300), "12.3µs");
+        assert_eq!(format_latency(2_500_000), "2.5ms");
+        assert_eq!(format_latency(3_210_000_000), "3.21s");
+    }
 }
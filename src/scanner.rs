@@ -1,10 +1,21 @@
+use colored::*;
 use rayon::prelude::*;
+use std::cell::Cell;
+use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
-use std::sync::Arc;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 
+use crate::cli::{DeleteOrder, EntryType, DEFAULT_MAX_SYMLINK_DEPTH};
 use crate::config::RemoveConfig;
 use crate::errors::RemoveError;
+use crate::expr::glob_match;
+use crate::ignores::IgnoreStack;
+use crate::path::follow_symlink_chain;
+use crate::progress::{RemoveProgress, SkipReason};
 use crate::queue::{AdaptiveQueue, FileJob};
 
 /// Recursively scan a path and enqueue all files/directories for deletion
@@ -12,71 +23,911 @@ use crate::queue::{AdaptiveQueue, FileJob};
 /// This function traverses the file system tree in parallel, enqueuing work items
 /// for the deleter threads to process. Directories are enqueued AFTER all their
 /// children to ensure correct deletion order.
+///
+/// Memory stays bounded by the queue capacity: `send` blocks when the queue is
+/// full, so only the directories currently being traversed hold state.
+///
+/// Returns whether the path was enqueued in full. A directory with skipped
+/// descendants is kept (its `EmptyDir` job is never enqueued), which makes its
+/// parent non-removable too.
 pub fn scan_path(
     path: &Path,
     queue: &AdaptiveQueue,
     config: &RemoveConfig,
+) -> Result<bool, RemoveError> {
+    let visited = VisitedSet::default();
+    let ctx = ScanContext {
+        ignores: IgnoreStack::default(),
+        visited: &visited,
+        ancestors: Vec::new(),
+    };
+    scan_entry(path, queue, config, &ctx)
+}
+
+/// Scan a path given on the command line
+///
+/// Like `scan_path`, except that with `--contents-only`, or when given as
+/// `link/`, a directory is emptied but its own job is never enqueued.
+pub fn scan_root(
+    path: &Path,
+    queue: &AdaptiveQueue,
+    config: &RemoveConfig,
+) -> Result<bool, RemoveError> {
+    if keeps_root(path, config) && fs::symlink_metadata(path).is_ok_and(|m| m.is_dir()) {
+        scan_contents(path, queue, config)
+    } else {
+        scan_path(path, queue, config)
+    }
+}
+
+/// Scan `path` on the current thread and return its jobs in deletion order
+///
+/// Deterministic counterpart of `scan_path` for tests and tools: entries are
+/// visited one at a time in name order (as with `--sequential`, or in the
+/// configured `--delete-order`) and collected instead of being handed to
+/// deleters. Children always precede their parent directory.
+#[allow(dead_code)]
+pub fn scan_to_vec(path: &Path, config: &RemoveConfig) -> Result<Vec<FileJob>, RemoveError> {
+    let config = RemoveConfig {
+        sequential: true,
+        ..config.clone()
+    };
+    let queue = AdaptiveQueue::unbounded();
+    scan_path(path, &queue, &config)?;
+    Ok(std::iter::from_fn(|| queue.try_recv().ok()).collect())
+}
+
+/// Capacity of the private queue `count_jobs` and `collect_jobs` drain
+const COUNT_QUEUE_CAPACITY: usize = 10_000;
+
+/// Version control metadata left in place by `--exclude-vcs`
+const VCS_DIRS: [&str; 3] = [".git", ".hg", ".svn"];
+
+/// What a scan would enqueue, as counted by `count_jobs`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JobCount {
+    /// Files, symlinks and directories
+    pub entries: u64,
+    /// Total size of the regular files
+    pub bytes: u64,
+}
+
+/// Count the jobs a scan of `path` would enqueue, and the bytes they hold,
+/// deleting nothing (`--pre-scan`, `--abort-over-bytes`)
+///
+/// Runs the ordinary scan, so filters, ignore files and keep lists are
+/// honoured, against a private queue drained by a counting thread. Progress,
+/// warnings and skip messages are not reported; the real scan does that.
+pub fn count_jobs(path: &Path, config: &RemoveConfig) -> Result<JobCount, RemoveError> {
+    let config = RemoveConfig {
+        progress: Some(RemoveProgress::new()),
+        diff: false,
+        ..config.clone()
+    };
+    let mut count = JobCount::default();
+    drain_scan(path, &config, |job| {
+        count.entries += 1;
+        if let FileJob::File { size, .. } = job {
+            count.bytes += size;
+        }
+    })?;
+    Ok(count)
+}
+
+/// Pick up to `n` regular files under `path` to delete as a probe, from as
+/// many directories as possible (`--probe`)
+///
+/// The first file of each directory is taken until there are `n`; with
+/// fewer directories than that, the rest comes from directories already
+/// used. The scan is quiet, like `count_jobs`, and stops as soon as `n`
+/// directories have a file, so a huge tree is not scanned in full.
+pub fn sample_files(
+    path: &Path,
+    config: &RemoveConfig,
+    n: usize,
+) -> Result<Vec<FileJob>, RemoveError> {
+    let config = RemoveConfig {
+        progress: Some(RemoveProgress::new()),
+        diff: false,
+        sequential: true,
+        cancelled: Arc::new(AtomicBool::new(false)),
+        ..config.clone()
+    };
+    let mut dirs = HashSet::new();
+    let mut picked = Vec::new();
+    let mut spare = Vec::new();
+    drain_scan(path, &config, |job| {
+        let FileJob::File { path: file, .. } = &job else {
+            return;
+        };
+        if picked.len() == n {
+            return;
+        }
+        if dirs.insert(file.parent().map(Path::to_path_buf)) {
+            picked.push(job);
+            if picked.len() == n {
+                config.cancelled.store(true, Ordering::Relaxed);
+            }
+        } else if spare.len() < n {
+            spare.push(job);
+        }
+    })?;
+    let fill = n - picked.len();
+    picked.extend(spare.into_iter().take(fill));
+    Ok(picked)
+}
+
+/// Scan `path` and hand its jobs, in deletion order, to `sink` (`--two-phase`)
+///
+/// Unlike `count_jobs` this is the real scan: progress, warnings and skips
+/// are reported as usual. Nothing is deleted.
+pub fn collect_jobs(
+    path: &Path,
+    config: &RemoveConfig,
+    sink: impl FnMut(FileJob) + Send,
+) -> Result<(), RemoveError> {
+    drain_scan(path, config, sink)
+}
+
+/// Run `scan_root` against a private queue drained into `sink` by another
+/// thread
+fn drain_scan(
+    path: &Path,
+    config: &RemoveConfig,
+    mut sink: impl FnMut(FileJob) + Send,
 ) -> Result<(), RemoveError> {
+    let queue = AdaptiveQueue::new(COUNT_QUEUE_CAPACITY);
+    let scan_done = AtomicBool::new(false);
+    std::thread::scope(|s| {
+        let drainer = s.spawn(|| loop {
+            match queue.recv_timeout(Duration::from_millis(10)) {
+                Ok(job) => sink(job),
+                Err(_) if scan_done.load(Ordering::Acquire) && queue.is_empty() => break,
+                Err(_) => {}
+            }
+        });
+        let result = scan_root(path, &queue, config);
+        scan_done.store(true, Ordering::Release);
+        drainer.join().expect("draining thread panicked");
+        result.map(|_| ())
+    })
+}
+
+/// Scan and enqueue the entries of directory `path`, but not `path` itself
+///
+/// Used to pick up entries that appeared after the first scan (`--force`)
+/// and to empty the given directories (`--contents-only`). Ignore files
+/// above `path` are not consulted.
+pub fn scan_contents(
+    path: &Path,
+    queue: &AdaptiveQueue,
+    config: &RemoveConfig,
+) -> Result<bool, RemoveError> {
+    let visited = VisitedSet::default();
+    let mut ctx = ScanContext {
+        ignores: IgnoreStack::default(),
+        visited: &visited,
+        ancestors: Vec::new(),
+    };
+    if config.follow_symlinks {
+        if let Ok(metadata) = fs::metadata(path) {
+            ctx.ancestors.push(file_key(path, &metadata));
+        }
+    }
+    scan_directory(path, queue, config, &ctx)
+}
+
+/// Enqueue every directory under `path`, and `path` itself, that is empty or
+/// holds only such directories, deepest first (`--prune-empty`)
+///
+/// Symlinks are never followed. With `--contents-only`, `path` itself is
+/// kept. Entries the scan leaves alone (`--keep-list`, `--keep`,
+/// `--skip-hidden`, `--exclude-vcs`, ignore files) keep their directories
+/// here too, as does a directory the path filter rejects; the caller decides
+/// which filter that is. Under `-c` a directory that cannot be listed is
+/// reported and kept. Returns whether `path` is empty apart from such
+/// directories.
+pub fn scan_empty_dirs(
+    path: &Path,
+    queue: &AdaptiveQueue,
+    config: &RemoveConfig,
+) -> Result<bool, RemoveError> {
+    prune_empty(
+        path,
+        queue,
+        config,
+        &IgnoreStack::default(),
+        keeps_root(path, config),
+    )
+}
+
+/// Whether the top-level `path` is to be emptied but kept
+fn keeps_root(path: &Path, config: &RemoveConfig) -> bool {
+    config.contents_only || config.emptied_roots.contains(path)
+}
+
+fn prune_empty(
+    path: &Path,
+    queue: &AdaptiveQueue,
+    config: &RemoveConfig,
+    ignores: &IgnoreStack,
+    keep_self: bool,
+) -> Result<bool, RemoveError> {
+    if config.keep.as_ref().is_some_and(|keep| keep.contains(path)) {
+        skip(path, config, SkipReason::Kept);
+        return Ok(false);
+    }
+    let entries = match list_dir(path, config) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return prune_failed(
+                path,
+                RemoveError::ReadDirFailed(path.to_path_buf(), e),
+                config,
+            )
+        }
+    };
+    let ignores = if config.use_ignore_files {
+        ignores.enter(path)?
+    } else {
+        ignores.clone()
+    };
+    let mut empty = true;
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                let err = RemoveError::DirEntryFailed(path.to_path_buf(), e);
+                empty = prune_failed(path, err, config)?;
+                continue;
+            }
+        };
+        if let Some(reason) = excluded(&entry, config, &ignores) {
+            skip(&entry.path, config, reason);
+            empty = false;
+        } else if !(entry.is_dir && prune_empty(&entry.path, queue, config, &ignores, false)?) {
+            empty = false;
+        }
+    }
+    if !empty || keep_self {
+        return Ok(empty);
+    }
+    if filtered_out(path, config) {
+        return Ok(false);
+    }
+    enqueue(queue, FileJob::EmptyDir(Arc::from(path)), config)?;
+    Ok(true)
+}
+
+/// An error while looking for empty directories: with `-c` it is counted
+/// and the directory kept (`Ok(false)`), otherwise it ends the pruning
+fn prune_failed(dir: &Path, err: RemoveError, config: &RemoveConfig) -> Result<bool, RemoveError> {
+    if !config.continue_on_error {
+        return Err(err);
+    }
+    match &config.progress {
+        Some(p) => p.inc_error(dir, err.category(), err.to_string()),
+        None => eprintln!("  {}", err),
+    }
+    Ok(false)
+}
+
+/// Identity of a file system object, used to detect objects reached twice
+#[cfg(unix)]
+type FileKey = (u64, u64);
+#[cfg(not(unix))]
+type FileKey = std::path::PathBuf;
+
+#[cfg(unix)]
+fn file_key(_path: &Path, metadata: &fs::Metadata) -> FileKey {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.dev(), metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn file_key(path: &Path, _metadata: &fs::Metadata) -> FileKey {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Objects already enqueued while following symlinks, keyed by `(dev, inode)`
+#[derive(Debug, Default)]
+struct VisitedSet(Mutex<HashSet<FileKey>>);
+
+impl VisitedSet {
+    /// Returns `false` if the object was already visited
+    fn insert(&self, key: FileKey) -> bool {
+        self.0.lock().unwrap().insert(key)
+    }
+}
+
+/// Counting semaphore bounding the directory handles the scan holds open
+/// (`--max-open-dirs`)
+#[derive(Debug)]
+pub struct OpenDirLimit {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+impl OpenDirLimit {
+    pub fn new(max: usize) -> Self {
+        Self {
+            available: Mutex::new(max.max(1)),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Block until a handle may be opened
+    fn acquire(&self) -> OpenDirPermit<'_> {
+        let mut available = self.available.lock().unwrap();
+        while *available == 0 {
+            available = self.released.wait(available).unwrap();
+        }
+        *available -= 1;
+        OpenDirPermit(self)
+    }
+}
+
+struct OpenDirPermit<'a>(&'a OpenDirLimit);
+
+impl Drop for OpenDirPermit<'_> {
+    fn drop(&mut self) {
+        *self.0.available.lock().unwrap() += 1;
+        self.0.released.notify_one();
+    }
+}
+
+/// What the scan needs of a directory entry
+///
+/// A `fs::DirEntry` keeps its directory's handle open, so listings that are
+/// buffered hold these instead.
+#[derive(Debug)]
+struct DirItem {
+    path: PathBuf,
+    is_dir: bool,
+    /// Only looked up under `--skip-hidden`, since on Windows it takes a
+    /// metadata call per entry
+    hidden: bool,
+    #[cfg(unix)]
+    ino: u64,
+}
+
+impl DirItem {
+    fn new(entry: fs::DirEntry, skip_hidden: bool) -> Self {
+        Self {
+            path: entry.path(),
+            is_dir: entry.file_type().is_ok_and(|t| t.is_dir()),
+            hidden: skip_hidden && is_hidden(&entry),
+            #[cfg(unix)]
+            ino: std::os::unix::fs::DirEntryExt::ino(&entry),
+        }
+    }
+}
+
+type DirItems = Box<dyn Iterator<Item = io::Result<DirItem>> + Send>;
+
+/// List directory `path`, within `--max-open-dirs` if set
+///
+/// Under the limit the listing is read to the end and its handle closed
+/// before any child is visited, so no scan waits for a permit while holding
+/// one and deep trees cannot deadlock.
+fn list_dir(path: &Path, config: &RemoveConfig) -> io::Result<DirItems> {
+    let skip_hidden = config.skip_hidden;
+    let entries = fs::read_dir(path)?.map(move |entry| entry.map(|e| DirItem::new(e, skip_hidden)));
+    match &config.open_dirs {
+        None => Ok(Box::new(entries)),
+        Some(limit) => {
+            let _permit = limit.acquire();
+            let items: Vec<_> = entries.collect();
+            Ok(Box::new(items.into_iter()))
+        }
+    }
+}
+
+/// Traversal state handed down one branch of the scan
+#[derive(Debug, Clone)]
+struct ScanContext<'a> {
+    ignores: IgnoreStack,
+    visited: &'a VisitedSet,
+    /// Directories from the root down to this branch, tracked only while
+    /// following symlinks; a link back to one of them is a loop
+    ancestors: Vec<FileKey>,
+}
+
+impl ScanContext<'_> {
+    fn descend(&self, dir: FileKey) -> Self {
+        let mut ctx = self.clone();
+        ctx.ancestors.push(dir);
+        ctx
+    }
+}
+
+fn scan_entry(
+    path: &Path,
+    queue: &AdaptiveQueue,
+    config: &RemoveConfig,
+    ctx: &ScanContext,
+) -> Result<bool, RemoveError> {
+    // Once cancelled, nothing more is enqueued and every parent is kept
+    if config.is_cancelled() {
+        return Ok(false);
+    }
+    // Deleted by an earlier run (`--resume`), together with all it contained
+    if config
+        .already_deleted
+        .as_ref()
+        .is_some_and(|done| done.contains(path))
+    {
+        config.log_skip(path, "already deleted");
+        return Ok(true);
+    }
+
     // Increment scanned counter
     if let Some(p) = &config.progress {
         p.inc_scanned();
     }
 
+    // Protected by `--keep-list`; returning false keeps every ancestor too
+    if config.keep.as_ref().is_some_and(|keep| keep.contains(path)) {
+        skip(path, config, SkipReason::Kept);
+        return Ok(false);
+    }
+
     // Get metadata without following symlinks
-    let metadata = fs::symlink_metadata(path)
-        .map_err(|e| RemoveError::MetadataFailed(path.to_path_buf(), e))?;
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if config.already_gone(path, &e) => return Ok(true),
+        Err(e) => return Err(RemoveError::MetadataFailed(path.to_path_buf(), e)),
+    };
 
     if metadata.file_type().is_symlink() {
+        // With --follow-symlinks the target goes first, then the link itself
+        if config.follow_symlinks && !scan_symlink_target(path, queue, config, ctx)? {
+            return Ok(false);
+        }
+        if filtered_out(path, config) || other_type(path, EntryType::Symlinks, config) {
+            return Ok(false);
+        }
+        // Only a link whose target is missing, and not its parents
+        if config.broken_symlinks_only
+            && !fs::metadata(path).is_err_and(|e| e.kind() == io::ErrorKind::NotFound)
+        {
+            skip(path, config, SkipReason::ValidLink);
+            return Ok(false);
+        }
         // Enqueue symlink for deletion
-        queue
-            .send(FileJob::Symlink(Arc::from(path)))
-            .map_err(|_| RemoveError::QueueFull)?;
-    } else if metadata.is_file() {
+        enqueue(queue, FileJob::Symlink(Arc::from(path)), config)?;
+        return Ok(true);
+    }
+
+    scan_resolved(path, &metadata, queue, config, ctx)
+}
+
+/// Follow a symlink and scan the object it points to
+///
+/// Broken links, and links that never resolve, have no target to delete, so
+/// only the link itself is removed. That includes a chain of links longer
+/// than `--max-symlink-depth`, such as one that loops, which is reported as
+/// not followed. A link to a directory this branch is already inside would
+/// be scanned forever; it is a `SymlinkLoop` error and the link and its
+/// parents are kept.
+fn scan_symlink_target(
+    path: &Path,
+    queue: &AdaptiveQueue,
+    config: &RemoveConfig,
+    ctx: &ScanContext,
+) -> Result<bool, RemoveError> {
+    let max_depth = config
+        .max_symlink_depth
+        .unwrap_or(DEFAULT_MAX_SYMLINK_DEPTH);
+    let end = match follow_symlink_chain(path, max_depth) {
+        Ok(Some(end)) => end,
+        Ok(None) => return Ok(true),
+        Err(err @ RemoveError::SymlinkChainTooLong(..)) => {
+            warn(config, err.to_string());
+            return Ok(true);
+        }
+        Err(err) => {
+            if let Some(p) = &config.progress {
+                p.inc_error(path, err.category(), err.to_string());
+            }
+            return Err(err);
+        }
+    };
+    let Ok(target_metadata) = fs::metadata(&end) else {
+        return Ok(true);
+    };
+    let target =
+        fs::canonicalize(&end).map_err(|e| RemoveError::MetadataFailed(path.to_path_buf(), e))?;
+    if ctx.ancestors.contains(&file_key(&target, &target_metadata)) {
+        let err = RemoveError::SymlinkLoop(path.to_path_buf(), target);
+        if let Some(p) = &config.progress {
+            p.inc_error(path, err.category(), err.to_string());
+        }
+        return Err(err);
+    }
+    scan_resolved(&target, &target_metadata, queue, config, ctx)
+}
+
+/// Enqueue a file or directory (never a symlink) for deletion
+fn scan_resolved(
+    path: &Path,
+    metadata: &fs::Metadata,
+    queue: &AdaptiveQueue,
+    config: &RemoveConfig,
+    ctx: &ScanContext,
+) -> Result<bool, RemoveError> {
+    // When following symlinks the same object can be reached through several
+    // routes (or a cycle); only the first visit enqueues it
+    if config.follow_symlinks && !ctx.visited.insert(file_key(path, metadata)) {
+        return Ok(true);
+    }
+
+    if metadata.is_file() {
+        if filtered_out(path, config) || other_type(path, EntryType::Files, config) {
+            return Ok(false);
+        }
         // Enqueue file for deletion
-        queue
-            .send(FileJob::File(Arc::from(path)))
-            .map_err(|_| RemoveError::QueueFull)?;
+        enqueue(
+            queue,
+            FileJob::File {
+                path: Arc::from(path),
+                size: metadata.len(),
+            },
+            config,
+        )?;
+        // A tombstone stays behind, and so must every directory above it
+        if config.tombstone {
+            return Ok(false);
+        }
     } else if metadata.is_dir() {
-        // Recursively scan directory, then enqueue the directory itself
-        scan_directory(path, queue, config)?;
+        // Like rm without -r: a directory is an error, not something to descend
+        if config.no_recursive {
+            let err = RemoveError::IsADirectory(path.to_path_buf());
+            if let Some(p) = &config.progress {
+                p.inc_error(path, err.category(), err.to_string());
+            }
+            return Err(err);
+        }
+        if is_mount_point(path, metadata) {
+            if config.no_special {
+                skip(path, config, SkipReason::Special);
+                warn(
+                    config,
+                    format!("skipped mount point {:?} (--no-special)", path),
+                );
+                return Ok(false);
+            }
+            warn(
+                config,
+                format!(
+                    "{:?} is a mount point: deleting the contents of another file system",
+                    path
+                ),
+            );
+        }
+
+        let inner;
+        let ctx = if config.follow_symlinks {
+            inner = ctx.descend(file_key(path, metadata));
+            &inner
+        } else {
+            ctx
+        };
+        // Recursively scan directory, then enqueue the directory itself;
+        // with --keep-dirs it stays, however its contents went
+        if !scan_directory(path, queue, config, ctx)?
+            || config.keep_dirs
+            || filtered_out(path, config)
+            || other_type(path, EntryType::Dirs, config)
+        {
+            return Ok(false);
+        }
 
         // Enqueue directory AFTER all children have been scanned
         // This ensures children are deleted before the parent
-        queue
-            .send(FileJob::EmptyDir(Arc::from(path)))
-            .map_err(|_| RemoveError::QueueFull)?;
+        enqueue(queue, FileJob::EmptyDir(Arc::from(path)), config)?;
+    } else if let Some(kind) = special_kind(&metadata.file_type()) {
+        if config.no_special {
+            skip(path, config, SkipReason::Special);
+            warn(
+                config,
+                format!("skipped {} {:?} (--no-special)", kind, path),
+            );
+            return Ok(false);
+        }
+        if filtered_out(path, config) || other_type(path, EntryType::Files, config) {
+            return Ok(false);
+        }
+        warn(config, format!("removing {} {:?}", kind, path));
+        // Unlinked like a regular file; there is no content to account for
+        enqueue(
+            queue,
+            FileJob::File {
+                path: Arc::from(path),
+                size: 0,
+            },
+            config,
+        )?;
     } else {
         return Err(RemoveError::UnsupportedType(path.to_path_buf()));
     }
 
+    Ok(true)
+}
+
+/// Whether a directory entry is hidden: a dot name, or the hidden attribute on Windows
+fn is_hidden(entry: &fs::DirEntry) -> bool {
+    if entry.file_name().to_string_lossy().starts_with('.') {
+        return true;
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        if let Ok(metadata) = entry.metadata() {
+            return metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0;
+        }
+    }
+    false
+}
+
+/// Count and log an entry the scanner leaves in place
+fn skip(path: &Path, config: &RemoveConfig, reason: SkipReason) {
+    if let Some(p) = &config.progress {
+        p.inc_skipped(reason);
+    }
+    config.log_skip(path, reason.label());
+}
+
+/// Report a warning: kept for the summary while progress is displayed,
+/// printed straight away otherwise
+fn warn(config: &RemoveConfig, message: String) {
+    match &config.progress {
+        Some(p) => p.add_warning(message),
+        None => eprintln!("{} {}", "Warning:".yellow(), message),
+    }
+}
+
+/// Kind of a FIFO, socket or device node, or `None` for anything else
+fn special_kind(file_type: &fs::FileType) -> Option<&'static str> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if file_type.is_fifo() {
+            return Some("FIFO");
+        } else if file_type.is_socket() {
+            return Some("socket");
+        } else if file_type.is_block_device() {
+            return Some("block device");
+        } else if file_type.is_char_device() {
+            return Some("character device");
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = file_type;
+    None
+}
+
+/// Whether directory `path` is on a different device than its parent
+pub fn is_mount_point(path: &Path, metadata: &fs::Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        path.parent()
+            .and_then(|parent| fs::metadata(parent).ok())
+            .is_some_and(|parent| parent.dev() != metadata.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, metadata);
+        false
+    }
+}
+
+/// Why a directory entry is left alone before it is even examined: hidden,
+/// kept by name, version control metadata or ignored; `None` to scan it
+fn excluded(entry: &DirItem, config: &RemoveConfig, ignores: &IgnoreStack) -> Option<SkipReason> {
+    if config.skip_hidden && entry.hidden {
+        Some(SkipReason::Hidden)
+    } else if keep_glob_matches(&entry.path, config) {
+        Some(SkipReason::Kept)
+    } else if config.exclude_vcs && is_vcs_metadata(&entry.path) {
+        Some(SkipReason::Vcs)
+    } else if ignores.is_ignored(&entry.path, entry.is_dir) {
+        Some(SkipReason::Ignored)
+    } else {
+        None
+    }
+}
+
+/// Whether `path`'s name matches a `--keep` glob
+fn keep_glob_matches(path: &Path, config: &RemoveConfig) -> bool {
+    if config.keep_globs.is_empty() {
+        return false;
+    }
+    let Some(name) = path.file_name() else {
+        return false;
+    };
+    let name = name.to_string_lossy();
+    config.keep_globs.iter().any(|glob| glob_match(glob, &name))
+}
+
+/// Whether `path` is version control metadata (`--exclude-vcs`); a `.git`
+/// file, as in a worktree or submodule, counts too
+fn is_vcs_metadata(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| VCS_DIRS.iter().any(|vcs| name == *vcs))
+}
+
+/// Whether the configured path filter rejects `path` (recording it as skipped)
+fn filtered_out(path: &Path, config: &RemoveConfig) -> bool {
+    match config.filter.as_ref().and_then(|f| f.rejection(path)) {
+        Some(reason) => {
+            skip(path, config, reason);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Keep an entry `--only-type` does not select, and with it every directory
+/// above it
+fn other_type(path: &Path, kind: EntryType, config: &RemoveConfig) -> bool {
+    if config.deletes_type(kind) {
+        return false;
+    }
+    skip(path, config, SkipReason::OtherType);
+    true
+}
+
+/// Send a job to the queue and record the resulting depth as a high-water mark
+fn enqueue(queue: &AdaptiveQueue, job: FileJob, config: &RemoveConfig) -> Result<(), RemoveError> {
+    config.log_would_delete(job.path());
+    if let Some(plan) = &config.plan {
+        plan.record(&job)?;
+    }
+    queue.wait_if_full();
+    queue.send(job).map_err(|_| RemoveError::QueueFull)?;
+    if let Some(p) = &config.progress {
+        p.record_queue_depth(queue.depth());
+    }
     Ok(())
 }
 
+thread_local! {
+    /// Directories the current thread is inside, nested or stolen by rayon
+    static SCAN_NESTING: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts the current thread in `RemoveProgress::busy_scanners` while it is
+/// inside a directory, once however deeply it recurses
+struct BusyScanner<'a>(&'a RemoveProgress);
+
+impl<'a> BusyScanner<'a> {
+    fn enter(progress: &'a RemoveProgress) -> Self {
+        if SCAN_NESTING.with(|n| n.replace(n.get() + 1)) == 0 {
+            progress.busy_scanners.fetch_add(1, Ordering::Relaxed);
+        }
+        Self(progress)
+    }
+}
+
+impl Drop for BusyScanner<'_> {
+    fn drop(&mut self) {
+        if SCAN_NESTING.with(|n| n.replace(n.get() - 1)) == 1 {
+            self.0.busy_scanners.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
 /// Scan all entries in a directory in parallel
+///
+/// Returns whether every entry was enqueued, i.e. whether the directory itself
+/// can be removed once its children are gone.
 fn scan_directory(
     path: &Path,
     queue: &AdaptiveQueue,
     config: &RemoveConfig,
-) -> Result<(), RemoveError> {
-    let entries =
-        fs::read_dir(path).map_err(|e| RemoveError::ReadDirFailed(path.to_path_buf(), e))?;
-
-    // Parallel scan of directory children
-    let results: Vec<Result<(), RemoveError>> = entries
-        .par_bridge()
-        .filter_map(|entry_result| match entry_result {
-            Ok(entry) => Some(scan_path(&entry.path(), queue, config)),
-            Err(e) => {
-                let error = RemoveError::DirEntryFailed(path.to_path_buf(), e);
-                if let Some(p) = &config.progress {
-                    p.inc_error(path, error.to_string());
-                } else {
-                    eprintln!("  {}", error.to_string());
-                }
-                Some(Err(error))
+    ctx: &ScanContext,
+) -> Result<bool, RemoveError> {
+    let _busy = config.progress.as_deref().map(BusyScanner::enter);
+    let started = config
+        .progress
+        .as_ref()
+        .filter(|p| p.profile_enabled())
+        .map(|_| Instant::now());
+    let entries = match list_dir(path, config) {
+        Ok(entries) => entries,
+        // With -c an unlistable directory is left alone, and so are its
+        // parents, rather than failing again when they are removed
+        Err(e) if config.continue_on_error && e.kind() == io::ErrorKind::PermissionDenied => {
+            skip(path, config, SkipReason::Unreadable);
+            warn(
+                config,
+                format!("skipped {:?}: permission denied (not removed)", path),
+            );
+            return Ok(false);
+        }
+        Err(e) if config.already_gone(path, &e) => return Ok(true),
+        Err(e) => return Err(RemoveError::ReadDirFailed(path.to_path_buf(), e)),
+    };
+
+    let ctx = if config.use_ignore_files {
+        ScanContext {
+            ignores: ctx.ignores.enter(path)?,
+            ..ctx.clone()
+        }
+    } else {
+        ctx.clone()
+    };
+    let scan_child = |entry_result: io::Result<DirItem>| match entry_result {
+        Ok(entry) => {
+            if let Some(reason) = excluded(&entry, config, &ctx.ignores) {
+                skip(&entry.path, config, reason);
+                return Some(Ok(false));
             }
-        })
-        .collect();
+            Some(scan_entry(&entry.path, queue, config, &ctx))
+        }
+        Err(e) => {
+            let error = RemoveError::DirEntryFailed(path.to_path_buf(), e);
+            if let Some(p) = &config.progress {
+                p.inc_error(path, error.category(), error.to_string());
+            } else {
+                eprintln!("  {}", error);
+            }
+            Some(Err(error))
+        }
+    };
+
+    let results: Vec<Result<bool, RemoveError>> = if config.sequential {
+        // Deterministic order: one entry at a time, sorted by name
+        let mut entries: Vec<_> = entries.collect();
+        match config.delete_order {
+            DeleteOrder::Natural => sort_entries(&mut entries, DeleteOrder::Name),
+            order => sort_entries(&mut entries, order),
+        }
+        entries.into_iter().filter_map(scan_child).collect()
+    } else if config.delete_order != DeleteOrder::Natural {
+        // Enqueue this directory's files in order from the current thread;
+        // subdirectories still fan out to the pool
+        let mut entries: Vec<_> = entries.collect();
+        sort_entries(&mut entries, config.delete_order);
+        let (dirs, others): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .partition(|entry| entry.as_ref().is_ok_and(|e| e.is_dir));
+        let mut results: Vec<_> = others.into_iter().filter_map(scan_child).collect();
+        results.extend(
+            dirs.into_par_iter()
+                .filter_map(scan_child)
+                .collect::<Vec<_>>(),
+        );
+        results
+    } else {
+        // Fanning out costs more than it saves for a handful of children, so
+        // only directories larger than --parallel-threshold go to the pool
+        let mut entries = entries;
+        let head: Vec<_> = entries
+            .by_ref()
+            .take(config.parallel_threshold + 1)
+            .collect();
+        if head.len() <= config.parallel_threshold {
+            head.into_iter().filter_map(scan_child).collect()
+        } else {
+            head.into_iter()
+                .chain(entries)
+                .par_bridge()
+                .filter_map(scan_child)
+                .collect()
+        }
+    };
+
+    if config.is_cancelled() {
+        return Ok(false);
+    }
+
+    let kept = results.iter().filter(|r| matches!(r, Ok(false))).count();
+    let removable = kept == 0;
+    let children = results.len();
 
     // Check for errors
     let errors: Vec<_> = results.into_iter().filter_map(Result::err).collect();
@@ -84,13 +935,38 @@ fn scan_directory(
         return Err(errors.into_iter().next().unwrap());
     }
 
-    Ok(())
+    if !removable {
+        if let Some(p) = &config.progress {
+            p.inc_kept_dir();
+        }
+        config.log_kept_dir(path, kept);
+    } else if let (Some(p), Some(started)) = (&config.progress, started) {
+        // The clock stops when the deleter removes the directory itself
+        p.dir_scanned(path, started, children);
+    }
+
+    Ok(removable)
+}
+
+/// Sort directory entries for `--delete-order`; unreadable entries go first
+fn sort_entries(entries: &mut [io::Result<DirItem>], order: DeleteOrder) {
+    match order {
+        DeleteOrder::Natural => {}
+        #[cfg(unix)]
+        DeleteOrder::Inode => entries.sort_by_key(|entry| entry.as_ref().ok().map(|e| e.ino)),
+        _ => entries.sort_by(|a, b| {
+            let name_a = a.as_ref().ok().map(|e| e.path.file_name());
+            name_a.cmp(&b.as_ref().ok().map(|e| e.path.file_name()))
+        }),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs::File;
+    use std::path::PathBuf;
+    use std::sync::atomic::Ordering;
     use tempfile::TempDir;
 
     #[test]
@@ -100,19 +976,14 @@ mod tests {
         File::create(&test_file).unwrap();
 
         let queue = AdaptiveQueue::new(10);
-        let config = RemoveConfig {
-            verbosity: crate::config::Verbosity::Simple,
-            dry_run: false,
-            continue_on_error: false,
-            progress: None,
-        };
+        let config = RemoveConfig::default();
 
         scan_path(&test_file, &queue, &config).unwrap();
 
         // Should have one file job
         assert_eq!(queue.depth(), 1);
         match queue.recv().unwrap() {
-            FileJob::File(_) => {}
+            FileJob::File { .. } => {}
             _ => panic!("Expected File job"),
         }
     }
@@ -129,12 +1000,7 @@ mod tests {
         }
 
         let queue = AdaptiveQueue::new(20);
-        let config = RemoveConfig {
-            verbosity: crate::config::Verbosity::Simple,
-            dry_run: false,
-            continue_on_error: false,
-            progress: None,
-        };
+        let config = RemoveConfig::default();
 
         scan_path(&test_dir, &queue, &config).unwrap();
 
@@ -146,7 +1012,7 @@ mod tests {
         let mut dirs = 0;
         while let Ok(job) = queue.try_recv() {
             match job {
-                FileJob::File(_) => files += 1,
+                FileJob::File { .. } => files += 1,
                 FileJob::EmptyDir(_) => dirs += 1,
                 _ => {}
             }
@@ -166,16 +1032,966 @@ mod tests {
         File::create(dir2.join("file.txt")).unwrap();
 
         let queue = AdaptiveQueue::new(20);
-        let config = RemoveConfig {
-            verbosity: crate::config::Verbosity::Simple,
-            dry_run: false,
-            continue_on_error: false,
-            progress: None,
-        };
+        let config = RemoveConfig::default();
 
         scan_path(&dir1, &queue, &config).unwrap();
 
         // Should have: 1 file + 2 directories = 3 jobs
         assert_eq!(queue.depth(), 3);
     }
+
+    /// Drain the queue into the set of enqueued paths, relative to `base`
+    fn drain_paths(queue: &AdaptiveQueue, base: &Path) -> std::collections::BTreeSet<String> {
+        let mut paths = std::collections::BTreeSet::new();
+        while let Ok(job) = queue.try_recv() {
+            let path = match job {
+                FileJob::File { path, .. } | FileJob::Symlink(path) | FileJob::EmptyDir(path) => {
+                    path
+                }
+            };
+            let rel = path
+                .strip_prefix(base)
+                .unwrap()
+                .to_string_lossy()
+                .to_string();
+            paths.insert(rel);
+        }
+        paths
+    }
+
+    #[test]
+    fn test_ignore_files_nested_override() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("ws");
+        let sub = root.join("sub");
+        let clean = root.join("clean");
+        fs::create_dir_all(&sub).unwrap();
+        fs::create_dir_all(&clean).unwrap();
+
+        // Root keeps all logs; sub re-includes one log and additionally keeps *.tmp
+        fs::write(root.join(".fastrmignore"), "*.log\n").unwrap();
+        fs::write(sub.join(".fastrmignore"), "!debug.log\n*.tmp\n").unwrap();
+        for name in ["a.log", "a.txt"] {
+            File::create(root.join(name)).unwrap();
+        }
+        for name in ["b.log", "debug.log", "b.tmp", "b.txt"] {
+            File::create(sub.join(name)).unwrap();
+        }
+        File::create(clean.join("c.txt")).unwrap();
+
+        let queue = AdaptiveQueue::new(100);
+        let progress = crate::progress::RemoveProgress::new();
+        let config = RemoveConfig {
+            progress: Some(progress.clone()),
+            use_ignore_files: true,
+            ..Default::default()
+        };
+
+        let removable = scan_path(&root, &queue, &config).unwrap();
+        assert!(!removable, "root keeps ignored entries");
+
+        let enqueued = drain_paths(&queue, &root);
+        let expected: std::collections::BTreeSet<String> = [
+            "a.txt",
+            "sub/debug.log",
+            "sub/b.txt",
+            "clean/c.txt",
+            "clean",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        assert_eq!(enqueued, expected);
+
+        // a.log, root .fastrmignore, b.log, b.tmp, sub .fastrmignore
+        assert_eq!(progress.skipped.load(Ordering::Relaxed), 5);
+        assert_eq!(progress.skipped_breakdown(), vec![(SkipReason::Ignored, 5)]);
+        // sub and the root are kept
+        assert_eq!(progress.kept_dirs.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_ignore_files_disabled_by_default() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("ws");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join(".fastrmignore"), "*.log\n").unwrap();
+        File::create(root.join("a.log")).unwrap();
+
+        let queue = AdaptiveQueue::new(10);
+        assert!(scan_path(&root, &queue, &RemoveConfig::default()).unwrap());
+        // .fastrmignore, a.log and the directory itself
+        assert_eq!(queue.depth(), 3);
+    }
+
+    #[test]
+    fn test_sequential_scan_follows_sorted_listing() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        for name in ["m.txt", "b.txt", "z.txt", "a.txt", "k.txt"] {
+            File::create(root.join(name)).unwrap();
+        }
+        fs::create_dir(root.join("d")).unwrap();
+        for name in ["y.txt", "c.txt"] {
+            File::create(root.join("d").join(name)).unwrap();
+        }
+
+        let queue = AdaptiveQueue::new(20);
+        let config = RemoveConfig {
+            sequential: true,
+            ..Default::default()
+        };
+        scan_path(&root, &queue, &config).unwrap();
+
+        // With a single deleter the queue order is the verbose output order
+        let mut order = Vec::new();
+        while let Ok(job) = queue.try_recv() {
+            let path = match job {
+                FileJob::File { path, .. } | FileJob::Symlink(path) | FileJob::EmptyDir(path) => {
+                    path
+                }
+            };
+            order.push(path.strip_prefix(temp_dir.path()).unwrap().to_path_buf());
+        }
+        let expected: Vec<std::path::PathBuf> = [
+            "root/a.txt",
+            "root/b.txt",
+            "root/d/c.txt",
+            "root/d/y.txt",
+            "root/d",
+            "root/k.txt",
+            "root/m.txt",
+            "root/z.txt",
+            "root",
+        ]
+        .iter()
+        .map(std::path::PathBuf::from)
+        .collect();
+        assert_eq!(order, expected);
+    }
+
+    #[test]
+    fn test_scan_to_vec_orders_children_before_parents() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        // 3 levels of 3 directories, each holding 2 files
+        fn build(dir: &Path, depth: usize) {
+            fs::create_dir(dir).unwrap();
+            File::create(dir.join("a.txt")).unwrap();
+            File::create(dir.join("b.txt")).unwrap();
+            if depth > 0 {
+                for i in 0..3 {
+                    build(&dir.join(format!("d{}", i)), depth - 1);
+                }
+            }
+        }
+        build(&root, 3);
+
+        let jobs = scan_to_vec(&root, &RemoveConfig::default()).unwrap();
+        let dirs = 1 + 3 + 9 + 27;
+        let files = 2 * dirs;
+        assert_eq!(jobs.len(), dirs + files);
+        assert_eq!(
+            jobs.iter()
+                .filter(|j| matches!(j, FileJob::EmptyDir(_)))
+                .count(),
+            dirs
+        );
+
+        // Every path comes after everything below it, and the root is last
+        for (i, job) in jobs.iter().enumerate() {
+            assert!(jobs[i + 1..]
+                .iter()
+                .all(|later| !later.path().starts_with(job.path()) || later.path() == job.path()));
+        }
+        assert_eq!(jobs.last().unwrap().path(), root);
+
+        // Deterministic: a second scan yields the same sequence
+        let again = scan_to_vec(&root, &RemoveConfig::default()).unwrap();
+        let paths = |jobs: &[FileJob]| {
+            jobs.iter()
+                .map(|j| j.path().to_path_buf())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(paths(&jobs), paths(&again));
+    }
+
+    #[test]
+    fn test_delete_order_sorts_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        for name in ["m.txt", "b.txt", "z.txt", "a.txt", "k.txt"] {
+            File::create(root.join(name)).unwrap();
+        }
+
+        let scan_order = |order: DeleteOrder| {
+            let queue = AdaptiveQueue::new(20);
+            let config = RemoveConfig {
+                delete_order: order,
+                parallel_threshold: 0,
+                dry_run: true,
+                ..Default::default()
+            };
+            scan_path(&root, &queue, &config).unwrap();
+            let mut files = Vec::new();
+            while let Ok(FileJob::File { path, .. }) = queue.try_recv() {
+                files.push(path.to_path_buf());
+            }
+            files
+        };
+
+        let names: Vec<_> = scan_order(DeleteOrder::Name)
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["a.txt", "b.txt", "k.txt", "m.txt", "z.txt"]);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let inodes: Vec<_> = scan_order(DeleteOrder::Inode)
+                .iter()
+                .map(|p| fs::metadata(p).unwrap().ino())
+                .collect();
+            assert_eq!(inodes.len(), 5);
+            assert!(inodes.windows(2).all(|w| w[0] < w[1]), "{:?}", inodes);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unreadable_dir_is_skipped_with_continue_on_error() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let locked = root.join("locked");
+        fs::create_dir_all(&locked).unwrap();
+        File::create(locked.join("secret.txt")).unwrap();
+        File::create(root.join("plain.txt")).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        let readable_anyway = fs::read_dir(&locked).is_ok();
+        let scan = |continue_on_error: bool| {
+            let progress = crate::progress::RemoveProgress::new();
+            let config = RemoveConfig {
+                progress: Some(progress.clone()),
+                continue_on_error,
+                ..Default::default()
+            };
+            let queue = AdaptiveQueue::new(10);
+            let result = scan_path(&root, &queue, &config);
+            (result, drain_paths(&queue, &root), progress)
+        };
+        let (result, paths, progress) = scan(true);
+        let (strict, _, _) = scan(false);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        // Permission bits do not apply to root
+        if readable_anyway {
+            return;
+        }
+
+        // Neither the locked directory nor its parent are enqueued
+        assert!(!result.unwrap());
+        assert_eq!(paths, ["plain.txt"].map(String::from).into());
+        assert_eq!(
+            progress.warnings(),
+            [format!(
+                "skipped {:?}: permission denied (not removed)",
+                locked
+            )]
+        );
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 0);
+        assert_eq!(progress.skipped_breakdown(), [(SkipReason::Unreadable, 1)]);
+
+        // Without -c it is still an error
+        assert!(matches!(strict, Err(RemoveError::ReadDirFailed(..))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_special_files_warn_or_skip() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        File::create(root.join("plain.txt")).unwrap();
+        let fifo = root.join("pipe");
+        let status = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let scan = |no_special: bool| {
+            let progress = crate::progress::RemoveProgress::new();
+            let config = RemoveConfig {
+                progress: Some(progress.clone()),
+                no_special,
+                ..Default::default()
+            };
+            let queue = AdaptiveQueue::new(10);
+            let removable = scan_path(&root, &queue, &config).unwrap();
+            (removable, drain_paths(&queue, &root), progress)
+        };
+
+        // By default the FIFO is removed like a file, with a warning
+        let (removable, paths, progress) = scan(false);
+        assert!(removable);
+        assert!(paths.contains("pipe"));
+        assert_eq!(progress.warnings(), [format!("removing FIFO {:?}", fifo)]);
+
+        // --no-special leaves it, and therefore its parent, in place
+        let (removable, paths, progress) = scan(true);
+        assert!(!removable);
+        assert_eq!(paths, ["plain.txt"].map(String::from).into());
+        assert_eq!(progress.skipped_breakdown(), [(SkipReason::Special, 1)]);
+        assert_eq!(
+            progress.warnings(),
+            [format!("skipped FIFO {:?} (--no-special)", fifo)]
+        );
+    }
+
+    #[test]
+    fn test_resume_skips_recorded_subtrees() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir_all(root.join("done")).unwrap();
+        File::create(root.join("done/leftover.txt")).unwrap();
+        File::create(root.join("todo.txt")).unwrap();
+
+        let progress = crate::progress::RemoveProgress::new();
+        let config = RemoveConfig {
+            progress: Some(progress.clone()),
+            already_deleted: Some(Arc::new(HashSet::from([root.join("done")]))),
+            ..Default::default()
+        };
+        let queue = AdaptiveQueue::new(10);
+        assert!(scan_path(&root, &queue, &config).unwrap());
+
+        // The recorded directory is neither scanned nor enqueued again
+        assert_eq!(
+            drain_paths(&queue, &root),
+            ["", "todo.txt"].map(String::from).into()
+        );
+        assert_eq!(progress.scanned.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_max_open_dirs_scans_deep_wide_tree() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let mut deep = root.clone();
+        for _ in 0..20 {
+            deep.push("d");
+        }
+        fs::create_dir_all(&deep).unwrap();
+        for i in 0..30 {
+            fs::create_dir_all(root.join(format!("w{}", i))).unwrap();
+            File::create(root.join(format!("w{}/f", i))).unwrap();
+        }
+
+        // A single handle: every listing is read and closed before descending
+        let config = RemoveConfig {
+            open_dirs: Some(Arc::new(OpenDirLimit::new(1))),
+            parallel_threshold: 0,
+            ..Default::default()
+        };
+        let jobs = scan_to_vec(&root, &config).unwrap();
+        // root, a 20-deep chain, 30 wide directories and their files
+        assert_eq!(jobs.len(), 1 + 20 + 30 + 30);
+        assert_eq!(*config.open_dirs.unwrap().available.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_no_recursive_refuses_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("dir");
+        fs::create_dir(&dir).unwrap();
+        File::create(dir.join("inner.txt")).unwrap();
+        let file = temp_dir.path().join("file.txt");
+        File::create(&file).unwrap();
+
+        let progress = crate::progress::RemoveProgress::new();
+        let config = RemoveConfig {
+            no_recursive: true,
+            progress: Some(progress.clone()),
+            ..Default::default()
+        };
+        let queue = AdaptiveQueue::new(10);
+        assert!(scan_path(&file, &queue, &config).unwrap());
+        assert!(matches!(
+            scan_path(&dir, &queue, &config),
+            Err(RemoveError::IsADirectory(_))
+        ));
+
+        // Only the file was enqueued; the directory was not descended into
+        assert_eq!(queue.try_recv().unwrap().path(), file);
+        assert!(queue.is_empty());
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_sample_files_spreads_across_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        for dir in ["a", "b", "c/d"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+            for i in 0..5 {
+                fs::write(root.join(dir).join(format!("{}.txt", i)), "x").unwrap();
+            }
+        }
+        let config = RemoveConfig::default();
+        let parents = |jobs: &[FileJob]| {
+            jobs.iter()
+                .map(|job| job.path().parent().unwrap().to_path_buf())
+                .collect::<HashSet<_>>()
+        };
+
+        let sample = sample_files(&root, &config, 3).unwrap();
+        assert_eq!(sample.len(), 3);
+        assert_eq!(parents(&sample).len(), 3);
+        assert!(sample.iter().all(|job| matches!(job, FileJob::File { .. })));
+
+        // Only three directories hold files, so the rest doubles up
+        let sample = sample_files(&root, &config, 7).unwrap();
+        assert_eq!(sample.len(), 7);
+        assert_eq!(parents(&sample).len(), 3);
+        assert_eq!(sample_files(&root, &config, 100).unwrap().len(), 15);
+        // Nothing was deleted
+        assert_eq!(fs::read_dir(root.join("a")).unwrap().count(), 5);
+    }
+
+    #[test]
+    fn test_contents_only_keeps_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        File::create(root.join("sub/a.txt")).unwrap();
+        File::create(root.join("b.txt")).unwrap();
+        let file = temp_dir.path().join("c.txt");
+        File::create(&file).unwrap();
+
+        let config = RemoveConfig {
+            contents_only: true,
+            ..Default::default()
+        };
+        let queue = AdaptiveQueue::new(10);
+        assert!(scan_root(&root, &queue, &config).unwrap());
+        assert_eq!(
+            drain_paths(&queue, &root),
+            ["b.txt", "sub", "sub/a.txt"].map(String::from).into()
+        );
+        assert_eq!(count_jobs(&root, &config).unwrap().entries, 3);
+
+        // Anything but a directory is removed as usual
+        assert!(scan_root(&file, &queue, &config).unwrap());
+        assert_eq!(queue.try_recv().unwrap().path(), file);
+    }
+
+    #[test]
+    fn test_keep_list_protects_file_and_ancestors() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap().join("root");
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::create_dir_all(root.join("other")).unwrap();
+        File::create(root.join("a/b/keep.txt")).unwrap();
+        File::create(root.join("a/b/gone.txt")).unwrap();
+        File::create(root.join("a/gone.txt")).unwrap();
+        File::create(root.join("other/gone.txt")).unwrap();
+        let list = temp_dir.path().join("keep");
+        fs::write(&list, "# protected\na/b/keep.txt\n\n").unwrap();
+
+        let keep = crate::filter::load_keep_list(&list, std::slice::from_ref(&root)).unwrap();
+        let config = RemoveConfig {
+            keep: Some(Arc::new(keep)),
+            ..Default::default()
+        };
+        let queue = AdaptiveQueue::new(20);
+        assert!(!scan_path(&root, &queue, &config).unwrap());
+
+        // Siblings go; the kept file, `a/b`, `a` and the root stay
+        assert_eq!(
+            drain_paths(&queue, &root),
+            ["a/b/gone.txt", "a/gone.txt", "other", "other/gone.txt"]
+                .map(String::from)
+                .into()
+        );
+    }
+
+    #[test]
+    fn test_filter_prefix_includes_only_subtree() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap().join("root");
+        fs::create_dir_all(root.join("build/out")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        File::create(root.join("build/out/a.o")).unwrap();
+        File::create(root.join("src/a.c")).unwrap();
+
+        let queue = AdaptiveQueue::new(20);
+        let config = RemoveConfig {
+            filter: Some(crate::filter::PathFilter::prefix(&root.join("build"))),
+            ..Default::default()
+        };
+        // Root and src are kept, so the scan is not fully removable
+        assert!(!scan_path(&root, &queue, &config).unwrap());
+        assert_eq!(
+            drain_paths(&queue, &root),
+            ["build", "build/out", "build/out/a.o"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        );
+    }
+
+    #[test]
+    fn test_filter_allowlist_keeps_unlisted_dirs() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap().join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        File::create(root.join("sub/listed.txt")).unwrap();
+        File::create(root.join("other.txt")).unwrap();
+
+        let listed = [root.join("sub/listed.txt")].into_iter().collect();
+        let queue = AdaptiveQueue::new(20);
+        let config = RemoveConfig {
+            filter: Some(crate::filter::PathFilter::allowlist(listed)),
+            ..Default::default()
+        };
+        assert!(!scan_path(&root, &queue, &config).unwrap());
+        // sub itself is not listed, so only the file goes
+        assert_eq!(
+            drain_paths(&queue, &root),
+            ["sub/listed.txt"].iter().map(|s| s.to_string()).collect()
+        );
+    }
+
+    #[test]
+    fn test_skip_hidden_keeps_dot_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join(".project");
+        fs::create_dir_all(root.join(".git/objects")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        File::create(root.join(".git/HEAD")).unwrap();
+        File::create(root.join(".env")).unwrap();
+        File::create(root.join("target/app")).unwrap();
+        File::create(root.join("README")).unwrap();
+
+        let queue = AdaptiveQueue::new(20);
+        let config = RemoveConfig {
+            skip_hidden: true,
+            ..Default::default()
+        };
+        // The explicitly named (hidden) root is scanned, but kept for its dot children
+        assert!(!scan_path(&root, &queue, &config).unwrap());
+        assert_eq!(
+            drain_paths(&queue, &root),
+            ["README", "target", "target/app"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        );
+
+        // An explicitly named dotfile is deleted
+        let queue = AdaptiveQueue::new(20);
+        assert!(scan_path(&root.join(".env"), &queue, &config).unwrap());
+        assert_eq!(queue.depth(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_only_type_selections() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir_all(root.join("data/empty")).unwrap();
+        fs::create_dir_all(root.join("links")).unwrap();
+        File::create(root.join("data/a.txt")).unwrap();
+        std::os::unix::fs::symlink("../data/a.txt", root.join("links/a")).unwrap();
+
+        let scan = |types: &[EntryType]| {
+            let queue = AdaptiveQueue::new(20);
+            let progress = RemoveProgress::new();
+            let config = RemoveConfig {
+                only_types: types.to_vec(),
+                progress: Some(progress.clone()),
+                ..Default::default()
+            };
+            let complete = scan_path(&root, &queue, &config).unwrap();
+            let skipped = progress.skipped_breakdown();
+            (complete, drain_paths(&queue, &root), skipped)
+        };
+        let set = |paths: &[&str]| paths.iter().map(|s| s.to_string()).collect();
+
+        // Files: directories are descended, never removed
+        let (complete, paths, skipped) = scan(&[EntryType::Files]);
+        assert!(!complete);
+        assert_eq!(paths, set(&["data/a.txt"]));
+        // Counted are the link and the empty directory, not the parents kept for them
+        assert_eq!(skipped, [(SkipReason::OtherType, 2)]);
+
+        // Symlinks alone
+        let (_, paths, _) = scan(&[EntryType::Symlinks]);
+        assert_eq!(paths, set(&["links/a"]));
+
+        // Dirs: only those holding nothing but other directories
+        let (_, paths, _) = scan(&[EntryType::Dirs]);
+        assert_eq!(paths, set(&["data/empty"]));
+
+        // Repeated: links go, and so does the directory they emptied
+        let (complete, paths, _) = scan(&[EntryType::Symlinks, EntryType::Dirs]);
+        assert!(!complete);
+        assert_eq!(paths, set(&["links", "links/a", "data/empty"]));
+
+        // All three is the same as none
+        let (complete, paths, _) = scan(&[EntryType::Files, EntryType::Dirs, EntryType::Symlinks]);
+        assert!(complete);
+        assert_eq!(paths.len(), 6);
+    }
+
+    #[test]
+    fn test_scan_empty_dirs_bottom_up() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir_all(root.join("a/b/c")).unwrap();
+        fs::create_dir_all(root.join("d")).unwrap();
+        File::create(root.join("d/keep.txt")).unwrap();
+
+        let queue = AdaptiveQueue::new(20);
+        assert!(!scan_empty_dirs(&root, &queue, &RemoveConfig::default()).unwrap());
+        let mut order = Vec::new();
+        while let Ok(job) = queue.try_recv() {
+            order.push(job.path().strip_prefix(&root).unwrap().to_path_buf());
+        }
+        let expected: Vec<PathBuf> = ["a/b/c", "a/b", "a"].iter().map(PathBuf::from).collect();
+        assert_eq!(order, expected);
+    }
+
+    #[test]
+    fn test_scan_empty_dirs_honours_exclusions() {
+        use crate::filter::PathFilter;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        for dir in [".hidden", "kept", "listed", "outside", "fresh/inner"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let prefix = root.clone();
+        let outside = root.join("outside");
+        let filter = PathFilter::new(SkipReason::Excluded, move |path| {
+            path.starts_with(&prefix) && !path.starts_with(&outside)
+        });
+        let config = RemoveConfig {
+            skip_hidden: true,
+            keep_globs: vec!["kept".to_string()],
+            keep: Some(Arc::new(HashSet::from([root.join("listed")]))),
+            filter: Some(filter),
+            ..Default::default()
+        };
+
+        let queue = AdaptiveQueue::new(20);
+        assert!(!scan_empty_dirs(&root, &queue, &config).unwrap());
+        let paths = drain_paths(&queue, &root);
+        assert_eq!(Vec::from_iter(paths), ["fresh", "fresh/inner"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_empty_dirs_continues_past_unreadable_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir_all(root.join("locked/inner")).unwrap();
+        fs::create_dir_all(root.join("empty")).unwrap();
+        let locked = root.join("locked");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        let readable_anyway = fs::read_dir(&locked).is_ok();
+        let prune = |continue_on_error: bool| {
+            let progress = crate::progress::RemoveProgress::new();
+            let config = RemoveConfig {
+                continue_on_error,
+                progress: Some(progress.clone()),
+                ..Default::default()
+            };
+            let queue = AdaptiveQueue::new(20);
+            let result = scan_empty_dirs(&root, &queue, &config);
+            (result, drain_paths(&queue, &root), progress)
+        };
+        let strict = prune(false).0;
+        let (result, paths, progress) = prune(true);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        // Root can read it anyway
+        if readable_anyway {
+            return;
+        }
+
+        assert!(matches!(strict, Err(RemoveError::ReadDirFailed(..))));
+        assert!(!result.unwrap());
+        assert_eq!(Vec::from_iter(paths), ["empty"]);
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_skip_reason_breakdown_mixed_run() {
+        use std::time::{Duration, SystemTime};
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap().join("root");
+        fs::create_dir_all(root.join("old")).unwrap();
+        fs::create_dir_all(root.join("other")).unwrap();
+        let week_ago = SystemTime::now() - Duration::from_secs(7 * 86400);
+        for name in [
+            "old/a.txt",
+            "old/b.txt",
+            "other/c.txt",
+            "other/d.txt",
+            "top.txt",
+        ] {
+            File::create(root.join(name))
+                .unwrap()
+                .set_modified(week_ago)
+                .unwrap();
+        }
+        File::create(root.join("old/new.txt")).unwrap();
+        File::create(root.join("old/new2.txt")).unwrap();
+        File::create(root.join("old/.hidden")).unwrap();
+
+        let progress = crate::progress::RemoveProgress::new();
+        let filter = crate::filter::PathFilter::prefix(&root.join("old")).and(
+            crate::filter::PathFilter::older_than(Duration::from_secs(86400)),
+        );
+        let config = RemoveConfig {
+            progress: Some(progress.clone()),
+            skip_hidden: true,
+            filter: Some(filter),
+            ..Default::default()
+        };
+        let queue = AdaptiveQueue::new(20);
+        scan_path(&root, &queue, &config).unwrap();
+
+        // Directories kept for their children are not skips themselves
+        assert_eq!(
+            progress.skipped_breakdown(),
+            vec![
+                (SkipReason::Excluded, 3),
+                (SkipReason::NewerThan, 2),
+                (SkipReason::Hidden, 1)
+            ]
+        );
+        assert_eq!(progress.skipped.load(Ordering::Relaxed), 6);
+        assert_eq!(progress.kept_dirs.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_parallel_threshold_scans_all_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        for i in 0..5 {
+            File::create(root.join(format!("file_{}.txt", i))).unwrap();
+        }
+
+        // Below, at and above the entry count: same jobs either way
+        for threshold in [0, 4, 5, 100] {
+            let queue = AdaptiveQueue::new(20);
+            let config = RemoveConfig {
+                parallel_threshold: threshold,
+                ..Default::default()
+            };
+            assert!(scan_path(&root, &queue, &config).unwrap());
+            assert_eq!(queue.depth(), 6, "threshold {}", threshold);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlink_to_dir() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let outside = temp_dir.path().join("outside");
+        fs::create_dir(&root).unwrap();
+        fs::create_dir(&outside).unwrap();
+        File::create(outside.join("target.txt")).unwrap();
+        symlink(&outside, root.join("link")).unwrap();
+
+        // Without the flag only the link is removed
+        let queue = AdaptiveQueue::new(20);
+        scan_path(&root, &queue, &RemoveConfig::default()).unwrap();
+        assert_eq!(
+            drain_paths(&queue, temp_dir.path()),
+            ["root", "root/link"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        );
+
+        let queue = AdaptiveQueue::new(20);
+        let config = RemoveConfig {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        scan_path(&root, &queue, &config).unwrap();
+        let base = temp_dir.path().canonicalize().unwrap();
+        let mut order = Vec::new();
+        while let Ok(job) = queue.try_recv() {
+            order.push(job);
+        }
+        let rel: Vec<String> = order
+            .iter()
+            .map(|job| {
+                let path = match job {
+                    FileJob::File { path, .. }
+                    | FileJob::Symlink(path)
+                    | FileJob::EmptyDir(path) => path,
+                };
+                let path = path
+                    .strip_prefix(&base)
+                    .unwrap_or_else(|_| path.strip_prefix(temp_dir.path()).unwrap());
+                path.to_string_lossy().to_string()
+            })
+            .collect();
+        // Target contents, the target directory, the link, then the root
+        assert_eq!(
+            rel,
+            vec!["outside/target.txt", "outside", "root/link", "root"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_self_referential_symlink_terminates() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        File::create(root.join("file.txt")).unwrap();
+        // Points back at its own parent directory
+        symlink(&root, root.join("loop")).unwrap();
+        // Points at itself, so it cannot be resolved at all
+        symlink(root.join("self"), root.join("self")).unwrap();
+
+        let queue = AdaptiveQueue::new(20);
+        let config = RemoveConfig {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        let err = scan_path(&root, &queue, &config).unwrap_err();
+        assert!(
+            matches!(&err, RemoveError::SymlinkLoop(link, _) if *link == root.join("loop")),
+            "{}",
+            err
+        );
+
+        // The file and the unresolvable link; the loop and root are kept
+        assert_eq!(queue.depth(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_gives_up_on_long_symlink_chain() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        File::create(root.join("target.txt")).unwrap();
+        // link_0 -> link_1 -> ... -> link_4 -> target.txt
+        for i in 0..5 {
+            let next = match i {
+                4 => root.join("target.txt"),
+                _ => root.join(format!("link_{}", i + 1)),
+            };
+            symlink(next, root.join(format!("link_{}", i))).unwrap();
+        }
+        let config = |max| RemoveConfig {
+            follow_symlinks: true,
+            sequential: true,
+            max_symlink_depth: Some(max),
+            ..Default::default()
+        };
+
+        // Five links are within a limit of five
+        let queue = AdaptiveQueue::new(20);
+        scan_contents(&root, &queue, &config(5)).unwrap();
+        assert_eq!(queue.depth(), 6);
+
+        // Past the limit the chain is not followed, but the link still goes
+        let progress = RemoveProgress::new();
+        let config = |max| RemoveConfig {
+            progress: Some(progress.clone()),
+            ..config(max)
+        };
+        let queue = AdaptiveQueue::new(20);
+        assert!(scan_path(&root.join("link_0"), &queue, &config(4)).unwrap());
+        let jobs: Vec<_> = std::iter::from_fn(|| queue.try_recv().ok()).collect();
+        assert!(matches!(&jobs[..], [FileJob::Symlink(link)] if **link == root.join("link_0")));
+        assert!(progress.warnings()[0].contains("longer than 4 links"));
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 0);
+
+        // A link to itself loops, and is given up on the same way
+        symlink(root.join("self"), root.join("self")).unwrap();
+        assert!(scan_path(&root.join("self"), &queue, &config(40)).unwrap());
+        assert_eq!(queue.depth(), 1);
+        assert_eq!(progress.warnings().len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_reports_loop_through_two_links() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap().join("root");
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::create_dir_all(root.join("b")).unwrap();
+        symlink(root.join("b"), root.join("a/to_b")).unwrap();
+        symlink(root.join("a"), root.join("b/to_a")).unwrap();
+
+        let queue = AdaptiveQueue::new(20);
+        let progress = crate::progress::RemoveProgress::new();
+        let config = RemoveConfig {
+            follow_symlinks: true,
+            sequential: true,
+            progress: Some(progress.clone()),
+            ..Default::default()
+        };
+        // a, then b through a/to_b, whose to_a leads back into a
+        match scan_path(&root, &queue, &config) {
+            Err(RemoveError::SymlinkLoop(link, target)) => {
+                assert_eq!(link, root.join("b/to_a"));
+                assert_eq!(target, root.join("a"));
+            }
+            other => panic!("expected a symlink loop, got {:?}", other),
+        }
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 1);
+        assert!(root.join("a/to_b").exists());
+    }
+
+    #[test]
+    fn test_scan_records_peak_queue_depth() {
+        let temp_dir = TempDir::new().unwrap();
+        for i in 0..5 {
+            File::create(temp_dir.path().join(format!("file{}.txt", i))).unwrap();
+        }
+
+        let queue = AdaptiveQueue::new(20);
+        let progress = crate::progress::RemoveProgress::new();
+        let config = RemoveConfig {
+            progress: Some(progress.clone()),
+            ..Default::default()
+        };
+
+        scan_path(temp_dir.path(), &queue, &config).unwrap();
+
+        // Nothing was drained, so the peak is every job: 5 files + 1 directory
+        assert_eq!(
+            progress
+                .peak_queue_depth
+                .load(std::sync::atomic::Ordering::Relaxed),
+            6
+        );
+    }
 }
@@ -0,0 +1,123 @@
+//! Versioned machine-readable output (`--output-version`)
+//!
+//! Every JSON object fast-rm emits starts with `"schema_version"`. Within a
+//! version fields are only ever added, never renamed, retyped or removed;
+//! anything else needs a new version, and the old one stays selectable.
+
+use clap::ValueEnum;
+use std::fmt::Display;
+
+/// Schema of the JSON outputs: the `--summary-json-file` summary and the
+/// `--progress-socket` snapshots
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputVersion {
+    /// Summary: outcome, exit_code, dry_run, items, errors, skipped,
+    /// kept_dirs, elapsed_secs. Progress: scanned, deleted, errors, rate,
+    /// eta_secs, queue_depth, done
+    #[default]
+    #[value(name = "1")]
+    V1,
+}
+
+impl OutputVersion {
+    pub fn number(self) -> u32 {
+        match self {
+            OutputVersion::V1 => 1,
+        }
+    }
+}
+
+/// A single-line JSON object, written field by field after its
+/// `"schema_version"`
+#[derive(Debug)]
+pub struct JsonObject {
+    out: String,
+}
+
+impl JsonObject {
+    pub fn new(version: OutputVersion) -> Self {
+        Self {
+            out: format!("{{\"schema_version\":{}", version.number()),
+        }
+    }
+
+    /// Add a number, boolean or already-encoded JSON value
+    pub fn raw(mut self, name: &str, value: impl Display) -> Self {
+        self.out.push_str(&format!(",\"{}\":{}", name, value));
+        self
+    }
+
+    /// Add a string value
+    pub fn string(mut self, name: &str, value: &str) -> Self {
+        self.out.push_str(&format!(",\"{}\":\"", name));
+        for c in value.chars() {
+            match c {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                c if (c as u32) < 0x20 => self.out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => self.out.push(c),
+            }
+        }
+        self.out.push('"');
+        self
+    }
+
+    pub fn finish(mut self) -> String {
+        self.out.push('}');
+        self.out
+    }
+}
+
+/// Top-level keys of a JSON object written by `JsonObject`, in order
+#[cfg(test)]
+pub fn keys(json: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    let mut depth = 0;
+    let mut chars = json.chars();
+    let mut expect_key = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                depth += 1;
+                expect_key = depth == 1;
+            }
+            '}' => depth -= 1,
+            ',' if depth == 1 => expect_key = true,
+            '"' => {
+                let mut text = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => text.extend(chars.next()),
+                        '"' => break,
+                        c => text.push(c),
+                    }
+                }
+                if expect_key {
+                    keys.push(text);
+                    expect_key = false;
+                }
+            }
+            _ => {}
+        }
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_leads_with_the_schema_version() {
+        let json = JsonObject::new(OutputVersion::V1)
+            .raw("count", 3)
+            .string("path", "a \"b\"\\c\n")
+            .raw("nested", "{\"x\":1}")
+            .finish();
+        assert_eq!(
+            json,
+            r#"{"schema_version":1,"count":3,"path":"a \"b\"\\c\u000a","nested":{"x":1}}"#
+        );
+        assert_eq!(keys(&json), ["schema_version", "count", "path", "nested"]);
+    }
+}
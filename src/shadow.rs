@@ -0,0 +1,163 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::errors::RemoveError;
+
+/// Prefix of the sibling directory holding the shadow links of a path
+pub const SHADOW_PREFIX: &str = ".fastrm-shadow-";
+
+/// Hardlinked copy of the deletion targets used by `--atomic`
+///
+/// Every file (and symlink) is hardlinked into a sibling shadow directory and
+/// every directory is recreated there, so deleting the originals only drops a
+/// link count. Hardlinks cannot span file systems, so each path must live on
+/// the same file system as its parent directory; creation fails otherwise and
+/// nothing is deleted.
+#[derive(Debug)]
+pub struct Shadow {
+    /// `(original path, shadow directory)` pairs; the original's shadow is
+    /// the entry named like it inside the shadow directory
+    entries: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Shadow {
+    /// Link every path into its shadow directory
+    pub fn create(paths: &[PathBuf]) -> Result<Self, RemoveError> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let mut shadow = Self {
+            entries: Vec::new(),
+        };
+        for path in paths {
+            let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+                let err = io::Error::new(io::ErrorKind::InvalidInput, "path has no parent");
+                shadow.discard().ok();
+                return Err(RemoveError::ShadowFailed(path.clone(), err));
+            };
+            let dir = parent.join(format!(
+                "{}{}-{}",
+                SHADOW_PREFIX,
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+
+            let linked = fs::create_dir(&dir).and_then(|()| {
+                shadow.entries.push((path.clone(), dir.clone()));
+                link_tree(path, &dir.join(name))
+            });
+            if let Err(e) = linked {
+                shadow.discard().ok();
+                return Err(RemoveError::ShadowFailed(path.clone(), e));
+            }
+        }
+        Ok(shadow)
+    }
+
+    /// Put back every entry that no longer exists under its original name,
+    /// then drop the shadow links. Returns the number of entries restored.
+    pub fn restore(self) -> io::Result<usize> {
+        let mut restored = 0;
+        for (path, dir) in &self.entries {
+            if let Some(name) = path.file_name() {
+                restored += link_tree(&dir.join(name), path)?;
+            }
+        }
+        self.discard()?;
+        Ok(restored)
+    }
+
+    /// Drop the shadow links, making the deletion permanent
+    pub fn discard(self) -> io::Result<()> {
+        for (_, dir) in &self.entries {
+            fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    /// Shadow directories, for pointing the user at them if cleanup fails
+    pub fn dirs(&self) -> impl Iterator<Item = &Path> {
+        self.entries.iter().map(|(_, dir)| dir.as_path())
+    }
+}
+
+/// Mirror `src` at `dst`: directories are recreated, everything else is
+/// hardlinked. Entries already present at `dst` are left alone. Returns the
+/// number of entries created.
+fn link_tree(src: &Path, dst: &Path) -> io::Result<usize> {
+    let metadata = fs::symlink_metadata(src)?;
+    if !metadata.is_dir() {
+        // link(2) does not follow symlinks, so the link itself is preserved
+        return match fs::hard_link(src, dst) {
+            Ok(()) => Ok(1),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(0),
+            Err(e) => Err(e),
+        };
+    }
+
+    let mut created = match fs::create_dir(dst) {
+        Ok(()) => 1,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => 0,
+        Err(e) => return Err(e),
+    };
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        created += link_tree(&entry.path(), &dst.join(entry.file_name()))?;
+    }
+    // Applied last so read-only directories can still be filled
+    if created > 0 {
+        fs::set_permissions(dst, metadata.permissions())?;
+    }
+    Ok(created)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::TempDir;
+
+    fn make_tree(root: &Path) {
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("sub/b.txt"), "b").unwrap();
+    }
+
+    #[test]
+    fn test_restore_after_partial_delete() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        make_tree(&root);
+
+        let shadow = Shadow::create(std::slice::from_ref(&root)).unwrap();
+        fs::remove_file(root.join("sub/b.txt")).unwrap();
+        fs::remove_dir(root.join("sub")).unwrap();
+
+        // sub and sub/b.txt come back; a.txt was never removed
+        assert_eq!(shadow.restore().unwrap(), 2);
+        assert_eq!(fs::read_to_string(root.join("sub/b.txt")).unwrap(), "b");
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "a");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_discard_drops_only_shadow() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let file = temp_dir.path().join("file.txt");
+        make_tree(&root);
+        File::create(&file).unwrap();
+
+        let shadow = Shadow::create(&[root.clone(), file.clone()]).unwrap();
+        let dirs: Vec<PathBuf> = shadow.dirs().map(Path::to_path_buf).collect();
+        assert_eq!(dirs.len(), 2);
+        assert!(dirs[0].join("root/sub/b.txt").exists());
+        assert!(dirs[1].join("file.txt").exists());
+
+        shadow.discard().unwrap();
+        assert!(dirs.iter().all(|d| !d.exists()));
+        assert!(root.join("sub/b.txt").exists());
+        assert!(file.exists());
+    }
+}
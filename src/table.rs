@@ -0,0 +1,109 @@
+//! Plain-text tables with aligned columns (`--summary-table`)
+
+/// Which side of its column a cell sticks to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// Rows of cells under a header, padded to the widest cell of each column
+#[derive(Debug)]
+pub struct Table {
+    columns: Vec<(String, Align)>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(columns: &[(&str, Align)]) -> Self {
+        Self {
+            columns: columns
+                .iter()
+                .map(|&(name, align)| (name.to_string(), align))
+                .collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Add a row; missing cells are left blank and extra ones dropped
+    pub fn row(&mut self, cells: Vec<String>) {
+        self.rows.push(cells);
+    }
+
+    /// The header line followed by one line per row, columns two spaces
+    /// apart and without trailing spaces
+    pub fn render(&self) -> Vec<String> {
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| {
+                self.rows
+                    .iter()
+                    .filter_map(|row| row.get(i))
+                    .map(|cell| cell.chars().count())
+                    .chain([name.chars().count()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let line = |cells: Vec<&str>| {
+            let padded: Vec<String> = cells
+                .iter()
+                .zip(&self.columns)
+                .zip(&widths)
+                .map(|((cell, (_, align)), &width)| match align {
+                    Align::Left => format!("{:<width$}", cell, width = width),
+                    Align::Right => format!("{:>width$}", cell, width = width),
+                })
+                .collect();
+            padded.join("  ").trim_end().to_string()
+        };
+        let header = line(self.columns.iter().map(|(name, _)| name.as_str()).collect());
+        let rows = self.rows.iter().map(|row| {
+            line(
+                (0..self.columns.len())
+                    .map(|i| row.get(i).map_or("", String::as_str))
+                    .collect(),
+            )
+        });
+        std::iter::once(header).chain(rows).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columns_align_across_widths() {
+        let mut table = Table::new(&[
+            ("Path", Align::Left),
+            ("Items", Align::Right),
+            ("Bytes", Align::Right),
+        ]);
+        table.row(vec!["/tmp/a".into(), "7".into(), "1.5 KiB".into()]);
+        table.row(vec![
+            "/var/cache/much-longer".into(),
+            "1,234,567".into(),
+            "512 B".into(),
+        ]);
+        table.row(vec!["/é".into(), "0".into()]);
+        assert_eq!(
+            table.render(),
+            [
+                "Path                        Items    Bytes",
+                "/tmp/a                          7  1.5 KiB",
+                "/var/cache/much-longer  1,234,567    512 B",
+                "/é                              0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_header_sets_the_minimum_width() {
+        let mut table = Table::new(&[("Name", Align::Left), ("Errors", Align::Right)]);
+        table.row(vec!["x".into(), "3".into(), "dropped".into()]);
+        assert_eq!(table.render(), ["Name  Errors", "x          3"]);
+    }
+}
@@ -0,0 +1,393 @@
+//! Scan everything, then delete once approved (`--two-phase`)
+
+use colored::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{self, BufRead, BufReader, BufWriter, Lines, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use tempfile::NamedTempFile;
+
+use crate::config::RemoveConfig;
+use crate::errors::RemoveError;
+use crate::human::{format_bytes, format_count};
+use crate::plan::{format_job, parse_job};
+use crate::queue::{AdaptiveQueue, FileJob};
+use crate::scanner::collect_jobs;
+
+/// Jobs kept in memory before the rest of the list goes to a temporary file;
+/// about a hundred bytes each plus the path
+pub const MEMORY_JOBS: usize = 1_000_000;
+
+/// Every job of a finished scan, in deletion order
+///
+/// The first `limit` jobs are kept in memory; past that the list spills to
+/// a temporary file in the `--plan-out` format, so a huge tree costs disk
+/// rather than memory. The file is removed once the jobs have been read.
+///
+/// The spill file gets a random name and is created exclusively, readable
+/// by us only, then read back through the same handle: nothing another user
+/// puts in the temporary directory can end up in the list.
+pub struct JobList {
+    limit: usize,
+    jobs: Vec<FileJob>,
+    spill: Option<(PathBuf, BufWriter<NamedTempFile>)>,
+    entries: u64,
+    bytes: u64,
+}
+
+impl JobList {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            jobs: Vec::new(),
+            spill: None,
+            entries: 0,
+            bytes: 0,
+        }
+    }
+
+    pub fn push(&mut self, job: FileJob) -> Result<(), RemoveError> {
+        self.entries += 1;
+        if let FileJob::File { size, .. } = job {
+            self.bytes += size;
+        }
+        if self.spill.is_none() && self.jobs.len() < self.limit {
+            self.jobs.push(job);
+            return Ok(());
+        }
+        if self.spill.is_none() {
+            let file = tempfile::Builder::new()
+                .prefix("fast-rm-jobs-")
+                .suffix(".plan")
+                .tempfile()
+                .map_err(|e| RemoveError::SpillFailed(std::env::temp_dir(), e))?;
+            let path = file.path().to_path_buf();
+            eprintln!(
+                "{} more than {} entries to delete; the rest of the list goes to {:?}",
+                "Warning:".yellow(),
+                format_count(self.limit as u64),
+                path
+            );
+            self.spill = Some((path, BufWriter::new(file)));
+        }
+        let (path, writer) = self.spill.as_mut().expect("spill file just created");
+        writer
+            .write_all(format_job(&job).as_bytes())
+            .map_err(|e| RemoveError::SpillFailed(path.clone(), e))
+    }
+
+    /// Files, symlinks and directories in the list
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// Total size of the regular files in the list
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Whether part of the list is on disk
+    #[allow(dead_code)]
+    pub fn spilled(&self) -> bool {
+        self.spill.is_some()
+    }
+
+    /// The jobs in order, memory first, then the spill file
+    pub fn into_jobs(mut self) -> Result<Jobs, RemoveError> {
+        let spilled = match self.spill.take() {
+            Some((path, writer)) => {
+                let file = writer
+                    .into_inner()
+                    .map_err(|e| e.into_error())
+                    .and_then(|mut file| file.seek(SeekFrom::Start(0)).map(|_| file))
+                    .map_err(|e| RemoveError::SpillFailed(path.clone(), e))?;
+                Some((path, BufReader::new(file).lines()))
+            }
+            None => None,
+        };
+        Ok(Jobs {
+            memory: std::mem::take(&mut self.jobs).into_iter(),
+            spilled,
+        })
+    }
+}
+
+/// Iterator over a `JobList`'s jobs; removes the spill file when dropped
+pub struct Jobs {
+    memory: std::vec::IntoIter<FileJob>,
+    spilled: Option<(PathBuf, Lines<BufReader<NamedTempFile>>)>,
+}
+
+impl Iterator for Jobs {
+    type Item = Result<FileJob, RemoveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(job) = self.memory.next() {
+            return Some(Ok(job));
+        }
+        let (path, lines) = self.spilled.as_mut()?;
+        let line = lines.next()?;
+        Some(
+            line.map_err(|e| RemoveError::SpillFailed(path.clone(), e))
+                .and_then(|line| {
+                    parse_job(&line).map_err(|reason| {
+                        RemoveError::SpillFailed(
+                            path.clone(),
+                            io::Error::new(io::ErrorKind::InvalidData, reason),
+                        )
+                    })
+                }),
+        )
+    }
+}
+
+/// Phase one: scan every path completely, deleting nothing
+///
+/// Scan errors are reported and the path counted as failed, as in a normal
+/// run; only failing to keep the list is fatal.
+pub fn scan_all(
+    paths: &[PathBuf],
+    config: &RemoveConfig,
+    limit: usize,
+) -> Result<JobList, RemoveError> {
+    let mut list = JobList::new(limit);
+    let mut kept: Result<(), RemoveError> = Ok(());
+    for path in paths {
+        let scanned = collect_jobs(path, config, |job| {
+            if kept.is_ok() {
+                kept = list.push(job);
+            }
+        });
+        if let Err(e) = scanned {
+            eprintln!("{} {}", "Scan error:".red().bold(), e);
+            if let Some(p) = &config.progress {
+                p.inc_path_error(path);
+            }
+        }
+        if kept.is_err() {
+            break;
+        }
+    }
+    kept.map(|()| list)
+}
+
+/// Phase one for `--top-largest`: scan every path and list only its `n`
+/// largest regular files, biggest first
+///
+/// A min-heap of the `n` largest seen so far keeps memory bounded however
+/// many files there are. Scan errors are reported as in `scan_all`.
+pub fn scan_largest(
+    paths: &[PathBuf],
+    config: &RemoveConfig,
+    n: usize,
+) -> Result<JobList, RemoveError> {
+    let mut largest = BinaryHeap::with_capacity(n + 1);
+    for path in paths {
+        let scanned = collect_jobs(path, config, |job| {
+            if let FileJob::File { path, size } = job {
+                largest.push(Reverse((size, path)));
+                if largest.len() > n {
+                    largest.pop();
+                }
+            }
+        });
+        if let Err(e) = scanned {
+            eprintln!("{} {}", "Scan error:".red().bold(), e);
+            if let Some(p) = &config.progress {
+                p.inc_path_error(path);
+            }
+        }
+    }
+    let mut list = JobList::new(MEMORY_JOBS);
+    // Ascending by Reverse, so the biggest come first
+    for Reverse((size, path)) in largest.into_sorted_vec() {
+        list.push(FileJob::File { path, size })?;
+    }
+    Ok(list)
+}
+
+/// Show what phase one found and wait for Enter before phase two
+///
+/// Returns `false` if anything else is typed or the input ends.
+pub fn confirm_totals(
+    list: &JobList,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<bool> {
+    writeln!(
+        output,
+        "Scan complete: {} entries ({}) to delete",
+        format_count(list.entries()),
+        format_bytes(list.bytes())
+    )?;
+    write!(
+        output,
+        "Press Enter to delete them, anything else to abort: "
+    )?;
+    output.flush()?;
+
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        return Ok(false);
+    }
+    Ok(answer.trim_end_matches(['\r', '\n']).is_empty())
+}
+
+/// Phase two: queue the jobs for the deleters
+///
+/// They were counted as scanned in phase one, so only the queue depth is
+/// recorded here.
+pub fn enqueue_jobs(
+    jobs: Jobs,
+    queue: &AdaptiveQueue,
+    config: &RemoveConfig,
+) -> Result<(), RemoveError> {
+    for job in jobs {
+        if config.is_cancelled() {
+            break;
+        }
+        queue.send(job?).map_err(|_| RemoveError::QueueFull)?;
+        if let Some(p) = &config.progress {
+            p.record_queue_depth(queue.depth());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deleter::delete_worker;
+    use crate::progress::RemoveProgress;
+    use std::fs::{self, File};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tempfile::TempDir;
+
+    fn spill_path(jobs: &Jobs) -> Option<&Path> {
+        jobs.spilled.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    fn tree(root: &Path) {
+        for dir in ["a", "b"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+            for i in 0..5 {
+                fs::write(root.join(dir).join(format!("f{}", i)), "data").unwrap();
+            }
+        }
+    }
+
+    fn run(root: &Path, limit: usize, answer: &str) -> bool {
+        let progress = RemoveProgress::new();
+        let config = RemoveConfig {
+            progress: Some(progress.clone()),
+            ..Default::default()
+        };
+
+        // Phase one deletes nothing
+        let list = scan_all(&[root.to_path_buf()], &config, limit).unwrap();
+        assert!(root.join("a/f0").exists());
+        assert_eq!(list.entries(), 13);
+        assert_eq!(list.bytes(), 40);
+        assert_eq!(list.spilled(), limit < 13);
+
+        let mut output = Vec::new();
+        if !confirm_totals(&list, &mut answer.as_bytes(), &mut output).unwrap() {
+            return false;
+        }
+        assert!(String::from_utf8(output).unwrap().contains("13 entries"));
+
+        let queue = AdaptiveQueue::unbounded();
+        enqueue_jobs(list.into_jobs().unwrap(), &queue, &config).unwrap();
+        delete_worker(&queue, &config, &AtomicBool::new(true));
+        assert_eq!(progress.deleted.load(Ordering::Relaxed), 13);
+        true
+    }
+
+    #[test]
+    fn test_two_phase_deletes_only_after_enter() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        tree(&root);
+
+        assert!(!run(&root, MEMORY_JOBS, "no\n"));
+        assert!(!run(&root, MEMORY_JOBS, ""));
+        assert!(root.join("b/f4").exists());
+
+        assert!(run(&root, MEMORY_JOBS, "\n"));
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn test_only_the_largest_files_are_deleted() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        for (name, size) in [
+            ("a", 10),
+            ("sub/b", 500),
+            ("c", 40),
+            ("sub/d", 300),
+            ("e", 20),
+        ] {
+            File::create(root.join(name))
+                .unwrap()
+                .set_len(size)
+                .unwrap();
+        }
+
+        let progress = RemoveProgress::new();
+        let config = RemoveConfig {
+            progress: Some(progress.clone()),
+            ..Default::default()
+        };
+        let list = scan_largest(std::slice::from_ref(&root), &config, 3).unwrap();
+        assert_eq!((list.entries(), list.bytes()), (3, 840));
+        let jobs: Vec<_> = list.into_jobs().unwrap().map(Result::unwrap).collect();
+        let sizes: Vec<_> = jobs
+            .iter()
+            .map(|job| match job {
+                FileJob::File { size, .. } => *size,
+                _ => panic!("only files are listed: {:?}", job),
+            })
+            .collect();
+        assert_eq!(sizes, [500, 300, 40]);
+
+        let list = scan_largest(std::slice::from_ref(&root), &config, 3).unwrap();
+        let queue = AdaptiveQueue::unbounded();
+        enqueue_jobs(list.into_jobs().unwrap(), &queue, &config).unwrap();
+        delete_worker(&queue, &config, &AtomicBool::new(true));
+        assert_eq!(progress.bytes.load(Ordering::Relaxed), 840);
+        for gone in ["sub/b", "sub/d", "c"] {
+            assert!(!root.join(gone).exists(), "{}", gone);
+        }
+        for kept in ["a", "e", "sub"] {
+            assert!(root.join(kept).exists(), "{}", kept);
+        }
+    }
+
+    #[test]
+    fn test_spilled_list_keeps_order_and_is_removed() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        tree(&root);
+
+        let config = RemoveConfig::default();
+        let list = scan_all(std::slice::from_ref(&root), &config, 4).unwrap();
+        let jobs = list.into_jobs().unwrap();
+        let spill = spill_path(&jobs).unwrap().to_path_buf();
+        assert!(spill.exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&spill).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let jobs: Vec<FileJob> = jobs.map(Result::unwrap).collect();
+        assert!(!spill.exists());
+
+        assert_eq!(jobs.len(), 13);
+        assert_eq!(jobs.last().unwrap().path(), root);
+        assert!(run(&root, 4, "\n"));
+    }
+}
@@ -0,0 +1,191 @@
+//! Naming the paths deleters are stuck on (`--stuck-timeout`)
+//!
+//! On a hung network mount a single unlink can block for good, and the
+//! display simply stops moving. Each deleter marks the path it is working
+//! on; when one of them has been on its path for the timeout, the watchdog
+//! reports it.
+
+use colored::*;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::human::format_duration;
+
+/// Longest pause between two looks at the deleted count
+const MAX_POLL: Duration = Duration::from_secs(1);
+
+/// The path one deleter is working on, and since when
+#[derive(Debug, Default)]
+pub struct Slot(Mutex<Option<(Arc<Path>, Instant)>>);
+
+impl Slot {
+    pub fn start(&self, path: &Path) {
+        *self.0.lock().unwrap() = Some((Arc::from(path), Instant::now()));
+    }
+
+    pub fn finish(&self) {
+        *self.0.lock().unwrap() = None;
+    }
+}
+
+/// What a deleter was on when the watchdog looked
+#[derive(Debug, Clone)]
+pub struct Busy {
+    /// Deleters are numbered in the order they started
+    pub worker: usize,
+    pub path: Arc<Path>,
+    pub elapsed: Duration,
+}
+
+/// The slots of every deleter started so far; one that has exited keeps an
+/// empty slot
+#[derive(Debug, Default)]
+pub struct InFlight {
+    slots: Mutex<Vec<Arc<Slot>>>,
+}
+
+impl InFlight {
+    /// A slot for a deleter that is starting
+    pub fn register(&self) -> Arc<Slot> {
+        let slot = Arc::new(Slot::default());
+        self.slots.lock().unwrap().push(slot.clone());
+        slot
+    }
+
+    /// The deleters currently on a path, by worker number
+    pub fn busy(&self) -> Vec<Busy> {
+        self.slots
+            .lock()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .filter_map(|(worker, slot)| {
+                let current = slot.0.lock().unwrap();
+                current.as_ref().map(|(path, started)| Busy {
+                    worker,
+                    path: path.clone(),
+                    elapsed: started.elapsed(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Watch the deleters until `done`, calling `report` with those whose
+/// current deletion has been running for `timeout`
+///
+/// Deleters are timed on their own operation rather than on the shared
+/// deleted count, which is batched (`--chunk-size`) and kept moving by the
+/// other deleters. Each stuck deletion is reported once, along with any
+/// others stuck at the time. Deleters idling on an empty queue, e.g. during
+/// a long scan, are no stall. Unpark the thread after
+/// setting `done` to have it return at once.
+pub fn watch(
+    in_flight: &InFlight,
+    timeout: Duration,
+    done: &AtomicBool,
+    mut report: impl FnMut(Duration, &[Busy]),
+) {
+    let poll = (timeout / 4).min(MAX_POLL);
+    // The stuck deletions already reported, by worker and path
+    let mut reported: Vec<(usize, Arc<Path>)> = Vec::new();
+    while !done.load(Ordering::Relaxed) {
+        thread::park_timeout(poll);
+        let mut stuck = in_flight.busy();
+        stuck.retain(|b| b.elapsed >= timeout);
+        let fresh = stuck
+            .iter()
+            .any(|b| !reported.contains(&(b.worker, b.path.clone())));
+        if let (true, Some(longest)) = (fresh, stuck.iter().map(|b| b.elapsed).max()) {
+            report(longest, &stuck);
+        }
+        reported = stuck.into_iter().map(|b| (b.worker, b.path)).collect();
+    }
+}
+
+/// Print a stall to stderr, one line per busy deleter
+pub fn print_stall(stalled: Duration, busy: &[Busy]) {
+    eprintln!(
+        "{} a deletion has been running for {}; the stuck deleters are on:",
+        "Warning:".yellow(),
+        format_duration(stalled)
+    );
+    for b in busy {
+        eprintln!(
+            "  worker {}: {:?} (for {})",
+            b.worker,
+            b.path,
+            format_duration(b.elapsed)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_stalled_deleter_is_reported() {
+        let in_flight = Arc::new(InFlight::default());
+        let busy_worker = in_flight.register();
+        let stuck = in_flight.register();
+        stuck.start(Path::new("/mnt/nfs/hung.bin"));
+
+        let done = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+        let watcher = {
+            let (in_flight, done) = (in_flight.clone(), done.clone());
+            thread::spawn(move || {
+                watch(&in_flight, Duration::from_millis(40), &done, |_, busy| {
+                    tx.send(busy.to_vec()).unwrap()
+                })
+            })
+        };
+        // Another deleter keeps getting through quick deletions meanwhile
+        let worker = {
+            let done = done.clone();
+            thread::spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    busy_worker.start(Path::new("/data/quick.txt"));
+                    thread::sleep(Duration::from_millis(1));
+                    busy_worker.finish();
+                }
+            })
+        };
+
+        let busy = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(busy.len(), 1);
+        assert_eq!(busy[0].worker, 1);
+        assert_eq!(&*busy[0].path, Path::new("/mnt/nfs/hung.bin"));
+        assert!(busy[0].elapsed >= Duration::from_millis(40));
+        // Reported once per stuck deletion
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+        done.store(true, Ordering::Relaxed);
+        watcher.thread().unpark();
+        watcher.join().unwrap();
+        worker.join().unwrap();
+    }
+
+    #[test]
+    fn test_idle_deleters_are_no_stall() {
+        let in_flight = InFlight::default();
+        in_flight.register();
+        let done = AtomicBool::new(false);
+        let mut reports = 0;
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(150));
+                done.store(true, Ordering::Relaxed);
+            });
+            watch(&in_flight, Duration::from_millis(20), &done, |_, _| {
+                reports += 1
+            });
+        });
+        assert_eq!(reports, 0);
+    }
+}
@@ -0,0 +1,1663 @@
+//! End-to-end tests that run the fast-rm binary
+
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn fast_rm() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_fast-rm"))
+}
+
+/// Create `count` empty files under `dir`
+fn create_files(dir: &Path, count: usize) {
+    fs::create_dir_all(dir).unwrap();
+    for i in 0..count {
+        File::create(dir.join(format!("file_{:06}.txt", i))).unwrap();
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_progress_socket_publishes_json() {
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixStream;
+    use std::time::{Duration, Instant};
+
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    let socket = temp_dir.path().join("progress.sock");
+    // Enough work that the run outlives the client's connect loop
+    create_files(&target, 20_000);
+
+    let mut child = fast_rm()
+        .args(["--delete-threads", "1", "--progress-socket"])
+        .arg(&socket)
+        .arg(&target)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let start = Instant::now();
+    let stream = loop {
+        match UnixStream::connect(&socket) {
+            Ok(stream) => break stream,
+            Err(_) if start.elapsed() < Duration::from_secs(10) => {
+                std::thread::sleep(Duration::from_millis(1))
+            }
+            Err(e) => panic!("could not connect to progress socket: {}", e),
+        }
+    };
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).unwrap();
+    assert!(
+        line.starts_with("{\"schema_version\":1,\"scanned\":"),
+        "unexpected message: {}",
+        line
+    );
+    assert!(line.contains("\"deleted\":"));
+
+    assert!(child.wait().unwrap().success());
+    assert!(!socket.exists(), "socket file should be removed on exit");
+    assert!(!target.exists());
+}
+
+#[test]
+fn test_color_never_emits_no_escape_codes() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    create_files(&target, 3);
+
+    // --color always is the control: the same run does produce escapes
+    let colored = fast_rm()
+        .args(["--color", "always", "-n"])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&colored.stdout).contains("\x1b["));
+
+    let output = fast_rm()
+        .args(["--color", "never", "-n"])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Dry run"));
+    assert!(
+        !stdout.contains('\x1b'),
+        "unexpected escape codes: {:?}",
+        stdout
+    );
+}
+
+#[test]
+fn test_piped_output_has_no_cursor_movement() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    create_files(&target, 10);
+
+    // Output is captured through a pipe, so the TUI must stay off
+    let output = fast_rm().arg(&target).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for stream in [&stdout, &stderr] {
+        assert!(
+            !stream.contains('\x1b'),
+            "unexpected escape codes: {:?}",
+            stream
+        );
+    }
+    assert!(stdout.contains("Complete: 11 scanned, 11 deleted, 0 errors"));
+}
+
+#[test]
+fn test_typed_confirm_mismatch_aborts() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    create_files(&target, 3);
+
+    let run = |answer: &str| {
+        let mut child = fast_rm()
+            .arg("--require-typed-confirm")
+            .arg(&target)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        writeln!(child.stdin.take().unwrap(), "{}", answer).unwrap();
+        let output = child.wait_with_output().unwrap();
+        (
+            output.status.code(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        )
+    };
+
+    let (code, stderr) = run("nope");
+    assert_eq!(code, Some(2));
+    // The prompt says how much is at stake: 3 files and their directory
+    assert!(stderr.contains("(4 entries, 0 B)"), "{}", stderr);
+    assert!(target.join("file_000000.txt").exists());
+
+    assert_eq!(run(target.to_str().unwrap()).0, Some(0));
+    assert!(!target.exists());
+}
+
+#[test]
+fn test_older_than_with_prune_empty() {
+    use std::time::{Duration, SystemTime};
+
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("root");
+    let week_ago = SystemTime::now() - Duration::from_secs(7 * 86400);
+    for dir in ["logs/2020", "cache"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+        File::create(root.join(dir).join("old.txt"))
+            .unwrap()
+            .set_modified(week_ago)
+            .unwrap();
+    }
+    File::create(root.join("fresh.txt")).unwrap();
+
+    let run = |extra: &[&str]| {
+        let output = fast_rm()
+            .args(["--older-than", "1d"])
+            .args(extra)
+            .arg(&root)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+    };
+
+    // The old files go, but their (freshly modified) directories are kept
+    run(&[]);
+    assert!(!root.join("logs/2020/old.txt").exists());
+    assert!(root.join("logs/2020").is_dir());
+    assert!(root.join("cache").is_dir());
+
+    // The second pass removes the empty skeleton, but not the root holding fresh.txt
+    run(&["--prune-empty"]);
+    assert!(!root.join("logs").exists());
+    assert!(!root.join("cache").exists());
+    assert!(root.join("fresh.txt").exists());
+}
+
+#[test]
+fn test_prune_empty_respects_scope_and_exclusions() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("root");
+    for dir in ["build/out", "src/empty", ".cache/empty"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+    }
+
+    let output = fast_rm()
+        .args(["--prune-empty", "--skip-hidden", "--match-prefix"])
+        .arg(root.join("build"))
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!root.join("build").exists());
+    assert!(root.join("src/empty").is_dir());
+    assert!(root.join(".cache/empty").is_dir());
+}
+
+#[test]
+fn test_dry_run_diff_shows_deleted_and_kept() {
+    use std::time::{Duration, SystemTime};
+
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("root");
+    fs::create_dir_all(root.join("logs")).unwrap();
+    File::create(root.join("logs/old.log"))
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(3 * 86400))
+        .unwrap();
+    File::create(root.join("logs/new.log")).unwrap();
+    File::create(root.join(".env")).unwrap();
+
+    let output = fast_rm()
+        .args(["-n", "--diff", "--color", "never", "--skip-hidden"])
+        .args(["--older-than", "1d"])
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = |path: &str| format!("{:?}", root.join(path));
+    assert!(
+        stdout.contains(&format!("- {}", line("logs/old.log"))),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains(&format!("  {} (kept: newer-than)", line("logs/new.log"))),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains(&format!("  {} (kept: hidden)", line(".env"))));
+    assert!(stdout.contains(&format!("  {} (kept: 1 entry kept)", line("logs"))));
+    assert!(!stdout.contains('\x1b'));
+    assert!(root.join("logs/old.log").exists());
+}
+
+#[test]
+fn test_backup_to_mirrors_deleted_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("build");
+    fs::create_dir_all(root.join("out/obj")).unwrap();
+    let files = [
+        ("out/app", "binary"),
+        ("out/obj/a.o", "object"),
+        ("log.txt", ""),
+    ];
+    for (name, contents) in files {
+        fs::write(root.join(name), contents).unwrap();
+    }
+    let backup = temp_dir.path().join("backup");
+
+    let output = fast_rm()
+        .arg("--backup-to")
+        .arg(&backup)
+        .arg("--backup-verify")
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!root.exists());
+    for (name, contents) in files {
+        let copy = backup.join("build").join(name);
+        assert_eq!(fs::read_to_string(&copy).unwrap(), contents, "{:?}", copy);
+    }
+
+    // A backup inside the tree being deleted is refused up front
+    create_files(&root, 2);
+    let output = fast_rm()
+        .arg("--backup-to")
+        .arg(root.join("backup"))
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(root.join("file_000000.txt").exists());
+}
+
+#[test]
+fn test_keep_glob_protects_matches_and_their_parents() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("build");
+    create_files(&root.join("deps/a/b"), 3);
+    create_files(&root.join("out"), 4);
+    fs::write(root.join("deps/a/b/Cargo.lock"), "").unwrap();
+
+    let output = fast_rm()
+        .args(["--keep", "*.lock"])
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(root.join("deps/a/b/Cargo.lock").exists());
+    assert_eq!(fs::read_dir(root.join("deps/a/b")).unwrap().count(), 1);
+    assert_eq!(fs::read_dir(root.join("deps")).unwrap().count(), 1);
+    assert_eq!(fs::read_dir(&root).unwrap().count(), 1);
+    assert!(!root.join("out").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_inaccessible_argument_is_reported_up_front() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let locked = temp_dir.path().join("locked");
+    let target = locked.join("target");
+    let other = temp_dir.path().join("other");
+    create_files(&target, 2);
+    create_files(&other, 3);
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+    let run = |continue_on_error: bool| {
+        let mut command = fast_rm();
+        command.args(["--color", "never"]);
+        if continue_on_error {
+            command.arg("--continue-on-error");
+        }
+        command.arg(&target).arg(&other).output().unwrap()
+    };
+    let accessible_anyway = fs::symlink_metadata(&target).is_ok();
+    let strict = run(false);
+    let lenient = run(true);
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    // Permission bits do not apply to root
+    if accessible_anyway {
+        return;
+    }
+
+    // Without -c nothing is deleted
+    assert_eq!(strict.status.code(), Some(1), "{:?}", strict);
+    let stderr = String::from_utf8_lossy(&strict.stderr);
+    assert!(
+        stderr.contains(&format!("Error: Cannot access argument {:?}", target)),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("hint: a directory above it lacks search"));
+    assert!(!stderr.contains("Failed to canonicalize"), "{}", stderr);
+
+    // With -c the other argument is deleted and the run succeeds
+    assert!(lenient.status.success(), "{:?}", lenient);
+    let stderr = String::from_utf8_lossy(&lenient.stderr);
+    assert!(
+        stderr.contains("Warning: Cannot access argument"),
+        "{}",
+        stderr
+    );
+    assert!(!other.exists());
+    assert!(target.exists());
+}
+
+#[test]
+fn test_engine_auto_recurses_only_on_small_plain_inputs() {
+    let temp_dir = TempDir::new().unwrap();
+    let engine = |extra: &[&str], files: usize| {
+        let root = temp_dir.path().join("tree");
+        create_files(&root.join("sub"), files);
+        let output = fast_rm()
+            .args(["-v", "--color", "never"])
+            .args(extra)
+            .arg(&root)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        assert!(!root.exists());
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let line = stdout.lines().find(|l| l.starts_with("Engine: "));
+        line.unwrap_or_default().to_string()
+    };
+    assert_eq!(engine(&[], 20), "Engine: recursive");
+    assert_eq!(engine(&[], 2_000), "Engine: pipeline");
+    assert_eq!(engine(&["--older-than", "0s"], 20), "Engine: pipeline");
+    assert_eq!(engine(&["--engine", "pipeline"], 20), "Engine: pipeline");
+
+    // Options the recursive engine cannot honour are refused up front
+    let root = temp_dir.path().join("kept");
+    create_files(&root, 3);
+    let output = fast_rm()
+        .args(["--engine", "recursive", "--keep", "*.txt"])
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert_eq!(fs::read_dir(&root).unwrap().count(), 3);
+}
+
+#[test]
+fn test_rename_children_removes_every_child() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    create_files(&target, 50);
+    create_files(&target.join("sub/deeper"), 20);
+    fs::write(target.join(".hidden"), "").unwrap();
+
+    let output = fast_rm()
+        .args(["--rename-children", "--color", "never"])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!target.exists());
+    // 71 files, sub, deeper, the staging directory and the target itself
+    assert!(String::from_utf8_lossy(&output.stdout).contains("75 deleted"));
+
+    // With --contents-only the staging directory goes, the target stays
+    create_files(&target.join("sub"), 5);
+    let output = fast_rm()
+        .args(["--rename-children", "--contents-only"])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fs::read_dir(&target).unwrap().count(), 0);
+}
+
+#[test]
+fn test_exclude_vcs_keeps_repository_metadata() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("project");
+    create_files(&root.join(".git/objects"), 3);
+    create_files(&root.join("vendor/lib/.hg"), 1);
+    create_files(&root.join("target/debug"), 5);
+    create_files(&root.join("src"), 2);
+
+    let output = fast_rm()
+        .args(["--exclude-vcs", "--color", "never"])
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(root.join(".git/objects/file_000000.txt").exists());
+    assert!(root.join("vendor/lib/.hg/file_000000.txt").exists());
+    assert!(!root.join("target").exists());
+    assert!(!root.join("src").exists());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("2 (vcs); 3 directories kept"), "{}", stdout);
+}
+
+#[test]
+fn test_dry_run_exit_code_tells_whether_anything_matched() {
+    use std::time::{Duration, SystemTime};
+
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("root");
+    fs::create_dir_all(root.join("logs")).unwrap();
+    File::create(root.join("logs/new.log")).unwrap();
+
+    let dry_run = |age: &str| {
+        fast_rm()
+            .args(["-n", "--older-than", age])
+            .arg(&root)
+            .output()
+            .unwrap()
+    };
+
+    let output = dry_run("1d");
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+
+    File::create(root.join("logs/old.log"))
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(3 * 86400))
+        .unwrap();
+    let output = dry_run("1d");
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    assert!(root.join("logs/old.log").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_inodes_from_deletes_only_listed_entries() {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("root");
+    for dir in ["lost", "keep"] {
+        create_files(&root.join(dir), 3);
+    }
+    let inode = |path: &Path| fs::symlink_metadata(path).unwrap().ino();
+    let listed: Vec<String> = fs::read_dir(root.join("lost"))
+        .unwrap()
+        .map(|entry| inode(&entry.unwrap().path()).to_string())
+        .chain([inode(&root.join("keep/file_000000.txt")).to_string()])
+        .collect();
+    let list = temp_dir.path().join("inodes.txt");
+    fs::write(&list, listed.join("\n") + "\n").unwrap();
+
+    let output = fast_rm()
+        .arg("--inodes-from")
+        .arg(&list)
+        .arg("--prune-empty")
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!root.join("lost").exists());
+    assert!(!root.join("keep/file_000000.txt").exists());
+    assert!(root.join("keep/file_000001.txt").exists());
+    assert!(root.join("keep/file_000002.txt").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_dereference_args_top_level_symlink() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    let link = temp_dir.path().join("link");
+    create_files(&target.join("inner"), 3);
+    // An interior link is removed as a link even when dereferencing arguments
+    let outside = temp_dir.path().join("outside");
+    create_files(&outside, 1);
+    std::os::unix::fs::symlink(&outside, target.join("to_outside")).unwrap();
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    // Without the flag only the link goes
+    let output = fast_rm().arg(&link).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(fs::symlink_metadata(&link).is_err());
+    assert!(target.join("inner/file_000000.txt").exists());
+
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+    let output = fast_rm()
+        .arg("--dereference-args")
+        .arg(&link)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!target.exists());
+    assert!(fs::symlink_metadata(&link).is_ok());
+    assert!(outside.join("file_000000.txt").exists());
+}
+
+#[test]
+fn test_timeout_stops_early() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    // Far more than one deleter gets through before the deadline
+    create_files(&target, 50_000);
+
+    let output = fast_rm()
+        .args(["--delete-threads", "1", "--timeout", "20ms"])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(124), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Timed out:"));
+    assert!(target.is_dir());
+    assert!(fs::read_dir(&target).unwrap().next().is_some());
+}
+
+#[test]
+fn test_parallel_top_level_deletes_every_path() {
+    let temp_dir = TempDir::new().unwrap();
+    let targets: Vec<_> = (0..5)
+        .map(|i| {
+            let target = temp_dir.path().join(format!("tree_{}", i));
+            create_files(&target.join("sub"), 50);
+            target
+        })
+        .collect();
+
+    let output = fast_rm()
+        .args(["--parallel-top-level", "2"])
+        .args(&targets)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(targets.iter().all(|t| !t.exists()));
+    // 5 × (root + sub + 50 files)
+    assert!(String::from_utf8_lossy(&output.stdout).contains("260 deleted"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_special_file_is_removed_with_warning() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    create_files(&target, 2);
+    let socket = std::os::unix::net::UnixListener::bind(target.join("app.sock")).unwrap();
+
+    let output = fast_rm().arg("--no-special").arg(&target).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(target.join("app.sock").exists());
+    assert!(!target.join("file_000000.txt").exists());
+
+    let output = fast_rm().arg(&target).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Warning: removing socket"));
+    assert!(!target.exists());
+    drop(socket);
+}
+
+#[test]
+fn test_journal_resume_after_crash() {
+    use std::time::{Duration, Instant};
+
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    let journal = temp_dir.path().join("journal");
+    for dir in 0..4 {
+        create_files(&target.join(format!("dir_{}", dir)), 5_000);
+    }
+    let total = 1 + 4 * (1 + 5_000);
+
+    // Kill the first run once the journal has its first synced batch
+    let mut child = fast_rm()
+        .args(["--delete-threads", "1", "--journal"])
+        .arg(&journal)
+        .arg(&target)
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let start = Instant::now();
+    while fs::metadata(&journal).map_or(0, |m| m.len()) == 0 {
+        assert!(
+            start.elapsed() < Duration::from_secs(20),
+            "journal stayed empty"
+        );
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert!(target.exists(), "first run finished before it was killed");
+
+    let output = fast_rm()
+        .arg("--resume")
+        .arg(&journal)
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!target.exists());
+
+    // Every path was deleted (and journaled) exactly once across both runs
+    let records: Vec<_> = fs::read(&journal)
+        .unwrap()
+        .split(|&b| b == 0)
+        .filter(|r| !r.is_empty())
+        .map(<[u8]>::to_vec)
+        .collect();
+    let unique: std::collections::HashSet<_> = records.iter().collect();
+    assert_eq!(unique.len(), records.len(), "a path was deleted twice");
+    assert!(records.len() <= total);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_max_errors_aborts_early() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("root");
+    let outside = temp_dir.path().join("outside");
+    create_files(&outside, 2_000);
+    fs::create_dir_all(&root).unwrap();
+    // Every link leads outside the root, so each followed target is an error
+    for i in 0..2_000 {
+        std::os::unix::fs::symlink(
+            outside.join(format!("file_{:06}.txt", i)),
+            root.join(format!("link_{}", i)),
+        )
+        .unwrap();
+    }
+
+    let output = fast_rm()
+        .args([
+            "--follow-symlinks",
+            "--stay-within-root",
+            "-c",
+            "--max-errors",
+            "10",
+            "--delete-threads",
+            "1",
+        ])
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Aborted: stopped after"), "{}", stderr);
+    // Stopped long before every link was tried
+    assert!(fs::read_dir(&root).unwrap().count() > 1_000);
+    assert!(outside.join("file_000000.txt").exists());
+}
+
+#[test]
+fn test_no_recursive_refuses_directories() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path().join("dir");
+    let file = temp_dir.path().join("file.txt");
+    create_files(&dir, 2);
+    File::create(&file).unwrap();
+
+    let output = fast_rm()
+        .arg("--no-recursive")
+        .arg(&file)
+        .arg(&dir)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is a directory"));
+    assert!(!file.exists());
+    assert!(dir.join("file_000000.txt").exists());
+}
+
+#[test]
+fn test_contents_only_keeps_directory() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    create_files(&target.join("nested/deeper"), 5);
+    create_files(&target, 5);
+
+    let output = fast_rm()
+        .arg("--contents-only")
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(target.is_dir());
+    assert_eq!(fs::read_dir(&target).unwrap().count(), 0);
+
+    // Pruning empty directories spares the kept one too; the fresh
+    // directories are only removed by the prune pass
+    fs::create_dir_all(target.join("empty/tree")).unwrap();
+    let output = fast_rm()
+        .args(["--contents-only", "--prune-empty", "--older-than", "1d"])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(target.is_dir());
+    assert_eq!(fs::read_dir(&target).unwrap().count(), 0);
+}
+
+#[test]
+fn test_keep_list_survives_with_parents() {
+    let temp_dir = TempDir::new().unwrap();
+    let roots = [temp_dir.path().join("one"), temp_dir.path().join("two")];
+    for root in &roots {
+        create_files(&root.join("config/nested"), 3);
+        create_files(&root.join("cache"), 3);
+    }
+    let list = temp_dir.path().join("keep.txt");
+    fs::write(&list, "config/nested/file_000001.txt\n").unwrap();
+
+    let output = fast_rm()
+        .arg("--keep-list")
+        .arg(&list)
+        .args(&roots)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    // The entry is resolved against each given path
+    for root in &roots {
+        assert!(root.join("config/nested/file_000001.txt").exists());
+        assert!(!root.join("config/nested/file_000000.txt").exists());
+        assert!(!root.join("cache").exists());
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_on_error_hook_runs_for_failed_path() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("root");
+    let outside = temp_dir.path().join("outside");
+    let log = temp_dir.path().join("hook.log");
+    create_files(&root, 1);
+    create_files(&outside, 1);
+    // Following this link leads outside the root, which is refused as an error
+    std::os::unix::fs::symlink(&outside, root.join("escape")).unwrap();
+
+    let output = fast_rm()
+        .args([
+            "--follow-symlinks",
+            "--stay-within-root",
+            "-c",
+            "--on-error",
+        ])
+        .arg(format!(
+            "echo \"$FASTRM_ERROR_PATH: $FASTRM_ERROR_MSG\" >> '{}'",
+            log.display()
+        ))
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+
+    let log = fs::read_to_string(&log).unwrap();
+    assert!(
+        log.contains("file_000000.txt: Refusing to delete"),
+        "{}",
+        log
+    );
+    assert!(outside.join("file_000000.txt").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_summary_json_file_written_on_failure() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("root");
+    let outside = temp_dir.path().join("outside");
+    let summary = temp_dir.path().join("summary.json");
+    create_files(&root, 3);
+    create_files(&outside, 1);
+    std::os::unix::fs::symlink(&outside, root.join("escape")).unwrap();
+
+    let output = fast_rm()
+        .args(["--follow-symlinks", "--stay-within-root", "-c"])
+        .arg("--summary-json-file")
+        .arg(&summary)
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    // The terminal output is unchanged
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Summary:"));
+
+    let json = fs::read_to_string(&summary).unwrap();
+    assert!(
+        json.starts_with(
+            "{\"schema_version\":1,\"outcome\":\"errors\",\"exit_code\":1,\"dry_run\":false,"
+        ),
+        "{}",
+        json
+    );
+    // The outside directory and its file are refused; the three files, the
+    // link and the root itself are removed
+    assert!(json.contains("\"items\":5,\"errors\":2,"), "{}", json);
+    assert!(json.ends_with("}\n"));
+}
+
+#[test]
+fn test_abort_over_bytes_deletes_nothing() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    fs::create_dir_all(target.join("nested")).unwrap();
+    fs::write(target.join("a.bin"), vec![0u8; 3000]).unwrap();
+    fs::write(target.join("nested/b.bin"), vec![0u8; 3000]).unwrap();
+
+    let output = fast_rm()
+        .args(["--abort-over-bytes", "4K"])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--abort-over-bytes 4.0 KiB"));
+    assert!(target.join("a.bin").exists());
+    assert!(target.join("nested/b.bin").exists());
+
+    // Under the cap the run goes ahead
+    let output = fast_rm()
+        .args(["--abort-over-bytes", "8K"])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!target.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_max_open_dirs_stays_under_fd_limit() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    // Deeper than the descriptor limit below, and wide
+    let mut deep = target.clone();
+    for _ in 0..60 {
+        deep.push("d");
+    }
+    create_files(&deep, 5);
+    for i in 0..100 {
+        create_files(&target.join(format!("wide_{}", i)), 2);
+    }
+
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(r#"ulimit -n 32 && exec "$0" --max-open-dirs 4 "$1""#)
+        .arg(env!("CARGO_BIN_EXE_fast-rm"))
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("Too many open files"));
+    assert!(!target.exists());
+}
+
+#[test]
+fn test_print0_lists_each_deleted_path_once() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    create_files(&target.join("nested"), 300);
+    create_files(&target, 200);
+    // Newlines in names are why NUL termination exists
+    File::create(target.join("odd\nname.txt")).unwrap();
+
+    let output = fast_rm().arg("--print0").arg(&target).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!target.exists());
+
+    let stdout = output.stdout;
+    assert_eq!(stdout.last(), Some(&0), "every path is NUL-terminated");
+    let mut paths: Vec<_> = stdout[..stdout.len() - 1]
+        .split(|&b| b == 0)
+        .map(|path| String::from_utf8(path.to_vec()).unwrap())
+        .collect();
+    let printed = paths.len();
+    paths.sort();
+    paths.dedup();
+    // 501 files and 2 directories, nothing twice and nothing else on stdout
+    assert_eq!(printed, 503);
+    assert_eq!(paths.len(), 503);
+    assert!(paths
+        .iter()
+        .all(|path| path.starts_with(&*target.to_string_lossy())));
+    assert!(paths.contains(&target.join("odd\nname.txt").to_string_lossy().into_owned()));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Complete"));
+}
+
+#[test]
+fn test_expr_deletes_only_matching_entries() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    fs::create_dir_all(target.join("logs")).unwrap();
+    fs::write(target.join("logs/big.log"), vec![0u8; 4096]).unwrap();
+    fs::write(target.join("logs/small.log"), "x").unwrap();
+    fs::write(target.join("big.txt"), vec![0u8; 4096]).unwrap();
+
+    let output = fast_rm()
+        .args([
+            "--expr",
+            "name ~ '*.log' and (size > 1K or name = small.log)",
+        ])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!target.join("logs/big.log").exists());
+    assert!(!target.join("logs/small.log").exists());
+    // Not matching, and so are the directories holding it
+    assert!(target.join("big.txt").exists());
+    assert!(target.join("logs").is_dir());
+
+    let output = fast_rm()
+        .args(["--expr", "size >"])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("expected a value after 'size >'"));
+}
+
+#[test]
+fn test_plan_out_then_plan_in_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    let plan = temp_dir.path().join("plan.txt");
+    create_files(&target.join("sub"), 50);
+    fs::write(target.join("big.bin"), vec![0u8; 4096]).unwrap();
+
+    let output = fast_rm()
+        .args(["--dry-run", "--plan-out"])
+        .arg(&plan)
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(target.join("big.bin").exists());
+    let text = fs::read_to_string(&plan).unwrap();
+    // 51 files and 2 directories, the top-level one last
+    assert_eq!(text.lines().filter(|l| !l.starts_with('#')).count(), 53);
+    assert!(text.contains(&format!("file 4096 {}", target.join("big.bin").display())));
+    assert!(text.ends_with(&format!("dir {}\n", target.display())));
+
+    // Executing the plan deletes what was planned and nothing that appeared
+    // since; the top-level directory is then not empty and stays
+    fs::write(target.join("sub/late.txt"), "new").unwrap();
+    fs::remove_file(target.join("big.bin")).unwrap();
+    let output = fast_rm()
+        .args(["-c", "--plan-in"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{:?}", output);
+    assert!(!target.join("sub/file_000000.txt").exists());
+    assert!(target.join("sub/late.txt").exists());
+    assert_eq!(fs::read_dir(&target).unwrap().count(), 1);
+
+    let output = fast_rm()
+        .arg("--plan-in")
+        .arg(&plan)
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+
+    // --force would clear entries the plan never listed
+    let output = fast_rm()
+        .args(["--force", "--plan-in"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+
+    // A plan root gets the checks of a command-line path
+    let output = fast_rm()
+        .arg("--plan-in")
+        .arg(&plan)
+        .current_dir(target.join("sub"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(target.join("sub/late.txt").exists());
+}
+
+#[test]
+fn test_summary_only_prints_one_line() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    create_files(&target, 200);
+    fs::write(target.join("data.bin"), vec![0u8; 2048]).unwrap();
+
+    let output = fast_rm()
+        .args(["--summary-only", "--dry-run"])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().count(), 1, "{}", stdout);
+    assert!(
+        stdout.starts_with("Dry run complete: 202 would be deleted, 0 errors"),
+        "{}",
+        stdout
+    );
+
+    let output = fast_rm()
+        .arg("--summary-only")
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!target.exists());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().count(), 1, "{}", stdout);
+    assert!(
+        stdout.starts_with("Complete: 202 deleted, 0 errors, 2.0 KiB freed in "),
+        "{}",
+        stdout
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_trailing_slash_on_symlink_empties_target() {
+    use std::os::unix::fs::symlink;
+
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    let link = temp_dir.path().join("link");
+    create_files(&target, 10);
+    symlink(&target, &link).unwrap();
+
+    // `link` is the link itself
+    let output = fast_rm().arg(&link).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(fs::symlink_metadata(&link).is_err());
+    assert_eq!(fs::read_dir(&target).unwrap().count(), 10);
+
+    // `link/` is the directory it points to, which is emptied but kept as
+    // rm keeps it; the link stays
+    symlink(&target, &link).unwrap();
+    let output = fast_rm()
+        .arg(format!("{}/", link.display()))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(target.is_dir());
+    assert_eq!(fs::read_dir(&target).unwrap().count(), 0);
+    assert!(fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+
+    // Once dangling, `link/` names no directory
+    fs::remove_dir(&target).unwrap();
+    let output = fast_rm()
+        .arg(format!("{}/", link.display()))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Not a directory"));
+    assert!(fs::symlink_metadata(&link).is_ok());
+}
+
+#[test]
+fn test_assume_yes_prunes_overlaps_and_skips_prompts() {
+    let temp_dir = TempDir::new().unwrap();
+    let parent = temp_dir.path().join("parent");
+    let child = parent.join("child");
+    create_files(&child, 5);
+
+    // Overlapping paths are refused without the flag
+    let output = fast_rm().arg(&parent).arg(&child).output().unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(child.exists());
+
+    // The typed confirmation would read end of input and refuse
+    let output = fast_rm()
+        .args(["-y", "--require-typed-confirm"])
+        .arg(&child)
+        .arg(&parent)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!parent.exists());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is inside"));
+
+    // Safety stops are not answered for
+    create_files(&parent, 1);
+    fs::write(parent.join("big.bin"), vec![0u8; 8192]).unwrap();
+    let output = fast_rm()
+        .args(["--assume-yes", "--abort-over-bytes", "4K"])
+        .arg(&parent)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(parent.join("big.bin").exists());
+}
+
+#[test]
+fn test_stage_dir_moves_targets_out_before_emptying() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    let single = temp_dir.path().join("single.txt");
+    let stage = temp_dir.path().join("stage");
+    create_files(&target, 5);
+    File::create(&single).unwrap();
+
+    // Stopped before the first deletion: the originals are already gone,
+    // and everything they held is waiting in the staging directory
+    let output = fast_rm()
+        .args(["--timeout", "0", "--stage-dir"])
+        .arg(&stage)
+        .arg(&target)
+        .arg(&single)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(124), "{:?}", output);
+    assert!(!target.exists());
+    assert!(!single.exists());
+    let staged: Vec<_> = fs::read_dir(&stage)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    assert_eq!(staged.len(), 2, "{:?}", staged);
+    let dir = staged.iter().find(|p| p.is_dir()).unwrap();
+    assert_eq!(fs::read_dir(dir).unwrap().count(), 5);
+
+    // A full run leaves only the empty staging directory
+    let output = fast_rm()
+        .args(["--stage-dir"])
+        .arg(&stage)
+        .args(&staged)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fs::read_dir(&stage).unwrap().count(), 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_target_containing_cwd() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    let inside = target.join("sub");
+    create_files(&inside, 3);
+    let summary = temp_dir.path().join("summary.json");
+
+    // Refused by default, even as `..` with --allow-dot
+    let output = fast_rm()
+        .args(["--allow-dot", ".."])
+        .current_dir(&inside)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--allow-delete-cwd"));
+    assert!(inside.join("file_000000.txt").exists());
+
+    // Allowed: the run moves to / first, and relative files still land
+    // where they were meant to
+    let output = fast_rm()
+        .args([
+            "--allow-delete-cwd",
+            "--allow-dot",
+            "--summary-json-file",
+            "../../summary.json",
+            ".",
+        ])
+        .current_dir(&inside)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!inside.exists());
+    assert!(summary.exists());
+
+    // A relative --match-prefix is also taken from where the run started
+    let other = target.join("other");
+    create_files(&inside, 3);
+    create_files(&other, 3);
+    let output = fast_rm()
+        .args([
+            "--allow-delete-cwd",
+            "--allow-dot",
+            "--match-prefix",
+            ".",
+            "..",
+        ])
+        .current_dir(&inside)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!inside.join("file_000000.txt").exists());
+    assert!(other.join("file_000000.txt").exists());
+}
+
+#[test]
+fn test_no_dedup_attempts_repeated_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+
+    // Each repeat is attempted; the second finds the path gone, which is
+    // counted as skipped rather than as an error
+    create_files(&target, 5);
+    let output = fast_rm()
+        .args(["--no-dedup", "--color", "never"])
+        .arg(&target)
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("6 deleted, 0 errors"), "{}", stdout);
+    assert!(stdout.contains("Skipped: 1 (gone)"), "{}", stdout);
+    assert!(!target.exists());
+
+    // In the pipeline both copies are scanned before either is deleted, so
+    // how many entries the second finds gone varies
+    create_files(&target.join("sub"), 50);
+    let output = fast_rm()
+        .args(["--no-dedup", "--engine", "pipeline", "--color", "never"])
+        .arg(&target)
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("52 deleted, 0 errors"), "{}", stdout);
+    assert!(!target.exists());
+}
+
+#[test]
+fn test_empty_and_dot_arguments() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    create_files(&target.join("sub"), 3);
+
+    // An empty argument is named, and nothing else is deleted either
+    let output = fast_rm().arg(&target).arg("").output().unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Path argument 2 is empty"), "{}", stderr);
+    assert!(target.exists());
+
+    // "." and ".." as the last component are refused without --allow-dot
+    for dot in [".", "..", "sub/."] {
+        let output = fast_rm().arg(dot).current_dir(&target).output().unwrap();
+        assert_eq!(output.status.code(), Some(1), "{:?}", output);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("--allow-dot"), "{}", stderr);
+    }
+    assert!(target.join("sub/file_000000.txt").exists());
+
+    // With it, `.` and --contents-only empty the current directory
+    let output = fast_rm()
+        .args(["--allow-dot", "--contents-only", "."])
+        .current_dir(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(target.exists());
+    assert_eq!(fs::read_dir(&target).unwrap().count(), 0);
+}
+
+#[test]
+fn test_long_progress_interval_does_not_delay_exit() {
+    use std::time::{Duration, Instant};
+
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    create_files(&target, 3);
+
+    // The display is woken for its final refresh rather than slept out
+    let start = Instant::now();
+    let output = fast_rm()
+        .args(["--engine", "pipeline", "--progress-interval", "10000"])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(
+        start.elapsed() < Duration::from_secs(5),
+        "{:?}",
+        start.elapsed()
+    );
+    assert!(!target.exists());
+
+    let output = fast_rm()
+        .args(["--progress-interval", "60000"])
+        .arg(temp_dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}
+
+#[test]
+fn test_env_fallbacks() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    create_files(&target, 3);
+    let threads = |args: &[&str]| {
+        let output = fast_rm()
+            .env("FASTRM_THREADS", "3")
+            .env("FASTRM_DELETE_THREADS", "5")
+            .args(["-v", "--dry-run", "--engine", "pipeline"])
+            .args(args)
+            .arg(&target)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        stdout
+            .lines()
+            .find(|line| line.starts_with("Threads:"))
+            .unwrap_or_else(|| panic!("{}", stdout))
+            .to_string()
+    };
+    assert_eq!(threads(&[]), "Threads: 3 scanners, 5 deleters");
+    // A flag on the command line beats the same option's variable
+    assert_eq!(
+        threads(&["-j", "2", "--delete-threads", "7"]),
+        "Threads: 2 scanners, 7 deleters"
+    );
+
+    // An argument that cannot be examined stops the run unless errors are
+    // to be continued past
+    let missing = temp_dir.path().join("missing");
+    let output = fast_rm()
+        .env("FASTRM_CONTINUE_ON_ERROR", "false")
+        .arg(&missing)
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(target.exists());
+    let output = fast_rm()
+        .env("FASTRM_CONTINUE_ON_ERROR", "1")
+        .arg(&missing)
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(!target.exists(), "{:?}", output);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_root_is_always_refused() {
+    let temp_dir = TempDir::new().unwrap();
+    let link = temp_dir.path().join("to_root");
+    std::os::unix::fs::symlink("/", &link).unwrap();
+
+    // Whatever else is allowed, and however `/` is reached
+    let spellings = [
+        vec!["/".to_string()],
+        vec!["--no-dedup".to_string(), "/tmp/..".to_string()],
+        vec![format!("{}/", link.display())],
+        vec!["--dereference-args".to_string(), link.display().to_string()],
+    ];
+    for args in spellings {
+        let output = fast_rm()
+            .args(["--dry-run", "--allow-delete-cwd", "--allow-dot"])
+            .args(&args)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1), "{:?}: {:?}", args, output);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("root of a file system"), "{}", stderr);
+    }
+    assert!(link.is_symlink());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_broken_symlinks_only() {
+    use std::os::unix::fs::symlink;
+
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("root");
+    create_files(&root.join("data"), 1);
+    fs::create_dir(root.join("empty")).unwrap();
+    symlink("data/file_000000.txt", root.join("valid")).unwrap();
+    symlink("data/missing.txt", root.join("broken")).unwrap();
+
+    let output = fast_rm()
+        .arg("--broken-symlinks-only")
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(fs::symlink_metadata(root.join("broken")).is_err());
+    assert!(fs::symlink_metadata(root.join("valid")).is_ok());
+    assert!(root.join("data/file_000000.txt").exists());
+    assert!(root.join("empty").is_dir());
+}
+
+#[test]
+fn test_stats_file_accumulates_runs() {
+    let temp_dir = TempDir::new().unwrap();
+    let stats = temp_dir.path().join("stats.csv");
+    for (run, size) in [(0, 100), (1, 250)] {
+        let target = temp_dir.path().join(format!("run{}", run));
+        fs::create_dir(&target).unwrap();
+        for i in 0..4 {
+            fs::write(target.join(format!("{}.bin", i)), vec![0u8; size]).unwrap();
+        }
+        let output = fast_rm()
+            .arg("--stats-file")
+            .arg(&stats)
+            .arg(&target)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+    }
+
+    let text = fs::read_to_string(&stats).unwrap();
+    let records: Vec<Vec<&str>> = text
+        .lines()
+        .skip(1)
+        .map(|l| l.split(',').collect())
+        .collect();
+    assert!(text.starts_with("timestamp,"));
+    assert_eq!(records.len(), 2);
+    // Four files and their directory each time
+    assert_eq!(records[0][3..5], ["5", "400"]);
+    assert_eq!(records[1][3..5], ["5", "1000"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_two_links_to_one_directory_are_scanned_once() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    create_files(&target, 20);
+    std::os::unix::fs::symlink(&target, temp_dir.path().join("link1")).unwrap();
+    std::os::unix::fs::symlink("target", temp_dir.path().join("link2")).unwrap();
+
+    let output = fast_rm()
+        .arg("--dereference-args")
+        .arg(temp_dir.path().join("link1"))
+        .arg(temp_dir.path().join("link2"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("21 scanned"), "{}", stdout);
+    assert!(stdout.contains("0 errors"), "{}", stdout);
+    assert!(!target.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_keep_dirs_leaves_the_skeleton() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("root");
+    create_files(&root.join("a/b/c"), 3);
+    create_files(&root.join("a/d"), 2);
+    fs::create_dir(root.join("empty")).unwrap();
+    fs::write(root.join("top.txt"), "top").unwrap();
+    symlink("top.txt", root.join("a/link")).unwrap();
+    fs::set_permissions(root.join("a/d"), fs::Permissions::from_mode(0o750)).unwrap();
+
+    let output = fast_rm().arg("--keep-dirs").arg(&root).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    for dir in ["", "a", "a/b", "a/b/c", "a/d", "empty"] {
+        let dir = root.join(dir);
+        assert!(dir.is_dir(), "{:?}", dir);
+        let mut entries = fs::read_dir(&dir).unwrap();
+        assert!(
+            entries.all(|e| e.unwrap().file_type().unwrap().is_dir()),
+            "{:?}",
+            dir
+        );
+    }
+    assert!(fs::symlink_metadata(root.join("a/link")).is_err());
+    let mode = fs::metadata(root.join("a/d")).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o750);
+}
+
+#[test]
+fn test_probe_deletes_exactly_n_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    for dir in ["a", "b", "c"] {
+        create_files(&target.join(dir), 5);
+    }
+    let count_files = |dir: &str| fs::read_dir(target.join(dir)).unwrap().count();
+
+    // Probe only: four sample files go, spread over all three directories
+    let output = fast_rm()
+        .args(["--probe", "4", "--color", "never"])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Probe: deleted 4 of 4 sample files in 3 directories"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("--probe-then-delete"), "{}", stdout);
+    let left: Vec<_> = ["a", "b", "c"].into_iter().map(count_files).collect();
+    assert_eq!(left.iter().sum::<usize>(), 11);
+    assert!(left.iter().all(|&n| n < 5), "{:?}", left);
+
+    // With --probe-then-delete the rest follows
+    let output = fast_rm()
+        .args(["--probe", "2", "--probe-then-delete", "--color", "never"])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!target.exists());
+}
+
+#[cfg(windows)]
+#[test]
+fn test_open_file_is_named_or_skipped() {
+    use std::fs::OpenOptions;
+    use std::os::windows::fs::OpenOptionsExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    create_files(&target.join("sub"), 3);
+    let open = target.join("sub").join("open.txt");
+    fs::write(&open, "x").unwrap();
+    // Without delete sharing, the file cannot go while this handle is open
+    let _file = OpenOptions::new()
+        .read(true)
+        .share_mode(0)
+        .open(&open)
+        .unwrap();
+
+    // The error names this test process as the one holding it
+    let output = fast_rm()
+        .args(["--engine", "recursive", "--color", "never"])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("it is open in"), "{}", stderr);
+    assert!(
+        stderr.contains(&format!("(pid {})", std::process::id())),
+        "{}",
+        stderr
+    );
+    assert!(open.exists());
+
+    // --skip-open leaves it and its directories, and deletes the rest
+    create_files(&target.join("sub"), 3);
+    let output = fast_rm()
+        .args(["--skip-open", "--color", "never"])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Skipped: 1 (open)"), "{}", stdout);
+    assert!(open.exists());
+    assert_eq!(fs::read_dir(target.join("sub")).unwrap().count(), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_max_symlink_depth_stops_long_chains() {
+    let temp_dir = TempDir::new().unwrap();
+    let target = temp_dir.path().join("target");
+    create_files(&target, 3);
+    // chain_0 -> chain_1 -> ... -> chain_9 -> target
+    let chain = |i: usize| temp_dir.path().join(format!("chain_{}", i));
+    for i in 0..10 {
+        let next = if i == 9 { target.clone() } else { chain(i + 1) };
+        std::os::unix::fs::symlink(next, chain(i)).unwrap();
+    }
+
+    let output = fast_rm()
+        .args([
+            "--dereference-args",
+            "--max-symlink-depth",
+            "5",
+            "--color",
+            "never",
+        ])
+        .arg(chain(0))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("longer than 5 links"), "{}", stderr);
+    assert!(target.join("file_000000.txt").exists());
+
+    // Under --follow-symlinks a link to itself is not followed, at the
+    // default depth, but the link is still removed
+    let looped = temp_dir.path().join("loop");
+    std::os::unix::fs::symlink(&looped, &looped).unwrap();
+    let output = fast_rm()
+        .args(["--follow-symlinks", "--color", "never"])
+        .arg(&looped)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let all = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(all.contains("longer than 40 links"), "{}", all);
+    assert!(fs::symlink_metadata(&looped).is_err());
+
+    // Within the limit the chain leads to the target directory
+    let output = fast_rm()
+        .args(["--dereference-args", "--max-symlink-depth", "10"])
+        .arg(chain(0))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!target.exists());
+    assert!(fs::symlink_metadata(chain(0)).is_ok());
+}